@@ -282,3 +282,8 @@ pub struct SummonInfo {
     loadout_config: Option<loadout_builder::Preset>,
     skillset_config: Option<skillset_builder::Preset>,
 }
+
+impl SummonInfo {
+    /// The height of the summoned creature, accounting for its scale.
+    pub fn height(&self) -> f32 { self.body.height() * self.scale.map_or(1.0, |scale| scale.0) }
+}
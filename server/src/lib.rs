@@ -298,6 +298,7 @@ impl Server {
                     FileOpts::LoadAsset(DEFAULT_WORLD_MAP.into())
                 },
                 calendar: Some(settings.calendar_mode.calendar_now()),
+                spawn_density_scale: settings.gameplay.spawn_density_scale,
             },
             &pools,
             &|stage| {
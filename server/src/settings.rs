@@ -100,13 +100,21 @@ pub struct GameplaySettings {
     #[serde(default)]
     // explosion_burn_marks by players
     pub explosion_burn_marks: bool,
+    /// Multiplies the chance of towns spawning their population, between 0.0
+    /// for none and 1.0 for everyone, to thin out NPCs on busy servers.
+    /// Values outside of that range are clamped to it, and NaN counts as 1.0.
+    #[serde(default = "default_spawn_density_scale")]
+    pub spawn_density_scale: f32,
 }
 
+fn default_spawn_density_scale() -> f32 { 1.0 }
+
 impl Default for GameplaySettings {
     fn default() -> Self {
         Self {
             battle_mode: ServerBattleMode::default(),
             explosion_burn_marks: true,
+            spawn_density_scale: default_spawn_density_scale(),
         }
     }
 }
@@ -19,6 +19,8 @@ impl Animation for SummonAnimation {
         Option<StageSection>,
         f32,
         Option<&'a str>,
+        Vec3<f32>,
+        f32,
     );
     type Skeleton = BipedLargeSkeleton;
 
@@ -32,16 +34,24 @@ impl Animation for SummonAnimation {
             active_tool_kind,
             _second_tool_kind,
             velocity,
-            _global_time,
+            global_time,
             stage_section,
             acc_vel,
             ability_id,
+            look_dir,
+            summon_scale,
         ): Self::Dependency<'_>,
         anim_time: f32,
         rate: &mut f32,
         s_a: &SkeletonAttr,
     ) -> Self::Skeleton {
-        *rate = 1.0;
+        // Bigger summons take more effort, with a slower buildup, a lower crouch
+        // and a wider sweep, while small ones only take a flick of the staff.
+        // `summon_scale` is the size of the summon relative to the caster.
+        let effort = summon_scale.clamp(0.5, 2.0);
+        let crouch = (effort - 1.0).max(0.0) * 3.0;
+
+        *rate = 1.0 / effort.sqrt();
         let mut next = (*skeleton).clone();
 
         let speed = Vec2::<f32>::from(velocity).magnitude();
@@ -57,7 +67,7 @@ impl Animation for SummonAnimation {
             * ((acc_vel * lab + PI * 0.4).sin());
 
         let (move1base, move2base, move3) = match stage_section {
-            Some(StageSection::Buildup) => ((anim_time.powf(0.5)), 0.0, 0.0),
+            Some(StageSection::Buildup) => ((anim_time.powf(0.5 * effort)), 0.0, 0.0),
             Some(StageSection::Action) => (1.0, (anim_time.powi(2)), 0.0),
             Some(StageSection::Recover) => (1.0, 1.0, anim_time),
             _ => (0.0, 0.0, 0.0),
@@ -65,8 +75,37 @@ impl Animation for SummonAnimation {
         let pullback = 1.0 - move3;
         let move1 = move1base * pullback;
         let move2 = move2base * pullback;
+        let (sweep1, sweep2) = (move1 * effort, move2 * effort);
+
+        // Slow breathing while waiting to cast, fading out over the buildup so
+        // that it doesn't disturb the summon itself
+        let breathe = (global_time * 1.5).sin() * (1.0 - move1base);
 
+        // Casters look towards where their summon appears, easing back to
+        // neutral as they recover. `look_dir` is relative to the body, so zero
+        // keeps the head still.
+        const MAX_HEAD_YAW: f32 = 0.8;
+        const MAX_HEAD_PITCH: f32 = 0.5;
+        let (head_yaw, head_pitch) = if look_dir.magnitude_squared() > 0.0 {
+            (
+                (-look_dir.x)
+                    .atan2(look_dir.y)
+                    .clamp(-MAX_HEAD_YAW, MAX_HEAD_YAW),
+                look_dir
+                    .z
+                    .atan2(Vec2::from(look_dir).magnitude())
+                    .clamp(-MAX_HEAD_PITCH, MAX_HEAD_PITCH),
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let head_look =
+            Quaternion::rotation_z(head_yaw * move1) * Quaternion::rotation_x(head_pitch * move1);
+
+        next.torso.position = Vec3::new(0.0, 0.0, move1 * -crouch);
         next.torso.orientation = Quaternion::rotation_z(0.0);
+        next.upper_torso.position =
+            Vec3::new(0.0, s_a.upper_torso.0, s_a.upper_torso.1 + breathe * 0.4);
 
         next.main.position = Vec3::new(0.0, 0.0, 0.0);
         next.main.orientation = Quaternion::rotation_x(0.0);
@@ -83,7 +122,7 @@ impl Animation for SummonAnimation {
                 next.shoulder_l.position = Vec3::new(
                     -s_a.shoulder.0,
                     s_a.shoulder.1,
-                    s_a.shoulder.2 - foothorir * 1.0,
+                    s_a.shoulder.2 - foothorir * 1.0 + breathe * 0.3,
                 );
                 next.shoulder_l.orientation = Quaternion::rotation_x(
                     move1 * 0.8 + 0.6 * speednorm + (footrotr * -0.2) * speednorm,
@@ -92,23 +131,23 @@ impl Animation for SummonAnimation {
                 next.shoulder_r.position = Vec3::new(
                     s_a.shoulder.0,
                     s_a.shoulder.1,
-                    s_a.shoulder.2 - foothoril * 1.0,
+                    s_a.shoulder.2 - foothoril * 1.0 + breathe * 0.3,
                 );
                 next.shoulder_r.orientation = Quaternion::rotation_x(
                     move1 * 0.8 + 0.6 * speednorm + (footrotl * -0.2) * speednorm,
                 );
-                next.head.orientation = Quaternion::rotation_x(0.0);
+                next.head.orientation = head_look;
                 next.control_l.position = Vec3::new(-1.0, 3.0, 12.0);
                 next.control_r.position = Vec3::new(
-                    1.0 + move1 * 3.0 + move2 * 20.0,
-                    2.0 + move1 * -5.0 + move2 * 5.0,
-                    2.0 + move1 * 15.0 + move2 * 0.0,
+                    1.0 + sweep1 * 3.0 + sweep2 * 20.0,
+                    2.0 + sweep1 * -5.0 + sweep2 * 5.0,
+                    2.0 + sweep1 * 15.0 + sweep2 * 0.0,
                 );
 
                 next.control.position = Vec3::new(
-                    -3.0 + move2 * 9.0,
-                    3.0 + s_a.grip.0 / 1.2 + move1 * 15.0 + move2 * 2.0,
-                    -11.0 + -s_a.grip.0 / 2.0 + move1 * 15.0 + move2 * -12.0,
+                    -3.0 + sweep2 * 9.0,
+                    3.0 + s_a.grip.0 / 1.2 + sweep1 * 15.0 + sweep2 * 2.0,
+                    -11.0 + -s_a.grip.0 / 2.0 + sweep1 * 15.0 + sweep2 * -12.0,
                 );
 
                 next.control_l.orientation = Quaternion::rotation_x(PI / 2.0 - move1 * 0.2)
@@ -125,7 +164,7 @@ impl Animation for SummonAnimation {
                 next.shoulder_l.position = Vec3::new(
                     -s_a.shoulder.0,
                     s_a.shoulder.1,
-                    s_a.shoulder.2 - foothorir * 1.0,
+                    s_a.shoulder.2 - foothorir * 1.0 + breathe * 0.3,
                 );
                 next.shoulder_l.orientation = Quaternion::rotation_x(
                     move1 * 0.8 + 0.6 * speednorm + (footrotr * -0.2) * speednorm,
@@ -134,23 +173,23 @@ impl Animation for SummonAnimation {
                 next.shoulder_r.position = Vec3::new(
                     s_a.shoulder.0,
                     s_a.shoulder.1,
-                    s_a.shoulder.2 - foothoril * 1.0,
+                    s_a.shoulder.2 - foothoril * 1.0 + breathe * 0.3,
                 );
                 next.shoulder_r.orientation = Quaternion::rotation_x(
                     move1 * 0.8 + 0.6 * speednorm + (footrotl * -0.2) * speednorm,
                 );
-                next.head.orientation = Quaternion::rotation_x(0.0);
+                next.head.orientation = head_look;
                 next.control_l.position = Vec3::new(-1.0, 3.0, 12.0);
                 next.control_r.position = Vec3::new(
-                    1.0 + move1 * 3.0 + move2 * 20.0,
-                    2.0 + move1 * -5.0 + move2 * 5.0,
-                    2.0 + move1 * 15.0 + move2 * 0.0,
+                    1.0 + sweep1 * 3.0 + sweep2 * 20.0,
+                    2.0 + sweep1 * -5.0 + sweep2 * 5.0,
+                    2.0 + sweep1 * 15.0 + sweep2 * 0.0,
                 );
 
                 next.control.position = Vec3::new(
-                    -3.0 + move2 * 9.0,
-                    3.0 + s_a.grip.0 / 1.2 + move1 * 8.0 + move2 * 2.0,
-                    -11.0 + -s_a.grip.0 / 2.0 + move1 * 8.0 + move2 * -12.0,
+                    -3.0 + sweep2 * 9.0,
+                    3.0 + s_a.grip.0 / 1.2 + sweep1 * 8.0 + sweep2 * 2.0,
+                    -11.0 + -s_a.grip.0 / 2.0 + sweep1 * 8.0 + sweep2 * -12.0,
                 );
 
                 next.control_l.orientation = Quaternion::rotation_x(PI / 2.0 - move1 * 0.2)
@@ -176,7 +215,7 @@ impl Animation for SummonAnimation {
                     let move2 = move2base * pullback;
                     next.torso.position = Vec3::new(0.0, 0.0 + move1 * 4.7, move1 * -18.8);
                     next.upper_torso.position =
-                        Vec3::new(0.0, s_a.upper_torso.0, s_a.upper_torso.1);
+                        Vec3::new(0.0, s_a.upper_torso.0, s_a.upper_torso.1 + breathe * 0.4);
 
                     next.lower_torso.position =
                         Vec3::new(0.0, s_a.lower_torso.0, s_a.lower_torso.1);
@@ -289,3 +328,161 @@ impl Animation for SummonAnimation {
         next
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upper_torso_z(global_time: f32, stage_section: Option<StageSection>) -> f32 {
+        SummonAnimation::update_skeleton_inner(
+            &BipedLargeSkeleton::default(),
+            (
+                Some(ToolKind::Staff),
+                (None, None),
+                Vec3::zero(),
+                global_time,
+                stage_section,
+                0.0,
+                None,
+                Vec3::zero(),
+                1.0,
+            ),
+            0.0,
+            &mut 1.0,
+            &SkeletonAttr::default(),
+        )
+        .upper_torso
+        .position
+        .z
+    }
+
+    #[test]
+    fn breathes_before_buildup() {
+        let heights = (0..8)
+            .map(|i| upper_torso_z(i as f32 * 0.5, None))
+            .collect::<Vec<_>>();
+        let lowest = heights.iter().copied().fold(f32::INFINITY, f32::min);
+        let highest = heights.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        assert!(highest - lowest > 0.1);
+        assert!(highest - lowest <= 0.8);
+
+        // Entering the buildup doesn't snap the pose
+        for i in 0..8 {
+            let global_time = i as f32 * 0.5;
+            assert_eq!(
+                upper_torso_z(global_time, None),
+                upper_torso_z(global_time, Some(StageSection::Buildup))
+            );
+        }
+    }
+
+    fn head(look_dir: Vec3<f32>, stage_section: StageSection, anim_time: f32) -> Quaternion<f32> {
+        SummonAnimation::update_skeleton_inner(
+            &BipedLargeSkeleton::default(),
+            (
+                Some(ToolKind::Staff),
+                (None, None),
+                Vec3::zero(),
+                0.0,
+                Some(stage_section),
+                0.0,
+                None,
+                look_dir,
+                1.0,
+            ),
+            anim_time,
+            &mut 1.0,
+            &SkeletonAttr::default(),
+        )
+        .head
+        .orientation
+    }
+
+    #[test]
+    fn head_tracks_look_dir() {
+        let neutral = Quaternion::rotation_x(0.0);
+        assert_eq!(head(Vec3::zero(), StageSection::Action, 0.5), neutral);
+
+        // Looking to the side turns the head while casting, within reason
+        let look_dir = Vec3::new(1.0, 1.0, 0.5);
+        let cast = head(look_dir, StageSection::Action, 0.5);
+        assert_ne!(cast, neutral);
+        let forward = cast * Vec3::unit_y();
+        assert!(forward.x > 0.0 && forward.z > 0.0);
+        let extreme = head(Vec3::new(1.0, -1.0, 0.0), StageSection::Action, 0.5) * Vec3::unit_y();
+        assert!(extreme.y > 0.0);
+
+        // ... and eases back to neutral by the end of the recovery
+        let recovered = head(look_dir, StageSection::Recover, 1.0);
+        assert!((recovered * Vec3::unit_y() - Vec3::unit_y()).magnitude() < 1e-4);
+    }
+
+    fn summon(
+        summon_scale: f32,
+        stage_section: StageSection,
+        anim_time: f32,
+    ) -> BipedLargeSkeleton {
+        SummonAnimation::update_skeleton_inner(
+            &BipedLargeSkeleton::default(),
+            (
+                Some(ToolKind::Staff),
+                (None, None),
+                Vec3::zero(),
+                0.0,
+                Some(stage_section),
+                0.0,
+                None,
+                Vec3::zero(),
+                summon_scale,
+            ),
+            anim_time,
+            &mut 1.0,
+            &SkeletonAttr::default(),
+        )
+    }
+
+    #[test]
+    fn larger_summons_take_more_effort() {
+        let rest = summon(1.0, StageSection::Buildup, 0.0).control.position;
+        let reach = |summon_scale| {
+            (summon(summon_scale, StageSection::Action, 1.0)
+                .control
+                .position
+                - rest)
+                .magnitude()
+        };
+        assert!(reach(2.0) > reach(0.5));
+
+        let torso_z = |summon_scale| {
+            summon(summon_scale, StageSection::Buildup, 0.5)
+                .torso
+                .position
+                .z
+        };
+        assert!(torso_z(2.0) < torso_z(0.5));
+
+        // Larger summons are also slower to gesture
+        let mut small_rate = 1.0;
+        let mut large_rate = 1.0;
+        for (summon_scale, rate) in [(0.5, &mut small_rate), (2.0, &mut large_rate)] {
+            SummonAnimation::update_skeleton_inner(
+                &BipedLargeSkeleton::default(),
+                (
+                    Some(ToolKind::Staff),
+                    (None, None),
+                    Vec3::zero(),
+                    0.0,
+                    Some(StageSection::Buildup),
+                    0.0,
+                    None,
+                    Vec3::zero(),
+                    summon_scale,
+                ),
+                0.5,
+                rate,
+                &SkeletonAttr::default(),
+            );
+        }
+        assert!(large_rate < small_rate);
+    }
+}
@@ -23,6 +23,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             seed_elements: true,
             world_file: sim::FileOpts::LoadAsset(sim::DEFAULT_WORLD_MAP.into()),
             calendar: None,
+            spawn_density_scale: 1.0,
         },
         &pool,
         &|_| {},
@@ -5479,6 +5479,9 @@ impl FigureMgr {
                                     Some(s.stage_section),
                                     state.acc_vel,
                                     ability_id,
+                                    ori.inverse() * *look_dir,
+                                    s.static_data.summon_info.height()
+                                        / Body::BipedLarge(*body).height(),
                                 ),
                                 stage_progress,
                                 &mut state_animation_rate,
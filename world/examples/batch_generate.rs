@@ -144,6 +144,7 @@ fn generate_one(
                 FileOpts::Generate(gen_opts.clone())
             },
             calendar: None,
+            spawn_density_scale: 1.0,
         },
         threadpool,
         &|stage| {
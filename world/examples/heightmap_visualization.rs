@@ -125,6 +125,7 @@ fn main() {
             seed_elements: true,
             world_file: FileOpts::LoadAsset(DEFAULT_WORLD_MAP.into()),
             calendar: None,
+            spawn_density_scale: 1.0,
         },
         &pool,
         &|_| {},
@@ -51,6 +51,7 @@ fn main() {
             // world_file: sim::FileOpts::Save(sim::SizeOpts::default()),
             // world_file: sim::FileOpts::Save(sim::SizeOpts::new(12, 12, 4.0)),
             calendar: None,
+            spawn_density_scale: 1.0,
         },
         &threadpool,
         &|_| {},
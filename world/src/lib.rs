@@ -599,6 +599,7 @@ impl World {
                 &mut supplement,
                 site.id(),
                 time.as_ref(),
+                self.sim.spawn_density_scale,
             )
         });
 
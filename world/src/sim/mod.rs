@@ -455,6 +455,10 @@ pub struct WorldOpts {
     pub seed_elements: bool,
     pub world_file: FileOpts,
     pub calendar: Option<Calendar>,
+    /// Multiplies the chance of settlements spawning their population, such
+    /// that busy servers can thin it out. 1.0 spawns everyone, 0.0 no one.
+    /// Values outside of that range are clamped to it, and NaN counts as 1.0.
+    pub spawn_density_scale: f32,
 }
 
 impl Default for WorldOpts {
@@ -463,6 +467,7 @@ impl Default for WorldOpts {
             seed_elements: true,
             world_file: Default::default(),
             calendar: None,
+            spawn_density_scale: 1.0,
         }
     }
 }
@@ -684,6 +689,7 @@ pub struct WorldSim {
     pub rng: ChaChaRng,
 
     pub(crate) calendar: Option<Calendar>,
+    pub(crate) spawn_density_scale: f32,
 }
 
 impl WorldSim {
@@ -695,6 +701,13 @@ impl WorldSim {
     ) -> Self {
         prof_span!("WorldSim::generate");
         let calendar = opts.calendar; // separate lifetime of elements
+        // Settings files may be edited by hand, so keep the scale within its
+        // range, and spawn everyone rather than no one when it isn't a number
+        let spawn_density_scale = if opts.spawn_density_scale.is_nan() {
+            1.0
+        } else {
+            opts.spawn_density_scale.clamp(0.0, 1.0)
+        };
         let world_file = opts.world_file;
 
         // Parse out the contents of various map formats into the values we need.
@@ -1613,6 +1626,7 @@ impl WorldSim {
             gen_ctx,
             rng,
             calendar,
+            spawn_density_scale,
         };
 
         this.generate_cliffs();
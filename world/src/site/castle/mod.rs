@@ -304,6 +304,7 @@ impl Castle {
                     } else {
                         StoneColor::Good
                     },
+                    wall_thickness: 2,
                 };
 
                 for z in -10..64 {
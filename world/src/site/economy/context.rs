@@ -344,6 +344,7 @@ mod tests {
                 world_file: sim::FileOpts::LoadAsset(sim::DEFAULT_WORLD_MAP.into()),
                 //sim::FileOpts::LoadAsset("world.map.economy_8x8".into()),
                 calendar: None,
+                spawn_density_scale: 1.0,
             };
             let mut index = crate::index::Index::new(seed);
             info!("Index created");
@@ -370,6 +371,7 @@ mod tests {
                 world_file: sim::FileOpts::LoadAsset(sim::DEFAULT_WORLD_MAP.into()),
                 //sim::FileOpts::LoadAsset("world.map.economy_8x8".into()),
                 calendar: None,
+                spawn_density_scale: 1.0,
             };
             let mut index = crate::index::Index::new(seed);
             info!("Index created");
@@ -509,6 +511,7 @@ mod tests {
                 seed_elements: true,
                 world_file: sim::FileOpts::LoadAsset(sim::DEFAULT_WORLD_MAP.into()),
                 calendar: Default::default(),
+                spawn_density_scale: 1.0,
             };
             let index = crate::index::Index::new(seed);
             info!("Index created");
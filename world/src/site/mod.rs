@@ -431,13 +431,22 @@ impl Site {
         supplement: &mut ChunkSupplement,
         site_id: common::trade::SiteId,
         time: Option<&(TimeOfDay, Calendar)>,
+        spawn_density_scale: f32,
     ) {
         match &self.kind {
             SiteKind::Settlement(s) => {
                 let economy = self
                     .trade_information(site_id)
                     .expect("Settlement has no economy");
-                s.apply_supplement(dynamic_rng, wpos2d, get_column, supplement, economy, time)
+                s.apply_supplement(
+                    dynamic_rng,
+                    wpos2d,
+                    get_column,
+                    supplement,
+                    economy,
+                    time,
+                    spawn_density_scale,
+                )
             },
             SiteKind::Dungeon(d) => d.apply_supplement(dynamic_rng, wpos2d, supplement),
             SiteKind::Castle(c) => c.apply_supplement(dynamic_rng, wpos2d, get_column, supplement),
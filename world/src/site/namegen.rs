@@ -1,11 +1,42 @@
 use rand::prelude::*;
 
+/// The style of the names produced by [`NameGen::generate`], each backed by
+/// its own syllable tables.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NameStyle {
+    #[default]
+    Common,
+    Fantasy,
+    Norse,
+    Desert,
+}
+
+impl NameStyle {
+    pub const ALL: [Self; 4] = [Self::Common, Self::Fantasy, Self::Norse, Self::Desert];
+
+    /// The style of names given to places in a region with the given climate.
+    pub fn for_climate(temp: f32, humidity: f32) -> Self {
+        use crate::CONFIG;
+
+        if temp < CONFIG.snow_temp {
+            Self::Norse
+        } else if temp > CONFIG.desert_temp && humidity < CONFIG.desert_hum {
+            Self::Desert
+        } else if temp > CONFIG.tropical_temp && humidity > CONFIG.jungle_hum {
+            Self::Fantasy
+        } else {
+            Self::Common
+        }
+    }
+}
+
 pub struct NameGen<'a, R: Rng> {
     // 2..
     pub approx_syllables: usize,
     pub approx_syllables_long: usize,
 
     rng: &'a mut R,
+    style: NameStyle,
 }
 
 impl<'a, R: Rng> NameGen<'a, R> {
@@ -15,10 +46,25 @@ impl<'a, R: Rng> NameGen<'a, R> {
             approx_syllables_long: rng.gen_range(2..4),
 
             rng,
+            style: NameStyle::default(),
         }
     }
 
-    pub fn generate(self) -> String {
+    /// Produce names of the given style from [`NameGen::generate`].
+    #[must_use]
+    pub fn with_style(mut self, style: NameStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn generate(mut self) -> String {
+        match self.style {
+            NameStyle::Common => {},
+            NameStyle::Fantasy => return self.generate_fantasy(),
+            NameStyle::Norse => return self.generate_norse(),
+            NameStyle::Desert => return self.generate_desert_custom(),
+        }
+
         let cons = vec![
             "d", "f", "ph", "r", "st", "t", "s", "p", "sh", "th", "br", "tr", "m", "k", "st", "w",
             "y", "cr", "fr", "dr", "pl", "wr", "sn", "g", "qu", "l",
@@ -300,6 +346,41 @@ impl<'a, R: Rng> NameGen<'a, R> {
         self.generate_custom_from_parts(&start, &middle, &vowel, &end)
     }
 
+    pub fn generate_norse(&mut self) -> String {
+        let start = [
+            "b", "d", "f", "g", "h", "k", "r", "s", "t", "v", "y", "bj", "dr", "fj", "gr", "hj",
+            "hr", "kj", "sk", "sn", "st", "sv", "tr", "th", "as", "ei", "ul", "ing", "ost", "ar",
+        ];
+        let middle = [
+            "g", "k", "l", "n", "r", "s", "t", "v", "gg", "ll", "nn", "rr", "nd", "ld", "lf", "rk",
+            "rn", "rd", "sk", "st", "ng", "lv", "rv",
+        ];
+        let vowel = ["a", "e", "i", "o", "u", "y", "ei", "au", "ja", "jo"];
+        let end = [
+            "heim", "gard", "vik", "fjord", "by", "stad", "dal", "holm", "nes", "havn", "berg",
+            "borg", "ey", "ey", "fell", "mark", "lund", "vall", "sund", "thorp", "rud", "vin",
+        ];
+        self.generate_custom_from_parts(&start, &middle, &vowel, &end)
+    }
+
+    pub fn generate_fantasy(&mut self) -> String {
+        let start = [
+            "c", "f", "g", "l", "m", "n", "s", "t", "v", "al", "am", "ar", "el", "er", "il", "ith",
+            "cel", "gal", "lor", "mel", "nim", "sil", "thal", "val", "ae", "ea", "ys",
+        ];
+        let middle = [
+            "l", "n", "r", "s", "th", "v", "ll", "nd", "nn", "ss", "lth", "ndr", "rv", "sh", "lw",
+            "nw", "rn", "ld",
+        ];
+        let vowel = ["a", "e", "i", "y", "ae", "ea", "ie", "ia", "io"];
+        let end = [
+            "iel", "ael", "uil", "oril", "adel", "ith", "eth", "ath", "iath", "wyn", "ia", "ara",
+            "ella", "anna", "ira", "alas", "elas", "ess", "is", "oriel", "avel", "ilith", "athil",
+            "aleth",
+        ];
+        self.generate_custom_from_parts(&start, &middle, &vowel, &end)
+    }
+
     pub fn generate_desert_engl(&mut self) -> String {
         let start = [
             "dry", "sun", "bright", "death", "dread", "sizzle", "scourge", "dearth", "dust",
@@ -914,3 +995,79 @@ impl<'a, R: Rng> NameGen<'a, R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashbrown::{HashMap, HashSet};
+    use rand_chacha::ChaChaRng;
+
+    /// How often each letter ends the names of the given style.
+    fn name_endings(style: NameStyle) -> HashMap<char, usize> {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut endings = HashMap::new();
+        for _ in 0..1000 {
+            let name = NameGen::location(&mut rng).with_style(style).generate();
+            *endings.entry(name.chars().last().unwrap()).or_default() += 1;
+        }
+        endings
+    }
+
+    #[test]
+    fn common_style_is_default() {
+        let mut a = ChaChaRng::seed_from_u64(0);
+        let mut b = ChaChaRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_eq!(
+                NameGen::location(&mut a).generate(),
+                NameGen::location(&mut b)
+                    .with_style(NameStyle::Common)
+                    .generate()
+            );
+        }
+    }
+
+    #[test]
+    fn styles_differ() {
+        let common = name_endings(NameStyle::Common);
+        for style in NameStyle::ALL
+            .into_iter()
+            .filter(|s| *s != NameStyle::Common)
+        {
+            let other = name_endings(style);
+            // Total variation distance between the distributions of endings
+            let distance = common
+                .keys()
+                .chain(other.keys())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .map(|c| {
+                    let p = *common.get(c).unwrap_or(&0) as f32;
+                    let q = *other.get(c).unwrap_or(&0) as f32;
+                    (p - q).abs()
+                })
+                .sum::<f32>()
+                / 2000.0;
+            assert!(distance > 0.25, "{:?} is too similar: {}", style, distance);
+        }
+    }
+
+    #[test]
+    fn climates_pick_every_style() {
+        use crate::CONFIG;
+
+        let styles = [-1.0, -0.5, 0.0, 0.5, 1.0]
+            .into_iter()
+            .flat_map(|temp| {
+                [0.0, 0.25, 0.5, 0.75, 1.0]
+                    .into_iter()
+                    .map(move |humidity| NameStyle::for_climate(temp, humidity))
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(styles.len(), NameStyle::ALL.len());
+        assert_eq!(
+            NameStyle::for_climate(CONFIG.tropical_temp + 0.1, CONFIG.jungle_hum + 0.1),
+            NameStyle::Fantasy
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use super::{super::skeleton::*, Archetype};
+use crate::{
+    site::BlockMask,
+    util::{RandomField, Sampler},
+    IndexRef,
+};
+use common::{
+    calendar::Calendar,
+    terrain::{Block, BlockKind, SpriteKind},
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use vek::*;
+
+#[derive(Deserialize)]
+pub struct Colors {
+    pub stone: (u8, u8, u8),
+    pub wood: (u8, u8, u8),
+    pub roof: (u8, u8, u8),
+    pub floor: (u8, u8, u8),
+}
+
+/// Barracks housing a settlement's guards: a long dormitory with stone
+/// footings and timber walls, lined with rows of bunks either side of a
+/// central aisle, with a door at each end.
+pub struct Barracks;
+
+/// Height of the walls, up to the eaves.
+const WALL_HEIGHT: i32 = 7;
+/// Height of the stone footings of the walls.
+const FOOTING_HEIGHT: i32 = 2;
+/// Distance between neighbouring bunks along the dormitory.
+const BUNK_SPACING: i32 = 3;
+
+/// Whether a bunk stands at the given offset from the center of the
+/// dormitory, across and along it.
+fn is_bunk(offset: Vec2<i32>, locus: i32, len: i32) -> bool {
+    // Bunks stand against the side walls, clear of the doors at the ends
+    offset.x.abs() == locus - 1
+        && offset.y.abs() <= len / 2 + locus - 3
+        && offset.y.rem_euclid(BUNK_SPACING) == 0
+}
+
+impl Barracks {
+    /// The bunks of the barracks, as the positions of their lower beds
+    /// relative to the building origin.
+    pub fn bunks(&self, skel: &Skeleton<()>) -> Vec<Vec3<i32>> {
+        let (locus, len) = (skel.root.locus, skel.root.len);
+        let reach = len / 2 + locus;
+        (-reach..=reach)
+            .flat_map(|y| [-(locus - 1), locus - 1].map(|x| Vec2::new(x, y)))
+            .filter(|offset| is_bunk(*offset, locus, len))
+            .map(|offset| Vec3::from(skel.from_root_center(offset)) + Vec3::unit_z())
+            .collect()
+    }
+}
+
+impl Archetype for Barracks {
+    type Attr = ();
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        let skel = Skeleton {
+            offset: 0,
+            ori: if rng.gen() { Ori::East } else { Ori::North },
+            root: Branch {
+                // Even, so that the bunks are symmetric about the center
+                len: rng.gen_range(7..11) * 2,
+                attr: (),
+                locus: 4,
+                border: 2,
+                children: Vec::new(),
+            },
+        };
+
+        (Self, skel)
+    }
+
+    fn height(&self, skel: &Skeleton<Self::Attr>) -> i32 {
+        // The ridge of the roof
+        WALL_HEIGHT + skel.root.locus + 1
+    }
+
+    fn draw(
+        &self,
+        index: IndexRef,
+        pos: Vec3<i32>,
+        dist: i32,
+        bound_offset: Vec2<i32>,
+        center_offset: Vec2<i32>,
+        z: i32,
+        ori: Ori,
+        locus: i32,
+        len: i32,
+        _attr: &Self::Attr,
+    ) -> BlockMask {
+        let colors = &index.colors.site.settlement.building.archetype.barracks;
+
+        let make_block = |(r, g, b), kind| {
+            let tex = RandomField::new(0).get(pos) as u8 % 12;
+            BlockMask::new(
+                Block::new(kind, Rgb::new(r, g, b).map(|e: u8| e.saturating_add(tex))),
+                2,
+            )
+        };
+        const EMPTY: BlockMask = BlockMask::nothing();
+        let internal = BlockMask::new(Block::air(SpriteKind::Empty), 2);
+        let stone = make_block(colors.stone, BlockKind::Rock);
+        let wood = make_block(colors.wood, BlockKind::Wood);
+        let roof = make_block(colors.roof, BlockKind::Wood);
+        let floor = make_block(colors.floor, BlockKind::Wood);
+
+        let side_ori = match ori {
+            Ori::East => 0,
+            Ori::North => 2,
+        };
+        let sprite =
+            |kind: SpriteKind, ori| BlockMask::new(Block::air(kind).with_ori(ori).unwrap(), 2);
+
+        // Across and along the dormitory, from its center
+        let (x, y) = (center_offset.x, center_offset.y);
+        // The roof is gabled across the dormitory, overhanging its walls
+        let roof_z = WALL_HEIGHT + locus + 1 - x.abs();
+
+        if z <= 0 - (dist - locus - 1).max(0) && dist < locus + 3 {
+            // Foundations
+            stone.with_priority(1)
+        } else if dist > locus + 1 {
+            EMPTY
+        } else if z == roof_z {
+            roof
+        } else if z > roof_z {
+            EMPTY
+        } else if dist == locus + 1 {
+            // Under the eaves
+            EMPTY
+        } else if z == 0 {
+            floor
+        } else if dist == locus {
+            let end_wall = bound_offset.y > 0;
+            if end_wall && x.abs() <= 1 && z <= 3 {
+                // Doors at both ends
+                internal
+            } else if !end_wall && z == 4 && y.rem_euclid(BUNK_SPACING) == 1 {
+                // Narrow windows between the bunks
+                internal
+            } else if z <= FOOTING_HEIGHT {
+                stone
+            } else {
+                wood
+            }
+        } else if is_bunk(Vec2::new(x, y), locus, len) {
+            // Two beds to each bunk, one above the other
+            match z {
+                1 | 3 => sprite(SpriteKind::Bed, (side_ori + 4 * (x < 0) as u8) % 8),
+                2 => wood,
+                _ => internal,
+            }
+        } else {
+            internal
+        }
+    }
+}
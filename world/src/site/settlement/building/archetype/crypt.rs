@@ -0,0 +1,240 @@
+use super::{super::skeleton::*, spots_around, Archetype};
+use crate::{
+    site::BlockMask,
+    util::{RandomField, Sampler},
+    IndexRef,
+};
+use common::{
+    calendar::Calendar,
+    terrain::{Block, BlockKind, SpriteKind},
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use vek::*;
+
+#[derive(Deserialize)]
+pub struct Colors {
+    pub stone: (u8, u8, u8),
+    pub vault: (u8, u8, u8),
+    pub roof: (u8, u8, u8),
+    pub yard: (u8, u8, u8),
+}
+
+/// A graveyard with a crypt beneath it. Rows of graves fill a walled yard
+/// around a small mausoleum, whose stairs lead down into a vault lined with
+/// burial chambers, each holding a sarcophagus.
+pub struct Crypt;
+
+/// Depth of the floor of the vault below the ground, deeper than other
+/// buildings reach.
+pub const CHAMBER_DEPTH: i32 = 9;
+/// Height of the space within the vault, between its floor and ceiling.
+const VAULT_HEIGHT: i32 = 4;
+/// Half the width of the vault, not counting its walls.
+const VAULT_HALF_WIDTH: i32 = 2;
+/// Distance between the walls separating neighbouring burial chambers.
+const CHAMBER_SPACING: i32 = 4;
+/// Half the width of the mausoleum, walls included.
+const MAUSOLEUM_RADIUS: i32 = 2;
+/// Height of the walls of the mausoleum.
+const MAUSOLEUM_HEIGHT: i32 = 4;
+
+impl Crypt {
+    /// How far along the root branch from its center the vault reaches.
+    fn reach(skel: &Skeleton<()>) -> i32 { skel.root.len / 2 + skel.root.locus - 2 }
+
+    /// Where the grave goods lie, at the far end of the vault, relative to
+    /// the building origin.
+    pub fn loot(&self, skel: &Skeleton<()>) -> Vec<Vec3<i32>> {
+        let pos = skel.from_root_center(Vec2::new(0, -Self::reach(skel)));
+        vec![Vec3::new(pos.x, pos.y, 1 - CHAMBER_DEPTH)]
+    }
+}
+
+impl Archetype for Crypt {
+    type Attr = ();
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        let skel = Skeleton {
+            offset: 0,
+            ori: if rng.gen() { Ori::East } else { Ori::North },
+            root: Branch {
+                // Long enough to leave room for the vault beyond the stairs
+                len: rng.gen_range(6..8) * 2,
+                attr: (),
+                locus: 7,
+                border: 1,
+                children: Vec::new(),
+            },
+        };
+
+        (Self, skel)
+    }
+
+    fn height(&self, _skel: &Skeleton<Self::Attr>) -> i32 { MAUSOLEUM_HEIGHT + 2 }
+
+    fn depth(&self, _skel: &Skeleton<Self::Attr>) -> i32 { CHAMBER_DEPTH + 2 }
+
+    fn sprite_spots(&self, skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> {
+        // The graves in the yard
+        let mut spots = Vec::new();
+        skel.for_each_segment(|segment, _, branch| {
+            spots.extend(spots_around(segment, 0..=branch.locus - 2, &[1]));
+        });
+        spots
+    }
+
+    fn draw(
+        &self,
+        index: IndexRef,
+        pos: Vec3<i32>,
+        dist: i32,
+        bound_offset: Vec2<i32>,
+        center_offset: Vec2<i32>,
+        z: i32,
+        ori: Ori,
+        locus: i32,
+        len: i32,
+        _attr: &Self::Attr,
+    ) -> BlockMask {
+        let colors = &index.colors.site.settlement.building.archetype.crypt;
+
+        let make_block = |(r, g, b), kind| {
+            let tex = RandomField::new(0).get(pos) as u8 % 12;
+            BlockMask::new(
+                Block::new(kind, Rgb::new(r, g, b).map(|e: u8| e.saturating_add(tex))),
+                2,
+            )
+        };
+        const EMPTY: BlockMask = BlockMask::nothing();
+        let internal = BlockMask::new(Block::air(SpriteKind::Empty), 2);
+        let stone = make_block(colors.stone, BlockKind::Rock);
+        let vault = make_block(colors.vault, BlockKind::Rock);
+        let roof = make_block(colors.roof, BlockKind::Rock);
+        let yard = make_block(colors.yard, BlockKind::Grass);
+
+        let gate_ori = match ori {
+            Ori::East => 2,
+            Ori::North => 4,
+        };
+        let sprite =
+            |kind: SpriteKind, ori| BlockMask::new(Block::air(kind).with_ori(ori).unwrap(), 2);
+
+        // Across and along the yard, from its center
+        let (x, y) = (center_offset.x, center_offset.y);
+        let floor = -CHAMBER_DEPTH;
+        let reach = len / 2 + locus - 2;
+        let mausoleum_dist = x.abs().max(y.abs());
+        // The stairs run down from the mausoleum to the near end of the vault
+        let stairs = (floor + 2..=1).contains(&y) && x.abs() <= VAULT_HALF_WIDTH;
+        let step = y - 1;
+        let vault_len = (-reach..=floor + 2).contains(&y);
+
+        if stairs && (step..step + VAULT_HEIGHT).contains(&z) && z <= 0 {
+            if z == step || x.abs() == VAULT_HALF_WIDTH {
+                vault
+            } else {
+                internal
+            }
+        } else if stairs && z == step + VAULT_HEIGHT && z < 0 {
+            vault
+        } else if z < 0 {
+            if !vault_len && y != -reach - 1 {
+                return EMPTY;
+            }
+            let ceiling = floor + VAULT_HEIGHT + 1;
+            if y == -reach - 1 {
+                // The far wall of the vault
+                if x.abs() < locus && (floor..=ceiling).contains(&z) {
+                    vault
+                } else {
+                    EMPTY
+                }
+            } else if x.abs() <= VAULT_HALF_WIDTH {
+                if z == floor || z == ceiling {
+                    vault
+                } else if (floor..ceiling).contains(&z) {
+                    internal
+                } else {
+                    EMPTY
+                }
+            } else if x.abs() == VAULT_HALF_WIDTH + 1 {
+                // Doorways lead from the vault into the burial chambers
+                let doorway = y.rem_euclid(CHAMBER_SPACING) == 2;
+                if doorway && (floor + 1..floor + VAULT_HEIGHT).contains(&z) {
+                    internal
+                } else if (floor..=ceiling).contains(&z) {
+                    vault
+                } else {
+                    EMPTY
+                }
+            } else if x.abs() < locus {
+                let chamber_ceiling = floor + VAULT_HEIGHT;
+                if y.rem_euclid(CHAMBER_SPACING) == 0 || x.abs() == locus - 1 {
+                    // Walls between and around the chambers
+                    if (floor..=chamber_ceiling).contains(&z) {
+                        vault
+                    } else {
+                        EMPTY
+                    }
+                } else if z == floor || z == chamber_ceiling {
+                    vault
+                } else if x.abs() == locus - 2 && z == floor + 1 {
+                    // Sarcophagi along the outer walls of the chambers
+                    stone
+                } else if x.abs() == locus - 2
+                    && z == floor + 2
+                    && y.rem_euclid(CHAMBER_SPACING) == 2
+                {
+                    sprite(SpriteKind::Bones, gate_ori)
+                } else if (floor..chamber_ceiling).contains(&z) {
+                    internal
+                } else {
+                    EMPTY
+                }
+            } else {
+                EMPTY
+            }
+        } else if z == 0 {
+            if mausoleum_dist <= MAUSOLEUM_RADIUS {
+                stone
+            } else if dist <= locus {
+                yard
+            } else {
+                EMPTY
+            }
+        } else if mausoleum_dist <= MAUSOLEUM_RADIUS {
+            if z <= MAUSOLEUM_HEIGHT {
+                let door = y == MAUSOLEUM_RADIUS && x.abs() <= 1 && z <= 3;
+                if mausoleum_dist == MAUSOLEUM_RADIUS && !door {
+                    stone
+                } else {
+                    internal
+                }
+            } else if z == MAUSOLEUM_HEIGHT + 1 {
+                roof
+            } else if z == MAUSOLEUM_HEIGHT + 2 && mausoleum_dist == 0 {
+                stone
+            } else {
+                EMPTY
+            }
+        } else if dist == locus && z == 1 {
+            // A low wall around the yard, with a gate at the near end
+            let gate = bound_offset.y > 0 && y > 0 && x.abs() <= 1;
+            if gate { internal } else { stone }
+        } else if dist < locus - 1 && z == 1 && mausoleum_dist > MAUSOLEUM_RADIUS + 1 {
+            // Rows of graves either side of the path to the mausoleum
+            if x.abs() < 2 || x.rem_euclid(2) != 0 {
+                EMPTY
+            } else if y.rem_euclid(3) == 0 {
+                sprite(SpriteKind::Gravestone, gate_ori)
+            } else if y.rem_euclid(3) == 1 {
+                sprite(SpriteKind::Grave, gate_ori)
+            } else {
+                EMPTY
+            }
+        } else {
+            EMPTY
+        }
+    }
+}
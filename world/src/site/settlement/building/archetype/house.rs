@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use super::{super::skeleton::*, Archetype};
+use super::{super::skeleton::*, spots_around, Archetype};
 use crate::{
     site::BlockMask,
     util::{RandomField, Sampler},
@@ -12,7 +12,7 @@ use common::{
     terrain::{Block, BlockKind, SpriteKind},
 };
 use rand::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use vek::*;
 
 #[derive(Deserialize)]
@@ -20,6 +20,7 @@ pub struct Colors {
     pub foundation: (u8, u8, u8),
     pub floor: (u8, u8, u8),
     pub roof: roof_color::PureCases<(u8, u8, u8)>,
+    pub thatch: (u8, u8, u8),
     pub wall: wall_color::PureCases<(u8, u8, u8)>,
     pub support: support_color::PureCases<(u8, u8, u8)>,
 }
@@ -103,12 +104,60 @@ const SUPPORT_COLORS: [SupportColor; support_color::NUM_VARIANTS] = [
     SupportColor::Support4,
 ];
 
+const STOREY_HEIGHT: i32 = 6;
+/// How far roofs reach out beyond the walls beneath them, as eaves. Branches
+/// have a wide enough border to hold them.
+const EAVES: i32 = 2;
+/// Approximate distance between the windows along the side of a house.
+const SIDE_WINDOW_SPACING: i32 = 8;
+/// Height of the sign hanging beside the door of a house that has one.
+const SIGN_HEIGHT: i32 = 3;
+/// The share of ground floor side windows with a flower box beneath them.
+const FLOWER_BOX_CHANCE: f32 = 0.5;
+/// Distance from the middle of a tower to its walls.
+const TOWER_WIDTH: i32 = 4;
+
 pub struct House {
     pub colors: ColorTheme,
     pub noise: RandomField,
     pub roof_ribbing: bool,
     pub roof_ribbing_diagonal: bool,
     pub christmas_decorations: bool,
+    pub porch: Option<Porch>,
+}
+
+/// A small roofed platform extruded from the outermost wall on one side of a
+/// house.
+#[derive(Copy, Clone)]
+pub struct Porch {
+    /// Footprint of the porch, relative to the house origin.
+    pub aabr: Aabr<i32>,
+}
+
+impl Porch {
+    pub const DEPTH: i32 = 4;
+    pub const HALF_WIDTH: i32 = 3;
+    pub const HEIGHT: i32 = 4;
+
+    pub fn facing(skel: &Skeleton<Attr>, dir: Vec2<i32>) -> Self {
+        // Find the branch whose wall reaches furthest in `dir`
+        let mut outermost = None::<(i32, Vec2<i32>)>;
+        skel.for_each(|node, ori, branch, _, _| {
+            let node2 = node + ori.dir() * branch.len;
+            let extent = node.dot(dir).max(node2.dot(dir)) + branch.locus - 1;
+            if outermost.map_or(true, |(e, _)| extent > e) {
+                outermost = Some((extent, (node + node2) / 2));
+            }
+        });
+        let (extent, center) = outermost.unwrap_or((0, Vec2::zero()));
+
+        let wall = center + dir * (extent - center.dot(dir));
+        let side = Vec2::new(-dir.y, dir.x) * Self::HALF_WIDTH;
+        Self {
+            aabr: Aabr::new_empty(wall + dir + side)
+                .expanded_to_contain_point(wall + dir * Self::DEPTH - side),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -118,11 +167,15 @@ pub enum Pillar {
     Tower(i32),
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RoofStyle {
     Hip,
     Gable,
     Rounded,
+    /// A hip roof of straw, without ribbing or eaves.
+    Thatch,
+    /// A flat roof just above the top storey.
+    Flat,
 }
 
 #[derive(Copy, Clone)]
@@ -147,6 +200,8 @@ pub struct Attr {
     pub pillar: Pillar,
     pub levels: i32,
     pub window: SpriteKind,
+    /// The sign hanging beside the door at the end of the branch, if any.
+    pub sign: Option<SpriteKind>,
 }
 
 impl Attr {
@@ -175,8 +230,52 @@ impl Attr {
                 2 => SpriteKind::Window3,
                 _ => SpriteKind::Window4,
             },
+            sign: None,
         }
     }
+
+    /// Height of the top of a pitched roof over walls `width` from the middle
+    /// of the branch.
+    fn roof_top(&self, width: i32) -> i32 { STOREY_HEIGHT * self.levels + 2 + width }
+
+    /// Height of the highest point of a roof `roof_top` high over walls
+    /// `width` from its middle, in the roof style of the branch. Flat roofs lie
+    /// as low as pitched roofs get at the walls.
+    fn roof_peak(&self, roof_top: i32, width: i32) -> i32 {
+        match self.roof_style {
+            RoofStyle::Flat => roof_top - width,
+            _ => roof_top,
+        }
+    }
+}
+
+impl House {
+    /// Generate a tavern: a house with a long, two-storey hall and a chimney
+    /// for its kitchen.
+    pub fn generate_tavern<R: Rng>(
+        rng: &mut R,
+        calendar: Option<&Calendar>,
+    ) -> (Self, Skeleton<Attr>) {
+        let (this, mut skel) = Self::generate(rng, calendar);
+        // Wings stay within the old length, and no taller than the hall
+        skel.root.len = skel.root.len.max(rng.gen_range(16..22));
+        skel.root.attr.levels = 2;
+        skel.root.attr.pillar = Pillar::Chimney(rng.gen_range(3..6));
+        skel.root.attr.sign = Some(SpriteKind::HangingSign);
+        (this, skel)
+    }
+
+    /// Where the sign of the house hangs, relative to the house origin: on
+    /// the outside of the end wall with the door of the main branch, just
+    /// beyond the door. Only houses with a sign have one.
+    pub fn sign(&self, skel: &Skeleton<Attr>) -> Option<Vec3<i32>> {
+        skel.root.attr.sign?;
+        let width = skel.root.locus - 1;
+        let (along, across) = (skel.ori.dir(), skel.ori.flip().dir());
+        let end = along * (skel.offset + skel.root.len);
+        let pos = end + along * (width + 1) + across * ((width - 1) / 2 + 2);
+        Some(Vec3::new(pos.x, pos.y, SIGN_HEIGHT))
+    }
 }
 
 impl Archetype for House {
@@ -243,22 +342,73 @@ impl Archetype for House {
             christmas_decorations: calendar
                 .map(|c| c.is_event(CalendarEvent::Christmas))
                 .unwrap_or_default(),
+            porch: None,
         };
 
         (this, skel)
     }
 
+    fn bounds(&self, skel: &Skeleton<Self::Attr>) -> Aabr<i32> {
+        let bounds = skel.bounds();
+        self.porch.map_or(bounds, |porch| bounds.union(porch.aabr))
+    }
+
+    fn height(&self, skel: &Skeleton<Self::Attr>) -> i32 {
+        let mut height = 0;
+        skel.for_each(|_, _, branch, _, _| {
+            let attr = &branch.attr;
+            let roof_top = attr.roof_top(branch.locus);
+            let pillar_top = match attr.pillar {
+                Pillar::None => 0,
+                Pillar::Chimney(chimney_height) => roof_top + chimney_height,
+                Pillar::Tower(tower_height) => attr.roof_peak(roof_top + tower_height, TOWER_WIDTH),
+            };
+            height = height
+                .max(attr.roof_peak(roof_top, branch.locus))
+                .max(pillar_top);
+        });
+        height
+    }
+
+    fn sprite_spots(&self, skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> {
+        let mut spots = Vec::new();
+        skel.for_each_segment(|segment, _, branch| {
+            let attr = &branch.attr;
+            // Furniture against the walls just above each floor, and ornaments
+            // on the outside of the walls a little higher up
+            let zs = (0..attr.levels)
+                .flat_map(|storey| [1, 3].map(|z| STOREY_HEIGHT * storey + z))
+                .collect::<Vec<_>>();
+            spots.extend(spots_around(segment, branch.locus - 2..=branch.locus, &zs));
+            // Workshops fill open ground floors
+            if !attr.storey_fill.has_lower() {
+                spots.extend(spots_around(segment, 0..=branch.locus - 2, &[1]));
+            }
+            let center = segment.center();
+            match attr.pillar {
+                // The fire in the hearth
+                Pillar::Chimney(_) => spots.push(Vec3::new(center.x, center.y, 1)),
+                Pillar::Tower(_) => {
+                    spots.extend(spots_around(Aabr::new_empty(center), 3..=5, &zs));
+                },
+                Pillar::None => {},
+            }
+        });
+        spots.extend(self.sign(skel));
+        spots
+    }
+
     fn draw(
         &self,
         index: IndexRef,
-        _pos: Vec3<i32>,
+        pos: Vec3<i32>,
         dist: i32,
         bound_offset: Vec2<i32>,
         center_offset: Vec2<i32>,
         z: i32,
         ori: Ori,
         locus: i32,
-        _len: i32,
+        len: i32,
         attr: &Self::Attr,
     ) -> BlockMask {
         let colors = &index.colors.site.settlement.building.archetype.house;
@@ -295,6 +445,25 @@ impl Archetype for House {
         let floor = make_block(colors.floor);
         let wall = make_block(wall_color).with_priority(facade_layer);
         let roof = make_block(roof_color).with_priority(facade_layer - 1);
+        let thatch = make_block(colors.thatch).with_priority(facade_layer - 1);
+
+        if let Some(porch) = self
+            .porch
+            .filter(|porch| porch.aabr.contains_point(pos.xy()))
+        {
+            let is_post = (pos.x == porch.aabr.min.x || pos.x == porch.aabr.max.x)
+                && (pos.y == porch.aabr.min.y || pos.y == porch.aabr.max.y);
+            if z == 0 {
+                // Porch decking
+                return floor;
+            } else if z == Porch::HEIGHT {
+                // Porch roof
+                return roof;
+            } else if z > 0 && z < Porch::HEIGHT && is_post {
+                return log;
+            }
+        }
+
         const EMPTY: BlockMask = BlockMask::nothing();
         // TODO: Take environment into account.
         let internal = BlockMask::new(Block::air(SpriteKind::Empty), internal_layer);
@@ -306,9 +475,13 @@ impl Archetype for House {
             Block::air(attr.window).with_ori(end_ori).unwrap(),
             structural_layer,
         );
+        let side_window = BlockMask::new(
+            Block::air(attr.window).with_ori((end_ori + 2) % 8).unwrap(),
+            structural_layer,
+        );
         let fire = BlockMask::new(Block::air(SpriteKind::Ember), foundation_layer);
 
-        let storey_height = 6;
+        let storey_height = STOREY_HEIGHT;
         let storey = ((z - 1) / storey_height).min(attr.levels - 1);
         let floor_height = storey_height * storey;
         let ceil_height = storey_height * (storey + 1);
@@ -320,7 +493,7 @@ impl Archetype for House {
             lower_width
         };
         let foundation_height = 0 - (dist - width - 1).max(0);
-        let roof_top = storey_height * attr.levels + 2 + width;
+        let roof_top = attr.roof_top(width);
 
         let edge_ori = if bound_offset.x.abs() > bound_offset.y.abs() {
             if center_offset.x > 0 { 6 } else { 2 }
@@ -383,160 +556,205 @@ impl Archetype for House {
             };
         }
 
+        // A sign hanging beside the door, in the end wall it shares
+        if let Some(sign) = attr.sign {
+            if bound_offset.y == lower_width + 1
+                && center_offset.y > 0
+                && center_offset.x == (lower_width - 1) / 2 + 2
+                && profile.y == SIGN_HEIGHT
+            {
+                return BlockMask::new(
+                    Block::air(sign).with_ori((edge_ori + 4) % 8).unwrap(),
+                    internal_layer,
+                );
+            }
+        }
+
+        // Where this column lies relative to the nearest of the windows
+        // spaced regularly along a side wall of `wall_len`
+        let side_window_slot = |wall_len: i32| {
+            let windows = (wall_len / SIDE_WINDOW_SPACING).max(1);
+            let spacing = wall_len / windows;
+            let along = center_offset.y + wall_len / 2;
+            along - (along / spacing).clamped(0, windows - 1) * spacing - spacing / 2
+        };
+
         // Roofs and walls
-        let do_roof_wall =
-            |profile: Vec2<i32>, width, dist, bound_offset: Vec2<i32>, roof_top, mansard| {
-                // Roof
-
-                let (roof_profile, roof_dist) = match &attr.roof_style {
-                    RoofStyle::Hip => (Vec2::new(dist, profile.y), dist),
-                    RoofStyle::Gable => (profile, dist),
-                    RoofStyle::Rounded => {
-                        let circular_dist = (bound_offset.map(|e| e.pow(4) as f32).sum().powf(0.25)
-                            - 0.5)
-                            .ceil() as i32;
-                        (Vec2::new(circular_dist, profile.y), circular_dist)
-                    },
-                };
+        let do_roof_wall = |profile: Vec2<i32>,
+                            width,
+                            dist,
+                            bound_offset: Vec2<i32>,
+                            roof_top,
+                            mansard,
+                            wall_len| {
+            // Roof
+
+            let (roof_profile, roof_dist) = match &attr.roof_style {
+                RoofStyle::Hip | RoofStyle::Thatch => (Vec2::new(dist, profile.y), dist),
+                RoofStyle::Gable => (profile, dist),
+                RoofStyle::Rounded => {
+                    let circular_dist = (bound_offset.map(|e| e.pow(4) as f32).sum().powf(0.25)
+                        - 0.5)
+                        .ceil() as i32;
+                    (Vec2::new(circular_dist, profile.y), circular_dist)
+                },
+                // As low as a pitched roof gets at the walls
+                RoofStyle::Flat => (Vec2::new(width, profile.y), dist),
+            };
 
-                let roof_level = roof_top - roof_profile.x.max(mansard);
+            let roof_level = roof_top - roof_profile.x.max(mansard);
 
-                if profile.y > roof_level {
-                    return EMPTY;
+            if profile.y > roof_level {
+                return EMPTY;
+            }
+
+            // Roof
+            if profile.y == roof_level && roof_dist <= width + EAVES {
+                if attr.roof_style == RoofStyle::Thatch {
+                    return thatch;
                 }
+                let is_ribbing = ((profile.y - ceil_height) % 3 == 0 && self.roof_ribbing)
+                    || (bound_offset.x == bound_offset.y && self.roof_ribbing_diagonal);
+                return if (roof_profile.x == 0 && mansard == 0)
+                    || roof_dist == width + EAVES
+                    || is_ribbing
+                {
+                    // Eaves
+                    log
+                } else {
+                    roof
+                };
+            }
+
+            // Wall
 
-                // Roof
-                if profile.y == roof_level && roof_dist <= width + 2 {
-                    let is_ribbing = ((profile.y - ceil_height) % 3 == 0 && self.roof_ribbing)
-                        || (bound_offset.x == bound_offset.y && self.roof_ribbing_diagonal);
-                    return if (roof_profile.x == 0 && mansard == 0)
-                        || roof_dist == width + 2
-                        || is_ribbing
+            if dist == width && profile.y < roof_level {
+                // Doors
+                if center_offset.x > 0
+                    && center_offset.y > 0
+                    && bound_offset.x > 0
+                    && bound_offset.x < width
+                    && profile.y < ceil_height
+                    && attr.storey_fill.has_lower()
+                    && storey == 0
+                {
+                    return if (bound_offset.x == (width - 1) / 2
+                        || bound_offset.x == (width - 1) / 2 + 1)
+                        && profile.y <= foundation_height + 3
                     {
-                        // Eaves
-                        log
+                        // Doors on first floor only
+                        if profile.y == foundation_height + 1 {
+                            BlockMask::new(
+                                Block::air(SpriteKind::Door)
+                                    .with_ori(
+                                        match ori {
+                                            Ori::East => 2,
+                                            Ori::North => 0,
+                                        } + if bound_offset.x == (width - 1) / 2 {
+                                            0
+                                        } else {
+                                            4
+                                        },
+                                    )
+                                    .unwrap(),
+                                structural_layer,
+                            )
+                        } else {
+                            EMPTY.with_priority(structural_layer)
+                        }
                     } else {
-                        roof
+                        wall
                     };
                 }
 
-                // Wall
-
-                if dist == width && profile.y < roof_level {
-                    // Doors
-                    if center_offset.x > 0
-                        && center_offset.y > 0
-                        && bound_offset.x > 0
-                        && bound_offset.x < width
-                        && profile.y < ceil_height
-                        && attr.storey_fill.has_lower()
-                        && storey == 0
-                    {
-                        return if (bound_offset.x == (width - 1) / 2
-                            || bound_offset.x == (width - 1) / 2 + 1)
-                            && profile.y <= foundation_height + 3
-                        {
-                            // Doors on first floor only
-                            if profile.y == foundation_height + 1 {
-                                BlockMask::new(
-                                    Block::air(SpriteKind::Door)
-                                        .with_ori(
-                                            match ori {
-                                                Ori::East => 2,
-                                                Ori::North => 0,
-                                            } + if bound_offset.x == (width - 1) / 2 {
-                                                0
-                                            } else {
-                                                4
-                                            },
-                                        )
-                                        .unwrap(),
-                                    structural_layer,
-                                )
-                            } else {
-                                EMPTY.with_priority(structural_layer)
-                            }
-                        } else {
-                            wall
+                return if bound_offset.x == bound_offset.y || profile.y == ceil_height {
+                    // Support beams
+                    log
+                } else if !attr.storey_fill.has_lower() && profile.y < ceil_height
+                    || !attr.storey_fill.has_upper()
+                {
+                    EMPTY
+                } else {
+                    let (frame_bounds, frame_borders) = if profile.y >= ceil_height {
+                        (
+                            Aabr {
+                                min: Vec2::new(-1, ceil_height + 2),
+                                max: Vec2::new(1, ceil_height + 5),
+                            },
+                            Vec2::new(1, 1),
+                        )
+                    } else {
+                        (
+                            Aabr {
+                                min: Vec2::new(2, floor_height + 2),
+                                max: Vec2::new(width - 2, ceil_height - 2),
+                            },
+                            Vec2::new(1, 0),
+                        )
+                    };
+                    let window_bounds = Aabr {
+                        min: (frame_bounds.min + frame_borders)
+                            .map2(frame_bounds.center(), |a, b| a.min(b)),
+                        max: (frame_bounds.max - frame_borders)
+                            .map2(frame_bounds.center(), |a, b| a.max(b)),
+                    };
+
+                    // Windows spaced regularly along the side walls, with
+                    // more of them on longer walls
+                    if bound_offset.y == 0 && profile.x == width && wall_len >= 5 {
+                        let slot = side_window_slot(wall_len);
+                        let height = profile.y - floor_height;
+                        if (-1..=0).contains(&slot) && (2..=3).contains(&height) {
+                            return side_window;
+                        } else if (-2..=1).contains(&slot) && (1..=4).contains(&height) {
+                            return log.with_priority(structural_layer);
+                        }
+                    }
+
+                    // Window
+                    if (frame_bounds.size() + 1).reduce_min() > 2 {
+                        // Window frame is large enough for a window
+                        let surface_pos = Vec2::new(bound_offset.x, profile.y);
+                        if window_bounds.contains_point(surface_pos) {
+                            return end_window;
+                        } else if frame_bounds.contains_point(surface_pos) {
+                            return log.with_priority(structural_layer);
                         };
                     }
 
-                    return if bound_offset.x == bound_offset.y || profile.y == ceil_height {
+                    // Wall
+                    if attr.central_supports && profile.x == 0 {
                         // Support beams
-                        log
-                    } else if !attr.storey_fill.has_lower() && profile.y < ceil_height
-                        || !attr.storey_fill.has_upper()
-                    {
-                        EMPTY
+                        log.with_priority(structural_layer)
                     } else {
-                        let (frame_bounds, frame_borders) = if profile.y >= ceil_height {
-                            (
-                                Aabr {
-                                    min: Vec2::new(-1, ceil_height + 2),
-                                    max: Vec2::new(1, ceil_height + 5),
-                                },
-                                Vec2::new(1, 1),
-                            )
-                        } else {
-                            (
-                                Aabr {
-                                    min: Vec2::new(2, floor_height + 2),
-                                    max: Vec2::new(width - 2, ceil_height - 2),
-                                },
-                                Vec2::new(1, 0),
-                            )
-                        };
-                        let window_bounds = Aabr {
-                            min: (frame_bounds.min + frame_borders)
-                                .map2(frame_bounds.center(), |a, b| a.min(b)),
-                            max: (frame_bounds.max - frame_borders)
-                                .map2(frame_bounds.center(), |a, b| a.max(b)),
-                        };
-
-                        // Window
-                        if (frame_bounds.size() + 1).reduce_min() > 2 {
-                            // Window frame is large enough for a window
-                            let surface_pos = Vec2::new(bound_offset.x, profile.y);
-                            if window_bounds.contains_point(surface_pos) {
-                                return end_window;
-                            } else if frame_bounds.contains_point(surface_pos) {
-                                return log.with_priority(structural_layer);
-                            };
-                        }
-
-                        // Wall
-                        if attr.central_supports && profile.x == 0 {
-                            // Support beams
-                            log.with_priority(structural_layer)
-                        } else {
-                            wall
-                        }
-                    };
-                }
+                        wall
+                    }
+                };
+            }
 
-                if dist < width {
-                    // Internals
-                    if profile.y == ceil_height {
-                        if profile.x == 0 {
-                            // Rafters
-                            return log;
-                        } else if attr.storey_fill.has_upper() {
-                            // Ceiling
-                            return floor;
-                        }
-                    } else if !attr.storey_fill.has_lower()
-                        && center_offset.sum() % 2 == 0
-                        && profile.y == 1
-                        && center_offset.map(|e| e % 3 == 0).reduce_and()
-                        && self
+            if dist < width {
+                // Internals
+                if profile.y == ceil_height {
+                    if profile.x == 0 {
+                        // Rafters
+                        return log;
+                    } else if attr.storey_fill.has_upper() {
+                        // Ceiling
+                        return floor;
+                    }
+                } else if !attr.storey_fill.has_lower()
+                    && center_offset.sum() % 2 == 0
+                    && profile.y == 1
+                    && center_offset.map(|e| e % 3 == 0).reduce_and()
+                    && self
+                        .noise
+                        .chance(Vec3::new(center_offset.x, center_offset.y, z), 0.8)
+                {
+                    let furniture =
+                        match self
                             .noise
-                            .chance(Vec3::new(center_offset.x, center_offset.y, z), 0.8)
-                    {
-                        let furniture = match self.noise.get(Vec3::new(
-                            center_offset.x,
-                            center_offset.y,
-                            z + 100,
-                        )) % 14
+                            .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
+                            % 14
                         {
                             0..=1 => SpriteKind::Crate,
                             2 => SpriteKind::Bench,
@@ -554,33 +772,39 @@ impl Archetype for House {
                             _ => unreachable!(),
                         };
 
-                        return BlockMask::new(Block::air(furniture).with_ori(end_ori).unwrap(), 1);
-                    } else if (!attr.storey_fill.has_lower() && profile.y < ceil_height)
-                        || (!attr.storey_fill.has_upper() && profile.y >= ceil_height)
-                    {
-                        return EMPTY;
-                    // Furniture
-                    } else if dist == width - 1
-                        && center_offset.sum() % 2 == 0
-                        && profile.y == floor_height + 1
-                        && self
+                    return BlockMask::new(Block::air(furniture).with_ori(end_ori).unwrap(), 1);
+                } else if (!attr.storey_fill.has_lower() && profile.y < ceil_height)
+                    || (!attr.storey_fill.has_upper() && profile.y >= ceil_height)
+                {
+                    return EMPTY;
+                // Furniture
+                } else if dist == width - 1
+                    && center_offset.sum() % 2 == 0
+                    && profile.y == floor_height + 1
+                    && self
+                        .noise
+                        .chance(Vec3::new(center_offset.x, center_offset.y, z), 0.2)
+                {
+                    // Derived from the noise rather than a thread rng so that the
+                    // settlement's interactables can be reproduced outside of rendering.
+                    let rare = |salt, chance| {
+                        self.noise.chance(
+                            Vec3::new(center_offset.x, center_offset.y, z + salt),
+                            chance,
+                        )
+                    };
+                    let furniture =
+                        match self
                             .noise
-                            .chance(Vec3::new(center_offset.x, center_offset.y, z), 0.2)
-                    {
-                        // NOTE: Used only for dynamic elements like chests and entities!
-                        let mut dynamic_rng = thread_rng();
-                        let furniture = match self.noise.get(Vec3::new(
-                            center_offset.x,
-                            center_offset.y,
-                            z + 100,
-                        )) % 12
+                            .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
+                            % 12
                         {
                             0 => SpriteKind::Planter,
                             1 => SpriteKind::ChairSingle,
                             2 => SpriteKind::ChairDouble,
                             3 => SpriteKind::CoatRack,
                             4 => {
-                                if dynamic_rng.gen_range(0..8) == 0 {
+                                if rare(200, 1.0 / 8.0) {
                                     SpriteKind::Chest
                                 } else {
                                     SpriteKind::Crate
@@ -591,14 +815,14 @@ impl Archetype for House {
                             8 => SpriteKind::TableSide,
                             9 => SpriteKind::WardrobeSingle,
                             10 => {
-                                if dynamic_rng.gen_range(0..10) == 0 {
+                                if rare(300, 1.0 / 10.0) {
                                     SpriteKind::PotionMinor
                                 } else {
                                     SpriteKind::VialEmpty
                                 }
                             },
                             _ => {
-                                if dynamic_rng.gen_range(0..2) == 0 {
+                                if rare(400, 0.5) {
                                     SpriteKind::Bowl
                                 } else {
                                     SpriteKind::Pot
@@ -606,58 +830,90 @@ impl Archetype for House {
                             },
                         };
 
-                        return BlockMask::new(
-                            Block::air(furniture).with_ori(edge_ori).unwrap(),
-                            internal_layer,
-                        );
-                    } else {
-                        return internal;
-                    }
+                    return BlockMask::new(
+                        Block::air(furniture).with_ori(edge_ori).unwrap(),
+                        internal_layer,
+                    );
+                } else {
+                    return internal;
                 }
+            }
 
-                // Wall ornaments
-                if dist == width + 1
-                    && center_offset.map(|e| e.abs()).reduce_min() == 0
-                    && profile.y == floor_height + 3
-                    && self.noise.chance(
-                        Vec3::new(center_offset.x, center_offset.y, z),
-                        if christmas_theme { 0.70 } else { 0.35 },
-                    )
-                    && attr.storey_fill.has_lower()
-                {
-                    let ornament = if christmas_theme {
-                        match self
-                            .noise
-                            .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
-                            % 4
-                        {
-                            0 => SpriteKind::ChristmasWreath,
-                            _ => SpriteKind::ChristmasOrnament,
-                        }
-                    } else {
-                        match self
-                            .noise
-                            .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
-                            % 6
-                        {
-                            0 => SpriteKind::HangingSign,
-                            1..=3 => SpriteKind::HangingBasket,
-                            4 => SpriteKind::WallSconce,
-                            5 => SpriteKind::WallLampSmall,
-                            _ => SpriteKind::DungeonWallDecor,
-                        }
-                    };
+            // Flower boxes beneath the windows of the ground floor
+            if bound_offset.y == 0
+                && profile.x == width + 1
+                && wall_len >= 5
+                && profile.y == 1
+                && attr.storey_fill.has_lower()
+                && (-1..=0).contains(&side_window_slot(wall_len))
+                && self.noise.chance(
+                    Vec3::new(
+                        center_offset.x,
+                        center_offset.y - side_window_slot(wall_len),
+                        z,
+                    ),
+                    FLOWER_BOX_CHANCE,
+                )
+            {
+                return BlockMask::new(
+                    Block::air(SpriteKind::Planter)
+                        .with_ori((edge_ori + 4) % 8)
+                        .unwrap(),
+                    internal_layer,
+                );
+            }
 
-                    BlockMask::new(
-                        Block::air(ornament).with_ori((edge_ori + 4) % 8).unwrap(),
-                        internal_layer,
-                    )
+            // Wall ornaments
+            if dist == width + 1
+                && center_offset.map(|e| e.abs()).reduce_min() == 0
+                && profile.y == floor_height + 3
+                && self.noise.chance(
+                    Vec3::new(center_offset.x, center_offset.y, z),
+                    if christmas_theme { 0.70 } else { 0.35 },
+                )
+                && attr.storey_fill.has_lower()
+            {
+                let ornament = if christmas_theme {
+                    match self
+                        .noise
+                        .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
+                        % 4
+                    {
+                        0 => SpriteKind::ChristmasWreath,
+                        _ => SpriteKind::ChristmasOrnament,
+                    }
                 } else {
-                    EMPTY
-                }
-            };
+                    match self
+                        .noise
+                        .get(Vec3::new(center_offset.x, center_offset.y, z + 100))
+                        % 6
+                    {
+                        // Signs are left to the houses they belong to
+                        0..=3 => SpriteKind::HangingBasket,
+                        4 => SpriteKind::WallSconce,
+                        5 => SpriteKind::WallLampSmall,
+                        _ => SpriteKind::DungeonWallDecor,
+                    }
+                };
+
+                BlockMask::new(
+                    Block::air(ornament).with_ori((edge_ori + 4) % 8).unwrap(),
+                    internal_layer,
+                )
+            } else {
+                EMPTY
+            }
+        };
 
-        let mut cblock = do_roof_wall(profile, width, dist, bound_offset, roof_top, attr.mansard);
+        let mut cblock = do_roof_wall(
+            profile,
+            width,
+            dist,
+            bound_offset,
+            roof_top,
+            attr.mansard,
+            len.abs(),
+        );
 
         if let Pillar::Tower(tower_height) = attr.pillar {
             let tower_top = roof_top + tower_height;
@@ -666,11 +922,12 @@ impl Archetype for House {
 
             cblock = cblock.resolve_with(do_roof_wall(
                 profile,
-                4,
+                TOWER_WIDTH,
                 dist,
                 center_offset.map(|e| e.abs()),
                 tower_top,
                 attr.mansard,
+                0,
             ));
         }
 
@@ -1,4 +1,4 @@
-use super::{super::skeleton::*, Archetype};
+use super::{super::skeleton::*, spots_around, Archetype};
 use crate::{
     site::BlockMask,
     util::{RandomField, Sampler},
@@ -10,7 +10,7 @@ use common::{
     terrain::{Block, BlockKind, SpriteKind},
 };
 use rand::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use vek::*;
 
 #[derive(Deserialize)]
@@ -25,8 +25,20 @@ pub struct Colors {
 pub struct Keep {
     pub flag_color: FlagColor,
     pub stone_color: StoneColor,
+    /// Thickness of the walls of the hall.
+    pub wall_thickness: i32,
 }
 
+const STOREY_HEIGHT: i32 = 9;
+/// Height of the torches on the outside of the walls, above each floor.
+const TORCH_HEIGHT: i32 = 4;
+/// Distance between the torches along the sides of the hall.
+const TORCH_SPACING: i32 = 8;
+/// Height of the tallest ramparts above the roof.
+const RAMPART_HEIGHT: i32 = 4;
+/// Height of the flag poles above the roofs of towers.
+const POLE_HEIGHT: i32 = 16;
+
 pub struct Attr {
     pub storeys: i32,
     pub is_tower: bool,
@@ -36,6 +48,22 @@ pub struct Attr {
     pub has_doors: bool,
 }
 
+impl Attr {
+    /// Height of the roof above the ground floor.
+    fn roof_height(&self) -> i32 { self.storeys * STOREY_HEIGHT }
+
+    /// Height of the top of the branch above the ground floor: that of its
+    /// flag pole if it has one, or else of its ramparts.
+    fn top(&self) -> i32 {
+        self.roof_height()
+            + if self.is_tower && self.flag {
+                POLE_HEIGHT
+            } else {
+                RAMPART_HEIGHT
+            }
+    }
+}
+
 make_case_elim!(
     flag_color,
     #[repr(u32)]
@@ -54,15 +82,70 @@ make_case_elim!(
     }
 );
 
-impl Archetype for Keep {
-    type Attr = Attr;
+/// How grand a keep is, chosen to suit the size of its settlement.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum KeepTier {
+    /// A small fort with a single tower.
+    Fort,
+    /// A hall with a single tower.
+    #[default]
+    Hold,
+    /// A large, tall hall with thick walls and a tower at either end.
+    Castle,
+}
 
-    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
-        let len = rng.gen_range(-8..24).max(0);
-        let storeys = rng.gen_range(1..3);
+impl KeepTier {
+    /// The next smaller tier, if any.
+    pub fn smaller(self) -> Option<Self> {
+        match self {
+            KeepTier::Fort => None,
+            KeepTier::Hold => Some(KeepTier::Fort),
+            KeepTier::Castle => Some(KeepTier::Hold),
+        }
+    }
+}
+
+impl Keep {
+    pub fn generate_tier<R: Rng>(rng: &mut R, tier: KeepTier) -> (Self, Skeleton<Attr>) {
+        let (max_len, storeys, locus, tower_locus, wall_thickness) = match tier {
+            KeepTier::Fort => (12, 1..2, 8..11, 6..8, 2),
+            KeepTier::Hold => (24, 1..3, 10..15, 6..9, 2),
+            KeepTier::Castle => (32, 2..4, 13..18, 7..10, 3),
+        };
+        let len = rng.gen_range(-8..max_len).max(match tier {
+            KeepTier::Castle => 8,
+            _ => 0,
+        });
+        let storeys = rng.gen_range(storeys);
+        let tower = |rng: &mut R, offset| {
+            (offset, Branch {
+                len: 0,
+                attr: Attr {
+                    storeys: storeys + rng.gen_range(1..3),
+                    is_tower: true,
+                    flag: true,
+                    ridged: false,
+                    rounded: true,
+                    has_doors: false,
+                },
+                locus: rng.gen_range(tower_locus.clone()),
+                border: 3,
+                children: Vec::new(),
+            })
+        };
+        let offset = -rng.gen_range(0..len + 7).clamped(0, len);
+        let ori = if rng.gen() { Ori::East } else { Ori::North };
+        let hall_locus = rng.gen_range(locus);
+        let towers = match tier {
+            KeepTier::Fort | KeepTier::Hold => {
+                let offset = rng.gen_range(-5..len + 5).clamped(0, len.max(1) - 1);
+                vec![tower(rng, offset)]
+            },
+            KeepTier::Castle => vec![tower(rng, 0), tower(rng, len)],
+        };
         let skel = Skeleton {
-            offset: -rng.gen_range(0..len + 7).clamped(0, len),
-            ori: if rng.gen() { Ori::East } else { Ori::North },
+            offset,
+            ori,
             root: Branch {
                 len,
                 attr: Attr {
@@ -73,29 +156,9 @@ impl Archetype for Keep {
                     rounded: true,
                     has_doors: true,
                 },
-                locus: 10 + rng.gen_range(0..5),
+                locus: hall_locus,
                 border: 3,
-                children: (0..1)
-                    .map(|_| {
-                        (
-                            rng.gen_range(-5..len + 5).clamped(0, len.max(1) - 1),
-                            Branch {
-                                len: 0,
-                                attr: Attr {
-                                    storeys: storeys + rng.gen_range(1..3),
-                                    is_tower: true,
-                                    flag: true,
-                                    ridged: false,
-                                    rounded: true,
-                                    has_doors: false,
-                                },
-                                locus: 6 + rng.gen_range(0..3),
-                                border: 3,
-                                children: Vec::new(),
-                            },
-                        )
-                    })
-                    .collect(),
+                children: towers,
             },
         };
 
@@ -103,11 +166,65 @@ impl Archetype for Keep {
             Self {
                 flag_color: FlagColor::Good,
                 stone_color: StoneColor::Good,
+                wall_thickness,
             },
             skel,
         )
     }
 
+    /// Where the treasury chest stands, in the middle of the great hall,
+    /// relative to the building origin.
+    pub fn treasury(&self, skel: &Skeleton<Attr>) -> Vec3<i32> {
+        Vec3::from(skel.from_root_center(Vec2::zero())) + Vec3::unit_z()
+    }
+
+    /// The footprints of the keep's towers.
+    pub fn towers(&self, skel: &Skeleton<Attr>) -> Vec<Aabr<i32>> {
+        let mut towers = Vec::new();
+        skel.for_each_segment(|segment, _, branch| {
+            if branch.attr.is_tower {
+                towers.push(Aabr {
+                    min: segment.min - branch.locus,
+                    max: segment.max + branch.locus,
+                });
+            }
+        });
+        towers
+    }
+}
+
+impl Archetype for Keep {
+    type Attr = Attr;
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        Self::generate_tier(rng, KeepTier::default())
+    }
+
+    fn height(&self, skel: &Skeleton<Self::Attr>) -> i32 {
+        let mut height = 0;
+        skel.for_each(|_, _, branch, _, _| height = height.max(branch.attr.top()));
+        height
+    }
+
+    fn sprite_spots(&self, skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> {
+        let mut spots = Vec::new();
+        skel.for_each_segment(|segment, _, branch| {
+            // Torches on every storey of each tower, and on the ground floor of
+            // the hall
+            let storeys = if branch.attr.is_tower {
+                branch.attr.storeys
+            } else {
+                1
+            };
+            let zs = (0..storeys)
+                .map(|storey| storey * STOREY_HEIGHT + TORCH_HEIGHT)
+                .collect::<Vec<_>>();
+            let reach = branch.locus + 1 + self.wall_thickness;
+            spots.extend(spots_around(segment, 0..=reach, &zs));
+        });
+        spots
+    }
+
     fn draw(
         &self,
         index: IndexRef,
@@ -170,6 +287,26 @@ impl Archetype for Keep {
         const AIR: Block = Block::empty();
         const EMPTY: BlockMask = BlockMask::nothing();
         let internal = BlockMask::new(AIR, internal_layer);
+        // Torches hang just outside the walls, so any other part of the keep
+        // that reaches them (a tower standing against the hall, say) wins
+        let edge_ori = if bound_offset.x.abs() > bound_offset.y.abs() {
+            if center_offset.x > 0 { 6 } else { 2 }
+        } else if (center_offset.y > 0) ^ (ori == Ori::East) {
+            0
+        } else {
+            4
+        };
+        let edge_ori = if ori == Ori::East {
+            (edge_ori + 2) % 8
+        } else {
+            edge_ori
+        };
+        let torch = BlockMask::new(
+            Block::air(SpriteKind::WallSconce)
+                .with_ori((edge_ori + 4) % 8)
+                .unwrap(),
+            weak_layer,
+        );
 
         let make_staircase = move |pos: Vec3<i32>, radius: f32, inner_radius: f32, stretch: f32| {
             let stone = BlockMask::new(Block::new(BlockKind::Rock, dungeon_stone.into()), 5);
@@ -194,21 +331,42 @@ impl Archetype for Keep {
         let ridge_x = (center_offset.map(|e| e.abs()).reduce_min() + 2) % 8;
         let width = locus + i32::from(ridge_x < 4 && attr.ridged && !attr.rounded);
         let rampart_width = 2 + width;
-        let storey_height = 9;
-        let roof_height = attr.storeys * storey_height;
+        let storey_height = STOREY_HEIGHT;
+        let roof_height = attr.roof_height();
         let storey_y = profile.y % storey_height;
         let door_height = 6;
-        let rampart_height = roof_height + if ridge_x % 2 == 0 { 3 } else { 4 };
+        let rampart_height = roof_height + RAMPART_HEIGHT - i32::from(ridge_x % 2 == 0);
         let min_dist = if attr.rounded {
             bound_offset.map(|e| e.pow(2) as f32).sum().sqrt() as i32
         } else {
             bound_offset.map(|e| e.abs()).reduce_max()
         };
+        // Torches on the outer faces of the walls: on every storey of each
+        // tower, and on the ground floor at regular intervals along the sides
+        // of the hall and either side of its doors. Those of towers that stand
+        // within the hall are hidden by it, leaving the ones above its roof
+        let is_torch = if attr.is_tower {
+            storey_y == TORCH_HEIGHT
+                && profile.y < roof_height
+                && min_dist == width + 1
+                && center_offset.map(|e| e.abs()).reduce_min() == 0
+        } else {
+            profile.y == TORCH_HEIGHT
+                && min_dist == width + self.wall_thickness
+                && if bound_offset.y == 0 {
+                    center_offset.y.rem_euclid(TORCH_SPACING) == 0
+                } else {
+                    attr.has_doors && bound_offset.x == 3
+                }
+        };
 
         if profile.y <= 0 - (min_dist - width - 1).max(0) && min_dist < width + 3 {
             // Foundations
             foundation
-        } else if (0..=roof_height).contains(&profile.y) && storey_y == 0 && min_dist <= width + 1 {
+        } else if (0..=roof_height).contains(&profile.y)
+            && storey_y == 0
+            && min_dist < width + self.wall_thickness
+        {
             if min_dist < width { floor } else { wall }
         } else if bound_offset.x.abs() < 3
             && profile.y < door_height - bound_offset.x.abs()
@@ -218,7 +376,8 @@ impl Archetype for Keep {
             && attr.has_doors
         {
             internal
-        } else if (min_dist == width || (!attr.is_tower && min_dist == width + 1))
+        } else if (min_dist == width
+            || (!attr.is_tower && (width..width + self.wall_thickness).contains(&min_dist)))
             && profile.y <= roof_height
         {
             if attr.is_tower
@@ -237,7 +396,7 @@ impl Archetype for Keep {
                 if attr.is_tower
                     && attr.flag
                     && center_offset == Vec2::zero()
-                    && profile.y < roof_height + 16
+                    && profile.y < roof_height + POLE_HEIGHT
                 {
                     pole
                 } else if attr.is_tower
@@ -263,6 +422,8 @@ impl Archetype for Keep {
             }
         } else if profile.y < roof_height && min_dist < width {
             internal
+        } else if is_torch {
+            torch
         } else {
             EMPTY
         }
@@ -1,17 +1,28 @@
+pub mod barracks;
+pub mod crypt;
 pub mod house;
 pub mod keep;
+pub mod monument;
+pub mod notice_board;
+pub mod temple;
 
 use super::skeleton::*;
 use crate::{site::BlockMask, IndexRef};
 use common::calendar::Calendar;
 use rand::prelude::*;
 use serde::Deserialize;
+use std::ops::RangeInclusive;
 use vek::*;
 
 #[derive(Deserialize)]
 pub struct Colors {
+    pub barracks: barracks::Colors,
+    pub crypt: crypt::Colors,
     pub house: house::Colors,
     pub keep: keep::Colors,
+    pub monument: monument::Colors,
+    pub notice_board: notice_board::Colors,
+    pub temple: temple::Colors,
 }
 
 pub trait Archetype {
@@ -21,6 +32,21 @@ pub trait Archetype {
     where
         Self: Sized;
 
+    /// The 2D bounds of the archetype, relative to the building origin.
+    fn bounds(&self, skel: &Skeleton<Self::Attr>) -> Aabr<i32> { skel.bounds() }
+
+    /// The height of the archetype's highest block above the building origin.
+    fn height(&self, _skel: &Skeleton<Self::Attr>) -> i32 { 48 }
+
+    /// How far below the building origin the archetype's lowest block lies.
+    fn depth(&self, _skel: &Skeleton<Self::Attr>) -> i32 { 8 }
+
+    /// The spots, relative to the building origin, where `draw` may place a
+    /// sprite that can be interacted with or that gives off light. Only these
+    /// are sampled when listing a building's sprites, so they must cover every
+    /// such sprite, but needn't all hold one.
+    fn sprite_spots(&self, _skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> { Vec::new() }
+
     fn draw(
         &self,
         index: IndexRef,
@@ -35,3 +61,20 @@ pub trait Archetype {
         attr: &Self::Attr,
     ) -> BlockMask;
 }
+
+/// The positions whose distance from `segment`, measured like the `dist`
+/// passed to [`Archetype::draw`], lies within `range`, at each height in `zs`.
+fn spots_around(segment: Aabr<i32>, range: RangeInclusive<i32>, zs: &[i32]) -> Vec<Vec3<i32>> {
+    let reach = *range.end();
+    (segment.min.x - reach..=segment.max.x + reach)
+        .flat_map(|x| (segment.min.y - reach..=segment.max.y + reach).map(move |y| Vec2::new(x, y)))
+        .filter(|pos| {
+            let closest = Vec2::new(
+                pos.x.clamped(segment.min.x, segment.max.x),
+                pos.y.clamped(segment.min.y, segment.max.y),
+            );
+            range.contains(&(*pos - closest).map(|e| e.abs()).reduce_max())
+        })
+        .flat_map(|pos| zs.iter().map(move |z| Vec3::new(pos.x, pos.y, *z)))
+        .collect()
+}
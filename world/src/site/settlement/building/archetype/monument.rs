@@ -0,0 +1,123 @@
+use super::{super::skeleton::*, Archetype};
+use crate::{
+    site::BlockMask,
+    util::{RandomField, Sampler},
+    IndexRef,
+};
+use common::{
+    calendar::Calendar,
+    terrain::{Block, BlockKind},
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use vek::*;
+
+#[derive(Deserialize)]
+pub struct Colors {
+    pub plinth: (u8, u8, u8),
+    pub stone: (u8, u8, u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MonumentKind {
+    Statue,
+    Obelisk,
+}
+
+/// A decorative stone monument standing on a plinth.
+pub struct Monument {
+    pub kind: MonumentKind,
+}
+
+pub struct Attr {
+    pub plinth_height: i32,
+}
+
+impl Archetype for Monument {
+    type Attr = Attr;
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        let skel = Skeleton {
+            offset: 0,
+            ori: if rng.gen() { Ori::East } else { Ori::North },
+            root: Branch {
+                len: 0,
+                attr: Attr {
+                    plinth_height: rng.gen_range(1..3),
+                },
+                locus: 3,
+                border: 2,
+                children: Vec::new(),
+            },
+        };
+
+        (
+            Self {
+                kind: if rng.gen() {
+                    MonumentKind::Statue
+                } else {
+                    MonumentKind::Obelisk
+                },
+            },
+            skel,
+        )
+    }
+
+    fn draw(
+        &self,
+        index: IndexRef,
+        pos: Vec3<i32>,
+        dist: i32,
+        _bound_offset: Vec2<i32>,
+        center_offset: Vec2<i32>,
+        z: i32,
+        _ori: Ori,
+        locus: i32,
+        _len: i32,
+        attr: &Self::Attr,
+    ) -> BlockMask {
+        let colors = &index.colors.site.settlement.building.archetype.monument;
+
+        let make_block = |(r, g, b)| {
+            let tex = RandomField::new(0).get(pos) as u8 % 12;
+            BlockMask::new(
+                Block::new(
+                    BlockKind::Rock,
+                    Rgb::new(r, g, b).map(|e: u8| e.saturating_add(tex)),
+                ),
+                2,
+            )
+        };
+        const EMPTY: BlockMask = BlockMask::nothing();
+
+        let plinth = make_block(colors.plinth);
+        let stone = make_block(colors.stone);
+
+        if z <= 0 - (dist - locus).max(0) && dist <= locus + 1 {
+            // Foundations
+            return plinth.with_priority(1);
+        } else if z <= attr.plinth_height && dist <= locus {
+            return plinth;
+        }
+
+        let height = z - attr.plinth_height;
+        let (x, y) = (center_offset.x, center_offset.y.abs());
+        let is_solid = match self.kind {
+            MonumentKind::Obelisk => {
+                let half_width = if height <= 8 { 1 } else { 0 };
+                height <= 12 && x.abs() <= half_width && y <= half_width
+            },
+            MonumentKind::Statue => match height {
+                // Legs
+                1..=3 => x.abs() == 1 && y == 0,
+                // Torso and arms
+                4..=6 => y == 0 && (x.abs() <= 1 || (x.abs() == 2 && height < 6)),
+                // Head
+                7..=8 => x == 0 && y == 0,
+                _ => false,
+            },
+        };
+
+        if is_solid { stone } else { EMPTY }
+    }
+}
@@ -0,0 +1,97 @@
+use super::{super::skeleton::*, Archetype};
+use crate::{site::BlockMask, IndexRef};
+use common::{
+    calendar::Calendar,
+    terrain::{Block, BlockKind, SpriteKind},
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use vek::*;
+
+#[derive(Deserialize)]
+pub struct Colors {
+    pub post: (u8, u8, u8),
+    pub board: (u8, u8, u8),
+}
+
+/// A framed board on two posts, with a sign in front of it, where the people
+/// of a town post their notices.
+pub struct NoticeBoard;
+
+impl NoticeBoard {
+    const HALF_WIDTH: i32 = 2;
+    const HEIGHT: i32 = 5;
+
+    /// Where the sign stands, across and along from the middle of the board
+    /// and above the ground.
+    fn sign_offset() -> Vec3<i32> { Vec3::new(0, 1, 1) }
+}
+
+impl Archetype for NoticeBoard {
+    type Attr = ();
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        let skel = Skeleton {
+            offset: 0,
+            ori: if rng.gen() { Ori::East } else { Ori::North },
+            root: Branch {
+                len: 0,
+                attr: (),
+                locus: Self::HALF_WIDTH,
+                border: 1,
+                children: Vec::new(),
+            },
+        };
+
+        (Self, skel)
+    }
+
+    fn height(&self, _skel: &Skeleton<Self::Attr>) -> i32 { Self::HEIGHT + 1 }
+
+    fn sprite_spots(&self, skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> {
+        let sign = Self::sign_offset();
+        vec![Vec3::from(skel.from_root_center(sign.xy())) + Vec3::unit_z() * sign.z]
+    }
+
+    fn draw(
+        &self,
+        index: IndexRef,
+        _pos: Vec3<i32>,
+        _dist: i32,
+        _bound_offset: Vec2<i32>,
+        center_offset: Vec2<i32>,
+        z: i32,
+        ori: Ori,
+        _locus: i32,
+        _len: i32,
+        _attr: &Self::Attr,
+    ) -> BlockMask {
+        let colors = &index.colors.site.settlement.building.archetype.notice_board;
+        let (x, y) = (center_offset.x.abs(), center_offset.y);
+
+        if y == 0 && x == Self::HALF_WIDTH && (-2..=Self::HEIGHT).contains(&z) {
+            // Posts
+            BlockMask::new(Block::new(BlockKind::Wood, colors.post.into()), 2)
+        } else if y == 0 && x < Self::HALF_WIDTH && (3..=Self::HEIGHT).contains(&z) {
+            // Board, framed at the top
+            let color = if z == Self::HEIGHT {
+                colors.post
+            } else {
+                colors.board
+            };
+            BlockMask::new(Block::new(BlockKind::Wood, color.into()), 2)
+        } else if Vec3::new(center_offset.x, y, z) == Self::sign_offset() {
+            BlockMask::new(
+                Block::air(SpriteKind::Sign)
+                    .with_ori(match ori {
+                        Ori::East => 2,
+                        Ori::North => 4,
+                    })
+                    .unwrap(),
+                2,
+            )
+        } else {
+            BlockMask::nothing()
+        }
+    }
+}
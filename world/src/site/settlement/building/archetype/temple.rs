@@ -0,0 +1,218 @@
+use super::{super::skeleton::*, Archetype};
+use crate::{
+    site::BlockMask,
+    util::{RandomField, Sampler},
+    IndexRef,
+};
+use common::{
+    calendar::Calendar,
+    terrain::{Block, BlockKind, SpriteKind},
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use vek::*;
+
+#[derive(Deserialize)]
+pub struct Colors {
+    pub stone: (u8, u8, u8),
+    pub roof: (u8, u8, u8),
+    pub floor: (u8, u8, u8),
+    pub bell: (u8, u8, u8),
+}
+
+/// A stone temple: a long nave under a steep roof, lit by stained glass
+/// windows, with pews facing an altar at one end and a bell tower topped by a
+/// spire at the other.
+pub struct Temple {
+    /// The height of the spire above the top of the tower.
+    pub spire_height: i32,
+}
+
+/// Height of the walls of the nave.
+const NAVE_HEIGHT: i32 = 9;
+/// Height of the top of the tower's walls, where its spire begins.
+const TOWER_HEIGHT: i32 = 22;
+/// Half the width of the tower.
+const TOWER_RADIUS: i32 = 3;
+/// Height of the belfry, the open top storey of the tower holding its bell.
+const BELFRY_HEIGHT: i32 = 5;
+
+impl Temple {
+    /// The height of the tip of the spire above the temple's floor.
+    pub fn spire_top(&self) -> i32 { TOWER_HEIGHT + self.spire_height }
+
+    /// How far along the nave from its center the altar stands, for a nave of
+    /// length `len` with walls `locus` blocks from its middle.
+    fn altar_y(len: i32, locus: i32) -> i32 { -(len / 2 + locus) + 2 }
+}
+
+impl Archetype for Temple {
+    type Attr = ();
+
+    fn generate<R: Rng>(rng: &mut R, _calendar: Option<&Calendar>) -> (Self, Skeleton<Self::Attr>) {
+        let skel = Skeleton {
+            offset: 0,
+            ori: if rng.gen() { Ori::East } else { Ori::North },
+            root: Branch {
+                // Even, so that the nave is symmetric about its center
+                len: rng.gen_range(6..9) * 2,
+                attr: (),
+                locus: rng.gen_range(5..7),
+                border: 2,
+                children: Vec::new(),
+            },
+        };
+
+        (
+            Self {
+                spire_height: rng.gen_range(10..16),
+            },
+            skel,
+        )
+    }
+
+    fn height(&self, _skel: &Skeleton<Self::Attr>) -> i32 {
+        // The finial above the tip of the spire
+        self.spire_top() + 2
+    }
+
+    fn sprite_spots(&self, skel: &Skeleton<Self::Attr>) -> Vec<Vec3<i32>> {
+        // The candles either side of the altar
+        let altar_y = Self::altar_y(skel.root.len, skel.root.locus);
+        [-1, 1]
+            .iter()
+            .map(|x| Vec3::from(skel.from_root_center(Vec2::new(*x, altar_y))) + Vec3::unit_z() * 2)
+            .collect()
+    }
+
+    fn draw(
+        &self,
+        index: IndexRef,
+        pos: Vec3<i32>,
+        dist: i32,
+        bound_offset: Vec2<i32>,
+        center_offset: Vec2<i32>,
+        z: i32,
+        ori: Ori,
+        locus: i32,
+        len: i32,
+        _attr: &Self::Attr,
+    ) -> BlockMask {
+        let colors = &index.colors.site.settlement.building.archetype.temple;
+
+        let make_block = |(r, g, b), kind| {
+            let tex = RandomField::new(0).get(pos) as u8 % 12;
+            BlockMask::new(
+                Block::new(kind, Rgb::new(r, g, b).map(|e: u8| e.saturating_add(tex))),
+                2,
+            )
+        };
+        const EMPTY: BlockMask = BlockMask::nothing();
+        let internal = BlockMask::new(Block::air(SpriteKind::Empty), 2);
+        let stone = make_block(colors.stone, BlockKind::Rock);
+        let roof = make_block(colors.roof, BlockKind::Wood);
+        let floor = make_block(colors.floor, BlockKind::Rock);
+        let bell = BlockMask::new(Block::new(BlockKind::Rock, colors.bell.into()), 2);
+
+        let end_ori = match ori {
+            Ori::East => 2,
+            Ori::North => 4,
+        };
+        let sprite =
+            |kind: SpriteKind, ori| BlockMask::new(Block::air(kind).with_ori(ori).unwrap(), 2);
+
+        // Across and along the nave, from its center
+        let (x, y) = (center_offset.x, center_offset.y);
+        let half_len = len / 2 + locus;
+        // The tower stands at the far end of the nave, sharing its end wall
+        let tower_y = half_len - TOWER_RADIUS;
+        let tower_offset = Vec2::new(x, y - tower_y);
+        let tower_dist = tower_offset.map(|e| e.abs()).reduce_max();
+
+        if z <= 0 - (dist - locus - 1).max(0) && dist < locus + 3 {
+            // Foundations
+            stone.with_priority(1)
+        } else if tower_dist <= TOWER_RADIUS {
+            let belfry = (TOWER_HEIGHT - BELFRY_HEIGHT..TOWER_HEIGHT).contains(&z);
+            if z == 0 {
+                floor
+            } else if tower_dist == TOWER_RADIUS && z < TOWER_HEIGHT {
+                // The side of the tower's face that this column is on
+                let across = if tower_offset.x.abs() == TOWER_RADIUS {
+                    tower_offset.y
+                } else {
+                    tower_offset.x
+                };
+                let door = tower_offset.y == TOWER_RADIUS && x.abs() <= 1 && z <= 4;
+                let arch = tower_offset.y == -TOWER_RADIUS && x.abs() <= 1 && z <= 5;
+                let opening = belfry && z > TOWER_HEIGHT - BELFRY_HEIGHT && across.abs() <= 1;
+                if door || arch || opening {
+                    internal
+                } else {
+                    stone
+                }
+            } else if z < TOWER_HEIGHT {
+                if tower_offset == Vec2::zero() && belfry && z < TOWER_HEIGHT - 2 {
+                    bell
+                } else if tower_offset == Vec2::zero() && z == TOWER_HEIGHT - 1 {
+                    // The beam the bell hangs from
+                    roof
+                } else {
+                    internal
+                }
+            } else if z <= self.spire_top() {
+                let radius = (self.spire_top() - z) * TOWER_RADIUS / self.spire_height;
+                if tower_dist == radius {
+                    roof
+                } else if tower_dist < radius {
+                    internal
+                } else {
+                    EMPTY
+                }
+            } else if tower_offset == Vec2::zero() && z <= self.spire_top() + 2 {
+                // Finial
+                bell
+            } else {
+                EMPTY
+            }
+        } else if dist <= locus + 1 {
+            // The roof is gabled across the nave, overhanging its walls
+            let roof_z = NAVE_HEIGHT + locus + 1 - x.abs();
+            if z == 0 && dist < locus {
+                floor
+            } else if z == roof_z {
+                roof
+            } else if dist == locus && z <= NAVE_HEIGHT {
+                // Stained glass windows along the sides
+                if bound_offset.y == 0 && (3..=6).contains(&z) && y.rem_euclid(4) == 0 {
+                    sprite(SpriteKind::Window3, (end_ori + 2) % 8)
+                } else {
+                    stone
+                }
+            } else if dist == locus && bound_offset.y > 0 && z < roof_z {
+                // Gables
+                stone
+            } else if dist < locus && z < roof_z {
+                let altar_y = Self::altar_y(len, locus);
+                if y == altar_y && x.abs() <= 1 && z == 1 {
+                    stone
+                } else if y == altar_y && x.abs() == 1 && z == 2 {
+                    sprite(SpriteKind::Candle, 0)
+                } else if z == 1
+                    && (altar_y + 3..tower_y - TOWER_RADIUS - 1).contains(&y)
+                    && y.rem_euclid(3) == 0
+                    && (2..locus - 1).contains(&x.abs())
+                {
+                    // Pews facing the altar, either side of the aisle
+                    sprite(SpriteKind::Bench, end_ori)
+                } else {
+                    internal
+                }
+            } else {
+                EMPTY
+            }
+        } else {
+            EMPTY
+        }
+    }
+}
@@ -3,56 +3,170 @@ pub mod skeleton;
 
 // Reexports
 pub use self::{
-    archetype::{house::House, keep::Keep, Archetype},
+    archetype::{
+        barracks::Barracks,
+        crypt::Crypt,
+        house::{House, Porch, RoofStyle},
+        keep::{Keep, KeepTier},
+        monument::Monument,
+        notice_board::NoticeBoard,
+        temple::Temple,
+        Archetype,
+    },
     skeleton::*,
 };
 
-use crate::IndexRef;
-use common::{calendar::Calendar, terrain::Block};
+use crate::{
+    util::{seed_expan, RandomField},
+    IndexRef,
+};
+use common::{
+    calendar::{Calendar, CalendarEvent},
+    comp::object,
+    terrain::Block,
+};
 use rand::prelude::*;
-use serde::Deserialize;
+use rand_chacha::ChaChaRng;
+use serde::{Deserialize, Serialize};
 use vek::*;
 
+/// The furniture of a house, in the order its spots are furnished in.
+const FURNITURE: [object::Body; 5] = [
+    object::Body::BedBlue,
+    object::Body::Table,
+    object::Body::Chair,
+    object::Body::Chest,
+    object::Body::Crate,
+];
+
 #[derive(Deserialize)]
 pub struct Colors {
     pub archetype: archetype::Colors,
 }
 
+/// What a building was generated from, beyond its seed and origin, such that
+/// it can be generated again.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildingParams {
+    /// The orientation the building was turned to, if any.
+    pub ori: Option<Ori>,
+    /// The direction the building's porch faces, if it has one.
+    pub porch: Option<Vec2<i32>>,
+    /// The roof style shared by every part of the building, if any.
+    pub roof_style: Option<RoofStyle>,
+    /// The tier of a keep generated for one.
+    pub tier: Option<KeepTier>,
+    /// Whether the house was generated as a tavern.
+    pub tavern: bool,
+    /// Whether the building was generated at Christmas.
+    pub christmas: bool,
+}
+
+impl BuildingParams {
+    /// A calendar that generates the building the way it was, as far as the
+    /// calendar goes.
+    pub fn calendar(&self) -> Option<Calendar> {
+        self.christmas
+            .then(|| Calendar::from_events(vec![CalendarEvent::Christmas]))
+    }
+
+    fn from_calendar(calendar: Option<&Calendar>) -> Self {
+        Self {
+            christmas: calendar.map_or(false, |c| c.is_event(CalendarEvent::Christmas)),
+            ..Self::default()
+        }
+    }
+}
+
 pub struct Building<A: Archetype> {
     skel: Skeleton<A::Attr>,
     archetype: A,
     origin: Vec3<i32>,
+    seed: u32,
+    params: BuildingParams,
 }
 
 impl<A: Archetype> Building<A> {
-    pub fn generate(rng: &mut impl Rng, origin: Vec3<i32>, calendar: Option<&Calendar>) -> Self
+    /// Generate a building from its own seed. Buildings of the same archetype
+    /// generated with the same seed (and calendar) share their geometry,
+    /// relative to their origin, regardless of where they are placed.
+    pub fn generate(seed: u32, origin: Vec3<i32>, calendar: Option<&Calendar>) -> Self
     where
         A: Sized,
     {
-        let (archetype, skel) = A::generate(rng, calendar);
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(seed));
+        let (archetype, skel) = A::generate(&mut rng, calendar);
         Self {
             skel,
             archetype,
             origin,
+            seed,
+            params: BuildingParams::from_calendar(calendar),
         }
     }
 
+    pub fn seed(&self) -> u32 { self.seed }
+
+    /// What the building was generated from, beyond its seed and origin.
+    pub fn params(&self) -> BuildingParams { self.params }
+
+    pub fn origin(&self) -> Vec3<i32> { self.origin }
+
+    /// Turn the building so that its main branch runs along `ori`.
+    #[must_use]
+    pub fn with_ori(mut self, ori: Ori) -> Self {
+        self.skel.ori = ori;
+        self.params.ori = Some(ori);
+        self
+    }
+
     pub fn bounds_2d(&self) -> Aabr<i32> {
-        let b = self.skel.bounds();
+        let b = self.archetype.bounds(&self.skel);
         Aabr {
             min: Vec2::from(self.origin) + b.min,
             max: Vec2::from(self.origin) + b.max,
         }
     }
 
+    /// Move the building by `offs`, keeping its geometry.
+    pub fn move_by(&mut self, offs: Vec3<i32>) { self.origin += offs; }
+
+    /// Like [`Building::move_by`], but taking the building by value.
+    #[must_use]
+    pub fn moved_by(mut self, offs: Vec3<i32>) -> Self {
+        self.move_by(offs);
+        self
+    }
+
     pub fn bounds(&self) -> Aabb<i32> {
         let aabr = self.bounds_2d();
         Aabb {
-            min: Vec3::from(aabr.min) + Vec3::unit_z() * (self.origin.z - 8),
-            max: Vec3::from(aabr.max) + Vec3::unit_z() * (self.origin.z + 48),
+            min: Vec3::from(aabr.min)
+                + Vec3::unit_z() * (self.origin.z - self.archetype.depth(&self.skel)),
+            max: Vec3::from(aabr.max)
+                + Vec3::unit_z() * (self.origin.z + self.archetype.height(&self.skel)),
         }
     }
 
+    /// The sprites at the building's [`Archetype::sprite_spots`], relative to
+    /// the settlement's origin, drawn just as [`Building::sample`] draws them.
+    pub fn sprites(&self, index: IndexRef) -> Vec<(Vec3<i32>, Block)> {
+        let mut spots = self.archetype.sprite_spots(&self.skel);
+        spots.sort_by_key(|pos| pos.into_tuple());
+        spots.dedup();
+        spots
+            .into_iter()
+            .map(|pos| self.origin + pos)
+            .filter_map(|pos| {
+                Some((
+                    pos,
+                    self.sample(index, pos)
+                        .filter(|b| b.get_sprite().is_some())?,
+                ))
+            })
+            .collect()
+    }
+
     pub fn sample(&self, index: IndexRef, pos: Vec3<i32>) -> Option<Block> {
         let rpos = pos - self.origin;
         self.skel
@@ -76,3 +190,175 @@ impl<A: Archetype> Building<A> {
             .finish()
     }
 }
+
+impl Building<Keep> {
+    /// Like [`Building::generate`], but for a keep of the given tier.
+    pub fn generate_tier(seed: u32, origin: Vec3<i32>, tier: KeepTier) -> Self {
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(seed));
+        let (archetype, skel) = Keep::generate_tier(&mut rng, tier);
+        Self {
+            skel,
+            archetype,
+            origin,
+            seed,
+            params: BuildingParams {
+                tier: Some(tier),
+                ..BuildingParams::default()
+            },
+        }
+    }
+
+    /// Where the keep's treasury chest stands, relative to the settlement's
+    /// origin.
+    pub fn treasury(&self) -> Vec3<i32> { self.origin + self.archetype.treasury(&self.skel) }
+
+    /// The footprints of the keep's towers, relative to the settlement's
+    /// origin.
+    pub fn towers(&self) -> Vec<Aabr<i32>> {
+        self.archetype
+            .towers(&self.skel)
+            .into_iter()
+            .map(|tower| Aabr {
+                min: tower.min + Vec2::from(self.origin),
+                max: tower.max + Vec2::from(self.origin),
+            })
+            .collect()
+    }
+}
+
+impl Building<Barracks> {
+    /// The bunks of the barracks, as the positions of their lower beds
+    /// relative to the settlement's origin.
+    pub fn bunks(&self) -> Vec<Vec3<i32>> {
+        self.archetype
+            .bunks(&self.skel)
+            .into_iter()
+            .map(|pos| self.origin + pos)
+            .collect()
+    }
+}
+
+impl Building<Crypt> {
+    /// Where the crypt's grave goods lie, relative to the settlement's origin.
+    pub fn loot(&self) -> Vec<Vec3<i32>> {
+        self.archetype
+            .loot(&self.skel)
+            .into_iter()
+            .map(|pos| self.origin + pos)
+            .collect()
+    }
+}
+
+impl Building<House> {
+    /// Like [`Building::generate`], but for a tavern.
+    pub fn generate_tavern(seed: u32, origin: Vec3<i32>, calendar: Option<&Calendar>) -> Self {
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(seed));
+        let (archetype, skel) = House::generate_tavern(&mut rng, calendar);
+        Self {
+            skel,
+            archetype,
+            origin,
+            seed,
+            params: BuildingParams {
+                tavern: true,
+                ..BuildingParams::from_calendar(calendar)
+            },
+        }
+    }
+
+    /// Where the sign of the house hangs, relative to the settlement's origin,
+    /// if it has one.
+    pub fn sign(&self) -> Option<Vec3<i32>> {
+        self.archetype.sign(&self.skel).map(|pos| self.origin + pos)
+    }
+
+    /// Like [`Building::bounds_2d`], but only the area within the walls of the
+    /// house, walls included, without its eaves and porch. The walls sit one
+    /// block within the locus of each branch; only the jetties of the upper
+    /// storeys reach it.
+    pub fn core_bounds_2d(&self) -> Aabr<i32> {
+        let b = self.skel.core_bounds();
+        Aabr {
+            min: Vec2::from(self.origin) + b.min + 1,
+            max: Vec2::from(self.origin) + b.max - 1,
+        }
+    }
+
+    /// Give the house a porch on the side facing `dir`, which must be a unit
+    /// cardinal direction.
+    #[must_use]
+    pub fn with_porch(mut self, dir: Vec2<i32>) -> Self {
+        self.archetype.porch = Some(Porch::facing(&self.skel, dir));
+        self.params.porch = Some(dir);
+        self
+    }
+
+    /// The furniture on the ground floor of the house, as positions relative
+    /// to the settlement's origin. Each branch of the house is a room,
+    /// furnished along its side walls: doors are only ever cut into end walls,
+    /// and spots near the walls of other rooms, where rooms open into each
+    /// other, are left clear. Unless `density` is zero, the first two spots
+    /// always get a bed and a table, and `density` is the share of the other
+    /// spots that get furnished. The result depends only on the building's
+    /// seed and origin.
+    pub fn furniture(&self, density: f32) -> Vec<(Vec3<i32>, object::Body)> {
+        // The segment running down the middle of each room, and its locus
+        let mut rooms = Vec::new();
+        self.skel
+            .for_each_segment(|segment, ori, branch| rooms.push((segment, ori, branch.locus)));
+        let dist = |pos: Vec2<i32>, segment: Aabr<i32>| {
+            let closest = Vec2::new(
+                pos.x.clamped(segment.min.x, segment.max.x),
+                pos.y.clamped(segment.min.y, segment.max.y),
+            );
+            (pos - closest).map(|e| e.abs()).reduce_max()
+        };
+
+        let noise = RandomField::new(self.seed);
+        let mut furniture = Vec::new();
+        let mut slot = 0;
+        for (i, (segment, ori, locus)) in rooms.iter().enumerate() {
+            // Keep a row clear of the walls, where the house has its own
+            // furniture sprites, and of the ends of the room
+            let inset = locus - 3;
+            let margin = locus - 4;
+            if inset < 1 || margin < 0 {
+                continue;
+            }
+            let (along, across) = (ori.dir(), ori.flip().dir());
+            let len = (segment.max - segment.min).sum();
+            let spots = (0..len + 2 * margin + 1)
+                .step_by(3)
+                .flat_map(|a| [-1, 1].map(|side| (a, side)))
+                .map(|(a, side)| segment.min + along * (a - margin) + across * side * inset)
+                .filter(|pos| {
+                    rooms
+                        .iter()
+                        .enumerate()
+                        .all(|(j, (other, _, other_locus))| {
+                            let dist = dist(*pos, *other);
+                            i == j || dist <= other_locus - 3 || dist >= other_locus + 2
+                        })
+                })
+                .collect::<Vec<_>>();
+            for pos in spots {
+                let pos = Vec3::new(pos.x, pos.y, 1) + self.origin;
+                let essential = slot < 2 && density > 0.0;
+                if essential || noise.chance(pos, density) {
+                    furniture.push((pos, FURNITURE[slot % FURNITURE.len()]));
+                }
+                slot += 1;
+            }
+        }
+        furniture
+    }
+
+    /// Give every part of the house the same style of roof.
+    #[must_use]
+    pub fn with_roof_style(mut self, roof_style: RoofStyle) -> Self {
+        self.skel
+            .for_each_attr_mut(|attr| attr.roof_style = roof_style);
+        self.params.roof_style = Some(roof_style);
+        self
+    }
+}
@@ -1,7 +1,8 @@
 use crate::site::BlockMask;
+use serde::{Deserialize, Serialize};
 use vek::*;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Ori {
     East,
     North,
@@ -46,6 +47,13 @@ impl<T> Branch<T> {
             child.for_each(node + ori.dir() * *offset, ori.flip(), true, self.locus, f);
         }
     }
+
+    fn for_each_attr_mut(&mut self, f: &mut impl FnMut(&mut T)) {
+        f(&mut self.attr);
+        for (_, child) in &mut self.children {
+            child.for_each_attr_mut(f);
+        }
+    }
 }
 
 pub struct Skeleton<T> {
@@ -60,6 +68,11 @@ impl<T> Skeleton<T> {
             .for_each(self.ori.dir() * self.offset, self.ori, false, 0, &mut f);
     }
 
+    /// Visit the attributes of every branch, children included.
+    pub fn for_each_attr_mut(&mut self, mut f: impl FnMut(&mut T)) {
+        self.root.for_each_attr_mut(&mut f);
+    }
+
     pub fn bounds(&self) -> Aabr<i32> {
         let mut bounds = Aabr::new_empty(self.ori.dir() * self.offset);
         self.for_each(|node, ori, branch, _, _| {
@@ -73,12 +86,25 @@ impl<T> Skeleton<T> {
         bounds
     }
 
-    pub fn sample_closest(
-        &self,
-        pos: Vec3<i32>,
-        mut f: impl FnMut(Vec3<i32>, i32, Vec2<i32>, Vec2<i32>, Ori, &Branch<T>) -> BlockMask,
-    ) -> BlockMask {
-        let mut min = None::<(_, BlockMask)>;
+    /// Like [`Skeleton::bounds`], but without the borders around the
+    /// branches: the area within the outermost walls.
+    pub fn core_bounds(&self) -> Aabr<i32> {
+        let mut bounds = Aabr::new_empty(self.ori.dir() * self.offset);
+        self.for_each(|node, ori, branch, _, _| {
+            let node2 = node + ori.dir() * branch.len;
+
+            let a = node.map2(node2, |a, b| a.min(b)) - branch.locus;
+            let b = node.map2(node2, |a, b| a.max(b)) + branch.locus;
+            bounds.expand_to_contain_point(a);
+            bounds.expand_to_contain_point(b);
+        });
+        bounds
+    }
+
+    /// Visit the segment running down the middle of each branch, from which
+    /// [`Skeleton::sample_closest`] measures distances: children start where
+    /// they emerge from the walls of their parent.
+    pub fn for_each_segment<'a>(&'a self, mut f: impl FnMut(Aabr<i32>, Ori, &'a Branch<T>)) {
         self.for_each(|node, ori, branch, is_child, parent_locus| {
             let node2 = node + ori.dir() * branch.len;
             let node = node
@@ -89,15 +115,40 @@ impl<T> Skeleton<T> {
                 } else {
                     Vec2::zero()
                 };
-            let bounds = Aabr::new_empty(node).expanded_to_contain_point(node2);
+            f(
+                Aabr::new_empty(node).expanded_to_contain_point(node2),
+                ori,
+                branch,
+            );
+        });
+    }
+
+    /// The position at `offset` across and along the root branch from its
+    /// center, the inverse of the center offsets passed to the closure of
+    /// [`Skeleton::sample_closest`] for the root.
+    pub fn from_root_center(&self, offset: Vec2<i32>) -> Vec2<i32> {
+        let node = self.ori.dir() * self.offset;
+        let center = Aabr::new_empty(node)
+            .expanded_to_contain_point(node + self.ori.dir() * self.root.len)
+            .center();
+        center + self.ori.flip().dir() * offset.x + self.ori.dir() * offset.y
+    }
+
+    pub fn sample_closest(
+        &self,
+        pos: Vec3<i32>,
+        mut f: impl FnMut(Vec3<i32>, i32, Vec2<i32>, Vec2<i32>, Ori, &Branch<T>) -> BlockMask,
+    ) -> BlockMask {
+        let mut min = None::<(_, BlockMask)>;
+        self.for_each_segment(|bounds, ori, branch| {
             let bound_offset = if ori == Ori::East {
                 Vec2::new(
-                    node.y - pos.y,
+                    bounds.min.y - pos.y,
                     pos.x - pos.x.clamped(bounds.min.x, bounds.max.x),
                 )
             } else {
                 Vec2::new(
-                    node.x - pos.x,
+                    bounds.min.x - pos.x,
                     pos.y - pos.y.clamped(bounds.min.y, bounds.max.y),
                 )
             }
@@ -2,15 +2,19 @@ pub mod building;
 mod town;
 
 use self::{
-    building::{Building, House, Keep},
+    building::{
+        Barracks, Building, BuildingParams, Crypt, House, Keep, KeepTier, Monument, NoticeBoard,
+        Ori, RoofStyle, Temple,
+    },
     town::{District, Town},
 };
 use super::SpawnRules;
 use crate::{
     column::ColumnSample,
+    config::CONFIG,
     sim::WorldSim,
-    site::namegen::NameGen,
-    util::{RandomField, Sampler, StructureGen2d},
+    site::namegen::{NameGen, NameStyle},
+    util::{seed_expan, structure::StructureField, RandomField, Sampler, StructureGen2d},
     IndexRef,
 };
 use common::{
@@ -21,14 +25,16 @@ use common::{
         inventory::{
             loadout_builder::LoadoutBuilder, slot::ArmorSlot, trade_pricing::TradePricing,
         },
-        quadruped_small, Item,
+        quadruped_medium, quadruped_small, Item,
     },
     generation::{ChunkSupplement, EntityInfo},
+    lottery::LootSpec,
     path::Path,
     resources::TimeOfDay,
     spiral::Spiral2d,
     store::{Id, Store},
-    terrain::{Block, BlockKind, SpriteKind, TerrainChunkSize},
+    terrain::{BiomeKind, Block, BlockKind, SpriteKind, TerrainChunk, TerrainChunkSize},
+    time::DayPeriod,
     trade::{Good, SiteInformation},
     vol::{ReadVol, RectSizedVol, RectVolSize, WriteVol},
 };
@@ -36,8 +42,17 @@ use common::{
 use fxhash::FxHasher64;
 use hashbrown::{HashMap, HashSet};
 use rand::prelude::*;
-use serde::Deserialize;
-use std::{collections::VecDeque, f32, hash::BuildHasherDefault};
+use rand_chacha::ChaChaRng;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+    f32,
+    hash::BuildHasherDefault,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    time::{Duration, Instant},
+};
 use vek::*;
 
 #[derive(Deserialize)]
@@ -48,16 +63,33 @@ pub struct Colors {
 
     pub plot_field_dirt: (u8, u8, u8),
     pub plot_field_mound: (u8, u8, u8),
+    pub plot_field_terrace: (u8, u8, u8),
 
     pub wall_low: (u8, u8, u8),
     pub wall_high: (u8, u8, u8),
+    pub stone_wall_low: (u8, u8, u8),
+    pub stone_wall_high: (u8, u8, u8),
 
     pub tower_color: (u8, u8, u8),
+    pub gate_door: (u8, u8, u8),
+    pub water_gate: (u8, u8, u8),
+
+    pub foundation_stone: (u8, u8, u8),
+    pub stilts: (u8, u8, u8),
+
+    pub bridge_deck: (u8, u8, u8),
+    pub bridge_railing: (u8, u8, u8),
 
     pub plot_dirt: (u8, u8, u8),
     pub plot_grass: (u8, u8, u8),
+    pub orchard_trunk: (u8, u8, u8),
+    pub orchard_leaves: (u8, u8, u8),
+    pub palm_trunk: (u8, u8, u8),
+    pub palm_leaves: (u8, u8, u8),
+    pub plot_pasture: (u8, u8, u8),
     pub plot_water: (u8, u8, u8),
     pub plot_town: (u8, u8, u8),
+    pub plot_snow: (u8, u8, u8),
 }
 
 #[allow(dead_code)]
@@ -101,6 +133,42 @@ pub fn center_of(p: [Vec2<f32>; 3]) -> Vec2<f32> {
     Vec2::new(x, y)
 }
 
+/// Computes the convex hull of a set of points using Andrew's monotone chain.
+/// The hull is returned in counter-clockwise order, without repeating the first
+/// point.
+pub fn convex_hull(mut points: Vec<Vec2<i32>>) -> Vec<Vec2<i32>> {
+    points.sort_unstable_by_key(|p| (p.x, p.y));
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    fn half_hull(points: impl Iterator<Item = Vec2<i32>>) -> Vec<Vec2<i32>> {
+        let mut hull = Vec::<Vec2<i32>>::new();
+        for p in points {
+            while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of each half is the first point of the other
+        hull.pop();
+        hull
+    }
+
+    let mut hull = half_hull(points.iter().copied());
+    hull.extend(half_hull(points.iter().rev().copied()));
+    hull
+}
+
+/// Z component of the cross product of `a - o` and `b - o`. Positive when
+/// `o -> a -> b` turns counter-clockwise.
+fn cross(o: Vec2<i32>, a: Vec2<i32>, b: Vec2<i32>) -> i64 {
+    let (oa, ob) = ((a - o).map(i64::from), (b - o).map(i64::from));
+    oa.x * ob.y - oa.y * ob.x
+}
+
 impl WorldSim {
     fn can_host_settlement(&self, pos: Vec2<i32>) -> bool {
         self.get(pos)
@@ -111,914 +179,5355 @@ impl WorldSim {
                 .map(|grad| grad < 0.75)
                 .unwrap_or(false)
     }
+
+    /// Like [`WorldSim::get_alt_approx`], but clamp `wpos` to the area where
+    /// altitudes can be interpolated, so that settlements near the edge of the
+    /// world stand on the nearest real terrain rather than on none.
+    fn get_alt_clamped(&self, wpos: Vec2<i32>) -> Option<f32> {
+        clamp_to_interpolable(wpos, self.get_size()).and_then(|wpos| self.get_alt_approx(wpos))
+    }
+}
+
+/// Clamp `wpos` to the area of a world `size` chunks across within which
+/// altitudes can be interpolated. Interpolation samples the chunk before and
+/// the two chunks after the one containing a position, so the outermost
+/// chunks are left out, and worlds less than 4 chunks across have no such
+/// area at all.
+fn clamp_to_interpolable(wpos: Vec2<i32>, size: Vec2<u32>) -> Option<Vec2<i32>> {
+    let sz = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+    let max = (size.map(|e| e as i32) - 2) * sz - 1;
+    (max.x >= sz.x && max.y >= sz.y)
+        .then(|| Vec2::new(wpos.x.clamp(sz.x, max.x), wpos.y.clamp(sz.y, max.y)))
 }
 
 const AREA_SIZE: u32 = 32;
 
 fn to_tile(e: i32) -> i32 { ((e as f32).div_euclid(AREA_SIZE as f32)).floor() as i32 }
 
+/// The tiles overlapped by `bounds`, given in settlement-local block
+/// coordinates.
+fn footprint_tiles(bounds: Aabr<i32>) -> impl Iterator<Item = Vec2<i32>> {
+    (to_tile(bounds.min.x)..=to_tile(bounds.max.x)).flat_map(move |x| {
+        (to_tile(bounds.min.y)..=to_tile(bounds.max.y)).map(move |y| Vec2::new(x, y))
+    })
+}
+
+/// Whether two areas meet along a line they share, without overlapping any
+/// further, like the walls of houses built against each other.
+fn shares_wall(a: Aabr<i32>, b: Aabr<i32>) -> bool {
+    let overlap = a.max.map2(b.max, i32::min) - a.min.map2(b.min, i32::max);
+    (overlap.x == 0 && overlap.y > 0) || (overlap.y == 0 && overlap.x > 0)
+}
+
+/// The area covered by a tile, in settlement-local block coordinates.
+fn tile_aabr(tile: Vec2<i32>) -> Aabr<i32> {
+    Aabr {
+        min: tile * AREA_SIZE as i32,
+        max: (tile + 1) * AREA_SIZE as i32 - 1,
+    }
+}
+
+/// The neighbours of a tile, in anticlockwise order starting from the east.
+const MOORE_DIRS: [Vec2<i32>; 8] = [
+    Vec2::new(1, 0),
+    Vec2::new(1, 1),
+    Vec2::new(0, 1),
+    Vec2::new(-1, 1),
+    Vec2::new(-1, 0),
+    Vec2::new(-1, -1),
+    Vec2::new(0, -1),
+    Vec2::new(1, -1),
+];
+
+/// The tiles along the outer edge of a connected set of tiles, in order around
+/// it and ending where they started. Consecutive tiles are cardinal
+/// neighbours, so the outline can be written with [`Land::write_path`], and
+/// the outline only passes through tiles of the set where it can.
+fn trace_outline(tiles: &HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>>) -> Vec<Vec2<i32>> {
+    let start = match tiles.iter().min_by_key(|tile| (tile.x, tile.y)) {
+        Some(start) => *start,
+        None => return Vec::new(),
+    };
+
+    // Moore neighbour tracing: walk around the edge, keeping the outside on
+    // one hand. Nothing lies west of the start, so begin looking from there.
+    let mut outline = vec![start];
+    let mut current = start;
+    let mut back = 4;
+    let mut first_step = None;
+    for _ in 0..tiles.len() * 8 + 8 {
+        let next = (1..=8)
+            .map(|k| (back + k) % 8)
+            .map(|i| (current + MOORE_DIRS[i], i))
+            .find(|(tile, _)| tiles.contains(tile));
+        let (next, i) = match next {
+            Some(next) => next,
+            // A lone tile
+            None => break,
+        };
+        if current == start && first_step == Some(next) {
+            break;
+        }
+        if current == start && first_step.is_none() {
+            first_step = Some(next);
+        }
+
+        // Step around corners rather than across them
+        let step = next - current;
+        if step.x != 0 && step.y != 0 {
+            let corner = current + Vec2::new(step.x, 0);
+            outline.push(if tiles.contains(&corner) {
+                corner
+            } else {
+                current + Vec2::new(0, step.y)
+            });
+        }
+        outline.push(next);
+
+        // Carry on from the last outside tile that was looked at
+        let outside = MOORE_DIRS[(i + 7) % 8] - MOORE_DIRS[i];
+        back = MOORE_DIRS
+            .iter()
+            .position(|dir| *dir == outside)
+            .unwrap_or(4);
+        current = next;
+    }
+    outline
+}
+
+/// A kind of structure defined outside of the settlement's built-in
+/// archetypes, so that new kinds of building can be placed without editing
+/// [`StructureKind`]. Positions are relative to the settlement's origin.
+pub trait StructureSource: Send + Sync {
+    fn bounds_2d(&self) -> Aabr<i32>;
+
+    fn bounds(&self) -> Aabb<i32>;
+
+    fn sample(&self, index: IndexRef, rpos: Vec3<i32>) -> Option<Block>;
+
+    /// The positions where the structure may place a sprite that can be
+    /// interacted with or that gives off light, see
+    /// [`building::Archetype::sprite_spots`].
+    fn sprite_spots(&self) -> Vec<Vec3<i32>> { Vec::new() }
+
+    /// The seed the structure was generated from. See [`Structure::seed`].
+    fn seed(&self) -> u32;
+
+    /// Add dynamic content, such as entities, to the chunk whose minimum
+    /// corner is at the world position `wpos2d`, for a settlement with the
+    /// given origin.
+    fn apply_supplement(
+        &self,
+        _dynamic_rng: &mut dyn RngCore,
+        _origin: Vec2<i32>,
+        _wpos2d: Vec2<i32>,
+        _supplement: &mut ChunkSupplement,
+    ) {
+    }
+}
+
+/// What a [`SpawnPolicy`] knows about a spot where the settlement spawns an
+/// entity.
+pub struct SpawnContext<'a> {
+    /// The plot the entity spawns on.
+    pub plot: &'a Plot,
+    /// The world position the entity spawns at.
+    pub wpos: Vec3<f32>,
+    pub settlement_name: &'a str,
+    pub settlement_origin: Vec2<i32>,
+    pub period: Option<DayPeriod>,
+    pub economy: &'a SiteInformation,
+    pub time: Option<&'a (TimeOfDay, Calendar)>,
+    /// Derived from the spawn position, for policies that make random
+    /// choices but must stay deterministic.
+    pub seed: u32,
+}
+
+/// Decides what the settlement spawns, replacing its built-in population.
+/// See [`Settlement::set_spawn_policy`].
+pub trait SpawnPolicy: Send + Sync {
+    /// The entity to spawn at a spawn site, if any.
+    fn entity_for(&self, ctx: &SpawnContext) -> Option<EntityInfo>;
+}
+
 pub enum StructureKind {
     House(Building<House>),
+    Tavern(Building<House>),
     Keep(Building<Keep>),
+    Monument(Building<Monument>),
+    NoticeBoard(Building<NoticeBoard>),
+    Temple(Building<Temple>),
+    Barracks(Building<Barracks>),
+    Crypt(Building<Crypt>),
+    Custom(Box<dyn StructureSource>),
 }
 
 pub struct Structure {
     kind: StructureKind,
 }
 
+/// The kinds of structure that can be described by a [`StructureDescriptor`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DescribedKind {
+    House,
+    Tavern,
+    Keep,
+    Monument,
+    NoticeBoard,
+    Temple,
+    Barracks,
+    Crypt,
+}
+
+/// The inputs a structure was generated from, which are enough to generate it
+/// again. Much cheaper to store or send than the structure's blocks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructureDescriptor {
+    pub kind: DescribedKind,
+    pub seed: u32,
+    /// The origin of the structure, relative to its settlement's origin.
+    pub origin: Vec3<i32>,
+    pub params: BuildingParams,
+}
+
 impl Structure {
     pub fn bounds_2d(&self) -> Aabr<i32> {
         match &self.kind {
-            StructureKind::House(house) => house.bounds_2d(),
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.bounds_2d(),
             StructureKind::Keep(keep) => keep.bounds_2d(),
+            StructureKind::Monument(monument) => monument.bounds_2d(),
+            StructureKind::NoticeBoard(board) => board.bounds_2d(),
+            StructureKind::Temple(temple) => temple.bounds_2d(),
+            StructureKind::Barracks(barracks) => barracks.bounds_2d(),
+            StructureKind::Crypt(crypt) => crypt.bounds_2d(),
+            StructureKind::Custom(custom) => custom.bounds_2d(),
         }
     }
 
     pub fn bounds(&self) -> Aabb<i32> {
         match &self.kind {
-            StructureKind::House(house) => house.bounds(),
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.bounds(),
             StructureKind::Keep(keep) => keep.bounds(),
+            StructureKind::Monument(monument) => monument.bounds(),
+            StructureKind::NoticeBoard(board) => board.bounds(),
+            StructureKind::Temple(temple) => temple.bounds(),
+            StructureKind::Barracks(barracks) => barracks.bounds(),
+            StructureKind::Crypt(crypt) => crypt.bounds(),
+            StructureKind::Custom(custom) => custom.bounds(),
         }
     }
 
-    pub fn sample(&self, index: IndexRef, rpos: Vec3<i32>) -> Option<Block> {
+    /// The house or tavern this structure is, if it's either.
+    fn house(&self) -> Option<&Building<House>> {
         match &self.kind {
-            StructureKind::House(house) => house.sample(index, rpos),
-            StructureKind::Keep(keep) => keep.sample(index, rpos),
+            StructureKind::House(house) | StructureKind::Tavern(house) => Some(house),
+            _ => None,
         }
     }
-}
-
-pub struct Settlement {
-    name: String,
-    seed: u32,
-    origin: Vec2<i32>,
-    land: Land,
-    farms: Store<Farm>,
-    structures: Vec<Structure>,
-    town: Option<Town>,
-    noise: RandomField,
-}
-
-pub struct Farm {
-    #[allow(dead_code)]
-    base_tile: Vec2<i32>,
-}
-
-pub struct GenCtx<'a, R: Rng> {
-    sim: Option<&'a WorldSim>,
-    rng: &'a mut R,
-}
-
-impl Settlement {
-    pub fn generate(wpos: Vec2<i32>, sim: Option<&WorldSim>, rng: &mut impl Rng) -> Self {
-        let mut ctx = GenCtx { sim, rng };
-        let mut this = Self {
-            name: NameGen::location(ctx.rng).generate(),
-            seed: ctx.rng.gen(),
-            origin: wpos,
-            land: Land::new(ctx.rng),
-            farms: Store::default(),
-            structures: Vec::new(),
-            town: None,
-            noise: RandomField::new(ctx.rng.gen()),
-        };
 
-        if let Some(sim) = ctx.sim {
-            this.designate_from_world(sim, ctx.rng);
+    /// The area within the walls of a house or tavern, see
+    /// [`Building::core_bounds_2d`]. Only houses share walls.
+    fn house_core(&self) -> Option<Aabr<i32>> { self.house().map(|house| house.core_bounds_2d()) }
+
+    /// Move the structure by `offs`, in place. Custom structures, which come
+    /// from elsewhere, stay where they are.
+    fn move_by(&mut self, offs: Vec3<i32>) {
+        match &mut self.kind {
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.move_by(offs),
+            StructureKind::Keep(keep) => keep.move_by(offs),
+            StructureKind::Monument(monument) => monument.move_by(offs),
+            StructureKind::NoticeBoard(board) => board.move_by(offs),
+            StructureKind::Temple(temple) => temple.move_by(offs),
+            StructureKind::Barracks(barracks) => barracks.move_by(offs),
+            StructureKind::Crypt(crypt) => crypt.move_by(offs),
+            StructureKind::Custom(_) => {},
         }
+    }
 
-        //this.place_river(rng);
+    /// Like [`Structure::move_by`], but taking the structure by value.
+    fn moved_by(mut self, offs: Vec3<i32>) -> Self {
+        self.move_by(offs);
+        self
+    }
 
-        this.place_farms(&mut ctx);
-        this.place_town(&mut ctx);
-        //this.place_paths(ctx.rng);
-        this.place_buildings(&mut ctx);
+    /// The altitudes spanned by the structure, from the bottom of its bounds
+    /// to just above their top.
+    pub fn z_range(&self) -> Range<i32> {
+        let bounds = self.bounds();
+        bounds.min.z..bounds.max.z + 1
+    }
 
-        this
+    pub fn sample(&self, index: IndexRef, rpos: Vec3<i32>) -> Option<Block> {
+        match &self.kind {
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.sample(index, rpos),
+            StructureKind::Keep(keep) => keep.sample(index, rpos),
+            StructureKind::Monument(monument) => monument.sample(index, rpos),
+            StructureKind::NoticeBoard(board) => board.sample(index, rpos),
+            StructureKind::Temple(temple) => temple.sample(index, rpos),
+            StructureKind::Barracks(barracks) => barracks.sample(index, rpos),
+            StructureKind::Crypt(crypt) => crypt.sample(index, rpos),
+            StructureKind::Custom(custom) => custom.sample(index, rpos),
+        }
     }
 
-    pub fn name(&self) -> &str { &self.name }
+    /// Render just this structure into `vol`, whose minimum corner is at
+    /// `wpos2d`, given relative to the origin of the structure's settlement
+    /// like the structure's bounds. `get_column` samples the terrain column at
+    /// an offset from `wpos2d`. The structure's blocks are the same as those
+    /// its settlement renders for it, but the foundations beneath it and the
+    /// snow on its roof, which depend on the settlement, are left out. Nothing
+    /// is rendered if the structure doesn't reach into `z_range`, see
+    /// [`Settlement::apply_structures_within`].
+    pub fn apply_to<'a>(
+        &self,
+        index: IndexRef,
+        wpos2d: Vec2<i32>,
+        z_range: Range<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
+    ) {
+        self.apply_dressed(
+            index,
+            Vec2::zero(),
+            z_range,
+            &mut |rpos| get_column(rpos - wpos2d),
+            &mut [(wpos2d, vol)],
+            |_| true,
+            |_| None,
+            |_| None,
+        );
+    }
 
-    pub fn get_origin(&self) -> Vec2<i32> { self.origin }
+    /// Render the structure into `vols`, as part of a settlement at `origin`,
+    /// unless it lies entirely outside `z_range`. Only the columns that
+    /// `claims` accepts, relative to `origin`, are rendered. `foundation` gives
+    /// the block filling the gap beneath the structure, at an offset from
+    /// the minimum corner of its bounds, and `roof_cap` the block capping
+    /// its roof at a world position.
+    fn apply_dressed<'a, V: RectSizedVol<Vox = Block> + ReadVol + WriteVol>(
+        &self,
+        index: IndexRef,
+        origin: Vec2<i32>,
+        z_range: Range<i32>,
+        get_column: &mut impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vols: &mut [(Vec2<i32>, &mut V)],
+        claims: impl Fn(Vec2<i32>) -> bool,
+        foundation: impl Fn(Vec2<i32>) -> Option<Block>,
+        roof_cap: impl Fn(Vec3<i32>) -> Option<Block>,
+    ) {
+        // Skip this structure if it's entirely above or below the volumes
+        let structure_z = self.z_range();
+        if structure_z.start >= z_range.end || structure_z.end <= z_range.start {
+            return;
+        }
 
-    /// Designate hazardous terrain based on world data
-    pub fn designate_from_world(&mut self, sim: &WorldSim, rng: &mut impl Rng) {
-        let tile_radius = self.radius() as i32 / AREA_SIZE as i32;
-        let hazard = self.land.hazard;
-        Spiral2d::new()
-            .take_while(|tile| tile.map(|e| e.abs()).reduce_max() < tile_radius)
-            .for_each(|tile| {
-                let wpos = self.origin + tile * AREA_SIZE as i32;
+        let bounds = self.bounds();
 
-                if (0..4)
-                    .flat_map(|x| (0..4).map(move |y| Vec2::new(x, y)))
-                    .any(|offs| {
-                        let wpos = wpos + offs * AREA_SIZE as i32 / 2;
-                        let cpos = wpos.map(|e| e.div_euclid(TerrainChunkSize::RECT_SIZE.x as i32));
-                        !sim.can_host_settlement(cpos)
-                    })
-                    || rng.gen_range(0..16) == 0
-                // Randomly consider some tiles inaccessible
-                {
-                    self.land.set(tile, hazard);
+        for x in bounds.min.x..bounds.max.x + 1 {
+            for y in bounds.min.y..bounds.max.y + 1 {
+                if !claims(Vec2::new(x, y)) {
+                    continue;
                 }
-            })
-    }
-
-    /// Testing only
-    pub fn place_river(&mut self, rng: &mut impl Rng) {
-        let river_dir = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5).normalized();
-        let radius = 500.0 + rng.gen::<f32>().powi(2) * 1000.0;
-        let river = self.land.new_plot(Plot::Water);
-        let river_offs = Vec2::new(rng.gen_range(-3..4), rng.gen_range(-3..4));
-
-        for x in (0..100).map(|e| e as f32 / 100.0) {
-            let theta0 = x * f32::consts::PI * 2.0;
-            let theta1 = (x + 0.01) * f32::consts::PI * 2.0;
+                let wpos2d = origin + Vec2::new(x, y);
+                let (vol_min, vol) = if let Some(vol) = vol_at(vols, wpos2d) {
+                    vol
+                } else {
+                    continue;
+                };
+                let col = if let Some(col) = get_column(wpos2d) {
+                    col
+                } else {
+                    continue;
+                };
 
-            let pos0 = (river_dir * radius + Vec2::new(theta0.sin(), theta0.cos()) * radius)
-                .map(|e| e.floor() as i32)
-                .map(to_tile)
-                + river_offs;
-            let pos1 = (river_dir * radius + Vec2::new(theta1.sin(), theta1.cos()) * radius)
-                .map(|e| e.floor() as i32)
-                .map(to_tile)
-                + river_offs;
+                // Where the bottom of the structure stands above the ground,
+                // its foundations fill the gap
+                let foundation = self
+                    .sample(index, Vec3::new(x, y, bounds.min.z))
+                    .filter(|block| block.is_filled())
+                    .and_then(|_| foundation(Vec2::new(x, y) - Vec2::from(bounds.min)));
+
+                let mut roof = None;
+                for z in bounds.min.z.min(col.alt.floor() as i32 - 1)..bounds.max.z + 1 {
+                    let rpos = Vec3::new(x, y, z);
+                    let wpos = Vec3::from(origin) + rpos;
+                    let coffs = wpos - Vec3::from(vol_min);
+
+                    if z < bounds.min.z {
+                        if let Some(block) = foundation {
+                            let _ = vol.set(coffs, block);
+                        }
+                        continue;
+                    }
 
-            if pos0.magnitude_squared() > 15i32.pow(2) {
-                continue;
-            }
+                    if let Some(block) = self.sample(index, rpos) {
+                        let _ = vol.set(coffs, block);
+                        if block.is_filled() {
+                            roof = Some(wpos);
+                        }
+                    }
+                }
 
-            if let Some(path) = self.land.find_path(pos0, pos1, |_, _| 1.0) {
-                for pos in path.iter().copied() {
-                    self.land.set(pos, river);
+                // Snow-capped roofs, but not the ceilings of rooms underground
+                if let Some(wpos) = roof.filter(|wpos| wpos.z >= col.alt.floor() as i32) {
+                    if let Some(block) = roof_cap(wpos) {
+                        let _ = vol.set(wpos - Vec3::from(vol_min), block);
+                    }
                 }
             }
         }
     }
 
-    pub fn place_paths(&mut self, rng: &mut impl Rng) {
-        const PATH_COUNT: usize = 6;
+    /// The inputs the structure was generated from, see
+    /// [`Structure::from_descriptor`]. Custom structures, which come from
+    /// elsewhere, can't be described.
+    pub fn descriptor(&self) -> Option<StructureDescriptor> {
+        let (kind, seed, origin, params) = match &self.kind {
+            StructureKind::House(house) => (
+                DescribedKind::House,
+                house.seed(),
+                house.origin(),
+                house.params(),
+            ),
+            StructureKind::Tavern(house) => (
+                DescribedKind::Tavern,
+                house.seed(),
+                house.origin(),
+                house.params(),
+            ),
+            StructureKind::Keep(keep) => (
+                DescribedKind::Keep,
+                keep.seed(),
+                keep.origin(),
+                keep.params(),
+            ),
+            StructureKind::Monument(monument) => (
+                DescribedKind::Monument,
+                monument.seed(),
+                monument.origin(),
+                monument.params(),
+            ),
+            StructureKind::NoticeBoard(board) => (
+                DescribedKind::NoticeBoard,
+                board.seed(),
+                board.origin(),
+                board.params(),
+            ),
+            StructureKind::Temple(temple) => (
+                DescribedKind::Temple,
+                temple.seed(),
+                temple.origin(),
+                temple.params(),
+            ),
+            StructureKind::Barracks(barracks) => (
+                DescribedKind::Barracks,
+                barracks.seed(),
+                barracks.origin(),
+                barracks.params(),
+            ),
+            StructureKind::Crypt(crypt) => (
+                DescribedKind::Crypt,
+                crypt.seed(),
+                crypt.origin(),
+                crypt.params(),
+            ),
+            StructureKind::Custom(_) => return None,
+        };
+        Some(StructureDescriptor {
+            kind,
+            seed,
+            origin,
+            params,
+        })
+    }
 
-        let mut dir = Vec2::zero();
-        for _ in 0..PATH_COUNT {
-            dir = (Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * 2.0 - dir)
-                .try_normalized()
-                .unwrap_or_else(Vec2::zero);
-            let origin = dir.map(|e| (e * 100.0) as i32);
-            let origin = self
-                .land
-                .find_tile_near(origin, |plot| matches!(plot, Some(&Plot::Field { .. })))
-                .unwrap();
+    /// Generate the structure described by `desc` again, identical to the
+    /// one it was taken from.
+    pub fn from_descriptor(desc: StructureDescriptor) -> Self {
+        let StructureDescriptor {
+            kind,
+            seed,
+            origin,
+            params,
+        } = desc;
+        let calendar = params.calendar();
+        let calendar = calendar.as_ref();
+        let kind = match kind {
+            DescribedKind::House | DescribedKind::Tavern => {
+                let house = if params.tavern {
+                    Building::<House>::generate_tavern(seed, origin, calendar)
+                } else {
+                    Building::<House>::generate(seed, origin, calendar)
+                };
+                // Applied in the same order as when placing buildings
+                let house = match params.ori {
+                    Some(ori) => house.with_ori(ori),
+                    None => house,
+                };
+                let house = match params.porch {
+                    Some(dir) => house.with_porch(dir),
+                    None => house,
+                };
+                let house = match params.roof_style {
+                    Some(roof_style) => house.with_roof_style(roof_style),
+                    None => house,
+                };
+                if kind == DescribedKind::Tavern {
+                    StructureKind::Tavern(house)
+                } else {
+                    StructureKind::House(house)
+                }
+            },
+            DescribedKind::Keep => {
+                let keep = match params.tier {
+                    Some(tier) => Building::<Keep>::generate_tier(seed, origin, tier),
+                    None => Building::<Keep>::generate(seed, origin, calendar),
+                };
+                StructureKind::Keep(match params.ori {
+                    Some(ori) => keep.with_ori(ori),
+                    None => keep,
+                })
+            },
+            DescribedKind::Monument => {
+                StructureKind::Monument(Building::generate(seed, origin, calendar))
+            },
+            DescribedKind::NoticeBoard => {
+                StructureKind::NoticeBoard(Building::generate(seed, origin, calendar))
+            },
+            DescribedKind::Temple => {
+                StructureKind::Temple(Building::generate(seed, origin, calendar))
+            },
+            DescribedKind::Barracks => {
+                StructureKind::Barracks(Building::generate(seed, origin, calendar))
+            },
+            DescribedKind::Crypt => {
+                StructureKind::Crypt(Building::generate(seed, origin, calendar))
+            },
+        };
+        Self { kind }
+    }
 
-            if let Some(path) = self.town.as_ref().and_then(|town| {
-                self.land
-                    .find_path(origin, town.base_tile, |from, to| match (from, to) {
-                        (_, Some(b)) if self.land.plot(b.plot) == &Plot::Dirt => 0.0,
-                        (_, Some(b)) if self.land.plot(b.plot) == &Plot::Water => 20.0,
-                        (_, Some(b)) if self.land.plot(b.plot) == &Plot::Hazard => 50.0,
-                        (Some(a), Some(b)) if a.contains(WayKind::Wall) => {
-                            if b.contains(WayKind::Wall) {
-                                1000.0
-                            } else {
-                                10.0
-                            }
-                        },
-                        (Some(_), Some(_)) => 1.0,
-                        _ => 1000.0,
-                    })
-            }) {
-                let path = path.iter().copied().collect::<Vec<_>>();
-                self.land.write_path(&path, WayKind::Path, |_| true, false);
-            }
+    /// The sprites that the structure may place where they can be interacted
+    /// with or give off light, found at the spots its archetype lists rather
+    /// than by sampling every block within its bounds.
+    pub fn sprites(&self, index: IndexRef) -> Vec<(Vec3<i32>, Block)> {
+        match &self.kind {
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.sprites(index),
+            StructureKind::Keep(keep) => keep.sprites(index),
+            StructureKind::Monument(monument) => monument.sprites(index),
+            StructureKind::NoticeBoard(board) => board.sprites(index),
+            StructureKind::Temple(temple) => temple.sprites(index),
+            StructureKind::Barracks(barracks) => barracks.sprites(index),
+            StructureKind::Crypt(crypt) => crypt.sprites(index),
+            StructureKind::Custom(custom) => custom
+                .sprite_spots()
+                .into_iter()
+                .filter_map(|rpos| {
+                    Some((
+                        rpos,
+                        custom
+                            .sample(index, rpos)
+                            .filter(|b| b.get_sprite().is_some())?,
+                    ))
+                })
+                .collect(),
         }
     }
 
-    pub fn place_town(&mut self, ctx: &mut GenCtx<impl Rng>) {
-        const PLOT_COUNT: usize = 3;
+    /// The seed the structure was generated from. The seed alone doesn't fix
+    /// the structure's geometry: its kind and [`BuildingParams`] (orientation,
+    /// porch, roof style, ...) shape it as well. Structures whose descriptors
+    /// differ only in their origin are identical relative to it, so meshes
+    /// should be shared on that basis rather than on the seed.
+    pub fn seed(&self) -> u32 {
+        match &self.kind {
+            StructureKind::House(house) | StructureKind::Tavern(house) => house.seed(),
+            StructureKind::Keep(keep) => keep.seed(),
+            StructureKind::Monument(monument) => monument.seed(),
+            StructureKind::NoticeBoard(board) => board.seed(),
+            StructureKind::Temple(temple) => temple.seed(),
+            StructureKind::Barracks(barracks) => barracks.seed(),
+            StructureKind::Crypt(crypt) => crypt.seed(),
+            StructureKind::Custom(custom) => custom.seed(),
+        }
+    }
+}
 
-        let mut origin = Vec2::new(ctx.rng.gen_range(-2..3), ctx.rng.gen_range(-2..3));
+/// Signs are only placed by settlements on notice boards, so they identify the
+/// town's notice board.
+fn is_interactable(sprite: &SpriteKind) -> bool {
+    sprite.is_container()
+        || matches!(
+            sprite,
+            SpriteKind::Anvil
+                | SpriteKind::Cauldron
+                | SpriteKind::CookingPot
+                | SpriteKind::CraftingBench
+                | SpriteKind::DismantlingBench
+                | SpriteKind::FireBowlGround
+                | SpriteKind::Forge
+                | SpriteKind::Loom
+                | SpriteKind::RepairBench
+                | SpriteKind::Sign
+                | SpriteKind::SpinningWheel
+                | SpriteKind::StreetLamp
+                | SpriteKind::StreetLampTall
+                | SpriteKind::TanningRack
+                | SpriteKind::Lantern
+        )
+}
 
-        for i in 0..PLOT_COUNT {
-            if let Some(base_tile) = self.land.find_tile_near(origin, |plot| {
-                matches!(plot, Some(Plot::Field { .. }) | Some(Plot::Dirt))
-            }) {
-                // self.land
-                //     .plot_at_mut(base_tile)
-                //     .map(|plot| *plot = Plot::Town { district: None });
+/// A notable structure that may be shown on maps or used for navigation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Landmark {
+    pub kind: LandmarkKind,
+    pub wpos: Vec2<i32>,
+    /// How far the top of the landmark rises above its base, in blocks.
+    pub height: i32,
+}
 
-                if i == 0 {
-                    let town = Town::generate(self.origin, base_tile, ctx);
+/// A broken invariant of a [`Settlement`], found by [`Settlement::validate`].
+/// Structures are identified by their index and tiles are given in tile
+/// coordinates relative to the settlement's origin.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SettlementIssue {
+    /// Two structures have overlapping footprints.
+    StructuresOverlap(usize, usize),
+    /// A structure's footprint covers hazardous terrain or water.
+    StructureOnUnbuildable { structure: usize, tile: Vec2<i32> },
+    /// A tile refers to a plot that doesn't exist.
+    DanglingPlot { tile: Vec2<i32> },
+    /// A field refers to a farm that doesn't exist.
+    DanglingFarm { tile: Vec2<i32> },
+    /// A town plot refers to a district that doesn't exist in the town.
+    DanglingDistrict { tile: Vec2<i32> },
+    /// A way leads to a neighbouring tile that doesn't exist.
+    DanglingWay { tile: Vec2<i32>, dir: Vec2<i32> },
+}
 
-                    for (id, district) in town.districts().iter() {
-                        let district_plot =
-                            self.land.plots.insert(Plot::Town { district: Some(id) });
+/// The generated content of a [`Settlement`], without any terrain applied.
+/// Tiles are given in tile coordinates relative to the settlement's origin,
+/// each tile covering a square of `AREA_SIZE` blocks.
+#[derive(Copy, Clone)]
+pub struct SettlementData<'a> {
+    settlement: &'a Settlement,
+}
 
-                        for x in district.aabr.min.x..district.aabr.max.x {
-                            for y in district.aabr.min.y..district.aabr.max.y {
-                                if !matches!(self.land.plot_at(Vec2::new(x, y)), Some(Plot::Hazard))
-                                {
-                                    self.land.set(Vec2::new(x, y), district_plot);
-                                }
-                            }
-                        }
-                    }
+impl<'a> SettlementData<'a> {
+    pub fn name(&self) -> &'a str { &self.settlement.name }
 
-                    self.town = Some(town);
-                    origin = base_tile;
-                }
-            }
-        }
+    pub fn origin(&self) -> Vec2<i32> { self.settlement.origin }
 
-        // Boundary wall
-        /*
-        let spokes = CARDINALS
+    /// Every tile of the settlement's land, with its plot.
+    pub fn plots(&self) -> impl Iterator<Item = (Vec2<i32>, &'a Plot)> + 'a {
+        let land = &self.settlement.land;
+        land.tiles
             .iter()
-            .filter_map(|dir| {
-                self.land.find_tile_dir(origin, *dir, |plot| match plot {
-                    Some(Plot::Water) => false,
-                    Some(Plot::Town) => false,
-                    _ => true,
-                })
-            })
-            .collect::<Vec<_>>();
-        let mut wall_path = Vec::new();
-        for i in 0..spokes.len() {
-            self.land
-                .find_path(spokes[i], spokes[(i + 1) % spokes.len()], |_, to| match to
-                    .map(|to| self.land.plot(to.plot))
-                {
-                    Some(Plot::Hazard) => 200.0,
-                    Some(Plot::Water) => 40.0,
-                    Some(Plot::Town) => 10000.0,
-                    _ => 10.0,
-                })
-                .map(|path| wall_path.extend(path.iter().copied()));
-        }
-        let grass = self.land.new_plot(Plot::Grass);
-        let buildable = |plot: &Plot| match plot {
-            Plot::Water => false,
-            _ => true,
+            .map(move |(pos, tile)| (*pos, land.plot(tile.plot)))
+    }
+
+    /// Every way leaving a tile, along with the direction in which it leaves.
+    pub fn ways(&self) -> impl Iterator<Item = (Vec2<i32>, Vec2<i32>, WayKind)> + 'a {
+        self.settlement.land.tiles.iter().flat_map(|(pos, tile)| {
+            WAY_DIRS
+                .iter()
+                .zip(tile.ways.iter())
+                .filter_map(move |(dir, way)| Some((*pos, *dir, (*way)?)))
+        })
+    }
+
+    pub fn structures(&self) -> &'a [Structure] { &self.settlement.structures }
+
+    /// The tile around which each farm's fields were placed.
+    pub fn farms(&self) -> impl Iterator<Item = Vec2<i32>> + 'a {
+        self.settlement.farms.values().map(|farm| farm.base_tile)
+    }
+
+    pub fn landmarks(&self) -> impl Iterator<Item = Landmark> + 'a { self.settlement.landmarks() }
+
+    pub fn light_sources(&self, index: IndexRef) -> Vec<Vec3<i32>> {
+        self.settlement.light_sources(index)
+    }
+
+    pub fn interactables(&self, index: IndexRef) -> Vec<(Vec3<i32>, SpriteKind)> {
+        self.settlement.interactables(index)
+    }
+}
+
+/// How a settlement is marked on the world map at low zoom.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapIconKind {
+    /// A handful of houses, or just farmland.
+    Hamlet,
+    Town,
+    /// A walled settlement with a keep.
+    Fort,
+    /// A settlement left with nothing standing.
+    Ruins,
+}
+
+/// An independent source of randomness within a settlement. Each stream has a
+/// seed of its own, derived from the settlement's seed, so that changing how
+/// one subsystem draws from its stream doesn't reshuffle the others.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SeedStream {
+    /// Where farms, the town and its structures are placed, the shape of its
+    /// tiles, and the seeds of the structures themselves.
+    Layout = 0,
+    /// The settlement's noise field, which colors, dresses and decorates its
+    /// ground and structures.
+    Decoration = 1,
+    /// The entities spawned in the settlement.
+    Population = 2,
+}
+
+impl SeedStream {
+    const ALL: [SeedStream; 3] = [
+        SeedStream::Layout,
+        SeedStream::Decoration,
+        SeedStream::Population,
+    ];
+
+    /// The seed of this stream for a settlement with the given seed, hashed
+    /// from it with a tag unique to the stream.
+    pub fn derive(self, seed: u32) -> u32 {
+        let tag = match self {
+            SeedStream::Layout => 0x4c41_594f,
+            SeedStream::Decoration => 0x4445_434f,
+            SeedStream::Population => 0x504f_5055,
         };
-        for pos in wall_path.iter() {
-            if self.land.tile_at(*pos).is_none() {
-                self.land.set(*pos, grass);
-            }
-            if self.land.plot_at(*pos).copied().filter(buildable).is_some() {
-                self.land
-                    .tile_at_mut(*pos)
-                    .map(|tile| tile.tower = Some(Tower::Wall));
-            }
-        }
-        if wall_path.len() > 0 {
-            wall_path.push(wall_path[0]);
+        seed_expan::diffuse_mult(&[seed, tag])
+    }
+}
+
+/// A use of a [`SeedStream`] that needs randomness of its own. Each use has a
+/// seed of its own, derived from its stream's seed, so that no two uses draw
+/// from the same field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SeedUse {
+    /// How blocks are warped onto the tiles they belong to.
+    Warp,
+    /// Whether a house is built as a tavern.
+    Tavern,
+    /// The roof style shared by every part of a house.
+    RoofStyle,
+    /// The settlement's orchard.
+    Orchard,
+    /// Whether an entity spawns on a column.
+    SpawnChance,
+    /// The entity spawned on a column.
+    Spawn,
+    /// Whether a spawn in town is a training dummy.
+    Dummy,
+    /// The starting inventories of townsfolk.
+    StartingInventory,
+    /// Whether a guard sleeps in a bunk.
+    BunkChance,
+    /// The guard sleeping in a bunk.
+    BunkGuard,
+}
+
+impl SeedUse {
+    /// The stream this use draws from.
+    fn stream(self) -> SeedStream {
+        match self {
+            SeedUse::Warp | SeedUse::Tavern | SeedUse::RoofStyle | SeedUse::Orchard => {
+                SeedStream::Layout
+            },
+            SeedUse::SpawnChance
+            | SeedUse::Spawn
+            | SeedUse::Dummy
+            | SeedUse::StartingInventory
+            | SeedUse::BunkChance
+            | SeedUse::BunkGuard => SeedStream::Population,
         }
-        self.land
-            .write_path(&wall_path, WayKind::Wall, buildable, true);
-        */
     }
 
-    pub fn place_buildings(&mut self, ctx: &mut GenCtx<impl Rng>) {
-        let town_center = if let Some(town) = self.town.as_ref() {
-            town.base_tile
-        } else {
-            return;
+    /// The seed of this use, hashed from the seed of its stream with a tag
+    /// unique to the use, like [`SeedStream::derive`].
+    fn derive(self, stream_seed: u32) -> u32 {
+        let tag = match self {
+            SeedUse::Warp => 0x5741_5250,
+            SeedUse::Tavern => 0x5441_5645,
+            SeedUse::RoofStyle => 0x524f_4f46,
+            SeedUse::Orchard => 0x4f52_4348,
+            SeedUse::SpawnChance => 0x5350_4348,
+            SeedUse::Spawn => 0x5350_574e,
+            SeedUse::Dummy => 0x4455_4d4d,
+            SeedUse::StartingInventory => 0x494e_5645,
+            SeedUse::BunkChance => 0x4255_4e4b,
+            SeedUse::BunkGuard => 0x4755_4152,
         };
+        seed_expan::diffuse_mult(&[stream_seed, tag])
+    }
+}
 
-        for tile in Spiral2d::new()
-            .map(|offs| town_center + offs)
-            .take(16usize.pow(2))
-        {
-            // This is a stupid way to decide how to place buildings
-            for i in 0..ctx.rng.gen_range(2..5) {
-                for _ in 0..25 {
-                    let house_pos = tile.map(|e| e * AREA_SIZE as i32 + AREA_SIZE as i32 / 2)
-                        + Vec2::<i32>::zero().map(|_| {
-                            ctx.rng
-                                .gen_range(-(AREA_SIZE as i32) / 4..AREA_SIZE as i32 / 4)
-                        });
+/// How a gate through the settlement's wall is shut.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GateKind {
+    /// An iron grate, guarding the gates of settlements with a keep.
+    Portcullis,
+    /// A pair of wooden doors.
+    Doors,
+}
 
-                    let tile_pos = house_pos.map(|e| e.div_euclid(AREA_SIZE as i32));
-                    if self
-                        .land
-                        .tile_at(tile_pos)
-                        .map(|t| t.contains(WayKind::Path))
-                        .unwrap_or(true)
-                        || ctx
-                            .sim
-                            .and_then(|sim| sim.get_nearest_path(self.origin + house_pos))
-                            .map(|(dist, _, _, _)| dist < 28.0)
-                            .unwrap_or(false)
-                    {
-                        continue;
-                    }
+impl GateKind {
+    /// The block filling a closed gate at height `z` above the ground.
+    fn closed_block(&self, colors: &Colors, wpos2d: Vec2<i32>, z: i32) -> Block {
+        match self {
+            GateKind::Portcullis => {
+                if (wpos2d.x + wpos2d.y).rem_euclid(2) == 0 || z.rem_euclid(2) == 0 {
+                    Block::new(BlockKind::Rock, colors.tower_color.into())
+                } else {
+                    Block::air(SpriteKind::Empty)
+                }
+            },
+            GateKind::Doors => Block::new(BlockKind::Wood, colors.gate_door.into()),
+        }
+    }
+}
 
-                    let alt = if let Some(Plot::Town { district }) = self.land.plot_at(tile_pos) {
-                        district
-                            .and_then(|d| self.town.as_ref().map(|t| t.districts().get(d)))
-                            .map(|d| d.alt)
-                            .filter(|_| false) // Temporary
-                            .unwrap_or_else(|| {
-                                ctx.sim
-                                    .and_then(|sim| sim.get_alt_approx(self.origin + house_pos))
-                                    .unwrap_or(0.0)
-                                    .ceil() as i32
-                            })
-                    } else {
-                        continue;
-                    };
+/// A gate where a path passes through the settlement's wall.
+struct Gate {
+    tile: Vec2<i32>,
+    /// The world position of the gate's threshold.
+    wpos: Vec3<i32>,
+    kind: GateKind,
+    open: bool,
+}
 
-                    let structure = Structure {
-                        kind: if tile == town_center && i == 0 {
-                            StructureKind::Keep(Building::<Keep>::generate(
-                                ctx.rng,
-                                Vec3::new(house_pos.x, house_pos.y, alt),
-                                None,
-                            ))
-                        } else {
-                            StructureKind::House(Building::<House>::generate(
-                                ctx.rng,
-                                Vec3::new(house_pos.x, house_pos.y, alt),
-                                ctx.sim.and_then(|sim| sim.calendar.as_ref()),
-                            ))
-                        },
-                    };
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LandmarkKind {
+    Keep,
+    Monument,
+    /// A temple, seen from afar by its spire.
+    Temple,
+}
 
-                    let bounds = structure.bounds_2d();
+pub struct Settlement {
+    name: String,
+    /// The seed of each [`SeedStream`], indexed by the stream.
+    stream_seeds: [u32; 3],
+    origin: Vec2<i32>,
+    land: Land,
+    farms: Store<Farm>,
+    structures: Vec<Structure>,
+    /// Tiles overlapped by the footprint of a structure, which fields and
+    /// paths must keep clear of.
+    reserved: HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>>,
+    town: Option<Town>,
+    noise: RandomField,
+    /// Whether the settlement lies in a cold biome, covering its surfaces and
+    /// roofs in snow.
+    cold: bool,
+    /// The biome the settlement lies in, which decides what its orchards grow.
+    biome: BiomeKind,
+    /// Crops that are never planted in the settlement's fields.
+    excluded_crops: Vec<Crop>,
+    /// The crops grown by each farm in turn, instead of crops picked at
+    /// random.
+    forced_crops: Option<Vec<Crop>>,
+    lamps: LampParams,
+    /// Chance of a prop standing on an open town column beside a structure.
+    prop_density: f32,
+    /// Farms to place, if not derived from the buildable land.
+    farm_params: Option<FarmParams>,
+    crossings: CrossingParams,
+    spawn_policy: Option<Box<dyn SpawnPolicy>>,
+    /// Whether fields on steep ground are cut into flat terraces.
+    terraced_fields: bool,
+    gates: Vec<Gate>,
+    /// The height of the surface of each water plot, in world coordinates.
+    /// Water plots without one sit just below the ground of each column.
+    water_levels: HashMap<Id<Plot>, i32, BuildHasherDefault<FxHasher64>>,
+    /// The height of the ground of each plot claimed with
+    /// [`Settlement::reserve_plot`], in world coordinates.
+    reserved_levels: HashMap<Id<Plot>, i32, BuildHasherDefault<FxHasher64>>,
+    /// Whether buildings are aligned to a regular grid and to their streets,
+    /// as in a planned town, rather than placed organically.
+    grid_snap: bool,
+    /// Share of the spots inside houses that are furnished with object
+    /// entities.
+    furniture_density: f32,
+    /// Whether the town is enclosed by a wall.
+    town_walls: bool,
+    structure_mix: StructureMix,
+    /// How far the ground of the town is leveled, from `0.0` (not at all) to
+    /// `1.0` (fully).
+    leveling: f32,
+    /// The smoothed altitude of each town tile, which its ground is leveled
+    /// towards. Empty unless the town is leveled.
+    town_alts: HashMap<Vec2<i32>, f32, BuildHasherDefault<FxHasher64>>,
+    foundation: FoundationStyle,
+    faction: Faction,
+    /// Whether the furrows of fields follow the contours of their slope,
+    /// rather than running in a fixed direction.
+    contour_furrows: bool,
+    /// The unit downhill direction of each sloping field, keyed by the tile it
+    /// grew from. Empty unless furrows follow contours.
+    field_slopes: HashMap<Vec2<i32>, Vec2<f32>, BuildHasherDefault<FxHasher64>>,
+    /// Telemetry of the generation so far, if it is being collected.
+    stats: Option<GenStats>,
+    /// Chance of a column of open grassy ground being decorated with grass,
+    /// flowers or small rocks.
+    decoration_density: f32,
+    /// The tile of a feature of the world, such as a cave mouth, that the
+    /// settlement formed around. It is kept clear and linked to the town by a
+    /// path.
+    feature: Option<Vec2<i32>>,
+    /// How steeply the number of buildings per tile falls off away from the
+    /// town center, see [`building_density`].
+    density_falloff: f32,
+    /// Whether fortified settlements get a moat around their wall.
+    moat: bool,
+    /// Whether townsfolk spawn carrying produce and coins, see
+    /// [`Settlement::starting_inventory`].
+    starting_inventories: bool,
+    /// The chance of each house being built against its nearest neighbour,
+    /// sharing a wall with it.
+    clustering: f32,
+    /// Whether towns get a temple near their center.
+    temple: bool,
+    /// How rich and dangerous the settlement is, from `0` up to
+    /// [`Settlement::MAX_TIER`]. Picks the loot of its chests.
+    tier: u8,
+}
 
-                    // Check for collision with other structures
-                    if self
-                        .structures
-                        .iter()
-                        .any(|s| s.bounds_2d().collides_with_aabr(bounds))
-                    {
-                        continue;
-                    }
+/// The blocks a settlement writes to each chunk it covers, recorded once by
+/// [`Settlement::bake`] so that they can be replayed onto the chunks later
+/// without sampling the settlement again.
+pub struct BakedSettlement {
+    /// The blocks written to each chunk, keyed by the world position of its
+    /// minimum corner. Positions are relative to the chunk.
+    chunks: HashMap<Vec2<i32>, Vec<(Vec3<i32>, Block)>, BuildHasherDefault<FxHasher64>>,
+}
 
-                    self.structures.push(structure);
-                    break;
+impl BakedSettlement {
+    /// Replay the blocks recorded for the chunk whose minimum corner is at
+    /// `wpos2d` onto `vol`. Returns whether anything was recorded for the
+    /// chunk.
+    pub fn apply_baked(&self, wpos2d: Vec2<i32>, vol: &mut impl WriteVol<Vox = Block>) -> bool {
+        match self.chunks.get(&wpos2d) {
+            Some(blocks) => {
+                for (pos, block) in blocks {
+                    let _ = vol.set(*pos, *block);
                 }
-            }
+                true
+            },
+            None => false,
         }
     }
 
-    pub fn place_farms(&mut self, ctx: &mut GenCtx<impl Rng>) {
-        const FARM_COUNT: usize = 6;
-        const FIELDS_PER_FARM: usize = 5;
+    /// The world positions of the minimum corners of the baked chunks.
+    pub fn chunks(&self) -> impl Iterator<Item = Vec2<i32>> + '_ { self.chunks.keys().copied() }
+}
 
-        for _ in 0..FARM_COUNT {
-            if let Some(base_tile) = self
-                .land
-                .find_tile_near(Vec2::zero(), |plot| plot.is_none())
-            {
-                // Farm
-                //let farmhouse = self.land.new_plot(Plot::Dirt);
-                //self.land.set(base_tile, farmhouse);
+/// How densely street lamps line the paths of a settlement's town.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LampParams {
+    /// Distance between lamps along the side of a path, in blocks.
+    pub spacing: f32,
+    /// Chance of a lamp standing on a town column far from any path.
+    pub random_chance: f32,
+    /// Distance between the posts of bridge railings, in blocks. Lamps stand
+    /// at either end of a bridge.
+    pub railing_spacing: f32,
+}
 
-                // Farmhouses
-                // for _ in 0..ctx.rng.gen_range(1..3) {
-                //     let house_pos = base_tile.map(|e| e * AREA_SIZE as i32 + AREA_SIZE as i32
-                // / 2)         + Vec2::new(ctx.rng.gen_range(-16..16),
-                // ctx.rng.gen_range(-16..16));
+impl Default for LampParams {
+    fn default() -> Self {
+        Self {
+            spacing: 15.0,
+            random_chance: 1.0 / 750.0,
+            railing_spacing: 4.0,
+        }
+    }
+}
 
-                //     self.structures.push(Structure {
-                //         kind: StructureKind::House(HouseBuilding::generate(ctx.rng,
-                // Vec3::new(             house_pos.x,
-                //             house_pos.y,
-                //             ctx.sim
-                //                 .and_then(|sim| sim.get_alt_approx(self.origin + house_pos))
-                //                 .unwrap_or(0.0)
-                //                 .ceil() as i32,
-                //         ))),
-                //     });
-                // }
+impl LampParams {
+    /// Lamps suiting a settlement with the given number of structures: small
+    /// hamlets make do with fewer lamps, while large towns get more.
+    pub fn for_size(structures: usize) -> Self {
+        match structures {
+            0..=11 => Self {
+                spacing: 25.0,
+                random_chance: 1.0 / 1500.0,
+                ..Self::default()
+            },
+            12..=39 => Self::default(),
+            _ => Self {
+                spacing: 10.0,
+                random_chance: 1.0 / 500.0,
+                ..Self::default()
+            },
+        }
+    }
+}
 
-                // Fields
-                let farmland = self.farms.insert(Farm { base_tile });
-                for _ in 0..FIELDS_PER_FARM {
-                    self.place_field(farmland, base_tile, ctx.rng);
-                }
-            }
+/// How the gap between the bottom of a structure and lower ground beneath it
+/// is filled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FoundationStyle {
+    /// A solid berm of earth.
+    Earth,
+    /// A solid stone plinth.
+    Stone,
+    /// Wooden posts, with open space between them.
+    Stilts,
+}
+
+impl FoundationStyle {
+    /// Stilts keep buildings clear of wet ground, and stone stands up to
+    /// harsh climates.
+    pub fn for_biome(biome: BiomeKind) -> Self {
+        match biome {
+            BiomeKind::Swamp | BiomeKind::Lake | BiomeKind::Ocean | BiomeKind::Jungle => {
+                Self::Stilts
+            },
+            BiomeKind::Mountain | BiomeKind::Snowland | BiomeKind::Taiga => Self::Stone,
+            _ => Self::Earth,
         }
     }
+}
 
-    pub fn place_field(
-        &mut self,
-        farm: Id<Farm>,
-        origin: Vec2<i32>,
-        rng: &mut impl Rng,
-    ) -> Option<Id<Plot>> {
-        const MAX_FIELD_SIZE: usize = 24;
+/// Who holds a settlement, which decides how its people greet visitors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Faction {
+    /// An ordinary settlement, whose guards keep the peace.
+    #[default]
+    Friendly,
+    /// An enemy-held settlement, such as a bandit camp, whose people attack
+    /// on sight. It has no guards or merchants.
+    Hostile,
+}
 
-        if let Some(center) = self.land.find_tile_near(origin, |plot| plot.is_none()) {
-            let field = self.land.new_plot(Plot::Field {
-                farm,
-                seed: rng.gen(),
-                crop: match rng.gen_range(0..8) {
-                    0 => Crop::Corn,
-                    1 => Crop::Wheat,
-                    2 => Crop::Cabbage,
-                    3 => Crop::Pumpkin,
-                    4 => Crop::Flax,
-                    5 => Crop::Carrot,
-                    6 => Crop::Tomato,
-                    7 => Crop::Radish,
-                    _ => Crop::Sunflower,
-                },
-            });
-            let tiles =
-                self.land
-                    .grow_from(center, rng.gen_range(5..MAX_FIELD_SIZE), rng, |plot| {
-                        plot.is_none()
-                    });
-            for pos in tiles.into_iter() {
-                self.land.set(pos, field);
-            }
-            Some(field)
-        } else {
-            None
+impl Faction {
+    /// The alignment of the settlement's people.
+    pub fn alignment(&self) -> comp::Alignment {
+        match self {
+            Faction::Friendly => comp::Alignment::Npc,
+            Faction::Hostile => comp::Alignment::Enemy,
         }
     }
+}
 
-    pub fn radius(&self) -> f32 { 400.0 }
+/// The relative frequencies of the kinds of structures that make up a town,
+/// besides its keep and other civic structures. Only the ratio between the
+/// weights matters.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StructureMix {
+    pub house: f32,
+    pub tavern: f32,
+}
 
-    pub fn spawn_rules(&self, wpos: Vec2<i32>) -> SpawnRules {
-        SpawnRules {
-            trees: self
-                .land
-                .get_at_block(wpos - self.origin)
-                .plot
-                .map(|p| matches!(p, Plot::Hazard))
-                .unwrap_or(true),
-            ..SpawnRules::default()
+impl Default for StructureMix {
+    fn default() -> Self {
+        Self {
+            house: 1.0,
+            tavern: 0.0,
         }
     }
+}
 
-    pub fn apply_to<'a>(
-        &'a self,
-        index: IndexRef,
-        wpos2d: Vec2<i32>,
-        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
-        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
-    ) {
-        let colors = &index.colors.site.settlement;
+impl StructureMix {
+    /// Whether a structure is a tavern, given a roll in `0.0..1.0`.
+    fn is_tavern(&self, roll: f32) -> bool {
+        let (house, tavern) = (self.house.max(0.0), self.tavern.max(0.0));
+        tavern > 0.0 && roll * (house + tavern) >= house
+    }
+}
 
-        for y in 0..vol.size_xy().y as i32 {
-            for x in 0..vol.size_xy().x as i32 {
-                let offs = Vec2::new(x, y);
+/// How many farms a settlement has, how many fields each of them tends and
+/// how far apart they are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FarmParams {
+    pub count: usize,
+    pub fields_per_farm: usize,
+    /// Smallest distance between the base tiles of two farms, in tiles.
+    pub min_spacing: i32,
+}
 
-                let wpos2d = wpos2d + offs;
-                let rpos = wpos2d - self.origin;
+impl Default for FarmParams {
+    fn default() -> Self {
+        Self {
+            count: 6,
+            fields_per_farm: 5,
+            min_spacing: 3,
+        }
+    }
+}
 
-                // Sample terrain
-                let col_sample = if let Some(col_sample) = get_column(offs) {
-                    col_sample
-                } else {
-                    continue;
-                };
-                let land_surface_z = col_sample.riverless_alt.floor() as i32;
-                let mut surface_z = land_surface_z;
+impl FarmParams {
+    /// Farms suiting a settlement with the given number of buildable tiles:
+    /// cramped sites get fewer farms, while roomy ones get the default.
+    pub fn for_land(buildable_tiles: usize) -> Self {
+        const TILES_PER_FARM: usize = 40;
 
-                // Sample settlement
-                let sample = self.land.get_at_block(rpos);
+        let default = Self::default();
+        Self {
+            count: (buildable_tiles / TILES_PER_FARM).clamp(1, default.count),
+            ..default
+        }
+    }
+}
 
-                let noisy_color = move |col: Rgb<u8>, factor: u32| {
-                    let nz = self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, surface_z));
-                    col.map(|e| {
-                        (e as u32 + nz % (factor * 2))
-                            .saturating_sub(factor)
-                            .min(255) as u8
-                    })
-                };
+/// How paths cross water: what routing a path across water costs, and which
+/// crossings are forded rather than bridged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrossingParams {
+    /// The cost of a path crossing a tile of water, relative to open ground.
+    pub water_cost: f32,
+    /// The widest crossing that may be forded, in tiles of water.
+    pub max_ford_width: usize,
+    /// The deepest crossing that may be forded, in blocks from the ground down
+    /// to the water's level.
+    pub max_ford_depth: i32,
+}
 
-                // District alt
-                if let Some(Plot::Town { district }) = sample.plot {
-                    if let Some(d) = district
-                        .and_then(|d| self.town.as_ref().map(|t| t.districts().get(d)))
-                        .filter(|_| false)
-                    // Temporary
-                    {
-                        let other = self
-                            .land
-                            .plot_at(sample.second_closest)
-                            .and_then(|p| match p {
-                                Plot::Town { district } => *district,
-                                _ => None,
-                            })
-                            .and_then(|d| {
-                                self.town.as_ref().map(|t| t.districts().get(d).alt as f32)
-                            })
-                            .filter(|_| false)
-                            .unwrap_or(surface_z as f32);
-                        surface_z = Lerp::lerp(
-                            (other + d.alt as f32) / 2.0,
-                            d.alt as f32,
-                            (1.25 * sample.edge_dist / (d.alt as f32 - other).abs()).min(1.0),
-                        ) as i32;
-                    }
-                }
+impl Default for CrossingParams {
+    fn default() -> Self {
+        Self {
+            water_cost: 20.0,
+            max_ford_width: 1,
+            max_ford_depth: 3,
+        }
+    }
+}
 
-                {
-                    let mut surface_sprite = None;
-
-                    let roll =
-                        |seed, n| self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, seed * 5)) % n;
-
-                    let color = match sample.plot {
-                        Some(Plot::Dirt) => Some(colors.plot_dirt.into()),
-                        Some(Plot::Grass) => Some(colors.plot_grass.into()),
-                        Some(Plot::Water) => Some(colors.plot_water.into()),
-                        //Some(Plot::Town { district }) => None,
-                        Some(Plot::Town { .. }) => {
-                            if let Some((_, path_nearest, _, _)) = col_sample.path {
-                                let path_dir = (path_nearest - wpos2d.map(|e| e as f32))
-                                    .rotated_z(f32::consts::PI / 2.0)
-                                    .normalized();
-                                let is_lamp = if path_dir.x.abs() > path_dir.y.abs() {
-                                    wpos2d.x as f32 % 15.0 / path_dir.dot(Vec2::unit_y()).abs()
-                                        <= 1.0
-                                } else {
-                                    (wpos2d.y as f32 + 10.0) % 15.0
-                                        / path_dir.dot(Vec2::unit_x()).abs()
-                                        <= 1.0
-                                };
-                                if (col_sample.path.map(|(dist, _, _, _)| dist > 6.0 && dist < 7.0).unwrap_or(false) && is_lamp) //roll(0, 50) == 0)
-                                    || (roll(0, 750) == 0 && col_sample.path.map(|(dist, _, _, _)| dist > 20.0).unwrap_or(true))
-                                {
-                                    surface_sprite = Some(SpriteKind::StreetLamp);
-                                }
-                            }
+/// Telemetry of a settlement's generation, for profiling world generation.
+/// See [`SettlementBuilder::generate_with_stats`].
+#[derive(Clone, Debug, Default)]
+pub struct GenStats {
+    /// How long each generation pass took, in the order they ran.
+    pub passes: Vec<(&'static str, Duration)>,
+    /// Number of nodes expanded by pathfinding, across all passes.
+    pub astar_nodes: usize,
+    /// Number of attempts at placing a building.
+    pub building_attempts: usize,
+    /// Number of attempts at placing a building that were given up on, because
+    /// the spot was unsuitable or the building collided with another.
+    pub failed_building_attempts: usize,
+}
 
-                            Some(
-                                Rgb::from(colors.plot_town_path)
-                                    .map2(Rgb::iota(), |e: u8, _i: i32| {
-                                        e.saturating_add(0_u8).saturating_sub(8)
-                                    }),
-                            )
-                        },
-                        Some(Plot::Field { seed, crop, .. }) => {
-                            let furrow_dirs = [
-                                Vec2::new(1, 0),
-                                Vec2::new(0, 1),
-                                Vec2::new(1, 1),
-                                Vec2::new(-1, 1),
-                            ];
-                            let furrow_dir = furrow_dirs[*seed as usize % furrow_dirs.len()];
-                            let in_furrow = (wpos2d * furrow_dir).sum().rem_euclid(5) < 2;
-
-                            let dirt = Rgb::<u8>::from(colors.plot_field_dirt).map(|e| {
-                                e + (self.noise.get(Vec3::broadcast((seed % 4096 + 0) as i32)) % 32)
-                                    as u8
-                            });
-                            let mound = Rgb::<u8>::from(colors.plot_field_mound)
-                                .map(|e| e + roll(0, 8) as u8)
-                                .map(|e| {
-                                    e + (self.noise.get(Vec3::broadcast((seed % 4096 + 1) as i32))
-                                        % 32) as u8
-                                });
-
-                            if in_furrow {
-                                if roll(0, 5) == 0 {
-                                    surface_sprite = match crop {
-                                        Crop::Corn => Some(SpriteKind::Corn),
-                                        Crop::Wheat if roll(1, 2) == 0 => {
-                                            Some(SpriteKind::WheatYellow)
-                                        },
-                                        Crop::Wheat => Some(SpriteKind::WheatGreen),
-                                        Crop::Cabbage if roll(2, 2) == 0 => {
-                                            Some(SpriteKind::Cabbage)
-                                        },
-                                        Crop::Pumpkin if roll(3, 2) == 0 => {
-                                            Some(SpriteKind::Pumpkin)
-                                        },
-                                        Crop::Flax if roll(4, 2) == 0 => Some(SpriteKind::Flax),
-                                        Crop::Carrot if roll(5, 2) == 0 => Some(SpriteKind::Carrot),
-                                        Crop::Tomato if roll(6, 2) == 0 => Some(SpriteKind::Tomato),
-                                        Crop::Radish if roll(7, 2) == 0 => Some(SpriteKind::Radish),
-                                        Crop::Turnip if roll(8, 2) == 0 => Some(SpriteKind::Turnip),
-                                        Crop::Sunflower => Some(SpriteKind::Sunflower),
-                                        _ => surface_sprite,
-                                    }
-                                    .or_else(|| {
-                                        if roll(9, 400) == 0 {
-                                            Some(SpriteKind::Scarecrow)
-                                        } else {
-                                            None
-                                        }
-                                    });
-                                }
-                            } else if roll(0, 20) == 0 {
-                                surface_sprite = Some(SpriteKind::ShortGrass);
-                            } else if roll(1, 30) == 0 {
-                                surface_sprite = Some(SpriteKind::MediumGrass);
-                            }
+impl GenStats {
+    /// How long the pass with the given name took, if it ran.
+    pub fn pass(&self, name: &str) -> Option<Duration> {
+        self.passes
+            .iter()
+            .find(|(pass, _)| *pass == name)
+            .map(|(_, duration)| *duration)
+    }
 
-                            Some(if in_furrow { dirt } else { mound })
-                        },
-                        _ => None,
-                    };
+    /// How long all passes took together.
+    pub fn total(&self) -> Duration { self.passes.iter().map(|(_, duration)| *duration).sum() }
+}
 
-                    if let Some(color) = color {
-                        let is_path = col_sample
-                            .path
-                            .map(|(dist, _, path, _)| dist < path.width)
-                            .unwrap_or(false);
+/// Generates a [`Settlement`] with non-default options.
+#[derive(Default)]
+pub struct SettlementBuilder {
+    excluded_crops: Vec<Crop>,
+    forced_crops: Option<Vec<Crop>>,
+    lamps: Option<LampParams>,
+    prop_density: Option<f32>,
+    farms: Option<FarmParams>,
+    crossings: CrossingParams,
+    terraced_fields: bool,
+    grid_snap: bool,
+    furniture_density: f32,
+    town_walls: bool,
+    structure_mix: StructureMix,
+    leveling: f32,
+    foundation: Option<FoundationStyle>,
+    faction: Faction,
+    contour_furrows: bool,
+    collect_stats: bool,
+    decoration_density: Option<f32>,
+    feature: Option<Vec2<i32>>,
+    density_falloff: f32,
+    moat: bool,
+    stream_seeds: [Option<u32>; 3],
+    starting_inventories: bool,
+    clustering: f32,
+    temple: bool,
+    tier: Option<u8>,
+}
 
-                        if col_sample.water_dist.map(|dist| dist > 2.0).unwrap_or(true) && !is_path
-                        {
-                            let diff = (surface_z - land_surface_z).abs();
+impl SettlementBuilder {
+    pub fn new() -> Self { Self::default() }
 
-                            for z in -8 - diff..8 + diff {
-                                let pos = Vec3::new(offs.x, offs.y, surface_z + z);
-                                let block = if let Ok(&block) = vol.get(pos) {
-                                    // TODO: Figure out whether extra filters are needed.
-                                    block
-                                } else {
-                                    break;
-                                };
+    /// Never plant the given crops, choosing among the remaining ones instead.
+    /// If every crop is excluded, the settlement gets no fields.
+    pub fn exclude_crops(mut self, crops: &[Crop]) -> Self {
+        self.excluded_crops.extend_from_slice(crops);
+        self
+    }
 
-                                if let (0, Some(sprite)) = (z, surface_sprite) {
-                                    let _ = vol.set(
-                                        pos,
-                                        // TODO: Make more principled.
-                                        if block.is_fluid() {
-                                            block.with_sprite(sprite)
-                                        } else {
-                                            Block::air(sprite)
-                                        },
-                                    );
-                                } else if z >= 0 {
-                                    if [
-                                        BlockKind::Air,
-                                        BlockKind::Grass,
-                                        BlockKind::Earth,
-                                        BlockKind::Sand,
-                                        BlockKind::Snow,
-                                        BlockKind::Rock,
-                                    ]
-                                    .contains(&block.kind())
-                                    {
-                                        let _ = vol.set(pos, Block::air(SpriteKind::Empty));
-                                    }
-                                } else {
-                                    let _ = vol.set(
-                                        pos,
-                                        Block::new(BlockKind::Earth, noisy_color(color, 4)),
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
+    /// Grow the given crops rather than crops picked at random, for themed
+    /// farms: the first farm grows the first crop in all of its fields, the
+    /// second farm the second crop, and so on, starting over once every crop
+    /// has its farm. Pass a single crop to grow it everywhere. Forced crops
+    /// are planted even if excluded, and an empty plan is ignored.
+    pub fn forced_crops(mut self, crops: Vec<Crop>) -> Self {
+        self.forced_crops = Some(crops).filter(|crops| !crops.is_empty());
+        self
+    }
 
-                // Walls
-                if let Some((WayKind::Wall, dist, _)) = sample.way {
-                    let color = Lerp::lerp(
-                        Rgb::<u8>::from(colors.wall_low).map(i32::from),
-                        Rgb::<u8>::from(colors.wall_high).map(i32::from),
-                        (RandomField::new(0).get(wpos2d.into()) % 256) as f32 / 256.0,
-                    )
-                    .map(|e| (e % 256) as u8);
+    /// Light the town with the given lamps, instead of lamps suiting its size.
+    pub fn lamps(mut self, lamps: LampParams) -> Self {
+        self.lamps = Some(lamps);
+        self
+    }
 
-                    let z_offset = if let Some(water_dist) = col_sample.water_dist {
-                        // Water gate
-                        ((water_dist.max(0.0) * 0.45).min(f32::consts::PI).cos() + 1.0) * 4.0
-                    } else {
-                        0.0
-                    } as i32;
+    /// Give the settlement the given farms, instead of farms suiting its
+    /// buildable land.
+    pub fn farms(mut self, farms: FarmParams) -> Self {
+        self.farms = Some(farms);
+        self
+    }
 
-                    for z in z_offset..12 {
-                        if dist / WayKind::Wall.width() < ((1.0 - z as f32 / 12.0) * 2.0).min(1.0) {
-                            let _ = vol.set(
-                                Vec3::new(offs.x, offs.y, surface_z + z),
-                                Block::new(BlockKind::Wood, color),
-                            );
-                        }
-                    }
-                }
+    /// Route paths across water and pick between fords and bridges with the
+    /// given parameters, instead of the default ones.
+    pub fn crossings(mut self, crossings: CrossingParams) -> Self {
+        self.crossings = crossings;
+        self
+    }
 
-                // Towers
-                if let Some((Tower::Wall, _pos)) = sample.tower {
-                    for z in -2..16 {
-                        let _ = vol.set(
-                            Vec3::new(offs.x, offs.y, surface_z + z),
-                            Block::new(BlockKind::Rock, colors.tower_color.into()),
-                        );
-                    }
-                }
-            }
-        }
+    /// Scatter props around the town's structures with the given chance per
+    /// column, instead of [`Settlement::DEFAULT_PROP_DENSITY`].
+    pub fn prop_density(mut self, prop_density: f32) -> Self {
+        self.prop_density = Some(prop_density);
+        self
+    }
 
-        // Apply structures
-        for structure in &self.structures {
-            let bounds = structure.bounds_2d();
+    /// Cut fields on steep ground into flat terraces held up by retaining
+    /// edges, as on hillside farms. Fields on gentle ground are unaffected.
+    pub fn terraced_fields(mut self, terraced_fields: bool) -> Self {
+        self.terraced_fields = terraced_fields;
+        self
+    }
 
-            // Skip this structure if it's not near this chunk
-            if !bounds.collides_with_aabr(Aabr {
-                min: wpos2d - self.origin,
-                max: wpos2d - self.origin + vol.size_xy().map(|e| e as i32 + 1),
-            }) {
-                continue;
-            }
+    /// Lay the town out as a planned town, with buildings aligned to a grid
+    /// of [`Settlement::TOWN_GRID`] blocks and facing their streets.
+    pub fn grid_snap(mut self, grid_snap: bool) -> Self {
+        self.grid_snap = grid_snap;
+        self
+    }
 
-            let bounds = structure.bounds();
+    /// Furnish the share `furniture_density` of the spots inside houses with
+    /// beds, tables and other furniture, spawned as object entities. Houses
+    /// are left unfurnished by default.
+    pub fn furniture_density(mut self, furniture_density: f32) -> Self {
+        self.furniture_density = furniture_density;
+        self
+    }
 
-            for x in bounds.min.x..bounds.max.x + 1 {
-                for y in bounds.min.y..bounds.max.y + 1 {
-                    let col = if let Some(col) = get_column(self.origin + Vec2::new(x, y) - wpos2d)
-                    {
-                        col
-                    } else {
-                        continue;
-                    };
+    /// Enclose the town in a wall that hugs its built-up area, leaving the
+    /// farms outside. Where the town is split up, only its largest part is
+    /// walled.
+    pub fn town_walls(mut self, town_walls: bool) -> Self {
+        self.town_walls = town_walls;
+        self
+    }
 
-                    for z in bounds.min.z.min(col.alt.floor() as i32 - 1)..bounds.max.z + 1 {
-                        let rpos = Vec3::new(x, y, z);
-                        let wpos = Vec3::from(self.origin) + rpos;
-                        let coffs = wpos - Vec3::from(wpos2d);
+    /// Build the town from the given mix of structures, instead of from
+    /// houses alone.
+    pub fn structure_mix(mut self, structure_mix: StructureMix) -> Self {
+        self.structure_mix = structure_mix;
+        self
+    }
 
-                        if let Some(block) = structure.sample(index, rpos) {
-                            let _ = vol.set(coffs, block);
-                        }
-                    }
-                }
-            }
-        }
+    /// Level the ground of the town towards a smoothed version of the
+    /// terrain, by `leveling` from `0.0` (not at all, the default) to `1.0`
+    /// (fully). Its outskirts blend back into the natural terrain.
+    pub fn leveling(mut self, leveling: f32) -> Self {
+        self.leveling = leveling.clamp(0.0, 1.0);
+        self
     }
 
-    pub fn apply_supplement<'a>(
-        &'a self,
-        // NOTE: Used only for dynamic elements like chests and entities!
-        dynamic_rng: &mut impl Rng,
-        wpos2d: Vec2<i32>,
-        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
-        supplement: &mut ChunkSupplement,
-        economy: SiteInformation,
-        time: Option<&(TimeOfDay, Calendar)>,
-    ) {
-        // let economy: HashMap<Good, (f32, f32)> = SiteInformation::economy
-        //     .values
-        //     .iter()
-        //     .map(|(g, v)| {
-        //         (
-        //             g,
-        //             (
-        //                 v.unwrap_or(Economy::MINIMUM_PRICE),
-        //                 economy.stocks[g] + economy.surplus[g],
-        //             ),
-        //         )
-        //     })
-        //     .collect();
-        for y in 0..TerrainChunkSize::RECT_SIZE.y as i32 {
-            for x in 0..TerrainChunkSize::RECT_SIZE.x as i32 {
-                let offs = Vec2::new(x, y);
+    /// Stand structures on the given foundations, instead of on foundations
+    /// suiting the biome.
+    pub fn foundation(mut self, foundation: FoundationStyle) -> Self {
+        self.foundation = Some(foundation);
+        self
+    }
 
-                let wpos2d = wpos2d + offs;
-                let rpos = wpos2d - self.origin;
+    /// Hand the settlement to the given faction, instead of leaving it
+    /// friendly.
+    pub fn faction(mut self, faction: Faction) -> Self {
+        self.faction = faction;
+        self
+    }
 
-                // Sample terrain
-                let col_sample = if let Some(col_sample) = get_column(offs) {
-                    col_sample
-                } else {
-                    continue;
-                };
+    /// Plough the furrows of fields along the contours of their slope, like
+    /// terraces, rather than in a fixed direction. Fields on flat ground keep
+    /// their fixed direction.
+    pub fn contour_furrows(mut self, contour_furrows: bool) -> Self {
+        self.contour_furrows = contour_furrows;
+        self
+    }
 
-                let sample = self.land.get_at_block(rpos);
+    /// Decorate open grassy ground with grass, flowers and small rocks with
+    /// the given chance per column, instead of a chance suiting the biome.
+    pub fn decoration_density(mut self, decoration_density: f32) -> Self {
+        self.decoration_density = Some(decoration_density);
+        self
+    }
 
-                let entity_wpos = Vec3::new(wpos2d.x as f32, wpos2d.y as f32, col_sample.alt + 3.0);
+    /// Form the settlement around a feature of the world at `feature`, such
+    /// as a cave mouth or dungeon entrance. The feature's tile is never built
+    /// over, and the town forms beside it with a path leading there.
+    pub fn around(mut self, feature: Vec2<i32>) -> Self {
+        self.feature = Some(feature);
+        self
+    }
 
-                if matches!(sample.plot, Some(Plot::Town { .. }))
-                    && RandomField::new(self.seed).chance(Vec3::from(wpos2d), 1.0 / (20.0 * 40.0))
-                {
-                    let is_dummy =
-                        RandomField::new(self.seed + 1).chance(Vec3::from(wpos2d), 1.0 / 15.0);
-                    let entity = if is_dummy {
-                        EntityInfo::at(entity_wpos)
-                            .with_agency(false)
-                            .with_asset_expect("common.entity.village.dummy", dynamic_rng, time)
-                    } else {
-                        match dynamic_rng.gen_range(0..=4) {
-                            0 => barnyard(entity_wpos, dynamic_rng),
-                            1 => bird(entity_wpos, dynamic_rng),
-                            _ => humanoid(entity_wpos, &economy, dynamic_rng, time),
-                        }
-                    };
+    /// Pack buildings densely at the town center and more sparsely towards
+    /// its fringe, falling off with the given steepness. With the default of
+    /// `0.0`, buildings are spread evenly.
+    pub fn density_falloff(mut self, steepness: f32) -> Self {
+        self.density_falloff = steepness.max(0.0);
+        self
+    }
 
-                    supplement.add_entity(entity);
-                }
-            }
+    /// Dig a moat around the town's wall where the settlement is fortified,
+    /// with both a wall (see [`SettlementBuilder::town_walls`]) and a keep.
+    /// Paths cross the moat on bridges.
+    pub fn moat(mut self, moat: bool) -> Self {
+        self.moat = moat;
+        self
+    }
+
+    /// Have villagers spawn carrying produce from the settlement's fields,
+    /// and merchants a little of every crop grown there, along with a few
+    /// coins.
+    pub fn starting_inventories(mut self, starting_inventories: bool) -> Self {
+        self.starting_inventories = starting_inventories;
+        self
+    }
+
+    /// Build houses against each other, so that they share a party wall and
+    /// form blocks, with the given chance per house from `0.0` to `1.0`. With
+    /// the default of `0.0`, every building stands on its own.
+    pub fn clustering(mut self, clustering: f32) -> Self {
+        self.clustering = clustering.clamped(0.0, 1.0);
+        self
+    }
+
+    /// Give towns a temple near their plaza or center, its spire standing
+    /// over the town as a landmark. Hamlets don't get one.
+    pub fn temple(mut self, temple: bool) -> Self {
+        self.temple = temple;
+        self
+    }
+
+    /// Give the settlement the given tier, up to [`Settlement::MAX_TIER`],
+    /// instead of a tier suiting the danger of its biome.
+    pub fn tier(mut self, tier: u8) -> Self {
+        self.tier = Some(tier.min(Settlement::MAX_TIER));
+        self
+    }
+
+    /// Seed the given stream of randomness with `seed`, rather than with a
+    /// seed derived from the settlement's own. The other streams are left as
+    /// they are, see [`SeedStream`].
+    pub fn stream_seed(mut self, stream: SeedStream, seed: u32) -> Self {
+        self.stream_seeds[stream as usize] = Some(seed);
+        self
+    }
+
+    pub fn generate(
+        self,
+        wpos: Vec2<i32>,
+        sim: Option<&WorldSim>,
+        rng: &mut impl Rng,
+    ) -> Settlement {
+        self.generate_with(wpos, sim, rng, |_, _| {})
+    }
+
+    /// Like [`SettlementBuilder::generate`], but also time each generation
+    /// pass and count the work done, for profiling. Plain generation collects
+    /// none of this.
+    pub fn generate_with_stats(
+        self,
+        wpos: Vec2<i32>,
+        sim: Option<&WorldSim>,
+        rng: &mut impl Rng,
+    ) -> (Settlement, GenStats) {
+        let mut settlement = Self {
+            collect_stats: true,
+            ..self
         }
+        .generate(wpos, sim, rng);
+        let stats = settlement.stats.take().unwrap_or_default();
+        (settlement, stats)
     }
 
-    pub fn get_color(&self, index: IndexRef, pos: Vec2<i32>) -> Option<Rgb<u8>> {
-        let colors = &index.colors.site.settlement;
+    /// Like [`SettlementBuilder::generate`], but call `on_structure` for each
+    /// structure of the settlement, with the world position of the center of
+    /// its footprint. Structures are reported in the order they were placed
+    /// in, once generation is done, so that they are seen where they finally
+    /// stand.
+    pub fn generate_with(
+        self,
+        wpos: Vec2<i32>,
+        sim: Option<&WorldSim>,
+        rng: &mut impl Rng,
+        mut on_structure: impl FnMut(&Structure, Vec2<i32>),
+    ) -> Settlement {
+        let mut ctx = GenCtx { sim, rng };
+        let mut this = Settlement::new(wpos, &mut ctx);
+        this.excluded_crops = self.excluded_crops;
+        this.forced_crops = self.forced_crops;
+        this.farm_params = self.farms;
+        this.crossings = self.crossings;
+        this.terraced_fields = self.terraced_fields;
+        this.grid_snap = self.grid_snap;
+        this.furniture_density = self.furniture_density;
+        this.town_walls = self.town_walls;
+        this.structure_mix = self.structure_mix;
+        this.leveling = self.leveling;
+        if let Some(foundation) = self.foundation {
+            this.foundation = foundation;
+        }
+        this.faction = self.faction;
+        this.contour_furrows = self.contour_furrows;
+        this.stats = self.collect_stats.then(GenStats::default);
+        if self.collect_stats {
+            this.land.count_astar_nodes();
+        }
+        if let Some(decoration_density) = self.decoration_density {
+            this.decoration_density = decoration_density;
+        }
+        this.density_falloff = self.density_falloff;
+        this.moat = self.moat;
+        this.starting_inventories = self.starting_inventories;
+        this.clustering = self.clustering;
+        this.temple = self.temple;
+        if let Some(tier) = self.tier {
+            this.tier = tier;
+        }
+        this.feature = self
+            .feature
+            .map(|feature| (feature - this.origin).map(to_tile));
+        for (seed, overridden) in this.stream_seeds.iter_mut().zip(self.stream_seeds) {
+            *seed = overridden.unwrap_or(*seed);
+        }
+        this.noise = RandomField::new(this.stream_seed(SeedStream::Decoration));
+        this.land.set_warp_seed(this.use_seed(SeedUse::Warp));
+
+        // Layout decisions are drawn from their own stream, so that they don't
+        // depend on how much of the caller's rng went into naming the
+        // settlement and the like
+        let mut layout_rng =
+            ChaChaRng::from_seed(seed_expan::rng_state(this.stream_seed(SeedStream::Layout)));
+        let mut ctx = GenCtx {
+            sim: ctx.sim,
+            rng: &mut layout_rng,
+        };
 
-        let sample = self.land.get_at_block(pos);
+        if let Some(sim) = ctx.sim {
+            this.timed("designate_from_world", |this| {
+                this.designate_from_world(sim, ctx.rng)
+            });
+        }
 
-        match sample.plot {
-            Some(Plot::Dirt) => return Some(colors.plot_dirt.into()),
-            Some(Plot::Grass) => return Some(colors.plot_grass.into()),
-            Some(Plot::Water) => return Some(colors.plot_water.into()),
-            Some(Plot::Town { .. }) => {
-                return Some(
-                    Rgb::from(colors.plot_town).map2(Rgb::iota(), |e: u8, i: i32| {
-                        e.saturating_add(
-                            (self.noise.get(Vec3::new(pos.x, pos.y, i * 5)) % 16) as u8,
-                        )
-                        .saturating_sub(8)
-                    }),
-                );
-            },
-            Some(Plot::Field { seed, .. }) => {
-                let furrow_dirs = [
-                    Vec2::new(1, 0),
-                    Vec2::new(0, 1),
-                    Vec2::new(1, 1),
-                    Vec2::new(-1, 1),
-                ];
-                let furrow_dir = furrow_dirs[*seed as usize % furrow_dirs.len()];
-                let furrow = (pos * furrow_dir).sum().rem_euclid(6) < 3;
-                // NOTE: Very hard to understand how to make this dynamically configurable.  The
-                // base values can easily cause the others to go out of range, and there's some
-                // weird scaling going on.  For now, we just let these remain hardcoded.
-                //
-                // FIXME: Rewrite this so that validity is not so heavily dependent on the exact
-                // color values.
-                return Some(Rgb::new(
-                    if furrow {
-                        100
-                    } else {
-                        32 + seed.to_le_bytes()[0] % 64
-                    },
-                    64 + seed.to_le_bytes()[1] % 128,
-                    16 + seed.to_le_bytes()[2] % 32,
-                ));
-            },
-            _ => {},
+        //this.place_river(rng);
+
+        this.populate(&mut ctx);
+        if let Some(lamps) = self.lamps {
+            this.lamps = lamps;
+        }
+        if let Some(prop_density) = self.prop_density {
+            this.prop_density = prop_density;
         }
 
-        None
+        for structure in &this.structures {
+            on_structure(structure, this.origin + structure.bounds_2d().center());
+        }
+        if let Some(stats) = &mut this.stats {
+            stats.astar_nodes = this.land.astar_nodes().unwrap_or(0);
+        }
+
+        this
     }
 }
 
-fn barnyard(pos: Vec3<f32>, dynamic_rng: &mut impl Rng) -> EntityInfo {
-    //TODO: use Lottery instead of ad-hoc RNG system
-    let species = match dynamic_rng.gen_range(0..5) {
-        0 => quadruped_small::Species::Pig,
-        1 => quadruped_small::Species::Sheep,
-        2 => quadruped_small::Species::Goat,
-        3 => quadruped_small::Species::Dog,
-        _ => quadruped_small::Species::Cat,
-    };
-    EntityInfo::at(pos)
-        .with_body(comp::Body::QuadrupedSmall(
-            quadruped_small::Body::random_with(dynamic_rng, &species),
-        ))
-        .with_alignment(comp::Alignment::Tame)
-        .with_automatic_name(None)
+pub struct Farm {
+    base_tile: Vec2<i32>,
 }
 
-fn bird(pos: Vec3<f32>, dynamic_rng: &mut impl Rng) -> EntityInfo {
-    //TODO: use Lottery instead of ad-hoc RNG system
-    let species = match dynamic_rng.gen_range(0..4) {
-        0 => bird_medium::Species::Duck,
-        1 => bird_medium::Species::Chicken,
-        2 => bird_medium::Species::Goose,
-        _ => bird_medium::Species::Peacock,
-    };
-    EntityInfo::at(pos)
-        .with_body(comp::Body::BirdMedium(bird_medium::Body::random_with(
-            dynamic_rng,
-            &species,
-        )))
-        .with_alignment(comp::Alignment::Tame)
-        .with_automatic_name(None)
+pub struct GenCtx<'a, R: Rng> {
+    sim: Option<&'a WorldSim>,
+    rng: &'a mut R,
 }
 
-fn humanoid(
-    pos: Vec3<f32>,
-    economy: &SiteInformation,
-    dynamic_rng: &mut impl Rng,
-    time: Option<&(TimeOfDay, Calendar)>,
-) -> EntityInfo {
-    let entity = EntityInfo::at(pos);
-    match dynamic_rng.gen_range(0..8) {
-        0 | 1 => entity
-            .with_agent_mark(agent::Mark::Guard)
-            .with_asset_expect("common.entity.village.guard", dynamic_rng, time),
-        2 => entity
-            .with_agent_mark(agent::Mark::Merchant)
-            .with_economy(economy)
-            .with_lazy_loadout(merchant_loadout)
-            .with_asset_expect("common.entity.village.merchant", dynamic_rng, time),
-        _ => entity.with_asset_expect("common.entity.village.villager", dynamic_rng, time),
+impl Settlement {
+    /// Default chance of a prop standing on an open town column beside a
+    /// structure.
+    pub const DEFAULT_PROP_DENSITY: f32 = 1.0 / 60.0;
+    /// The highest tier of a settlement, see [`Settlement::tier`].
+    pub const MAX_TIER: u8 = 3;
+    /// The smallest gap, in blocks, that [`Settlement::relax`] keeps between
+    /// structures that don't share a wall.
+    pub const MIN_SPACING: i32 = 2;
+    pub const RADIUS: f32 = 400.0;
+    /// How far, in blocks, [`Settlement::relax`] may nudge a structure.
+    const RELAX_RADIUS: i32 = 6;
+    /// Spacing, in blocks, of the grid that the buildings of planned towns
+    /// are aligned to.
+    pub const TOWN_GRID: i32 = 8;
+
+    pub fn generate(wpos: Vec2<i32>, sim: Option<&WorldSim>, rng: &mut impl Rng) -> Self {
+        SettlementBuilder::new().generate(wpos, sim, rng)
     }
-}
 
-pub fn merchant_loadout(
-    loadout_builder: LoadoutBuilder,
-    economy: Option<&SiteInformation>,
+    /// Generate a settlement formed around a feature of the world at
+    /// `feature`, see [`SettlementBuilder::around`].
+    pub fn generate_around(
+        wpos: Vec2<i32>,
+        feature: Vec2<i32>,
+        sim: Option<&WorldSim>,
+        rng: &mut impl Rng,
+    ) -> Self {
+        SettlementBuilder::new()
+            .around(feature)
+            .generate(wpos, sim, rng)
+    }
+
+    /// Create a settlement with no land designated and nothing placed.
+    fn new(wpos: Vec2<i32>, ctx: &mut GenCtx<impl Rng>) -> Self {
+        let sim = ctx.sim;
+        let name_style = sim
+            .and_then(|sim| sim.get_wpos(wpos))
+            .map_or(NameStyle::default(), |chunk| {
+                NameStyle::for_climate(chunk.temp, chunk.humidity)
+            });
+        let name = NameGen::location(ctx.rng).with_style(name_style).generate();
+        let seed = ctx.rng.gen();
+        let biome = sim
+            .and_then(|sim| sim.get_wpos(wpos))
+            .map_or(BiomeKind::Grassland, |chunk| chunk.get_biome());
+        let mut this = Self {
+            name,
+            stream_seeds: SeedStream::ALL.map(|stream| stream.derive(seed)),
+            origin: wpos,
+            land: Land::with_warp_seed(SeedUse::Warp.derive(SeedStream::Layout.derive(seed))),
+            farms: Store::default(),
+            structures: Vec::new(),
+            reserved: HashSet::default(),
+            town: None,
+            noise: RandomField::new(SeedStream::Decoration.derive(seed)),
+            cold: sim
+                .and_then(|sim| sim.get_wpos(wpos))
+                .map_or(false, |chunk| chunk.temp < CONFIG.snow_temp),
+            biome,
+            excluded_crops: Vec::new(),
+            forced_crops: None,
+            lamps: LampParams::default(),
+            prop_density: Self::DEFAULT_PROP_DENSITY,
+            farm_params: None,
+            crossings: CrossingParams::default(),
+            terraced_fields: false,
+            gates: Vec::new(),
+            water_levels: HashMap::default(),
+            reserved_levels: HashMap::default(),
+            spawn_policy: None,
+            grid_snap: false,
+            furniture_density: 0.0,
+            town_walls: false,
+            structure_mix: StructureMix::default(),
+            leveling: 0.0,
+            town_alts: HashMap::default(),
+            foundation: FoundationStyle::for_biome(biome),
+            faction: Faction::default(),
+            contour_furrows: false,
+            field_slopes: HashMap::default(),
+            stats: None,
+            decoration_density: Self::decoration_density_for_biome(biome),
+            feature: None,
+            density_falloff: 0.0,
+            moat: false,
+            starting_inventories: false,
+            clustering: 0.0,
+            temple: false,
+            tier: Self::tier_for_biome(biome),
+        };
+        this.land
+            .set_astar_budget(Land::astar_budget_for_radius(this.radius()));
+        this
+    }
+
+    /// Place farms, the town and its buildings on the designated land.
+    fn populate(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        // Nothing is placed on the feature the settlement formed around
+        if let Some(feature) = self.feature {
+            let plot = self.land.new_plot(Plot::Reserved);
+            self.land.set(feature, plot);
+        }
+        self.timed("place_farms", |this| this.place_farms(ctx));
+        if let Some(sim) = ctx.sim.filter(|_| self.contour_furrows) {
+            self.timed("place_field_slopes", |this| {
+                this.place_field_slopes(|wpos| sim.get_alt_clamped(wpos))
+            });
+        }
+        self.timed("place_town", |this| {
+            this.place_town(ctx);
+            if this.town.is_none() {
+                // Retry with relaxed criteria, accepting any unclaimed tile
+                this.place_town_with(ctx, |plot| {
+                    matches!(plot, None | Some(Plot::Dirt | Plot::Grass))
+                });
+            }
+        });
+        if self.town.is_none() {
+            tracing::warn!(
+                wpos = ?self.origin,
+                "Settlement failed to place its town: no buildable tile within its radius"
+            );
+        }
+        //self.place_paths(ctx.rng);
+        self.timed("place_streets", |this| this.place_streets());
+        self.place_feature_path();
+        if let Some(sim) = ctx.sim.filter(|_| self.leveling > 0.0) {
+            self.timed("level_town", |this| {
+                this.level_town(|wpos| sim.get_alt_clamped(wpos))
+            });
+        }
+        self.timed("place_buildings", |this| this.place_buildings(ctx));
+        if self.town_walls {
+            self.timed("place_town_wall", |this| this.place_town_wall());
+            if self.moat {
+                self.timed("place_moat", |this| this.place_moat());
+            }
+        }
+        // Barracks are built with the keep, before it's known whether the
+        // town ends up walled
+        if self.map_icon() != MapIconKind::Fort {
+            self.retain_structures(|structure| {
+                !matches!(structure.kind, StructureKind::Barracks(_))
+            });
+        }
+        self.land.normalize_ways();
+        self.timed("place_gates", |this| this.place_gates(ctx));
+        self.timed("place_water_levels", |this| this.place_water_levels(ctx));
+        if let Some(sim) = ctx.sim {
+            self.timed("place_crossings", |this| {
+                this.place_crossings(|wpos| sim.get_alt_clamped(wpos))
+            });
+        }
+        self.clear_reserved_fields();
+        let sim = ctx.sim;
+        self.timed("relax", |this| match sim {
+            Some(sim) => this.relax_with(|wpos| sim.get_alt_clamped(wpos)),
+            None => this.relax(),
+        });
+        self.lamps = LampParams::for_size(self.structures.len());
+    }
+
+    /// Tidy up the small inconsistencies left by the placement passes, which
+    /// don't fully coordinate with each other. Structures standing closer
+    /// than [`Settlement::MIN_SPACING`] to another are nudged apart, staying
+    /// on their plots, ways leading onto a tile with no way back are removed,
+    /// and fields left beneath moved structures are cleared. Relaxing a
+    /// relaxed settlement changes nothing.
+    pub fn relax(&mut self) { self.relax_with(|_| None) }
+
+    /// Like [`Settlement::relax`], but also settle buildings standing above
+    /// the ground, given by `get_alt` at a world position, back onto it.
+    fn relax_with(&mut self, get_alt: impl Fn(Vec2<i32>) -> Option<f32>) {
+        // Every nudge leaves fewer structures crowding each other, so this
+        // ends
+        while let Some((i, offs)) = self.find_nudge() {
+            self.move_structure(i, offs);
+        }
+
+        for i in 0..self.structures.len() {
+            let origin = match self.structures[i].descriptor() {
+                Some(descriptor) => descriptor.origin,
+                None => continue,
+            };
+            let ground = match get_alt(self.origin + Vec2::from(origin)) {
+                Some(alt) => self.leveled_alt(origin.into(), alt).ceil() as i32,
+                None => continue,
+            };
+            if origin.z > ground {
+                self.move_structure(i, Vec3::unit_z() * (ground - origin.z));
+            }
+        }
+
+        self.reserve_structures();
+        self.clear_reserved_fields();
+
+        self.land.prune_ways();
+        let land = &self.land;
+        self.gates.retain(|gate| {
+            land.tile_at(gate.tile).map_or(false, |tile| {
+                tile.contains(WayKind::Wall) && tile.contains(WayKind::Path)
+            })
+        });
+    }
+
+    /// The nearest offset, within [`Settlement::RELAX_RADIUS`], that leaves
+    /// the last structure crowding another crowding none, along with the
+    /// structure's index. Structures placed later give way to those placed
+    /// earlier. The structure stays on its plot, off the streets and off
+    /// unbuildable land.
+    fn find_nudge(&self) -> Option<(usize, Vec3<i32>)> {
+        let parts = |structure: &Structure, offs: Vec2<i32>| {
+            let shift = |aabr: Aabr<i32>| Aabr {
+                min: aabr.min + offs,
+                max: aabr.max + offs,
+            };
+            (
+                shift(structure.bounds_2d()),
+                structure.house_core().map(shift),
+            )
+        };
+        let crowds_others = |i: usize, offs: Vec2<i32>| {
+            let moved = parts(&self.structures[i], offs);
+            self.structures
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && self.crowded(moved, parts(other, Vec2::zero())))
+        };
+
+        (0..self.structures.len()).rev().find_map(|i| {
+            let structure = &self.structures[i];
+            if matches!(structure.kind, StructureKind::Custom(_)) || !crowds_others(i, Vec2::zero())
+            {
+                return None;
+            }
+            let center = structure.bounds_2d().center();
+            let plot = self.land.tile_at(center.map(to_tile))?.plot;
+            Spiral2d::new()
+                .skip(1)
+                .take((Self::RELAX_RADIUS as usize * 2 + 1).pow(2) - 1)
+                .find(|offs| {
+                    let on_plot = self
+                        .land
+                        .tile_at((center + *offs).map(to_tile))
+                        .map_or(false, |tile| {
+                            tile.plot == plot && !tile.contains(WayKind::Path)
+                        });
+                    let buildable = footprint_tiles(parts(structure, *offs).0).all(|tile| {
+                        !matches!(
+                            self.land.plot_at(tile),
+                            Some(Plot::Reserved | Plot::Hazard | Plot::Water)
+                        )
+                    });
+                    on_plot && buildable && !crowds_others(i, *offs)
+                })
+                .map(|offs| (i, Vec3::new(offs.x, offs.y, 0)))
+        })
+    }
+
+    fn move_structure(&mut self, i: usize, offs: Vec3<i32>) { self.structures[i].move_by(offs); }
+
+    /// Whether two structures stand closer than [`Settlement::MIN_SPACING`]
+    /// to each other, other than by sharing a wall.
+    fn structures_crowd(&self, a: &Structure, b: &Structure) -> bool {
+        self.crowded(
+            (a.bounds_2d(), a.house_core()),
+            (b.bounds_2d(), b.house_core()),
+        )
+    }
+
+    /// Like [`Settlement::structures_crowd`], for structures with the given
+    /// bounds and, for houses, the areas within their walls.
+    fn crowded(
+        &self,
+        (a, a_core): (Aabr<i32>, Option<Aabr<i32>>),
+        (b, b_core): (Aabr<i32>, Option<Aabr<i32>>),
+    ) -> bool {
+        match (a_core, b_core) {
+            (Some(a_core), Some(b_core))
+                if self.clustering > 0.0 && shares_wall(a_core, b_core) =>
+            {
+                false
+            },
+            _ => Aabr {
+                min: a.min - Self::MIN_SPACING,
+                max: a.max + Self::MIN_SPACING,
+            }
+            .collides_with_aabr(b),
+        }
+    }
+
+    /// Run a generation pass, recording how long it took if telemetry is being
+    /// collected.
+    fn timed(&mut self, name: &'static str, pass: impl FnOnce(&mut Self)) {
+        if self.stats.is_none() {
+            pass(self);
+            return;
+        }
+        let start = Instant::now();
+        pass(self);
+        if let Some(stats) = &mut self.stats {
+            stats.passes.push((name, start.elapsed()));
+        }
+    }
+
+    /// Whether generation failed to place a town, leaving the settlement
+    /// without any content.
+    pub fn is_empty(&self) -> bool { self.town.is_none() && self.structures.is_empty() }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    pub fn faction(&self) -> Faction { self.faction }
+
+    /// How rich and dangerous the settlement is, from `0` up to
+    /// [`Settlement::MAX_TIER`].
+    pub fn tier(&self) -> u8 { self.tier }
+
+    /// The loot table of the chests of the settlement's keep and crypt.
+    pub fn chest_loot(&self) -> &'static str { CHEST_LOOT[self.tier as usize] }
+
+    /// The entity config of the raiders living in a hostile settlement.
+    pub fn raider_entity(&self) -> &'static str { RAIDER_ENTITY[self.tier as usize] }
+
+    /// The icon marking the settlement on the world map at low zoom, judged
+    /// from its keep, its walls and its number of houses.
+    pub fn map_icon(&self) -> MapIconKind {
+        // Settlements with fewer houses than this are only hamlets. This is
+        // the same threshold as `LampParams::for_size`, but that counts every
+        // structure rather than only houses
+        const MIN_TOWN_HOUSES: usize = 12;
+
+        let has_keep = self
+            .structures
+            .iter()
+            .any(|structure| matches!(structure.kind, StructureKind::Keep(_)));
+        let has_walls = self.land.tiles.values().any(|tile| {
+            tile.tower.is_some() || tile.ways.iter().any(|way| *way == Some(WayKind::Wall))
+        });
+        let houses = self
+            .structures
+            .iter()
+            .filter(|structure| {
+                matches!(
+                    structure.kind,
+                    StructureKind::House(_) | StructureKind::Tavern(_)
+                )
+            })
+            .count();
+        let has_fields = self
+            .land
+            .tiles
+            .values()
+            .any(|tile| matches!(self.land.plot(tile.plot), Plot::Field { .. }));
+
+        if has_keep && has_walls {
+            MapIconKind::Fort
+        } else if houses >= MIN_TOWN_HOUSES {
+            MapIconKind::Town
+        } else if houses > 0 || has_keep || has_fields {
+            MapIconKind::Hamlet
+        } else {
+            MapIconKind::Ruins
+        }
+    }
+
+    /// A single color representing the settlement on the world map at low
+    /// zoom.
+    pub fn map_color(&self) -> Rgb<u8> {
+        match self.map_icon() {
+            MapIconKind::Hamlet => Rgb::new(150, 110, 60),
+            MapIconKind::Town => Rgb::new(120, 80, 40),
+            MapIconKind::Fort => Rgb::new(110, 105, 100),
+            MapIconKind::Ruins => Rgb::new(90, 85, 75),
+        }
+    }
+
+    /// The notable structures of the settlement, such as its keep and
+    /// monuments.
+    pub fn landmarks(&self) -> impl Iterator<Item = Landmark> + '_ {
+        self.structures.iter().filter_map(move |structure| {
+            let kind = match &structure.kind {
+                StructureKind::Keep(_) => LandmarkKind::Keep,
+                StructureKind::Monument(_) => LandmarkKind::Monument,
+                StructureKind::Temple(_) => LandmarkKind::Temple,
+                StructureKind::House(_)
+                | StructureKind::Tavern(_)
+                | StructureKind::NoticeBoard(_)
+                | StructureKind::Barracks(_)
+                | StructureKind::Crypt(_)
+                | StructureKind::Custom(_) => return None,
+            };
+            let bounds = structure.bounds();
+            let base = structure
+                .descriptor()
+                .map_or(bounds.min.z, |descriptor| descriptor.origin.z);
+            Some(Landmark {
+                kind,
+                wpos: self.origin + structure.bounds_2d().center(),
+                height: bounds.max.z - base,
+            })
+        })
+    }
+
+    pub fn get_origin(&self) -> Vec2<i32> { self.origin }
+
+    /// The approximate center of the settlement as it was actually built, in
+    /// world coordinates: the average position of its structures, weighted by
+    /// footprint area, and its town tiles. The keep, monuments and plaza count
+    /// for more, so that the result lands in the civic center. Falls back to
+    /// the origin for an empty settlement.
+    pub fn center_of_mass(&self) -> Vec2<i32> {
+        // How much more civic structures and tiles count than ordinary ones
+        const CIVIC_WEIGHT: f32 = 4.0;
+        // Town tiles count for a quarter of their area, so that they don't
+        // drown out the structures standing on them
+        const TILE_WEIGHT: f32 = (AREA_SIZE * AREA_SIZE) as f32 / 4.0;
+
+        let structures = self.structures.iter().map(|structure| {
+            let bounds = structure.bounds_2d();
+            let civic = matches!(
+                structure.kind,
+                StructureKind::Keep(_) | StructureKind::Monument(_) | StructureKind::Temple(_)
+            );
+            let area = bounds.size().product().max(1) as f32;
+            (
+                bounds.center().map(|e| e as f32),
+                if civic { area * CIVIC_WEIGHT } else { area },
+            )
+        });
+        let plaza = self.town.as_ref().and_then(|town| town.plaza());
+        let tiles = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(self.land.plot(tile.plot), Plot::Town { .. }))
+            .map(|(tile_pos, _)| {
+                let civic = plaza.map_or(false, |plaza| plaza.contains(*tile_pos));
+                (
+                    (*tile_pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2).map(|e| e as f32),
+                    if civic {
+                        TILE_WEIGHT * CIVIC_WEIGHT
+                    } else {
+                        TILE_WEIGHT
+                    },
+                )
+            });
+
+        let (sum, total) = structures
+            .chain(tiles)
+            .fold((Vec2::zero(), 0.0), |(sum, total), (pos, weight)| {
+                (sum + pos * weight, total + weight)
+            });
+        if total > 0.0 {
+            self.origin + (sum / total).map(|e: f32| e.round() as i32)
+        } else {
+            self.origin
+        }
+    }
+
+    /// The total harvest of the settlement's fields, per crop.
+    pub fn total_yield(&self) -> HashMap<Crop, u32> {
+        let mut yields = HashMap::new();
+        for tile in self.land.tiles.values() {
+            if let Plot::Field { crop, .. } = self.land.plot(tile.plot) {
+                *yields.entry(*crop).or_default() += crop.base_yield();
+            }
+        }
+        yields
+    }
+
+    /// Every interactable sprite (chests, crafting stations, lamps, ...) placed
+    /// by the settlement's structures, in world coordinates, so that the server
+    /// can track them without waiting for the chunks to be generated.
+    ///
+    /// Each archetype lists the spots where it places such sprites, which are
+    /// drawn exactly as they are when applied to terrain, so the result is
+    /// deterministic. Sprites that depend on the world's columns (such as
+    /// street lamps along paths) are not included.
+    pub fn interactables(&self, index: IndexRef) -> Vec<(Vec3<i32>, SpriteKind)> {
+        self.structure_sprites(index)
+            .filter_map(|(wpos, block)| Some((wpos, block.get_sprite().filter(is_interactable)?)))
+            .collect()
+    }
+
+    /// Every block placed by the settlement's structures that emits light, in
+    /// world coordinates. Like [`Settlement::interactables`], this excludes
+    /// street lamps along paths.
+    pub fn light_sources(&self, index: IndexRef) -> Vec<Vec3<i32>> {
+        self.structure_sprites(index)
+            .filter(|(_, block)| block.get_glow().is_some())
+            .map(|(wpos, _)| wpos)
+            .collect()
+    }
+
+    /// The sprites of the settlement's structures that may be interactable or
+    /// give off light, see [`Structure::sprites`], in world coordinates.
+    fn structure_sprites<'a>(
+        &'a self,
+        index: IndexRef<'a>,
+    ) -> impl Iterator<Item = (Vec3<i32>, Block)> + 'a {
+        self.structures.iter().flat_map(move |structure| {
+            structure
+                .sprites(index)
+                .into_iter()
+                .map(move |(rpos, block)| (Vec3::from(self.origin) + rpos, block))
+        })
+    }
+
+    /// A read-only view of everything the settlement generated, for callers
+    /// that never apply it to terrain.
+    pub fn data(&self) -> SettlementData { SettlementData { settlement: self } }
+
+    /// The seed of the settlement's noise field, that of its
+    /// [`SeedStream::Decoration`]. A `RandomField` built from it produces the
+    /// same values as [`Settlement::noise_at`].
+    pub fn seed(&self) -> u32 { self.stream_seed(SeedStream::Decoration) }
+
+    /// The seed of one of the settlement's streams of randomness.
+    pub fn stream_seed(&self, stream: SeedStream) -> u32 { self.stream_seeds[stream as usize] }
+
+    /// The seed of the given use of one of the settlement's streams.
+    fn use_seed(&self, seed_use: SeedUse) -> u32 {
+        seed_use.derive(self.stream_seed(seed_use.stream()))
+    }
+
+    /// Sample the settlement's deterministic noise, for decorations that should
+    /// stay consistent with the settlement itself.
+    pub fn noise_at(&self, wpos: Vec3<i32>) -> u32 { self.noise.get(wpos) }
+
+    /// A coarse polygon (in world coordinates, counter-clockwise) enclosing
+    /// every town and field tile of the settlement, for drawing its safe zone
+    /// on a map. It's computed at tile resolution and is deterministic.
+    pub fn safe_zone(&self) -> Vec<Vec2<i32>> {
+        let corners = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| {
+                matches!(
+                    self.land.plot(tile.plot),
+                    Plot::Town { .. } | Plot::Field { .. } | Plot::Pasture | Plot::Orchard { .. }
+                )
+            })
+            .flat_map(|(&tile_pos, _)| {
+                let min = self.origin + tile_pos * AREA_SIZE as i32;
+                [
+                    Vec2::new(0, 0),
+                    Vec2::new(1, 0),
+                    Vec2::new(0, 1),
+                    Vec2::new(1, 1),
+                ]
+                .map(|corner| min + corner * AREA_SIZE as i32)
+            })
+            .collect();
+
+        convex_hull(corners)
+    }
+
+    /// Check the invariants that generation is expected to uphold, returning
+    /// every issue found rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<SettlementIssue>> {
+        let mut issues = Vec::new();
+
+        for (i, a) in self.structures.iter().enumerate() {
+            let bounds = a.bounds_2d();
+            for (j, b) in self.structures.iter().enumerate().skip(i + 1) {
+                if self.structures_collide(a, b) {
+                    issues.push(SettlementIssue::StructuresOverlap(i, j));
+                }
+            }
+            for tile in footprint_tiles(bounds) {
+                let plot = self
+                    .land
+                    .tile_at(tile)
+                    .filter(|tile| self.land.plots.recreate_id(tile.plot.id()).is_some())
+                    .map(|tile| self.land.plot(tile.plot));
+                if matches!(plot, Some(Plot::Hazard | Plot::Water)) {
+                    issues.push(SettlementIssue::StructureOnUnbuildable { structure: i, tile });
+                }
+            }
+        }
+
+        let mut tiles = self.land.tiles.iter().collect::<Vec<_>>();
+        tiles.sort_by_key(|(pos, _)| (pos.x, pos.y));
+        for (&pos, tile) in tiles {
+            if self.land.plots.recreate_id(tile.plot.id()).is_none() {
+                issues.push(SettlementIssue::DanglingPlot { tile: pos });
+                continue;
+            }
+            match self.land.plot(tile.plot) {
+                Plot::Field { farm, .. } if self.farms.recreate_id(farm.id()).is_none() => {
+                    issues.push(SettlementIssue::DanglingFarm { tile: pos });
+                },
+                Plot::Town {
+                    district: Some(district),
+                } if self.town.as_ref().map_or(true, |town| {
+                    town.districts().recreate_id(district.id()).is_none()
+                }) =>
+                {
+                    issues.push(SettlementIssue::DanglingDistrict { tile: pos });
+                },
+                _ => {},
+            }
+            for ((neighbor, neighbor_tile), way) in self.land.neighbors(pos).zip(tile.ways.iter()) {
+                if way.is_some() && neighbor_tile.is_none() {
+                    issues.push(SettlementIssue::DanglingWay {
+                        tile: pos,
+                        dir: neighbor - pos,
+                    });
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// The number of unclaimed tiles within the settlement's radius, as an
+    /// estimate of how much land it has to build on.
+    pub fn buildable_tiles(&self) -> usize {
+        let tile_radius = self.radius() as i32 / AREA_SIZE as i32;
+        Spiral2d::new()
+            .take_while(|tile| tile.map(|e| e.abs()).reduce_max() < tile_radius)
+            .filter(|tile| self.land.plot_at(*tile).is_none())
+            .count()
+    }
+
+    /// How far the world position `wpos` lies within the tile it falls on, in
+    /// blocks: its distance to the second closest tile center less its
+    /// distance to the closest one, where tile centers are warped like the
+    /// rest of the settlement. It is zero on the boundary between two tiles,
+    /// never negative, and grows smoothly towards the tile's center, making it
+    /// a signal for effects that fade in and out at the edges of plots.
+    /// Neighbouring tiles may share a plot, so not every boundary is the edge
+    /// of one.
+    pub fn edge_distance(&self, wpos: Vec2<i32>) -> f32 {
+        self.land.get_at_block(wpos - self.origin).edge_dist
+    }
+
+    /// The altitude of the ground at the world position `wpos` once the
+    /// settlement is applied to the terrain, where `get_column` samples the
+    /// terrain column at a world position: the height a character would stand
+    /// at, accounting for the leveling of the town and terraced fields. Over
+    /// ponds, it is the surface of the water. Walls and structures aren't
+    /// included. `None` where `get_column` has no column.
+    pub fn surface_alt<'a>(
+        &self,
+        wpos: Vec2<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+    ) -> Option<f32> {
+        let col_sample = get_column(wpos)?;
+        let sample = self.land.get_at_block(wpos - self.origin);
+        if !Self::reshapes_column(&sample, col_sample) {
+            return Some(col_sample.alt);
+        }
+
+        let land_surface_z = col_sample.riverless_alt.floor() as i32;
+        let (surface_z, _) = self.surface_z(wpos, land_surface_z, &sample, &mut get_column);
+        Some(if matches!(sample.plot, Some(Plot::Water)) {
+            // See `water_block`
+            surface_z - 1
+        } else {
+            surface_z
+        } as f32)
+    }
+
+    /// The center of the buildable tile nearest to the world position `wpos`,
+    /// for content placed in the settlement after it was generated. Tiles
+    /// more than `max_radius` tiles away aren't considered.
+    pub fn nearest_buildable(&self, wpos: Vec2<i32>, max_radius: u32) -> Option<Vec2<i32>> {
+        let tile = (wpos - self.origin).map(to_tile);
+        Spiral2d::new()
+            .take((2 * max_radius as usize + 1).pow(2))
+            .map(|offs| tile + offs)
+            .find(|tile| self.is_buildable(*tile))
+            .map(|tile| self.origin + tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2)
+    }
+
+    /// Whether the tile `tile` is open ground, free of hazards, water,
+    /// structures, ways and reservations.
+    fn is_buildable(&self, tile: Vec2<i32>) -> bool {
+        matches!(
+            self.land.plot_at(tile),
+            None | Some(Plot::Grass | Plot::Dirt)
+        ) && !self.reserved.contains(&tile)
+            && self.land.tile_at(tile).map_or(true, |t| {
+                t.tower.is_none() && t.ways.iter().all(|way| way.is_none())
+            })
+    }
+
+    /// Designate hazardous terrain based on world data
+    pub fn designate_from_world(&mut self, sim: &WorldSim, rng: &mut impl Rng) {
+        self.designate(|cpos| sim.can_host_settlement(cpos), rng);
+    }
+
+    /// Mark tiles as hazardous where `can_host` rejects any of the chunks
+    /// they cover.
+    fn designate(&mut self, can_host: impl Fn(Vec2<i32>) -> bool, rng: &mut impl Rng) {
+        let tile_radius = self.radius() as i32 / AREA_SIZE as i32;
+        let hazard = self.land.hazard;
+        Spiral2d::new()
+            .take_while(|tile| tile.map(|e| e.abs()).reduce_max() < tile_radius)
+            .for_each(|tile| {
+                let wpos = self.origin + tile * AREA_SIZE as i32;
+
+                if (0..4)
+                    .flat_map(|x| (0..4).map(move |y| Vec2::new(x, y)))
+                    .any(|offs| {
+                        let wpos = wpos + offs * AREA_SIZE as i32 / 2;
+                        let cpos = wpos.map(|e| e.div_euclid(TerrainChunkSize::RECT_SIZE.x as i32));
+                        !can_host(cpos)
+                    })
+                    || rng.gen_range(0..16) == 0
+                // Randomly consider some tiles inaccessible
+                {
+                    self.land.set(tile, hazard);
+                }
+            })
+    }
+
+    /// Testing only
+    pub fn place_river(&mut self, rng: &mut impl Rng) {
+        let river_dir = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5).normalized();
+        let radius = 500.0 + rng.gen::<f32>().powi(2) * 1000.0;
+        let river = self.land.new_plot(Plot::Water);
+        let river_offs = Vec2::new(rng.gen_range(-3..4), rng.gen_range(-3..4));
+
+        for x in (0..100).map(|e| e as f32 / 100.0) {
+            let theta0 = x * f32::consts::PI * 2.0;
+            let theta1 = (x + 0.01) * f32::consts::PI * 2.0;
+
+            let pos0 = (river_dir * radius + Vec2::new(theta0.sin(), theta0.cos()) * radius)
+                .map(|e| e.floor() as i32)
+                .map(to_tile)
+                + river_offs;
+            let pos1 = (river_dir * radius + Vec2::new(theta1.sin(), theta1.cos()) * radius)
+                .map(|e| e.floor() as i32)
+                .map(to_tile)
+                + river_offs;
+
+            if pos0.magnitude_squared() > 15i32.pow(2) {
+                continue;
+            }
+
+            if let Some(path) = self.land.find_path(pos0, pos1, |_, _| 1.0) {
+                for pos in path.iter().copied() {
+                    self.land.set(pos, river);
+                }
+            }
+        }
+    }
+
+    /// Link fields around the settlement to its town by paths. This pass is
+    /// currently disabled in [`Settlement::populate`].
+    pub fn place_paths(&mut self, rng: &mut impl Rng) {
+        const PATH_COUNT: usize = 6;
+
+        let mut dir = Vec2::zero();
+        let origins = (0..PATH_COUNT)
+            .map(|_| {
+                dir = (Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * 2.0 - dir)
+                    .try_normalized()
+                    .unwrap_or_else(Vec2::zero);
+                let origin = dir.map(|e| (e * 100.0) as i32);
+                self.land
+                    .find_tile_near(origin, |plot| matches!(plot, Some(&Plot::Field { .. })))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let paths = if let Some(town) = self.town.as_ref() {
+            self.land
+                .find_paths_to(&origins, town.base_tile, |from, to| match (from, to) {
+                    (_, Some(b)) if self.land.plot(b.plot) == &Plot::Dirt => 0.0,
+                    (_, Some(b)) if self.land.plot(b.plot) == &Plot::Water => {
+                        self.crossings.water_cost
+                    },
+                    (_, Some(b)) if self.land.plot(b.plot) == &Plot::Hazard => 50.0,
+                    (Some(a), Some(b)) if a.contains(WayKind::Wall) => {
+                        if b.contains(WayKind::Wall) {
+                            1000.0
+                        } else {
+                            10.0
+                        }
+                    },
+                    (Some(_), Some(_)) => 1.0,
+                    _ => 1000.0,
+                })
+        } else {
+            return;
+        };
+
+        for path in paths.into_iter().flatten() {
+            let path = path.iter().copied().collect::<Vec<_>>();
+            for run in path.split(|pos| self.reserved.contains(pos)) {
+                self.land.write_path(run, WayKind::Path, |_| true, false);
+            }
+        }
+    }
+
+    /// Link the feature that the settlement formed around to the town by a
+    /// path, stopping short of the town's base tile like its streets.
+    fn place_feature_path(&mut self) {
+        let (feature, base_tile) = match (self.feature, self.town.as_ref()) {
+            (Some(feature), Some(town)) => (feature, town.base_tile),
+            _ => return,
+        };
+        let path = self.land.find_path(feature, base_tile, |_, to| {
+            match to.map(|to| self.land.plot(to.plot)) {
+                Some(Plot::Hazard) => 200.0,
+                Some(Plot::Water) => 40.0,
+                Some(Plot::Field { .. }) => 20.0,
+                _ => 1.0,
+            }
+        });
+        if let Some(path) = path {
+            let mut path = path.iter().copied().collect::<Vec<_>>();
+            path.pop();
+            // Ways are only written to tiles that exist
+            let grass = self.land.intern_plot(Plot::Grass);
+            for tile in path.iter() {
+                if self.land.tile_at(*tile).is_none() {
+                    self.land.set(*tile, grass);
+                }
+            }
+            self.land.write_path(&path, WayKind::Path, |_| true, false);
+        }
+    }
+
+    /// Lay a street along the spine of each district, through its center,
+    /// and link it to the town's base tile so that houses have a street to
+    /// front onto. The direction of each district's street is picked by its
+    /// seed. The plaza is left open and the base tile is kept free for the
+    /// keep.
+    fn place_streets(&mut self) {
+        let town = if let Some(town) = self.town.as_ref() {
+            town
+        } else {
+            return;
+        };
+        let base_tile = town.base_tile;
+        let district_at = |tile: Vec2<i32>| match self.land.plot_at(tile) {
+            Some(Plot::Town { district }) => *district,
+            _ => None,
+        };
+
+        let mut streets = Vec::new();
+        for (id, district) in town.districts().iter() {
+            if town.is_plaza(id) {
+                continue;
+            }
+            let in_district = |tile: Vec2<i32>| tile != base_tile && district_at(tile) == Some(id);
+            // Fields may have taken the heart of the district
+            let aabr_center = district.aabr.center();
+            let center = if let Some(center) = district
+                .tiles()
+                .filter(|tile| in_district(*tile))
+                .min_by_key(|tile| (tile.distance_squared(aabr_center), tile.x, tile.y))
+            {
+                center
+            } else {
+                continue;
+            };
+
+            let axes = if district.seed % 2 == 0 {
+                [Vec2::unit_x(), Vec2::unit_y()]
+            } else {
+                [Vec2::unit_y(), Vec2::unit_x()]
+            };
+            let spine = axes
+                .iter()
+                .map(|axis| {
+                    let mut start = center;
+                    while in_district(start - *axis) {
+                        start -= *axis;
+                    }
+                    let mut spine = vec![start];
+                    while in_district(spine[spine.len() - 1] + *axis) {
+                        spine.push(spine[spine.len() - 1] + *axis);
+                    }
+                    spine
+                })
+                .find(|spine| spine.len() > 1);
+            streets.extend(spine);
+
+            // Stop short of the base tile itself
+            let link = self.land.find_path(center, base_tile, |_, to| {
+                match to.map(|to| self.land.plot(to.plot)) {
+                    Some(Plot::Town { district })
+                        if district.map_or(false, |d| town.is_plaza(d)) =>
+                    {
+                        10.0
+                    },
+                    Some(Plot::Town { .. }) => 1.0,
+                    _ => 1000.0,
+                }
+            });
+            if let Some(link) = link {
+                let mut link = link.iter().copied().collect::<Vec<_>>();
+                link.pop();
+                streets.push(link);
+            }
+        }
+
+        for street in streets {
+            self.land.write_path(
+                &street,
+                WayKind::Path,
+                |plot| matches!(plot, Plot::Town { .. }),
+                false,
+            );
+        }
+    }
+
+    pub fn place_town(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        self.place_town_with(ctx, |plot| matches!(plot, None | Some(Plot::Dirt)));
+    }
+
+    fn place_town_with(
+        &mut self,
+        ctx: &mut GenCtx<impl Rng>,
+        mut is_base: impl FnMut(Option<&Plot>) -> bool,
+    ) {
+        const PLOT_COUNT: usize = 3;
+        // Largest share of a town's tiles that may already be taken by fields
+        // before the town is moved elsewhere
+        const MAX_FIELD_CONFLICT: f32 = 0.5;
+
+        let mut origin = match self.feature {
+            // Form the town beside the feature, just clear of it
+            Some(feature) => feature + *CARDINALS.choose(ctx.rng).unwrap() * Town::RADIUS,
+            None => Vec2::new(ctx.rng.gen_range(-2..3), ctx.rng.gen_range(-2..3)),
+        };
+
+        // Fields are never built over, so the town must not be based on one
+        let mut is_base =
+            move |plot: Option<&Plot>| !matches!(plot, Some(Plot::Field { .. })) && is_base(plot);
+
+        for i in 0..PLOT_COUNT {
+            // Prefer a base with few fields around it, falling back to the
+            // nearest base if every one conflicts
+            let preferred = Spiral2d::new()
+                .take(Land::SEARCH_TILES)
+                .map(|offs| origin + offs)
+                .filter(|tile| Land::is_designated(*tile))
+                .filter(|tile| is_base(self.land.plot_at(*tile)))
+                .find(|tile| self.land.field_share(*tile, Town::RADIUS) <= MAX_FIELD_CONFLICT);
+            let base_tile = preferred.or_else(|| self.land.find_tile_near(origin, &mut is_base));
+            if let Some(base_tile) = base_tile {
+                // self.land
+                //     .plot_at_mut(base_tile)
+                //     .map(|plot| *plot = Plot::Town { district: None });
+
+                if i == 0 {
+                    let town = Town::generate(self.origin, base_tile, ctx);
+
+                    for (id, district) in town.districts().iter() {
+                        let district_plot =
+                            self.land.plots.insert(Plot::Town { district: Some(id) });
+
+                        for tile in district.tiles() {
+                            if !matches!(
+                                self.land.plot_at(tile),
+                                Some(
+                                    Plot::Hazard
+                                        | Plot::Reserved
+                                        | Plot::Field { .. }
+                                        | Plot::Orchard { .. }
+                                )
+                            ) {
+                                self.land.set(tile, district_plot);
+                            }
+                        }
+                    }
+
+                    self.town = Some(town);
+                    origin = base_tile;
+                }
+            }
+        }
+
+        // Boundary wall
+        /*
+        let spokes = CARDINALS
+            .iter()
+            .filter_map(|dir| {
+                self.land.find_tile_dir(origin, *dir, |plot| match plot {
+                    Some(Plot::Water) => false,
+                    Some(Plot::Town) => false,
+                    _ => true,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut wall_path = Vec::new();
+        for i in 0..spokes.len() {
+            self.land
+                .find_path(spokes[i], spokes[(i + 1) % spokes.len()], |_, to| match to
+                    .map(|to| self.land.plot(to.plot))
+                {
+                    Some(Plot::Hazard) => 200.0,
+                    Some(Plot::Water) => 40.0,
+                    Some(Plot::Town) => 10000.0,
+                    _ => 10.0,
+                })
+                .map(|path| wall_path.extend(path.iter().copied()));
+        }
+        let grass = self.land.intern_plot(Plot::Grass);
+        let buildable = |plot: &Plot| match plot {
+            Plot::Water => false,
+            _ => true,
+        };
+        for pos in wall_path.iter() {
+            if self.land.tile_at(*pos).is_none() {
+                self.land.set(*pos, grass);
+            }
+            if self.land.plot_at(*pos).copied().filter(buildable).is_some() {
+                self.land
+                    .tile_at_mut(*pos)
+                    .map(|tile| tile.tower = Some(Tower::Wall));
+            }
+        }
+        if wall_path.len() > 0 {
+            wall_path.push(wall_path[0]);
+        }
+        self.land
+            .write_path(&wall_path, WayKind::Wall, buildable, true);
+        */
+    }
+
+    /// Wall in the town, following the outline of its largest connected
+    /// cluster of town tiles so that fields and other plots around the town
+    /// are left outside. Towers stand at the corners of the wall.
+    fn place_town_wall(&mut self) {
+        let outline = trace_outline(&self.walled_tiles());
+        if outline.len() < 2 {
+            return;
+        }
+        for (i, tile) in outline.iter().enumerate() {
+            let prev = outline[(i + outline.len() - 2) % (outline.len() - 1)];
+            let next = outline[(i + 1) % outline.len()];
+            let is_corner = (next - *tile) != (*tile - prev);
+            if is_corner {
+                if let Some(tile) = self.land.tile_at_mut(*tile) {
+                    tile.tower = Some(Tower::Wall);
+                }
+            }
+        }
+        self.land.write_path(
+            &outline,
+            WayKind::Wall,
+            |plot| matches!(plot, Plot::Town { .. }),
+            true,
+        );
+    }
+
+    /// The tiles of the town that its wall encloses: its largest connected
+    /// cluster of town tiles.
+    fn walled_tiles(&self) -> HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>> {
+        let town_tiles = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(self.land.plot(tile.plot), Plot::Town { .. }))
+            .map(|(pos, _)| *pos)
+            .collect::<HashSet<_, BuildHasherDefault<FxHasher64>>>();
+
+        // Split the town into clusters, visiting tiles in a fixed order so
+        // that ties between clusters are broken the same way every time
+        let mut unvisited = town_tiles.iter().copied().collect::<Vec<_>>();
+        unvisited.sort_by_key(|tile| (tile.x, tile.y));
+        let mut clustered = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+        let mut largest = HashSet::default();
+        for seed in unvisited {
+            if !clustered.insert(seed) {
+                continue;
+            }
+            let mut cluster = HashSet::default();
+            let mut open = vec![seed];
+            while let Some(tile) = open.pop() {
+                cluster.insert(tile);
+                for dir in WAY_DIRS.iter() {
+                    let neighbor = tile + *dir;
+                    if town_tiles.contains(&neighbor) && clustered.insert(neighbor) {
+                        open.push(neighbor);
+                    }
+                }
+            }
+            if cluster.len() > largest.len() {
+                largest = cluster;
+            }
+        }
+
+        largest
+    }
+
+    /// Dig a moat on the tiles just outside the town's wall, for settlements
+    /// fortified with both a wall and a keep. Structures reaching into the
+    /// moat are taken down. Where no path crosses the wall yet, one is laid
+    /// from the tile closest to the town center, so that the town has a main
+    /// gate, and every path crossing the moat does so on a bridge. Reserved
+    /// tiles are left as they are.
+    fn place_moat(&mut self) {
+        if self.map_icon() != MapIconKind::Fort {
+            return;
+        }
+        let walled = self.walled_tiles();
+        let outline = trace_outline(&walled);
+        let inner = walled
+            .iter()
+            .chain(outline.iter())
+            .copied()
+            .collect::<HashSet<_, BuildHasherDefault<FxHasher64>>>();
+        let mut ring = inner
+            .iter()
+            .flat_map(|tile| MOORE_DIRS.iter().map(move |dir| *tile + *dir))
+            .filter(|tile| !inner.contains(tile))
+            .filter(|tile| !matches!(self.land.plot_at(*tile), Some(Plot::Reserved)))
+            .collect::<Vec<_>>();
+        ring.sort_by_key(|tile| (tile.x, tile.y));
+        ring.dedup();
+
+        // Paths already crossing the ring are kept, to be bridged below
+        let moat = self.land.new_plot(Plot::Water);
+        for tile in ring.iter() {
+            match self.land.tile_at_mut(*tile) {
+                Some(tile) => tile.plot = moat,
+                None => self.land.set(*tile, moat),
+            }
+        }
+        self.retain_structures(|structure| {
+            !footprint_tiles(structure.bounds_2d()).any(|tile| ring.contains(&tile))
+        });
+
+        // A main gate facing the town center, crossing the wall from within
+        // and the moat to the land beyond it
+        let has_gate = outline.iter().any(|tile| {
+            self.land.tile_at(*tile).map_or(false, |tile| {
+                tile.contains(WayKind::Wall) && tile.contains(WayKind::Path)
+            })
+        });
+        if let (false, Some(center)) = (has_gate, self.town.as_ref().map(|t| t.base_tile)) {
+            let gate = outline
+                .iter()
+                .filter(|tile| walled.contains(tile))
+                .flat_map(|tile| WAY_DIRS.iter().map(move |dir| (*tile, *dir)))
+                .filter(|(tile, dir)| {
+                    walled.contains(&(*tile - *dir)) && ring.contains(&(*tile + *dir))
+                })
+                .min_by_key(|(tile, dir)| {
+                    (
+                        (*tile - center).magnitude_squared(),
+                        tile.x,
+                        tile.y,
+                        dir.x,
+                        dir.y,
+                    )
+                });
+            if let Some((tile, dir)) = gate {
+                let beyond = tile + dir * 2;
+                if self.land.tile_at(beyond).is_none() {
+                    let grass = self.land.intern_plot(Plot::Grass);
+                    self.land.set(beyond, grass);
+                }
+                self.land.write_path(
+                    &[tile - dir, tile, tile + dir, beyond],
+                    WayKind::Path,
+                    |_| true,
+                    false,
+                );
+            }
+        }
+
+        // Paths cross the moat on bridges. Their halves on the tiles either
+        // side follow once ways are normalized.
+        for tile in ring.iter() {
+            if let Some(tile) = self.land.tile_at_mut(*tile) {
+                for way in tile.ways.iter_mut() {
+                    if *way == Some(WayKind::Path) {
+                        *way = Some(WayKind::Bridge);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Put a gate wherever a path crosses the wall. Gates start out open.
+    fn place_gates(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let kind = if self
+            .structures
+            .iter()
+            .any(|structure| matches!(structure.kind, StructureKind::Keep(_)))
+        {
+            GateKind::Portcullis
+        } else {
+            GateKind::Doors
+        };
+
+        let mut tiles = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| tile.contains(WayKind::Wall) && tile.contains(WayKind::Path))
+            .map(|(tile_pos, _)| *tile_pos)
+            .collect::<Vec<_>>();
+        tiles.sort_by_key(|tile| (tile.x, tile.y));
+
+        self.gates = tiles
+            .into_iter()
+            .map(|tile| {
+                let wpos = self.origin + tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+                let alt = ctx
+                    .sim
+                    .and_then(|sim| sim.get_alt_clamped(wpos))
+                    .unwrap_or(0.0)
+                    .ceil() as i32;
+                Gate {
+                    tile,
+                    wpos: Vec3::new(wpos.x, wpos.y, alt),
+                    kind,
+                    open: true,
+                }
+            })
+            .collect();
+    }
+
+    /// Give every water plot a single level, at the lowest ground found at the
+    /// centers of the tiles it covers, so that connected water doesn't form
+    /// steps.
+    fn place_water_levels(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let sim = if let Some(sim) = ctx.sim {
+            sim
+        } else {
+            return;
+        };
+
+        for (tile_pos, tile) in self.land.tiles.iter() {
+            if self.land.plot(tile.plot) != &Plot::Water {
+                continue;
+            }
+            let wpos = self.origin + *tile_pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            if let Some(alt) = sim.get_alt_clamped(wpos) {
+                let level = alt.floor() as i32 - 2;
+                self.water_levels
+                    .entry(tile.plot)
+                    .and_modify(|e| *e = (*e).min(level))
+                    .or_insert(level);
+            }
+        }
+    }
+
+    /// Carry paths across water. Narrow crossings of shallow water, where the
+    /// ground (given by `get_alt` at a world position) lies close to the
+    /// water's level, are forded, and other crossings get a bridge, see
+    /// [`CrossingParams`]. A crossing's width is the run of water along the
+    /// path's direction.
+    fn place_crossings(&mut self, get_alt: impl Fn(Vec2<i32>) -> Option<f32>) {
+        let is_water = |tile: Vec2<i32>| matches!(self.land.plot_at(tile), Some(Plot::Water));
+        let mut crossings = Vec::new();
+        for (pos, tile) in self.land.tiles.iter() {
+            if !is_water(*pos) {
+                continue;
+            }
+            let wpos = self.origin + *pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            let depth = self
+                .water_level(*pos)
+                .zip(get_alt(wpos))
+                .map(|(level, alt)| alt.floor() as i32 - level);
+            for (idx, way) in tile.ways.iter().enumerate() {
+                if *way != Some(WayKind::Path) {
+                    continue;
+                }
+                let dir = WAY_DIRS[idx];
+                let width = 1 + [dir, -dir]
+                    .iter()
+                    .map(|dir| (1..).take_while(|i| is_water(pos + dir * *i)).count())
+                    .sum::<usize>();
+                let ford = width <= self.crossings.max_ford_width
+                    && depth.map_or(false, |depth| depth <= self.crossings.max_ford_depth);
+                let kind = if ford { WayKind::Ford } else { WayKind::Bridge };
+                crossings.push((*pos, idx, kind));
+            }
+        }
+        for (pos, idx, kind) in crossings {
+            if let Some(tile) = self.land.tile_at_mut(pos) {
+                tile.ways[idx] = Some(kind);
+            }
+        }
+        // Carry the crossings up onto the banks
+        self.land.normalize_ways();
+    }
+
+    /// Set the height, in world coordinates, of the surface of the water plot
+    /// covering `tile`, along with all water connected to it. Returns whether
+    /// there is water there.
+    pub fn set_water_level(&mut self, tile: Vec2<i32>, level: i32) -> bool {
+        match self.land.tile_at(tile) {
+            Some(t) if self.land.plot(t.plot) == &Plot::Water => {
+                self.water_levels.insert(t.plot, level);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// The height of the surface of the water plot covering `tile`, if it has
+    /// one.
+    pub fn water_level(&self, tile: Vec2<i32>) -> Option<i32> {
+        self.land
+            .tile_at(tile)
+            .and_then(|t| self.water_levels.get(&t.plot))
+            .copied()
+    }
+
+    /// The settlement's paths, bridges and fords as a graph, for routing along
+    /// them. Each straight run of road between two tiles where roads end, turn
+    /// or meet becomes an edge. Tiles are only linked where both halves of the
+    /// way between them were written.
+    pub fn road_graph(&self) -> RoadGraph {
+        let is_road = |way: Option<WayKind>| {
+            matches!(way, Some(WayKind::Path | WayKind::Bridge | WayKind::Ford))
+        };
+        // The directions, as indices into `WAY_DIRS`, that roads leave a tile in
+        let roads = |pos: Vec2<i32>| {
+            let tile = self.land.tile_at(pos);
+            (0..4)
+                .filter(|idx| {
+                    tile.map_or(false, |tile| is_road(tile.ways[*idx]))
+                        && self
+                            .land
+                            .tile_at(pos + WAY_DIRS[*idx])
+                            .map_or(false, |other| is_road(other.ways[(idx + 2) % 4]))
+                })
+                .collect::<Vec<_>>()
+        };
+        let is_node = |pos: Vec2<i32>| match roads(pos).as_slice() {
+            [a, b] => (a + 2) % 4 != *b,
+            _ => true,
+        };
+
+        let mut nodes = self
+            .land
+            .tiles
+            .keys()
+            .copied()
+            .filter(|pos| !roads(*pos).is_empty() && is_node(*pos))
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|pos| (pos.x, pos.y));
+        let index = |pos: Vec2<i32>| nodes.iter().position(|node| *node == pos);
+
+        let mut edges = Vec::new();
+        for (from, node) in nodes.iter().enumerate() {
+            for start in roads(*node) {
+                // Follow the run to the next node
+                let (mut pos, mut idx) = (*node, start);
+                let (mut length, mut cost) = (0, 0.0);
+                loop {
+                    let way = self.land.tile_at(pos).and_then(|tile| tile.ways[idx]);
+                    pos += WAY_DIRS[idx];
+                    length += 1;
+                    cost += AREA_SIZE as f32 * if way == Some(WayKind::Ford) { 2.0 } else { 1.0 };
+                    if is_node(pos) {
+                        break;
+                    }
+                    idx = roads(pos)
+                        .into_iter()
+                        .find(|next| *next != (idx + 2) % 4)
+                        .unwrap_or(idx);
+                }
+                // Each run is found from both of its ends, so keep only one
+                let to = index(pos).unwrap_or(from);
+                let end = (idx + 2) % 4;
+                if (from, start) <= (to, end) {
+                    edges.push(RoadEdge {
+                        from,
+                        to,
+                        length,
+                        cost,
+                    });
+                }
+            }
+        }
+
+        RoadGraph { nodes, edges }
+    }
+
+    /// The tile of the world position `wpos`, in world-tile coordinates.
+    ///
+    /// Most of the settlement works in land-local tile coordinates, in which
+    /// tile `(0, 0)` starts at the settlement's origin. World-tile coordinates
+    /// are the same grid of `AREA_SIZE`-block tiles, but numbered from the
+    /// world origin: the land-local tile `t` is the world tile `t +
+    /// origin.div_euclid(AREA_SIZE)`. Because the grid stays anchored to the
+    /// settlement's origin, a world tile only lines up with the world blocks
+    /// `tile * AREA_SIZE..(tile + 1) * AREA_SIZE` when the origin is a multiple
+    /// of `AREA_SIZE`; use [`Settlement::world_tile_center`] to find the
+    /// blocks of a tile.
+    pub fn world_tile_at(&self, wpos: Vec2<i32>) -> Vec2<i32> {
+        (wpos - self.origin).map(to_tile) + self.origin_tile()
+    }
+
+    /// The world position of the center of the world tile `tile`.
+    pub fn world_tile_center(&self, tile: Vec2<i32>) -> Vec2<i32> {
+        self.origin + (tile - self.origin_tile()) * AREA_SIZE as i32 + AREA_SIZE as i32 / 2
+    }
+
+    /// The offset from land-local to world-tile coordinates.
+    fn origin_tile(&self) -> Vec2<i32> { self.origin.map(|e| e.div_euclid(AREA_SIZE as i32)) }
+
+    /// The plot covering the world tile `tile`, if any. This is the plot that
+    /// [`Land::get_at_block`] samples at the tile's center.
+    pub fn plot_at_tile(&self, tile: Vec2<i32>) -> Option<&Plot> {
+        self.land.plot_at(tile - self.origin_tile())
+    }
+
+    /// Every tile of the settlement with its plot, in world-tile coordinates
+    /// and in no particular order.
+    pub fn tiles(&self) -> impl Iterator<Item = (Vec2<i32>, &Plot)> + '_ {
+        let origin_tile = self.origin_tile();
+        self.land
+            .tiles
+            .iter()
+            .map(move |(pos, tile)| (*pos + origin_tile, self.land.plot(tile.plot)))
+    }
+
+    /// The gate whose opening contains `rpos`, relative to the origin.
+    fn gate_at(&self, rpos: Vec2<i32>) -> Option<&Gate> {
+        self.gates.iter().find(|gate| {
+            let center = gate.tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            (rpos - center).map(|e| e.abs()).reduce_max() <= GATE_HALF_WIDTH
+        })
+    }
+
+    /// Every gate through the settlement's wall, in world coordinates.
+    pub fn gates(&self) -> Vec<(Vec3<i32>, GateKind)> {
+        self.gates
+            .iter()
+            .map(|gate| (gate.wpos, gate.kind))
+            .collect()
+    }
+
+    /// Open or close the gate at `wpos`, returning whether there is one. The
+    /// change only shows once the gate's terrain is applied again, such as
+    /// with [`Settlement::apply_terrain`].
+    pub fn set_gate_open(&mut self, wpos: Vec2<i32>, open: bool) -> bool {
+        let tile = (wpos - self.origin).map(to_tile);
+        self.gates
+            .iter_mut()
+            .find(|gate| gate.tile == tile)
+            .map(|gate| gate.open = open)
+            .is_some()
+    }
+
+    /// Derive a structure's seed from its position rather than from the
+    /// generation rng, so that it doesn't depend on placement order.
+    fn structure_seed(&self, pos: Vec3<i32>) -> u32 {
+        RandomField::new(self.stream_seed(SeedStream::Layout)).get(Vec3::from(self.origin) + pos)
+    }
+
+    pub fn place_buildings(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let town_center = if let Some(town) = self.town.as_ref() {
+            town.base_tile
+        } else {
+            return;
+        };
+
+        // Plazas are left open around a central monument
+        if let Some(plaza) = self.town.as_ref().and_then(|town| town.plaza()) {
+            let pos = plaza.center() * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            let alt = ctx
+                .sim
+                .and_then(|sim| sim.get_alt_clamped(self.origin + pos))
+                .map(|alt| self.leveled_alt(pos, alt))
+                .unwrap_or(0.0)
+                .ceil() as i32;
+            let structure = Structure {
+                kind: StructureKind::Monument(Building::<Monument>::generate(
+                    self.structure_seed(Vec3::new(pos.x, pos.y, alt)),
+                    Vec3::new(pos.x, pos.y, alt),
+                    None,
+                )),
+            };
+            if !footprint_tiles(structure.bounds_2d())
+                .any(|tile| matches!(self.land.plot_at(tile), Some(Plot::Reserved)))
+            {
+                self.reserve_footprint(structure.bounds_2d());
+                self.structures.push(structure);
+            }
+        }
+
+        if self.temple {
+            self.place_temple(ctx);
+        }
+        self.place_crypt(ctx);
+
+        // Bigger towns get grander keeps
+        let town_tiles = self
+            .land
+            .tiles
+            .values()
+            .filter(|tile| matches!(self.land.plot(tile.plot), Plot::Town { .. }))
+            .count();
+        let keep_tier = match town_tiles {
+            0..=23 => KeepTier::Fort,
+            24..=39 => KeepTier::Hold,
+            _ => KeepTier::Castle,
+        };
+
+        for tile in Spiral2d::new()
+            .map(|offs| town_center + offs)
+            .take(16usize.pow(2))
+        {
+            // This is a stupid way to decide how to place buildings
+            let density = building_density(
+                tile.map(|e| e as f32)
+                    .distance(town_center.map(|e| e as f32)),
+                self.density_falloff,
+            );
+            for i in 0..(ctx.rng.gen_range(2..5) as f32 * density).round() as i32 {
+                let mut attempts = 0;
+                let mut placed = false;
+                for attempt in 0..25 {
+                    attempts += 1;
+                    let house_pos = tile.map(|e| e * AREA_SIZE as i32 + AREA_SIZE as i32 / 2)
+                        + Vec2::<i32>::zero().map(|_| {
+                            ctx.rng
+                                .gen_range(-(AREA_SIZE as i32) / 4..AREA_SIZE as i32 / 4)
+                        });
+                    let house_pos = if self.grid_snap {
+                        house_pos.map(|e| e.div_euclid(Self::TOWN_GRID) * Self::TOWN_GRID)
+                    } else {
+                        house_pos
+                    };
+
+                    let tile_pos = house_pos.map(|e| e.div_euclid(AREA_SIZE as i32));
+                    if self
+                        .land
+                        .tile_at(tile_pos)
+                        .map(|t| t.contains(WayKind::Path))
+                        .unwrap_or(true)
+                        || self
+                            .town
+                            .as_ref()
+                            .and_then(|town| town.plaza())
+                            .map_or(false, |plaza| plaza.contains(tile_pos))
+                        || ctx
+                            .sim
+                            .and_then(|sim| sim.get_nearest_path(self.origin + house_pos))
+                            .map(|(dist, _, _, _)| dist < 28.0)
+                            .unwrap_or(false)
+                    {
+                        continue;
+                    }
+
+                    let alt = if let Some(Plot::Town { district }) = self.land.plot_at(tile_pos) {
+                        district
+                            .and_then(|d| self.town.as_ref().map(|t| t.districts().get(d)))
+                            .map(|d| d.alt)
+                            .filter(|_| false) // Temporary
+                            .unwrap_or_else(|| {
+                                ctx.sim
+                                    .and_then(|sim| sim.get_alt_clamped(self.origin + house_pos))
+                                    .map(|alt| self.leveled_alt(house_pos, alt))
+                                    .unwrap_or(0.0)
+                                    .ceil() as i32
+                            })
+                    } else {
+                        continue;
+                    };
+
+                    let street_dir = CARDINALS
+                        .iter()
+                        .copied()
+                        .find(|dir| {
+                            self.land
+                                .tile_at(tile_pos + *dir)
+                                .map_or(false, |t| t.contains(WayKind::Path))
+                        })
+                        .or_else(|| {
+                            let wpos = self.origin + house_pos;
+                            ctx.sim
+                                .and_then(|sim| sim.get_nearest_path(wpos))
+                                .filter(|(dist, _, _, _)| *dist < 48.0)
+                                .map(|(_, path_pos, _, _)| {
+                                    let dir = path_pos - wpos.map(|e| e as f32);
+                                    if dir.x.abs() > dir.y.abs() {
+                                        Vec2::new(dir.x.signum() as i32, 0)
+                                    } else {
+                                        Vec2::new(0, dir.y.signum() as i32)
+                                    }
+                                })
+                        });
+                    // Houses beside a path occasionally get a porch facing it
+                    let path_dir = street_dir.filter(|_| ctx.rng.gen_bool(0.35));
+                    // Planned towns run their buildings along the street
+                    let street_ori = street_dir
+                        .filter(|_| self.grid_snap)
+                        .map(|dir| if dir.x != 0 { Ori::North } else { Ori::East });
+
+                    let structure = Structure {
+                        kind: if tile == town_center && i == 0 {
+                            // Fall back to a smaller keep if a grand one
+                            // doesn't fit
+                            let tier = if attempt < 12 {
+                                keep_tier
+                            } else {
+                                keep_tier.smaller().unwrap_or(keep_tier)
+                            };
+                            let keep = Building::<Keep>::generate_tier(
+                                self.structure_seed(Vec3::new(house_pos.x, house_pos.y, alt)),
+                                Vec3::new(house_pos.x, house_pos.y, alt),
+                                tier,
+                            );
+                            StructureKind::Keep(match street_ori {
+                                Some(ori) => keep.with_ori(ori),
+                                None => keep,
+                            })
+                        } else {
+                            let origin = Vec3::new(house_pos.x, house_pos.y, alt);
+                            let seed = self.structure_seed(origin);
+                            let roll = (RandomField::new(self.use_seed(SeedUse::Tavern))
+                                .get(origin)
+                                % 1024) as f32
+                                / 1024.0;
+                            let tavern = self.structure_mix.is_tavern(roll);
+                            let calendar = ctx.sim.and_then(|sim| sim.calendar.as_ref());
+                            let house = if tavern {
+                                Building::<House>::generate_tavern(seed, origin, calendar)
+                            } else {
+                                Building::<House>::generate(seed, origin, calendar)
+                            };
+                            let house = match street_ori {
+                                Some(ori) => house.with_ori(ori),
+                                None => house,
+                            };
+                            let house = match path_dir {
+                                Some(dir) => house.with_porch(dir),
+                                None => house,
+                            };
+                            // Some houses share a single roof style, picked by
+                            // position. Flat roofs would collect snow.
+                            let roof_style =
+                                match RandomField::new(self.use_seed(SeedUse::RoofStyle))
+                                    .get(origin)
+                                    % 6
+                                {
+                                    0 => Some(RoofStyle::Thatch),
+                                    1 if !self.cold => Some(RoofStyle::Flat),
+                                    _ => None,
+                                };
+                            let house = match roof_style {
+                                Some(roof_style) => house.with_roof_style(roof_style),
+                                None => house,
+                            };
+                            if tavern {
+                                StructureKind::Tavern(house)
+                            } else {
+                                StructureKind::House(house)
+                            }
+                        },
+                    };
+
+                    // Dense towns build houses against their neighbours
+                    let structure =
+                        if self.clustering > 0.0 && ctx.rng.gen_bool(self.clustering as f64) {
+                            match self.party_wall_offset(&structure) {
+                                Some(offs) => structure.moved_by(offs),
+                                None => structure,
+                            }
+                        } else {
+                            structure
+                        };
+                    let bounds = structure.bounds_2d();
+
+                    // Check for collision with other structures
+                    if self
+                        .structures
+                        .iter()
+                        .any(|s| self.structures_collide(s, &structure))
+                        || footprint_tiles(bounds)
+                            .any(|tile| matches!(self.land.plot_at(tile), Some(Plot::Reserved)))
+                    {
+                        continue;
+                    }
+
+                    let is_keep = matches!(structure.kind, StructureKind::Keep(_));
+                    self.reserve_footprint(bounds);
+                    self.structures.push(structure);
+                    if is_keep && self.town_walls {
+                        self.place_barracks(ctx);
+                    }
+                    placed = true;
+                    break;
+                }
+                if let Some(stats) = &mut self.stats {
+                    // Every attempt but a successful last one was given up on
+                    stats.building_attempts += attempts;
+                    stats.failed_building_attempts += attempts - usize::from(placed);
+                }
+            }
+        }
+
+        // Only towns keep their temple, which is placed before it's known
+        // whether the settlement grows into one
+        if !matches!(self.map_icon(), MapIconKind::Town | MapIconKind::Fort) {
+            self.retain_structures(|structure| !matches!(structure.kind, StructureKind::Temple(_)));
+        }
+
+        self.place_notice_board(ctx);
+    }
+
+    /// Build a temple near the plaza or, for towns without a plaza, near the
+    /// town center, before the town's houses fill it in. The temple stays off
+    /// the streets, the plaza and the base tile, where the keep stands.
+    fn place_temple(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let (base_tile, center_tile) = match self.town.as_ref() {
+            Some(town) => (
+                town.base_tile,
+                town.plaza().map_or(town.base_tile, |plaza| plaza.center()),
+            ),
+            None => return,
+        };
+        let plaza = self.town.as_ref().and_then(|town| town.plaza());
+        let center = center_tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+
+        // Walk outwards from the center in a fixed order, like the notice board
+        for offs in Spiral2d::new().take(32usize.pow(2)) {
+            let pos = center + offs * 4;
+            let tile_pos = pos.map(to_tile);
+            if !matches!(self.land.plot_at(tile_pos), Some(Plot::Town { .. }))
+                || self
+                    .land
+                    .tile_at(tile_pos)
+                    .map_or(true, |tile| tile.contains(WayKind::Path))
+                || plaza.map_or(false, |plaza| plaza.contains(tile_pos))
+            {
+                continue;
+            }
+
+            let alt = ctx
+                .sim
+                .and_then(|sim| sim.get_alt_clamped(self.origin + pos))
+                .map(|alt| self.leveled_alt(pos, alt))
+                .unwrap_or(0.0)
+                .ceil() as i32;
+            let origin = Vec3::new(pos.x, pos.y, alt);
+            let structure = Structure {
+                kind: StructureKind::Temple(Building::<Temple>::generate(
+                    self.structure_seed(origin),
+                    origin,
+                    None,
+                )),
+            };
+            let bounds = structure.bounds_2d();
+            if self
+                .structures
+                .iter()
+                .any(|s| self.structures_collide(s, &structure))
+                || footprint_tiles(bounds).any(|tile| {
+                    tile == base_tile
+                        || matches!(
+                            self.land.plot_at(tile),
+                            Some(Plot::Reserved | Plot::Hazard | Plot::Water)
+                        )
+                })
+            {
+                continue;
+            }
+
+            self.reserve_footprint(bounds);
+            self.structures.push(structure);
+            return;
+        }
+    }
+
+    /// Lay out a graveyard with a crypt beneath it in towns of at least
+    /// [`CRYPT_MIN_TOWN_TILES`] tiles, before the town's houses fill it in.
+    /// Graveyards lie on the outskirts, on the town tile farthest from the
+    /// town center that the crypt fits on whole, clear of the streets, the
+    /// plaza and the line of any wall.
+    fn place_crypt(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let (base_tile, plaza) = match self.town.as_ref() {
+            Some(town) => (town.base_tile, town.plaza()),
+            None => return,
+        };
+        let walled = self.walled_tiles();
+        let outline = trace_outline(&walled);
+        let mut tiles = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(self.land.plot(tile.plot), Plot::Town { .. }))
+            .map(|(pos, _)| *pos)
+            .collect::<Vec<_>>();
+        if tiles.len() < CRYPT_MIN_TOWN_TILES {
+            return;
+        }
+        tiles.sort_by_key(|tile| (-tile.distance_squared(base_tile), tile.x, tile.y));
+
+        for tile_pos in tiles {
+            if tile_pos == base_tile
+                || outline.contains(&tile_pos)
+                || plaza.map_or(false, |plaza| plaza.contains(tile_pos))
+            {
+                continue;
+            }
+
+            let pos = tile_pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            let alt = ctx
+                .sim
+                .and_then(|sim| sim.get_alt_clamped(self.origin + pos))
+                .map(|alt| self.leveled_alt(pos, alt))
+                .unwrap_or(0.0)
+                .ceil() as i32;
+            let origin = Vec3::new(pos.x, pos.y, alt);
+            let crypt = Building::<Crypt>::generate(self.structure_seed(origin), origin, None);
+            // Centered on the tile
+            let offs = pos - crypt.bounds_2d().center();
+            let structure = Structure {
+                kind: StructureKind::Crypt(crypt.moved_by(Vec3::from(offs))),
+            };
+            let bounds = structure.bounds_2d();
+            if self
+                .structures
+                .iter()
+                .any(|s| self.structures_collide(s, &structure))
+                || footprint_tiles(bounds).any(|tile| {
+                    tile != tile_pos
+                        || self
+                            .land
+                            .tile_at(tile)
+                            .map_or(true, |tile| tile.contains(WayKind::Path))
+                })
+            {
+                continue;
+            }
+
+            self.reserve_footprint(bounds);
+            self.structures.push(structure);
+            return;
+        }
+    }
+
+    /// Build barracks near the keep of a town that is to be walled, to house
+    /// its guards: one for every [`GUARDS_PER_BARRACKS`] guards the town is
+    /// expected to spawn, or part thereof. Once it has barracks, the town's
+    /// guards spawn in their bunks rather than in its streets. Barracks are
+    /// built right after the keep, before houses crowd it, and stay within
+    /// the line of the wall, off the streets and the plaza.
+    fn place_barracks(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        let guards = self.population_summary().guards;
+        let count = (guards / GUARDS_PER_BARRACKS).ceil() as usize;
+        let keep = self
+            .structures
+            .iter()
+            .find_map(|structure| match &structure.kind {
+                StructureKind::Keep(keep) => Some(keep.bounds_2d().center()),
+                _ => None,
+            });
+        let keep = match keep {
+            Some(keep) if count > 0 => keep,
+            _ => return,
+        };
+        let walled = self.walled_tiles();
+        let outline = trace_outline(&walled);
+        let inside = |tile: Vec2<i32>| walled.contains(&tile) && !outline.contains(&tile);
+        let plaza = self.town.as_ref().and_then(|town| town.plaza());
+
+        let mut placed = 0;
+        // Walk outwards from the keep in a fixed order, like the temple
+        for offs in Spiral2d::new().take(32usize.pow(2)) {
+            if placed == count {
+                break;
+            }
+            let pos = keep + offs * 4;
+            let tile_pos = pos.map(to_tile);
+            if !inside(tile_pos)
+                || self
+                    .land
+                    .tile_at(tile_pos)
+                    .map_or(true, |tile| tile.contains(WayKind::Path))
+                || plaza.map_or(false, |plaza| plaza.contains(tile_pos))
+            {
+                continue;
+            }
+
+            let alt = ctx
+                .sim
+                .and_then(|sim| sim.get_alt_clamped(self.origin + pos))
+                .map(|alt| self.leveled_alt(pos, alt))
+                .unwrap_or(0.0)
+                .ceil() as i32;
+            let origin = Vec3::new(pos.x, pos.y, alt);
+            let structure = Structure {
+                kind: StructureKind::Barracks(Building::<Barracks>::generate(
+                    self.structure_seed(origin),
+                    origin,
+                    None,
+                )),
+            };
+            let bounds = structure.bounds_2d();
+            if self
+                .structures
+                .iter()
+                .any(|s| self.structures_collide(s, &structure))
+                || footprint_tiles(bounds).any(|tile| {
+                    !inside(tile)
+                        || matches!(
+                            self.land.plot_at(tile),
+                            Some(Plot::Reserved | Plot::Hazard | Plot::Water)
+                        )
+                })
+            {
+                continue;
+            }
+
+            self.reserve_footprint(bounds);
+            self.structures.push(structure);
+            placed += 1;
+        }
+    }
+
+    /// The bunks of the settlement's barracks, as positions relative to its
+    /// origin.
+    fn bunks(&self) -> impl Iterator<Item = Vec3<i32>> + '_ {
+        self.structures
+            .iter()
+            .filter_map(|structure| match &structure.kind {
+                StructureKind::Barracks(barracks) => Some(barracks.bunks()),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Towns get a single notice board, next to the plaza's monument or, for
+    /// towns without a plaza, near the town center. Hamlets don't get one.
+    fn place_notice_board(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        if !matches!(self.map_icon(), MapIconKind::Town | MapIconKind::Fort) {
+            return;
+        }
+        let center_tile = match self.town.as_ref() {
+            Some(town) => town.plaza().map_or(town.base_tile, |plaza| plaza.center()),
+            None => return,
+        };
+        let center = center_tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+
+        // Walk outwards from the center in a fixed order so that the board's
+        // position only depends on what has already been placed
+        for offs in Spiral2d::new().take(24usize.pow(2)) {
+            let pos = center + offs * 2;
+            if self.land.get_at_block(pos).way.is_some() {
+                continue;
+            }
+            let alt = ctx
+                .sim
+                .and_then(|sim| sim.get_alt_clamped(self.origin + pos))
+                .map(|alt| self.leveled_alt(pos, alt))
+                .unwrap_or(0.0)
+                .ceil() as i32;
+            let pos3 = Vec3::new(pos.x, pos.y, alt);
+            let structure = Structure {
+                kind: StructureKind::NoticeBoard(Building::<NoticeBoard>::generate(
+                    self.structure_seed(pos3),
+                    pos3,
+                    None,
+                )),
+            };
+            let bounds = structure.bounds_2d();
+            if self
+                .structures
+                .iter()
+                .any(|s| s.bounds_2d().collides_with_aabr(bounds))
+                || footprint_tiles(bounds)
+                    .any(|tile| matches!(self.land.plot_at(tile), Some(Plot::Reserved)))
+            {
+                continue;
+            }
+
+            self.reserve_footprint(bounds);
+            self.structures.push(structure);
+            return;
+        }
+    }
+
+    /// The offset that moves a house flush against the nearest house, so
+    /// that the two share a wall, if the house would still stand on a street
+    /// block there. The house keeps its altitude. Houses aren't built against
+    /// the side of a neighbour that either of their porches faces.
+    fn party_wall_offset(&self, structure: &Structure) -> Option<Vec3<i32>> {
+        let house = structure.house()?;
+        let core = house.core_bounds_2d();
+        let (neighbour, other) = self
+            .structures
+            .iter()
+            .filter_map(|s| Some((s.house()?, s.house_core()?)))
+            .filter(|(_, other)| {
+                (other.center() - core.center())
+                    .map(|e| e.abs())
+                    .reduce_max()
+                    < AREA_SIZE as i32
+            })
+            .min_by_key(|(_, other)| (other.center() - core.center()).map(|e| e.abs()).sum())?;
+
+        let delta = other.center() - core.center();
+        let (dir, offs) = if delta.x.abs() > delta.y.abs() {
+            let x = if delta.x > 0 {
+                other.min.x - core.max.x
+            } else {
+                other.max.x - core.min.x
+            };
+            (
+                Vec2::new(delta.x.signum(), 0),
+                Vec2::new(x, other.min.y - core.min.y),
+            )
+        } else {
+            let y = if delta.y > 0 {
+                other.min.y - core.max.y
+            } else {
+                other.max.y - core.min.y
+            };
+            (
+                Vec2::new(0, delta.y.signum()),
+                Vec2::new(other.min.x - core.min.x, y),
+            )
+        };
+        if house.params().porch == Some(dir) || neighbour.params().porch == Some(-dir) {
+            return None;
+        }
+
+        let tile_pos = (core.center() + offs).map(to_tile);
+        let on_block = matches!(self.land.plot_at(tile_pos), Some(Plot::Town { .. }))
+            && self
+                .land
+                .tile_at(tile_pos)
+                .map_or(false, |tile| !tile.contains(WayKind::Path))
+            && self
+                .town
+                .as_ref()
+                .and_then(|town| town.plaza())
+                .map_or(true, |plaza| !plaza.contains(tile_pos));
+        on_block.then(|| Vec3::new(offs.x, offs.y, 0))
+    }
+
+    /// Mark every tile overlapped by `bounds` as reserved.
+    fn reserve_footprint(&mut self, bounds: Aabr<i32>) {
+        self.reserved.extend(footprint_tiles(bounds));
+    }
+
+    /// Reserve exactly the tiles overlapped by the structures, releasing those
+    /// of structures that have since moved or been taken down.
+    fn reserve_structures(&mut self) {
+        self.reserved = self
+            .structures
+            .iter()
+            .flat_map(|structure| footprint_tiles(structure.bounds_2d()))
+            .collect();
+    }
+
+    /// Take down the structures that `keep` rejects, releasing their tiles.
+    fn retain_structures(&mut self, keep: impl FnMut(&Structure) -> bool) {
+        self.structures.retain(keep);
+        self.reserve_structures();
+    }
+
+    /// Place a custom structure in the settlement, keeping fields and paths
+    /// placed afterwards clear of it.
+    pub fn add_custom_structure(&mut self, source: Box<dyn StructureSource>) {
+        let structure = Structure {
+            kind: StructureKind::Custom(source),
+        };
+        self.reserve_footprint(structure.bounds_2d());
+        self.structures.push(structure);
+    }
+
+    /// Replace the built-in choice of entities spawned by the settlement, or
+    /// restore it with `None`. The spawn sites themselves are unchanged.
+    pub fn set_spawn_policy(&mut self, policy: Option<Box<dyn SpawnPolicy>>) {
+        self.spawn_policy = policy;
+    }
+
+    /// Claim an empty, buildable tile near `near` (in tile coordinates) for
+    /// content placed after generation. The tile is kept free of structures,
+    /// fields and NPCs, and is rendered as plain ground, flattened to the
+    /// height of its center as given by `get_alt` at a world position.
+    pub fn reserve_plot(
+        &mut self,
+        near: Vec2<i32>,
+        get_alt: impl Fn(Vec2<i32>) -> Option<f32>,
+    ) -> Option<Id<Plot>> {
+        let tile = Spiral2d::new()
+            .map(|offs| near + offs)
+            .take(32usize.pow(2))
+            .filter(|tile| Land::is_designated(*tile))
+            .find(|tile| {
+                matches!(
+                    self.land.plot_at(*tile),
+                    None | Some(Plot::Dirt | Plot::Grass | Plot::Town { .. })
+                ) && !self.reserved.contains(tile)
+                    && self
+                        .land
+                        .tile_at(*tile)
+                        .map_or(true, |tile| tile.ways.iter().all(Option::is_none))
+            })?;
+
+        let plot = self.land.new_plot(Plot::Reserved);
+        self.land.set(tile, plot);
+        let center = tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+        if let Some(alt) = get_alt(self.origin + center) {
+            let level = self.leveled_alt(center, alt).round() as i32;
+            self.reserved_levels.insert(plot, level);
+        }
+        Some(plot)
+    }
+
+    /// Fields are placed before buildings, so turn any field tiles that ended
+    /// up beneath a structure into bare dirt.
+    fn clear_reserved_fields(&mut self) {
+        let dirt = self.land.intern_plot(Plot::Dirt);
+        for tile in self.reserved.iter() {
+            if matches!(self.land.plot_at(*tile), Some(Plot::Field { .. })) {
+                self.land.set(*tile, dirt);
+            }
+        }
+    }
+
+    pub fn place_farms(&mut self, ctx: &mut GenCtx<impl Rng>) {
+        // Give up on further fields after this many fail to be placed in a row
+        const MAX_FAILED_FIELDS: usize = 3;
+
+        let params = self
+            .farm_params
+            .unwrap_or_else(|| FarmParams::for_land(self.buildable_tiles()));
+        let mut failed_fields = 0;
+
+        for _ in 0..params.count {
+            if failed_fields >= MAX_FAILED_FIELDS {
+                break;
+            }
+            // Keep farms apart, so that their fields spread out rather than
+            // competing for the same tiles
+            let farms = &self.farms;
+            let base_tile = Spiral2d::new()
+                .take(Land::SEARCH_TILES)
+                .filter(|tile| self.land.plot_at(*tile).is_none())
+                .find(|tile| {
+                    farms.values().all(|farm| {
+                        farm.base_tile.distance_squared(*tile) >= params.min_spacing.pow(2)
+                    })
+                });
+            if let Some(base_tile) = base_tile {
+                // Farm
+                //let farmhouse = self.land.new_plot(Plot::Dirt);
+                //self.land.set(base_tile, farmhouse);
+
+                // Farmhouses
+                // for _ in 0..ctx.rng.gen_range(1..3) {
+                //     let house_pos = base_tile.map(|e| e * AREA_SIZE as i32 + AREA_SIZE as i32
+                // / 2)         + Vec2::new(ctx.rng.gen_range(-16..16),
+                // ctx.rng.gen_range(-16..16));
+
+                //     self.structures.push(Structure {
+                //         kind: StructureKind::House(HouseBuilding::generate(ctx.rng,
+                // Vec3::new(             house_pos.x,
+                //             house_pos.y,
+                //             ctx.sim
+                //                 .and_then(|sim| sim.get_alt_approx(self.origin + house_pos))
+                //                 .unwrap_or(0.0)
+                //                 .ceil() as i32,
+                //         ))),
+                //     });
+                // }
+
+                // Fields
+                let farmland = self.farms.insert(Farm { base_tile });
+                for _ in 0..params.fields_per_farm {
+                    if self.place_field(farmland, base_tile, ctx.rng).is_some() {
+                        failed_fields = 0;
+                    } else {
+                        failed_fields += 1;
+                        if failed_fields >= MAX_FAILED_FIELDS {
+                            break;
+                        }
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        if ctx.rng.gen_bool(0.5) {
+            self.place_pasture(ctx);
+        }
+        self.place_orchard();
+    }
+
+    /// Maybe plant an orchard of trees suiting the settlement's biome. The
+    /// orchard is picked from the settlement's seed alone, so that it doesn't
+    /// disturb the rest of generation.
+    pub fn place_orchard(&mut self) -> Option<Id<Plot>> {
+        const MAX_ORCHARD_SIZE: usize = 12;
+
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(self.use_seed(SeedUse::Orchard)));
+        if !rng.gen_bool(0.5) {
+            return None;
+        }
+        let species = *TreeSpecies::for_biome(self.biome).choose(&mut rng)?;
+
+        let origin = Vec2::new(rng.gen_range(-4..5), rng.gen_range(-4..5));
+        let center = self.land.find_tile_near(origin, |plot| plot.is_none())?;
+        let orchard = self.land.new_plot(Plot::Orchard { species });
+        let tiles = self.land.grow_from(
+            center,
+            rng.gen_range(MAX_ORCHARD_SIZE / 2..MAX_ORCHARD_SIZE),
+            &mut rng,
+            |plot| plot.is_none(),
+        );
+        for pos in tiles.into_iter().filter(|pos| !self.reserved.contains(pos)) {
+            self.land.set(pos, orchard);
+        }
+        Some(orchard)
+    }
+
+    /// Place a large, open pasture for grazing herds, preferring flat ground.
+    pub fn place_pasture(&mut self, ctx: &mut GenCtx<impl Rng>) -> Option<Id<Plot>> {
+        const MAX_PASTURE_SIZE: usize = 48;
+        const MAX_GRADIENT: f32 = 0.3;
+
+        let is_flat = |tile: Vec2<i32>| {
+            let wpos = self.origin + tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2;
+            ctx.sim
+                .and_then(|sim| {
+                    sim.get_gradient_approx(
+                        wpos.map2(TerrainChunkSize::RECT_SIZE, |e, sz| e.div_euclid(sz as i32)),
+                    )
+                })
+                .map_or(true, |gradient| gradient < MAX_GRADIENT)
+        };
+        let origin = Vec2::new(ctx.rng.gen_range(-4..5), ctx.rng.gen_range(-4..5));
+        let center = Spiral2d::new()
+            .take(Land::SEARCH_TILES)
+            .map(|pos| origin + pos)
+            .filter(|pos| Land::is_designated(*pos))
+            .filter(|pos| self.land.plot_at(*pos).is_none())
+            .find(|pos| is_flat(*pos))
+            .or_else(|| self.land.find_tile_near(origin, |plot| plot.is_none()))?;
+
+        let pasture = self.land.new_plot(Plot::Pasture);
+        let tiles = self.land.grow_from(
+            center,
+            ctx.rng.gen_range(MAX_PASTURE_SIZE / 2..MAX_PASTURE_SIZE),
+            ctx.rng,
+            |plot| plot.is_none(),
+        );
+        for pos in tiles {
+            self.land.set(pos, pasture);
+        }
+        Some(pasture)
+    }
+
+    pub fn place_field(
+        &mut self,
+        farm: Id<Farm>,
+        origin: Vec2<i32>,
+        rng: &mut impl Rng,
+    ) -> Option<Id<Plot>> {
+        const MAX_FIELD_SIZE: usize = 24;
+        const CROPS: [Crop; 8] = [
+            Crop::Corn,
+            Crop::Wheat,
+            Crop::Cabbage,
+            Crop::Pumpkin,
+            Crop::Flax,
+            Crop::Carrot,
+            Crop::Tomato,
+            Crop::Radish,
+        ];
+
+        // Themed farms grow the crop planned for them
+        let forced = self.forced_crops.as_ref().map(|crops| {
+            let farm_idx = self
+                .farms
+                .iter()
+                .position(|(id, _)| id == farm)
+                .unwrap_or(0);
+            crops[farm_idx % crops.len()]
+        });
+        let crops = CROPS
+            .iter()
+            .copied()
+            .filter(|crop| !self.excluded_crops.contains(crop))
+            .collect::<Vec<_>>();
+        if crops.is_empty() && forced.is_none() {
+            return None;
+        }
+
+        if let Some(center) = self.land.find_tile_near(origin, |plot| plot.is_none()) {
+            let seed = rng.gen();
+            let crop = forced.unwrap_or_else(|| crops[rng.gen_range(0..crops.len())]);
+            let field = self.land.new_plot(Plot::Field {
+                farm,
+                seed,
+                crop,
+                center,
+            });
+            let tiles =
+                self.land
+                    .grow_from(center, rng.gen_range(5..MAX_FIELD_SIZE), rng, |plot| {
+                        plot.is_none()
+                    });
+            for pos in tiles.into_iter().filter(|pos| !self.reserved.contains(pos)) {
+                self.land.set(pos, field);
+            }
+            Some(field)
+        } else {
+            None
+        }
+    }
+
+    pub fn radius(&self) -> f32 { Self::RADIUS }
+
+    /// Record what [`Settlement::apply_to`] writes to every chunk the
+    /// settlement covers, for settlements that never change. `get_chunk`
+    /// gives the terrain of the chunk whose minimum corner is at a world
+    /// position, before the settlement is applied, or `None` to skip it;
+    /// replaying the result onto that same terrain gives the same blocks as
+    /// applying the settlement would. `get_column` samples the terrain column
+    /// at a world position.
+    pub fn bake<'a>(
+        &'a self,
+        index: IndexRef,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        mut get_chunk: impl FnMut(Vec2<i32>) -> Option<TerrainChunk>,
+    ) -> BakedSettlement {
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let radius = Self::RADIUS as i32;
+        let area = self.structures.iter().fold(
+            Aabr {
+                min: -Vec2::broadcast(radius),
+                max: Vec2::broadcast(radius),
+            },
+            |area, structure| area.union(structure.bounds_2d()),
+        );
+        let (min_chunk, max_chunk) = (
+            (self.origin + area.min).map2(size, |e, sz| e.div_euclid(sz)),
+            (self.origin + area.max).map2(size, |e, sz| e.div_euclid(sz)),
+        );
+
+        let mut chunks = HashMap::default();
+        for y in min_chunk.y..max_chunk.y + 1 {
+            for x in min_chunk.x..max_chunk.x + 1 {
+                let wpos2d = Vec2::new(x, y) * size;
+                let base = if let Some(base) = get_chunk(wpos2d) {
+                    base
+                } else {
+                    continue;
+                };
+                let mut vol = base.clone();
+                self.apply_to(index, wpos2d, |offs| get_column(wpos2d + offs), &mut vol);
+
+                // Keep only the blocks that changed
+                let min_z = base.get_min_z().min(vol.get_min_z());
+                let max_z = base.get_max_z().max(vol.get_max_z());
+                let mut blocks = Vec::new();
+                for z in min_z..max_z {
+                    for y in 0..size.y {
+                        for x in 0..size.x {
+                            let pos = Vec3::new(x, y, z);
+                            if let (Ok(before), Ok(after)) = (base.get(pos), vol.get(pos)) {
+                                if before != after {
+                                    blocks.push((pos, *after));
+                                }
+                            }
+                        }
+                    }
+                }
+                chunks.insert(wpos2d, blocks);
+            }
+        }
+
+        BakedSettlement { chunks }
+    }
+
+    pub fn spawn_rules(&self, wpos: Vec2<i32>) -> SpawnRules {
+        SpawnRules {
+            trees: self
+                .land
+                .get_at_block(wpos - self.origin)
+                .plot
+                .map(|p| matches!(p, Plot::Hazard))
+                .unwrap_or(true),
+            ..SpawnRules::default()
+        }
+    }
+
+    /// Whether a street lamp stands on the town column at `wpos2d`, given the
+    /// distance to and nearest point of the closest path, if any. Lamps line
+    /// both sides of paths, with the odd one standing further away or where
+    /// there is no path at all.
+    fn has_street_lamp(&self, wpos2d: Vec2<i32>, path: Option<(f32, Vec2<f32>)>) -> bool {
+        let is_random_lamp = self.lamps.random_chance > 0.0
+            && self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, 0))
+                % (1.0 / self.lamps.random_chance).round().max(1.0) as u32
+                == 0;
+        let (dist, nearest) = if let Some(path) = path {
+            path
+        } else {
+            return is_random_lamp;
+        };
+
+        let path_dir = (nearest - wpos2d.map(|e| e as f32))
+            .rotated_z(f32::consts::PI / 2.0)
+            .normalized();
+        let spacing = self.lamps.spacing;
+        let is_lamp = if path_dir.x.abs() > path_dir.y.abs() {
+            wpos2d.x as f32 % spacing / path_dir.dot(Vec2::unit_y()).abs() <= 1.0
+        } else {
+            (wpos2d.y as f32 + 10.0) % spacing / path_dir.dot(Vec2::unit_x()).abs() <= 1.0
+        };
+
+        (dist > 6.0 && dist < 7.0 && is_lamp) || (dist > 20.0 && is_random_lamp)
+    }
+
+    /// Default chance of a column of open grassy ground being decorated, lush
+    /// in temperate and wet biomes and sparse in arid and cold ones.
+    pub fn decoration_density_for_biome(biome: BiomeKind) -> f32 {
+        match biome {
+            BiomeKind::Desert => 0.01,
+            BiomeKind::Savannah | BiomeKind::Mountain | BiomeKind::Snowland => 0.04,
+            BiomeKind::Forest | BiomeKind::Jungle | BiomeKind::Swamp => 0.12,
+            _ => 0.08,
+        }
+    }
+
+    /// Default tier of a settlement, rising with the danger of its biome.
+    pub fn tier_for_biome(biome: BiomeKind) -> u8 {
+        (biome.difficulty() - 1).clamp(0, Self::MAX_TIER as i32) as u8
+    }
+
+    /// The decoration, if any, on the open grassy column at the given world
+    /// position: mostly grass, with the odd flower or small rock.
+    fn decoration_at(&self, wpos2d: Vec2<i32>) -> Option<SpriteKind> {
+        if !self
+            .noise
+            .chance(Vec3::new(wpos2d.x, wpos2d.y, 66), self.decoration_density)
+        {
+            return None;
+        }
+        Some(
+            match self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, 67)) % 16 {
+                0..=7 => SpriteKind::ShortGrass,
+                8..=11 => SpriteKind::MediumGrass,
+                12 => SpriteKind::RedFlower,
+                13 => SpriteKind::YellowFlower,
+                14 => SpriteKind::WhiteFlower,
+                _ => SpriteKind::Stones2,
+            },
+        )
+    }
+
+    /// The prop, if any, standing on the open town column at the given world
+    /// position. Props are scattered beside structures, but never on paths or
+    /// within the footprint of a structure.
+    fn prop_at(&self, wpos2d: Vec2<i32>, on_path: bool) -> Option<SpriteKind> {
+        const PROP_REACH: i32 = 6;
+
+        if on_path
+            || !self
+                .noise
+                .chance(Vec3::new(wpos2d.x, wpos2d.y, 64), self.prop_density)
+        {
+            return None;
+        }
+
+        let rpos = wpos2d - self.origin;
+        let mut near_structure = false;
+        for bounds in self.structures.iter().map(Structure::bounds_2d) {
+            if bounds.contains_point(rpos) {
+                return None;
+            }
+            near_structure |= Aabr {
+                min: bounds.min - PROP_REACH,
+                max: bounds.max + PROP_REACH,
+            }
+            .contains_point(rpos);
+        }
+
+        near_structure.then(
+            || match self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, 65)) % 3 {
+                0 => SpriteKind::Barrel,
+                1 => SpriteKind::CrateBlock,
+                // Haystack
+                _ => SpriteKind::WheatYellow,
+            },
+        )
+    }
+
+    /// Whether the exposed surface at the given world position is covered in
+    /// snow. Only cold settlements get snow, with bare patches becoming rarer
+    /// with altitude.
+    fn snow_cover(&self, wpos: Vec3<i32>) -> bool {
+        let intensity = (0.8 + (wpos.z as f32 - CONFIG.sea_level) / 2000.0).clamp(0.8, 1.0);
+        self.cold && self.noise.chance(wpos + Vec3::unit_z() * 4096, intensity)
+    }
+
+    pub fn apply_to<'a>(
+        &'a self,
+        index: IndexRef,
+        wpos2d: Vec2<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
+    ) {
+        let region = Aabr {
+            min: wpos2d,
+            max: wpos2d + vol.size_xy().map(|e| e as i32),
+        };
+        self.apply_to_region(index, region, |wpos| get_column(wpos - wpos2d), &mut [(
+            wpos2d, vol,
+        )]);
+    }
+
+    /// Like [`Settlement::apply_to`], but only shape the terrain: plots,
+    /// paths, walls and their sprites.
+    pub fn apply_terrain<'a>(
+        &'a self,
+        index: IndexRef,
+        wpos2d: Vec2<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
+    ) {
+        let region = Aabr {
+            min: wpos2d,
+            max: wpos2d + vol.size_xy().map(|e| e as i32),
+        };
+        self.apply_terrain_to_region(index, region, |wpos| get_column(wpos - wpos2d), &mut [(
+            wpos2d, vol,
+        )]);
+    }
+
+    /// Like [`Settlement::apply_to`], but only place the settlement's
+    /// structures.
+    pub fn apply_structures<'a>(
+        &'a self,
+        index: IndexRef,
+        wpos2d: Vec2<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
+    ) {
+        let region = Aabr {
+            min: wpos2d,
+            max: wpos2d + vol.size_xy().map(|e| e as i32),
+        };
+        self.apply_structures_to_region(index, region, |wpos| get_column(wpos - wpos2d), &mut [(
+            wpos2d, vol,
+        )]);
+    }
+
+    /// Apply the settlement to several volumes at once, each paired with the
+    /// world position of its minimum corner. `region` is the area, in world
+    /// coordinates, covered by the volumes and `get_column` samples the
+    /// terrain column at a world position. Structures that straddle the
+    /// borders between volumes are drawn once, across all of them, rather than
+    /// once per volume.
+    pub fn apply_to_region<'a, V: RectSizedVol<Vox = Block> + ReadVol + WriteVol>(
+        &'a self,
+        index: IndexRef,
+        region: Aabr<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vols: &mut [(Vec2<i32>, &mut V)],
+    ) {
+        self.apply_terrain_to_region(index, region, &mut get_column, vols);
+        self.apply_structures_to_region(index, region, &mut get_column, vols);
+    }
+
+    /// The terrain phase of [`Settlement::apply_to_region`].
+    pub fn apply_terrain_to_region<'a, V: RectSizedVol<Vox = Block> + ReadVol + WriteVol>(
+        &'a self,
+        index: IndexRef,
+        region: Aabr<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vols: &mut [(Vec2<i32>, &mut V)],
+    ) {
+        let colors = &index.colors.site.settlement;
+
+        for y in region.min.y..region.max.y {
+            for x in region.min.x..region.max.x {
+                let wpos2d = Vec2::new(x, y);
+                let (vol_min, vol) = if let Some(vol) = vol_at(vols, wpos2d) {
+                    vol
+                } else {
+                    continue;
+                };
+                let col_sample = if let Some(col_sample) = get_column(wpos2d) {
+                    col_sample
+                } else {
+                    continue;
+                };
+
+                self.apply_column(
+                    colors,
+                    wpos2d,
+                    wpos2d - vol_min,
+                    col_sample,
+                    &mut get_column,
+                    vol,
+                );
+            }
+        }
+    }
+
+    /// The structure phase of [`Settlement::apply_to_region`].
+    pub fn apply_structures_to_region<'a, V: RectSizedVol<Vox = Block> + ReadVol + WriteVol>(
+        &'a self,
+        index: IndexRef,
+        region: Aabr<i32>,
+        get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vols: &mut [(Vec2<i32>, &mut V)],
+    ) {
+        self.apply_structures_within(index, region, i32::MIN..i32::MAX, get_column, vols);
+    }
+
+    /// Like [`Settlement::apply_structures_to_region`], but skip structures
+    /// that don't reach into `z_range`, the altitudes covered by the volumes.
+    /// Terrain chunks grow to fit whatever is written to them, so they have no
+    /// z-range of their own to cull against and it is up to the caller to
+    /// provide one.
+    pub fn apply_structures_within<'a, V: RectSizedVol<Vox = Block> + ReadVol + WriteVol>(
+        &'a self,
+        index: IndexRef,
+        region: Aabr<i32>,
+        z_range: Range<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vols: &mut [(Vec2<i32>, &mut V)],
+    ) {
+        let colors = &index.colors.site.settlement;
+
+        for structure in self.structures_in_region(region) {
+            // The eaves of a house don't reach into the houses it shares a
+            // wall with
+            let neighbours = self.party_wall_neighbours(structure);
+
+            structure.apply_dressed(
+                index,
+                self.origin,
+                z_range.clone(),
+                &mut get_column,
+                vols,
+                |rpos| {
+                    !neighbours.iter().any(|core| {
+                        rpos.map2(core.min, |e, min| e > min).reduce_and()
+                            && rpos.map2(core.max, |e, max| e < max).reduce_and()
+                    })
+                },
+                |offs| self.foundation_block(colors, offs),
+                |wpos| {
+                    self.snow_cover(wpos)
+                        .then(|| Block::new(BlockKind::ArtSnow, colors.plot_snow.into()))
+                },
+            );
+        }
+    }
+
+    /// The areas within the walls of the houses that `structure` shares a wall
+    /// with, see [`SettlementBuilder::clustering`].
+    fn party_wall_neighbours(&self, structure: &Structure) -> Vec<Aabr<i32>> {
+        match structure.house_core() {
+            Some(core) if self.clustering > 0.0 => self
+                .structures
+                .iter()
+                .filter_map(|other| other.house_core())
+                .filter(|other| shares_wall(core, *other))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether two structures are too close to both stand. Houses that share
+    /// a wall in a clustered town may stand closer than their bounds allow,
+    /// their eaves meeting over the wall.
+    fn structures_collide(&self, a: &Structure, b: &Structure) -> bool {
+        match (a.house_core(), b.house_core()) {
+            (Some(a), Some(b)) if self.clustering > 0.0 && shares_wall(a, b) => false,
+            _ => a.bounds_2d().collides_with_aabr(b.bounds_2d()),
+        }
+    }
+
+    /// Every structure whose footprint overlaps the chunk with its minimum
+    /// corner at the world position `chunk_wpos`, in the order they're
+    /// placed in: exactly those that [`Settlement::apply_to`] applies to the
+    /// chunk.
+    pub fn structures_in_chunk(&self, chunk_wpos: Vec2<i32>) -> Vec<&Structure> {
+        self.structures_in_region(Aabr {
+            min: chunk_wpos,
+            max: chunk_wpos + TerrainChunkSize::RECT_SIZE.map(|e| e as i32),
+        })
+        .collect()
+    }
+
+    /// The structures whose footprint overlaps `region`, in world coordinates.
+    fn structures_in_region(&self, region: Aabr<i32>) -> impl Iterator<Item = &Structure> {
+        let region = Aabr {
+            min: region.min - self.origin,
+            max: region.max - self.origin,
+        };
+        self.structures
+            .iter()
+            .filter(move |structure| structure.bounds_2d().collides_with_aabr(region))
+    }
+
+    /// The block of the foundations beneath a structure, at `offs` from the
+    /// minimum corner of its bounds. `None` between stilts.
+    fn foundation_block(&self, colors: &Colors, offs: Vec2<i32>) -> Option<Block> {
+        match self.foundation {
+            FoundationStyle::Earth => Some(Block::new(BlockKind::Earth, colors.plot_dirt.into())),
+            FoundationStyle::Stone => {
+                Some(Block::new(BlockKind::Rock, colors.foundation_stone.into()))
+            },
+            FoundationStyle::Stilts => (offs.map(|e| e.rem_euclid(STILT_SPACING)) == Vec2::zero())
+                .then(|| Block::new(BlockKind::Wood, colors.stilts.into())),
+        }
+    }
+
+    /// Find the downhill direction of each field from the altitude `alt`,
+    /// averaging the gradient across the field's tiles. Fields on flat ground
+    /// are left out.
+    fn place_field_slopes(&mut self, alt: impl Fn(Vec2<i32>) -> Option<f32>) {
+        let half = AREA_SIZE as i32 / 2;
+        let mut gradients =
+            HashMap::<Vec2<i32>, Vec2<f32>, BuildHasherDefault<FxHasher64>>::default();
+        for (pos, tile) in self.land.tiles.iter() {
+            if let Plot::Field { center, .. } = self.land.plot(tile.plot) {
+                let wpos = self.origin + *pos * AREA_SIZE as i32 + half;
+                let slope =
+                    |axis: Vec2<i32>| Some(alt(wpos + axis * half)? - alt(wpos - axis * half)?);
+                if let (Some(x), Some(y)) = (slope(Vec2::unit_x()), slope(Vec2::unit_y())) {
+                    *gradients.entry(*center).or_default() += Vec2::new(x, y);
+                }
+            }
+        }
+        self.field_slopes = gradients
+            .into_iter()
+            .filter(|(_, gradient)| gradient.magnitude_squared() > 0.01)
+            .map(|(center, gradient)| (center, -gradient.normalized()))
+            .collect();
+    }
+
+    /// Whether `pos` lies within one of the crop rows of the field grown from
+    /// `center`, following its contours if it has a known slope.
+    fn in_field_furrow(&self, pos: Vec2<i32>, seed: u32, crop: Crop, center: Vec2<i32>) -> bool {
+        match self.field_slopes.get(&center) {
+            Some(downhill) => in_contour_furrow(pos, *downhill, crop),
+            None => in_furrow(pos, seed, crop),
+        }
+    }
+
+    /// Smooth the altitude of each town tile, as given at its center by `alt`,
+    /// by averaging it with the town tiles around it.
+    fn level_town(&mut self, alt: impl Fn(Vec2<i32>) -> Option<f32>) {
+        let raw = self
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(self.land.plot(tile.plot), Plot::Town { .. }))
+            .filter_map(|(pos, _)| {
+                alt(self.origin + *pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2)
+                    .map(|alt| (*pos, alt))
+            })
+            .collect::<HashMap<_, _, BuildHasherDefault<FxHasher64>>>();
+        self.town_alts = raw
+            .keys()
+            .map(|tile| {
+                let (sum, count) = (-LEVEL_RADIUS..LEVEL_RADIUS + 1)
+                    .flat_map(|x| (-LEVEL_RADIUS..LEVEL_RADIUS + 1).map(move |y| Vec2::new(x, y)))
+                    .filter_map(|offs| raw.get(&(*tile + offs)))
+                    .fold((0.0, 0), |(sum, count), alt| (sum + alt, count + 1));
+                (*tile, sum / count as f32)
+            })
+            .collect();
+    }
+
+    /// The altitude at `rpos`, relative to the origin, after leveling the
+    /// town, where `alt` is the natural altitude. The leveled altitudes of the
+    /// surrounding tile centers are blended bilinearly, so that the town's
+    /// ground meets the natural terrain smoothly at its edges.
+    fn leveled_alt(&self, rpos: Vec2<i32>, alt: f32) -> f32 {
+        if self.town_alts.is_empty() {
+            return alt;
+        }
+        let tile_pos = (rpos - AREA_SIZE as i32 / 2).map(|e| e as f32 / AREA_SIZE as f32);
+        let min = tile_pos.map(|e| e.floor() as i32);
+        let frac = tile_pos - min.map(|e| e as f32);
+        let at = |tile: Vec2<i32>| {
+            self.town_alts
+                .get(&tile)
+                .map_or(alt, |level| Lerp::lerp(alt, *level, self.leveling))
+        };
+        Lerp::lerp(
+            Lerp::lerp(at(min), at(min + Vec2::unit_x()), frac.x),
+            Lerp::lerp(at(min + Vec2::unit_y()), at(min + Vec2::one()), frac.x),
+            frac.y,
+        )
+    }
+
+    /// The height that a terraced field column at `wpos2d` is flattened to,
+    /// and whether it forms the retaining edge of its terrace above a lower
+    /// one. `None` where the ground is gentle enough to farm as it is.
+    fn terrace_at<'a>(
+        &self,
+        wpos2d: Vec2<i32>,
+        get_column: &mut impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+    ) -> Option<(i32, bool)> {
+        let mut alt = |wpos| get_column(wpos).map(|col| col.riverless_alt);
+        let here = alt(wpos2d)?;
+        let neighbors = CARDINALS
+            .iter()
+            .filter_map(|dir| alt(wpos2d + *dir))
+            .collect::<Vec<_>>();
+
+        let slope = neighbors
+            .iter()
+            .map(|neighbor| (neighbor - here).abs())
+            .fold(0.0, f32::max);
+        if slope < TERRACE_MIN_SLOPE {
+            return None;
+        }
+
+        let level = |alt: f32| (alt / TERRACE_HEIGHT as f32).floor() as i32 * TERRACE_HEIGHT;
+        let edge = neighbors
+            .iter()
+            .any(|neighbor| level(*neighbor) < level(here));
+        Some((level(here), edge))
+    }
+
+    /// The height of the ground in the column at `wpos2d` once the settlement
+    /// is applied to it, where `land_surface_z` is its natural height and
+    /// `sample` its sample of the settlement, and whether the column forms the
+    /// retaining edge of a terrace.
+    fn surface_z<'a>(
+        &self,
+        wpos2d: Vec2<i32>,
+        land_surface_z: i32,
+        sample: &Sample,
+        get_column: &mut impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+    ) -> (i32, bool) {
+        let rpos = wpos2d - self.origin;
+        let mut surface_z = land_surface_z;
+
+        // District alt
+        if let Some(Plot::Town { district }) = sample.plot {
+            if let Some(d) = district
+                .and_then(|d| self.town.as_ref().map(|t| t.districts().get(d)))
+                .filter(|_| false)
+            // Temporary
+            {
+                let other = self
+                    .land
+                    .plot_at(sample.second_closest)
+                    .and_then(|p| match p {
+                        Plot::Town { district } => *district,
+                        _ => None,
+                    })
+                    .and_then(|d| self.town.as_ref().map(|t| t.districts().get(d).alt as f32))
+                    .filter(|_| false)
+                    .unwrap_or(surface_z as f32);
+                surface_z = Lerp::lerp(
+                    (other + d.alt as f32) / 2.0,
+                    d.alt as f32,
+                    (1.25 * sample.edge_dist / (d.alt as f32 - other).abs()).min(1.0),
+                ) as i32;
+            }
+        }
+
+        // The town's ground is leveled
+        surface_z = self.leveled_alt(rpos, surface_z as f32).round() as i32;
+
+        // Terraces flatten steep fields into steps
+        let terrace = if self.terraced_fields && matches!(sample.plot, Some(Plot::Field { .. })) {
+            self.terrace_at(wpos2d, get_column)
+        } else {
+            None
+        };
+        let retaining = terrace.map_or(false, |(_, edge)| edge);
+        if let Some((level, _)) = terrace {
+            surface_z = level;
+        }
+
+        // Reserved plots are flat, ready for whatever is placed on them
+        if let Some(level) = self
+            .land
+            .tile_at(sample.closest)
+            .and_then(|t| self.reserved_levels.get(&t.plot))
+        {
+            surface_z = *level;
+        }
+
+        // Water fills to its plot's level, wherever the ground lies
+        if matches!(sample.plot, Some(Plot::Water)) {
+            if let Some(level) = self.water_level(sample.closest) {
+                // The surface of the water is two blocks below the column's
+                // surface, see `water_block`
+                surface_z = level + 2;
+            }
+        }
+
+        (surface_z, retaining)
+    }
+
+    /// Whether [`Settlement::apply_column`] reshapes the ground of a column,
+    /// given its settlement and terrain samples: wherever the settlement has a
+    /// plot other than a hazard, except near water and on the world's paths.
+    fn reshapes_column(sample: &Sample, col_sample: &ColumnSample) -> bool {
+        !matches!(sample.plot, None | Some(Plot::Hazard))
+            && col_sample.water_dist.map_or(true, |dist| dist > 2.0)
+            && !col_sample
+                .path
+                .map_or(false, |(dist, _, path, _)| dist < path.width)
+    }
+
+    /// Apply the plot, ways and towers of the settlement to a single column,
+    /// found at `offs` within `vol`.
+    fn apply_column<'a>(
+        &self,
+        colors: &Colors,
+        wpos2d: Vec2<i32>,
+        offs: Vec2<i32>,
+        col_sample: &ColumnSample,
+        get_column: &mut impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        vol: &mut (impl RectSizedVol<Vox = Block> + ReadVol + WriteVol),
+    ) {
+        let rpos = wpos2d - self.origin;
+        let land_surface_z = col_sample.riverless_alt.floor() as i32;
+
+        // Sample settlement
+        let sample = self.land.get_at_block(rpos);
+
+        let noisy_color = move |col: Rgb<u8>, factor: u32| {
+            let nz = self
+                .noise
+                .get(Vec3::new(wpos2d.x, wpos2d.y, land_surface_z));
+            col.map(|e| {
+                (e as u32 + nz % (factor * 2))
+                    .saturating_sub(factor)
+                    .min(255) as u8
+            })
+        };
+
+        let (surface_z, retaining) = self.surface_z(wpos2d, land_surface_z, &sample, get_column);
+
+        {
+            let mut surface_sprite = None;
+
+            let roll = |seed, n| self.noise.get(Vec3::new(wpos2d.x, wpos2d.y, seed * 5)) % n;
+
+            let color = match sample.plot {
+                Some(Plot::Dirt | Plot::Reserved) => Some(colors.plot_dirt.into()),
+                Some(Plot::Grass) => {
+                    if sample.way.is_none() {
+                        surface_sprite = self.decoration_at(wpos2d);
+                    }
+                    Some(colors.plot_grass.into())
+                },
+                Some(Plot::Pasture) => {
+                    surface_sprite = if roll(0, 3) == 0 {
+                        Some(SpriteKind::LongGrass)
+                    } else if roll(1, 150) == 0 {
+                        // Hay left out for the herds
+                        Some(SpriteKind::WheatYellow)
+                    } else if roll(2, 800) == 0 {
+                        // Feed barrels
+                        Some(SpriteKind::Barrel)
+                    } else {
+                        None
+                    };
+                    Some(colors.plot_pasture.into())
+                },
+                Some(Plot::Orchard { .. }) => {
+                    if roll(0, 8) == 0 {
+                        surface_sprite = Some(SpriteKind::ShortGrass);
+                    }
+                    Some(colors.plot_grass.into())
+                },
+                Some(Plot::Water) => Some(colors.plot_water.into()),
+                //Some(Plot::Town { district }) => None,
+                Some(Plot::Town { .. }) => {
+                    if self.has_street_lamp(
+                        wpos2d,
+                        col_sample.path.map(|(dist, nearest, _, _)| (dist, nearest)),
+                    ) {
+                        surface_sprite = Some(SpriteKind::StreetLamp);
+                    } else {
+                        let on_path = sample.way.is_some()
+                            || col_sample
+                                .path
+                                .map_or(false, |(dist, _, path, _)| dist < path.width + 1.0);
+                        surface_sprite = self.prop_at(wpos2d, on_path);
+                    }
+
+                    Some(
+                        Rgb::from(colors.plot_town_path).map2(Rgb::iota(), |e: u8, _i: i32| {
+                            e.saturating_add(0_u8).saturating_sub(8)
+                        }),
+                    )
+                },
+                Some(Plot::Field { .. }) if retaining => Some(colors.plot_field_terrace.into()),
+                Some(Plot::Field {
+                    seed, crop, center, ..
+                }) => {
+                    let in_furrow = self.in_field_furrow(wpos2d, *seed, *crop, *center);
+
+                    let dirt = Rgb::<u8>::from(colors.plot_field_dirt).map(|e| {
+                        e + (self.noise.get(Vec3::broadcast((seed % 4096 + 0) as i32)) % 32) as u8
+                    });
+                    let mound = Rgb::<u8>::from(colors.plot_field_mound)
+                        .map(|e| e + roll(0, 8) as u8)
+                        .map(|e| {
+                            e + (self.noise.get(Vec3::broadcast((seed % 4096 + 1) as i32)) % 32)
+                                as u8
+                        });
+
+                    if in_furrow {
+                        if self
+                            .noise
+                            .chance(Vec3::new(wpos2d.x, wpos2d.y, 0), crop.planting_density())
+                        {
+                            surface_sprite = Some(match crop {
+                                Crop::Corn => SpriteKind::Corn,
+                                Crop::Wheat if roll(1, 2) == 0 => SpriteKind::WheatYellow,
+                                Crop::Wheat => SpriteKind::WheatGreen,
+                                Crop::Cabbage => SpriteKind::Cabbage,
+                                Crop::Pumpkin => SpriteKind::Pumpkin,
+                                Crop::Flax => SpriteKind::Flax,
+                                Crop::Carrot => SpriteKind::Carrot,
+                                Crop::Tomato => SpriteKind::Tomato,
+                                Crop::Radish => SpriteKind::Radish,
+                                Crop::Turnip => SpriteKind::Turnip,
+                                Crop::Sunflower => SpriteKind::Sunflower,
+                            });
+                        } else if roll(9, 2000) == 0 {
+                            surface_sprite = Some(SpriteKind::Scarecrow);
+                        }
+                    } else if sample.way.is_none() {
+                        surface_sprite = self.decoration_at(wpos2d);
+                    }
+
+                    // Every field has a scarecrow watching over it
+                    if rpos == scarecrow_pos(*center) {
+                        surface_sprite = Some(SpriteKind::Scarecrow);
+                    }
+
+                    Some(if in_furrow { dirt } else { mound })
+                },
+                _ => None,
+            };
+
+            if let Some(color) = color {
+                if Self::reshapes_column(&sample, col_sample) {
+                    let diff = (surface_z - land_surface_z).abs();
+                    // Paths are trodden clear of snow
+                    let trodden = matches!(sample.way, Some((WayKind::Path | WayKind::Ford, _, _)));
+                    let basin_depth = matches!(sample.plot, Some(Plot::Water)).then(|| {
+                        // Fords are shallow enough to wade across
+                        if matches!(sample.way, Some((WayKind::Ford, _, _))) {
+                            FORD_DEPTH
+                        } else {
+                            water_depth(sample.edge_dist)
+                        }
+                    });
+
+                    for z in -8 - diff..8 + diff {
+                        let pos = Vec3::new(offs.x, offs.y, surface_z + z);
+                        let block = if let Ok(&block) = vol.get(pos) {
+                            // TODO: Figure out whether extra filters are needed.
+                            block
+                        } else {
+                            break;
+                        };
+
+                        if let Some(depth) = basin_depth {
+                            let _ = vol.set(pos, water_block(z, depth, noisy_color(color, 4)));
+                        } else if let (0, Some(sprite)) = (z, surface_sprite) {
+                            let _ = vol.set(
+                                pos,
+                                // TODO: Make more principled.
+                                if block.is_fluid() {
+                                    block.with_sprite(sprite)
+                                } else {
+                                    Block::air(sprite)
+                                },
+                            );
+                        } else if z >= 0 {
+                            if [
+                                BlockKind::Air,
+                                BlockKind::Grass,
+                                BlockKind::Earth,
+                                BlockKind::Sand,
+                                BlockKind::Snow,
+                                BlockKind::Rock,
+                            ]
+                            .contains(&block.kind())
+                            {
+                                let _ = vol.set(pos, Block::air(SpriteKind::Empty));
+                            }
+                        } else if z == -1
+                            && !trodden
+                            && self.snow_cover(Vec3::new(wpos2d.x, wpos2d.y, surface_z + z))
+                        {
+                            let _ = vol.set(
+                                pos,
+                                Block::new(
+                                    BlockKind::ArtSnow,
+                                    noisy_color(colors.plot_snow.into(), 4),
+                                ),
+                            );
+                        } else {
+                            let kind = if retaining {
+                                BlockKind::Rock
+                            } else {
+                                BlockKind::Earth
+                            };
+                            let _ = vol.set(pos, Block::new(kind, noisy_color(color, 4)));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Orchard trees, planted in rows and standing on the ground at their
+        // trunks so that their canopies stay level on slopes
+        if let Some(Plot::Orchard { species }) = sample.plot {
+            let tree = orchard_tree_pos(rpos);
+            let tree_sample = self.land.get_at_block(tree);
+            if tree_sample.plot == sample.plot && tree_sample.way.is_none() {
+                if let Some(tree_z) =
+                    get_column(self.origin + tree).map(|col| col.riverless_alt.floor() as i32)
+                {
+                    for z in 0..species.height() {
+                        if let Some(block) = species.block(colors, rpos - tree, z) {
+                            let _ = vol.set(Vec3::new(offs.x, offs.y, tree_z + z), block);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Walls
+        if let Some((WayKind::Wall, dist, _)) = sample.way {
+            // Richer settlements build their walls of stone
+            let (kind, low, high) = if self.tier >= STONE_WALL_TIER {
+                (
+                    BlockKind::Rock,
+                    colors.stone_wall_low,
+                    colors.stone_wall_high,
+                )
+            } else {
+                (BlockKind::Wood, colors.wall_low, colors.wall_high)
+            };
+            let color = Lerp::lerp(
+                Rgb::<u8>::from(low).map(i32::from),
+                Rgb::<u8>::from(high).map(i32::from),
+                (RandomField::new(0).get(wpos2d.into()) % 256) as f32 / 256.0,
+            )
+            .map(|e| (e % 256) as u8);
+
+            let z_offset = if let Some(water_dist) = col_sample.water_dist {
+                // Water gate
+                ((water_dist.max(0.0) * 0.45).min(f32::consts::PI).cos() + 1.0) * 4.0
+            } else {
+                0.0
+            } as i32;
+
+            // On steep ground, reach down to the top of lower neighbouring
+            // wall columns so that there are no gaps between them
+            let bottom_z = CARDINALS
+                .iter()
+                .map(|dir| wpos2d + *dir)
+                .filter(|npos| {
+                    matches!(
+                        self.land.get_at_block(*npos - self.origin).way,
+                        Some((WayKind::Wall, _, _))
+                    )
+                })
+                .filter_map(|npos| get_column(npos))
+                .map(|col| col.riverless_alt.floor() as i32)
+                .filter(|neighbor_z| *neighbor_z < surface_z)
+                .map(|neighbor_z| neighbor_z + WALL_WALK_Z - 1 - surface_z)
+                .fold(z_offset, i32::min);
+
+            // Where the wall is raised over water, build it as an iron grate
+            // so that the span reads as a gate rather than a collapsed wall
+            let block = if z_offset > 0 {
+                Block::new(BlockKind::Rock, colors.water_gate.into())
+            } else {
+                Block::new(kind, color)
+            };
+
+            // Alternate raised merlons along the parapet
+            let merlon = (wpos2d.x + wpos2d.y).div_euclid(2).rem_euclid(2) == 0;
+            for z in bottom_z..WALL_WALK_Z + 2 {
+                if wall_is_solid(dist, z, merlon) {
+                    let _ = vol.set(Vec3::new(offs.x, offs.y, surface_z + z), block);
+                }
+            }
+
+            // Dig a foundation down through any dip in the ground beneath the
+            // wall, so that it doesn't float
+            if wall_is_solid(dist, bottom_z, merlon) {
+                for z in (bottom_z - WALL_FOUNDATION_DEPTH..bottom_z).rev() {
+                    let pos = Vec3::new(offs.x, offs.y, surface_z + z);
+                    match vol.get(pos) {
+                        Ok(block) if block.kind() == BlockKind::Air => {
+                            let _ =
+                                vol.set(pos, Block::new(BlockKind::Rock, colors.wall_low.into()));
+                        },
+                        _ => break,
+                    }
+                }
+            }
+        }
+
+        // Bridges carry their way on a wooden deck, with a railing along each
+        // edge and lamps at either end
+        if let Some((WayKind::Bridge, dist, _)) = sample.way {
+            let deck = Block::new(BlockKind::Wood, colors.bridge_deck.into());
+            let railing = Block::new(BlockKind::Wood, colors.bridge_railing.into());
+            let _ = vol.set(Vec3::new(offs.x, offs.y, surface_z - 1), deck);
+            for z in 0..BRIDGE_CLEARANCE {
+                let _ = vol.set(
+                    Vec3::new(offs.x, offs.y, surface_z + z),
+                    Block::air(SpriteKind::Empty),
+                );
+            }
+
+            if dist >= WayKind::Bridge.width() - 1.0 {
+                let is_end = sample.way_along < 1.0
+                    && self.land.tile_at(sample.closest).map_or(false, |tile| {
+                        tile.ways
+                            .iter()
+                            .filter(|way| **way == Some(WayKind::Bridge))
+                            .count()
+                            == 1
+                    });
+                let is_post = sample.way_along % self.lamps.railing_spacing < 1.0;
+                let pos = Vec3::new(offs.x, offs.y, surface_z);
+                if is_end {
+                    let _ = vol.set(pos, Block::air(SpriteKind::StreetLamp));
+                } else {
+                    if is_post {
+                        let _ = vol.set(pos, railing);
+                    }
+                    let _ = vol.set(pos + Vec3::unit_z(), railing);
+                }
+            }
+        }
+
+        // Gates are cleared while open and filled in while closed, so that
+        // applying the terrain again shows their current state
+        if let Some(gate) = self.gate_at(rpos) {
+            for z in 0..GATE_HEIGHT {
+                let block = if gate.open {
+                    Block::air(SpriteKind::Empty)
+                } else {
+                    gate.kind.closed_block(colors, wpos2d, z)
+                };
+                let _ = vol.set(Vec3::new(offs.x, offs.y, surface_z + z), block);
+            }
+        }
+
+        // Towers
+        if let Some((Tower::Wall, _pos)) = sample.tower {
+            for z in -2..16 {
+                let _ = vol.set(
+                    Vec3::new(offs.x, offs.y, surface_z + z),
+                    Block::new(BlockKind::Rock, colors.tower_color.into()),
+                );
+            }
+        }
+    }
+
+    pub fn apply_supplement<'a>(
+        &'a self,
+        // NOTE: Used only for dynamic elements like chests and entities!
+        dynamic_rng: &mut impl Rng,
+        wpos2d: Vec2<i32>,
+        get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        supplement: &mut ChunkSupplement,
+        economy: SiteInformation,
+        time: Option<&(TimeOfDay, Calendar)>,
+        spawn_density_scale: f32,
+    ) {
+        for entity in self.sample_entities(wpos2d, get_column, &economy, time, spawn_density_scale)
+        {
+            supplement.add_entity(entity);
+        }
+
+        let chunk = Aabr {
+            min: wpos2d - self.origin,
+            max: wpos2d - self.origin + TerrainChunkSize::RECT_SIZE.map(|e| e as i32),
+        };
+        for structure in &self.structures {
+            if let StructureKind::Custom(custom) = &structure.kind {
+                if custom.bounds_2d().collides_with_aabr(chunk) {
+                    custom.apply_supplement(dynamic_rng, self.origin, wpos2d, supplement);
+                }
+            }
+        }
+    }
+
+    /// The items a townsperson of the given kind starts out carrying, as
+    /// amounts of item assets, when starting inventories are enabled (see
+    /// [`SettlementBuilder::starting_inventories`]). Villagers work the
+    /// settlement's fields and carry some of the produce of one of its crops,
+    /// while merchants carry a little of every crop grown there. Both have a
+    /// few coins. Everyone else carries nothing extra. The contents depend
+    /// only on `seed`.
+    fn starting_inventory(&self, kind: Townsfolk, seed: u32) -> Vec<(u32, &'static str)> {
+        const COINS: &str = "common.items.utility.coins";
+
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(seed));
+        let mut crops = self.total_yield().keys().copied().collect::<Vec<_>>();
+        crops.sort_by_key(|crop| *crop as u8);
+
+        let mut items = Vec::new();
+        let coins = match kind {
+            Townsfolk::Villager => {
+                if let Some(crop) = crops.choose(&mut rng) {
+                    items.push((rng.gen_range(2..6), crop.item_asset()));
+                }
+                rng.gen_range(1..10)
+            },
+            Townsfolk::Merchant => {
+                for crop in crops {
+                    items.push((rng.gen_range(1..4), crop.item_asset()));
+                }
+                rng.gen_range(20..50)
+            },
+            Townsfolk::Guard | Townsfolk::Animal | Townsfolk::Bird => return items,
+        };
+        items.push((coins, COINS));
+        items
+    }
+
+    /// Give a townsperson spawned at `wpos2d` its starting inventory, if the
+    /// settlement hands them out. The inventory is seeded by position.
+    fn with_starting_inventory(
+        &self,
+        mut entity: EntityInfo,
+        kind: Townsfolk,
+        wpos2d: Vec2<i32>,
+    ) -> EntityInfo {
+        if self.starting_inventories {
+            let seed =
+                RandomField::new(self.use_seed(SeedUse::StartingInventory)).get(Vec3::from(wpos2d));
+            entity.inventory.extend(
+                self.starting_inventory(kind, seed)
+                    .into_iter()
+                    .map(|(amount, asset)| (amount, Item::new_from_asset_expect(asset))),
+            );
+        }
+        entity
+    }
+
+    /// The entities spawned by [`Settlement::apply_supplement`] in the chunk
+    /// whose minimum corner is at `wpos2d`, excluding those of custom
+    /// structures. Every choice is seeded by position, so sampling the same
+    /// chunk always gives the same entities.
+    ///
+    /// `spawn_density_scale` multiplies the chance of every spawn, from 0.0
+    /// for none to 1.0 for the settlement's full population. Since the chances
+    /// are compared against the same positional noise, the spawns at a lower
+    /// scale are a subset of those at a higher one.
+    pub fn sample_entities<'a>(
+        &'a self,
+        wpos2d: Vec2<i32>,
+        mut get_column: impl FnMut(Vec2<i32>) -> Option<&'a ColumnSample<'a>>,
+        economy: &SiteInformation,
+        time: Option<&(TimeOfDay, Calendar)>,
+        spawn_density_scale: f32,
+    ) -> Vec<EntityInfo> {
+        let mut entities = Vec::new();
+        // let economy: HashMap<Good, (f32, f32)> = SiteInformation::economy
+        //     .values
+        //     .iter()
+        //     .map(|(g, v)| {
+        //         (
+        //             g,
+        //             (
+        //                 v.unwrap_or(Economy::MINIMUM_PRICE),
+        //                 economy.stocks[g] + economy.surplus[g],
+        //             ),
+        //         )
+        //     })
+        //     .collect();
+        let period = time.map(|(time_of_day, _)| DayPeriod::from(time_of_day.0));
+        let spawn_chance = RandomField::new(self.use_seed(SeedUse::SpawnChance));
+        let spawn_seed = RandomField::new(self.use_seed(SeedUse::Spawn));
+        // Guards spawn in their barracks, where there are any
+        let has_barracks = self.bunks().next().is_some();
+
+        for y in 0..TerrainChunkSize::RECT_SIZE.y as i32 {
+            for x in 0..TerrainChunkSize::RECT_SIZE.x as i32 {
+                let offs = Vec2::new(x, y);
+
+                let wpos2d = wpos2d + offs;
+                let rpos = wpos2d - self.origin;
+
+                // Sample terrain
+                let col_sample = if let Some(col_sample) = get_column(offs) {
+                    col_sample
+                } else {
+                    continue;
+                };
+
+                let sample = self.land.get_at_block(rpos);
+
+                let entity_wpos = Vec3::new(wpos2d.x as f32, wpos2d.y as f32, col_sample.alt + 3.0);
+                // Seeded by position, so that spawns don't depend on the order in
+                // which chunks are populated
+                let spawn_rng = || {
+                    ChaChaRng::from_seed(seed_expan::rng_state(spawn_seed.get(Vec3::from(wpos2d))))
+                };
+
+                let is_town = matches!(sample.plot, Some(Plot::Town { .. }))
+                    && spawn_chance
+                        .chance(Vec3::from(wpos2d), TOWN_SPAWN_CHANCE * spawn_density_scale);
+                let is_pasture = matches!(sample.plot, Some(Plot::Pasture))
+                    && spawn_chance.chance(
+                        Vec3::from(wpos2d),
+                        PASTURE_SPAWN_CHANCE * spawn_density_scale,
+                    );
+
+                if let (Some(policy), Some(plot), true) =
+                    (&self.spawn_policy, sample.plot, is_town || is_pasture)
+                {
+                    entities.extend(policy.entity_for(&SpawnContext {
+                        plot,
+                        wpos: entity_wpos,
+                        settlement_name: &self.name,
+                        settlement_origin: self.origin,
+                        period,
+                        economy,
+                        time,
+                        seed: spawn_seed.get(Vec3::from(wpos2d)),
+                    }));
+                } else if is_town {
+                    let mut rng = spawn_rng();
+                    let is_dummy = RandomField::new(self.use_seed(SeedUse::Dummy))
+                        .chance(Vec3::from(wpos2d), DUMMY_CHANCE);
+                    let entity = if is_dummy {
+                        EntityInfo::at(entity_wpos)
+                            .with_agency(false)
+                            .with_asset_expect("common.entity.village.dummy", &mut rng, time)
+                    } else {
+                        match Townsfolk::pick(period, &mut rng) {
+                            Some(Townsfolk::Animal) => barnyard(entity_wpos, &mut rng),
+                            Some(Townsfolk::Bird) => bird(entity_wpos, &mut rng),
+                            Some(Townsfolk::Guard) if has_barracks => continue,
+                            // Raiders grow stronger with the settlement's tier
+                            Some(_) if self.faction == Faction::Hostile => self
+                                .with_starting_inventory(
+                                    EntityInfo::at(entity_wpos)
+                                        .with_asset_expect(self.raider_entity(), &mut rng, time)
+                                        .with_alignment(self.faction.alignment()),
+                                    Townsfolk::Villager,
+                                    wpos2d,
+                                ),
+                            Some(kind) => self.with_starting_inventory(
+                                humanoid(kind, entity_wpos, economy, &mut rng, time),
+                                kind,
+                                wpos2d,
+                            ),
+                            None => continue,
+                        }
+                    };
+
+                    entities.push(entity);
+                } else if is_pasture {
+                    entities.push(grazer(entity_wpos, &mut spawn_rng()));
+                }
+            }
+        }
+
+        // The chunk, relative to the settlement's origin
+        let chunk = Aabr {
+            min: wpos2d - self.origin,
+            max: wpos2d - self.origin + TerrainChunkSize::RECT_SIZE.map(|e| e as i32) - 1,
+        };
+
+        // Guards in their bunks
+        if self.spawn_policy.is_none() {
+            for bunk in self.bunks().filter(|bunk| chunk.contains_point(bunk.xy())) {
+                let wpos = Vec3::from(self.origin) + bunk;
+                if !RandomField::new(self.use_seed(SeedUse::BunkChance))
+                    .chance(wpos, BUNK_GUARD_CHANCE * spawn_density_scale)
+                {
+                    continue;
+                }
+                let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(
+                    RandomField::new(self.use_seed(SeedUse::BunkGuard)).get(wpos),
+                ));
+                entities.push(humanoid(
+                    Townsfolk::Guard,
+                    // On the lower bed, beneath the plank of the one above
+                    wpos.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0),
+                    economy,
+                    &mut rng,
+                    time,
+                ));
+            }
+        }
+
+        // The keep's treasury and the crypt's grave goods, which are there
+        // whatever the population, unless spawns are replaced or turned off.
+        // They are objects rather than sprites so that their loot can suit the
+        // settlement's tier.
+        if self.spawn_policy.is_none() && spawn_density_scale > 0.0 {
+            for structure in &self.structures {
+                let chests = match &structure.kind {
+                    StructureKind::Keep(keep) => vec![keep.treasury()],
+                    StructureKind::Crypt(crypt) => crypt.loot(),
+                    _ => continue,
+                };
+                for pos in chests
+                    .into_iter()
+                    .filter(|pos| chunk.contains_point(pos.xy()))
+                {
+                    let wpos = Vec3::from(self.origin) + pos;
+                    entities.push(
+                        EntityInfo::at(wpos.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0))
+                            .with_body(comp::Body::Object(comp::object::Body::Chest))
+                            .with_loot_drop(LootSpec::LootTable(self.chest_loot().into()))
+                            .with_agency(false),
+                    );
+                }
+            }
+        }
+
+        // House furniture, which isn't thinned out with the population
+        if self.furniture_density > 0.0 {
+            for structure in &self.structures {
+                if let StructureKind::House(house) | StructureKind::Tavern(house) = &structure.kind
+                {
+                    if !house.bounds_2d().collides_with_aabr(chunk) {
+                        continue;
+                    }
+                    for (pos, body) in house.furniture(self.furniture_density) {
+                        if chunk.contains_point(pos.xy()) {
+                            let wpos = Vec3::from(self.origin) + pos;
+                            entities.push(
+                                EntityInfo::at(wpos.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0))
+                                    .with_body(comp::Body::Object(body))
+                                    .with_agency(false),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        entities
+    }
+
+    /// The number of entities of each kind that the settlement is expected to
+    /// spawn by day, from the chance of a spawn on each column of its town
+    /// and pastures and in each bunk of its barracks. Custom structures and
+    /// spawn policies aren't accounted for.
+    pub fn population_summary(&self) -> PopulationSummary {
+        let columns = |is_plot: fn(&Plot) -> bool| {
+            self.land
+                .tiles
+                .values()
+                .filter(|tile| is_plot(self.land.plot(tile.plot)))
+                .count() as f32
+                * (AREA_SIZE * AREA_SIZE) as f32
+        };
+        let town_spawns = columns(|plot| matches!(plot, Plot::Town { .. })) * TOWN_SPAWN_CHANCE;
+        let grazers = columns(|plot| matches!(plot, Plot::Pasture)) * PASTURE_SPAWN_CHANCE;
+
+        let townsfolk = town_spawns * (1.0 - DUMMY_CHANCE);
+        let expected = |kind: Townsfolk| townsfolk * kind.daytime_chance();
+        let (guards, merchants, villagers) = match self.faction {
+            // Forts with barracks house their guards in them
+            Faction::Friendly if self.bunks().next().is_some() => (
+                self.bunks().count() as f32 * BUNK_GUARD_CHANCE,
+                expected(Townsfolk::Merchant),
+                expected(Townsfolk::Villager),
+            ),
+            Faction::Friendly => (
+                expected(Townsfolk::Guard),
+                expected(Townsfolk::Merchant),
+                expected(Townsfolk::Villager),
+            ),
+            // Hostile settlements spawn villagers in place of guards and
+            // merchants
+            Faction::Hostile => (
+                0.0,
+                0.0,
+                expected(Townsfolk::Guard)
+                    + expected(Townsfolk::Merchant)
+                    + expected(Townsfolk::Villager),
+            ),
+        };
+
+        PopulationSummary {
+            villagers,
+            guards,
+            merchants,
+            animals: expected(Townsfolk::Animal) + grazers,
+            birds: expected(Townsfolk::Bird),
+            dummies: town_spawns * DUMMY_CHANCE,
+        }
+    }
+
+    pub fn get_color(&self, index: IndexRef, pos: Vec2<i32>) -> Option<Rgb<u8>> {
+        self.color_at(
+            &index.colors.site.settlement,
+            pos,
+            self.land.get_at_block(pos),
+        )
+    }
+
+    /// Rasterize the colors of every position within `rect` (exclusive of its
+    /// maximum), row by row, as [`Settlement::get_color`] would give them.
+    /// The warp field is sampled once per cell rather than once per position.
+    pub fn color_map(&self, index: IndexRef, rect: Aabr<i32>) -> Vec<Option<Rgb<u8>>> {
+        let colors = &index.colors.site.settlement;
+        let mut neighbors = HashMap::<Vec2<i32>, [StructureField; 9]>::default();
+
+        (rect.min.y..rect.max.y)
+            .flat_map(|y| (rect.min.x..rect.max.x).map(move |x| Vec2::new(x, y)))
+            .map(|pos| {
+                let neighbors = neighbors
+                    .entry(self.land.warp_cell(pos))
+                    .or_insert_with(|| self.land.sampler_warp.get(pos));
+                self.color_at(colors, pos, self.land.get_at_block_with(pos, neighbors))
+            })
+            .collect()
+    }
+
+    fn color_at(&self, colors: &Colors, pos: Vec2<i32>, sample: Sample) -> Option<Rgb<u8>> {
+        match sample.plot {
+            Some(Plot::Dirt | Plot::Reserved) => return Some(colors.plot_dirt.into()),
+            Some(Plot::Grass) => return Some(colors.plot_grass.into()),
+            Some(Plot::Orchard { .. }) => return Some(colors.orchard_leaves.into()),
+            Some(Plot::Pasture) => return Some(colors.plot_pasture.into()),
+            Some(Plot::Water) => return Some(colors.plot_water.into()),
+            Some(Plot::Town { .. }) => {
+                return Some(
+                    Rgb::from(colors.plot_town).map2(Rgb::iota(), |e: u8, i: i32| {
+                        e.saturating_add(
+                            (self.noise.get(Vec3::new(pos.x, pos.y, i * 5)) % 16) as u8,
+                        )
+                        .saturating_sub(8)
+                    }),
+                );
+            },
+            Some(Plot::Field {
+                seed, crop, center, ..
+            }) => {
+                let furrow = self.in_field_furrow(pos, *seed, *crop, *center);
+                // NOTE: Very hard to understand how to make this dynamically configurable.  The
+                // base values can easily cause the others to go out of range, and there's some
+                // weird scaling going on.  For now, we just let these remain hardcoded.
+                //
+                // FIXME: Rewrite this so that validity is not so heavily dependent on the exact
+                // color values.
+                return Some(Rgb::new(
+                    if furrow {
+                        100
+                    } else {
+                        32 + seed.to_le_bytes()[0] % 64
+                    },
+                    64 + seed.to_le_bytes()[1] % 128,
+                    16 + seed.to_le_bytes()[2] % 32,
+                ));
+            },
+            _ => {},
+        }
+
+        None
+    }
+}
+
+fn barnyard(pos: Vec3<f32>, dynamic_rng: &mut impl Rng) -> EntityInfo {
+    //TODO: use Lottery instead of ad-hoc RNG system
+    let species = match dynamic_rng.gen_range(0..5) {
+        0 => quadruped_small::Species::Pig,
+        1 => quadruped_small::Species::Sheep,
+        2 => quadruped_small::Species::Goat,
+        3 => quadruped_small::Species::Dog,
+        _ => quadruped_small::Species::Cat,
+    };
+    EntityInfo::at(pos)
+        .with_body(comp::Body::QuadrupedSmall(
+            quadruped_small::Body::random_with(dynamic_rng, &species),
+        ))
+        .with_alignment(comp::Alignment::Tame)
+        .with_automatic_name(None)
+}
+
+fn grazer(pos: Vec3<f32>, dynamic_rng: &mut impl Rng) -> EntityInfo {
+    let body = match dynamic_rng.gen_range(0..4) {
+        0 => comp::Body::QuadrupedSmall(quadruped_small::Body::random_with(
+            dynamic_rng,
+            &quadruped_small::Species::Sheep,
+        )),
+        1 => comp::Body::QuadrupedSmall(quadruped_small::Body::random_with(
+            dynamic_rng,
+            &quadruped_small::Species::Goat,
+        )),
+        _ => comp::Body::QuadrupedMedium(quadruped_medium::Body::random_with(
+            dynamic_rng,
+            &quadruped_medium::Species::Cattle,
+        )),
+    };
+    EntityInfo::at(pos)
+        .with_body(body)
+        .with_alignment(comp::Alignment::Tame)
+        .with_automatic_name(None)
+}
+
+fn bird(pos: Vec3<f32>, dynamic_rng: &mut impl Rng) -> EntityInfo {
+    //TODO: use Lottery instead of ad-hoc RNG system
+    let species = match dynamic_rng.gen_range(0..4) {
+        0 => bird_medium::Species::Duck,
+        1 => bird_medium::Species::Chicken,
+        2 => bird_medium::Species::Goose,
+        _ => bird_medium::Species::Peacock,
+    };
+    EntityInfo::at(pos)
+        .with_body(comp::Body::BirdMedium(bird_medium::Body::random_with(
+            dynamic_rng,
+            &species,
+        )))
+        .with_alignment(comp::Alignment::Tame)
+        .with_automatic_name(None)
+}
+
+/// Chance of an entity spawning on each column of a town.
+const TOWN_SPAWN_CHANCE: f32 = 1.0 / (20.0 * 40.0);
+
+/// Chance of an animal grazing on each column of a pasture.
+const PASTURE_SPAWN_CHANCE: f32 = 1.0 / (20.0 * 10.0);
+
+/// Fewest tiles a town may have to get a graveyard with a crypt.
+const CRYPT_MIN_TOWN_TILES: usize = 30;
+
+/// The loot table of the chests of keeps and crypts, by the tier of their
+/// settlement.
+const CHEST_LOOT: [&str; Settlement::MAX_TIER as usize + 1] = [
+    "common.loot_tables.settlement.chest-tier-0",
+    "common.loot_tables.settlement.chest-tier-1",
+    "common.loot_tables.settlement.chest-tier-2",
+    "common.loot_tables.settlement.chest-tier-3",
+];
+
+/// The entity config of the raiders of hostile settlements, by the tier of
+/// their settlement.
+const RAIDER_ENTITY: [&str; Settlement::MAX_TIER as usize + 1] = [
+    "common.entity.settlement.raider-tier-0",
+    "common.entity.settlement.raider-tier-1",
+    "common.entity.settlement.raider-tier-2",
+    "common.entity.settlement.raider-tier-3",
+];
+
+/// Lowest tier of a settlement to build its town wall of stone rather than
+/// wood.
+const STONE_WALL_TIER: u8 = 2;
+
+/// Chance of a guard sleeping in each bunk of a barracks.
+const BUNK_GUARD_CHANCE: f32 = 1.0 / 2.0;
+
+/// How many guards a fort houses in each of its barracks. See
+/// [`Settlement::place_barracks`].
+const GUARDS_PER_BARRACKS: f32 = 6.0;
+
+/// Chance of a town spawn being a training dummy.
+const DUMMY_CHANCE: f32 = 1.0 / 15.0;
+
+/// The expected number of entities of each kind spawned by a settlement. See
+/// [`Settlement::population_summary`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PopulationSummary {
+    pub villagers: f32,
+    pub guards: f32,
+    pub merchants: f32,
+    /// Barnyard animals wandering the town and herds grazing its pastures.
+    pub animals: f32,
+    pub birds: f32,
+    pub dummies: f32,
+}
+
+impl PopulationSummary {
+    pub fn total(&self) -> f32 {
+        self.villagers + self.guards + self.merchants + self.animals + self.birds + self.dummies
+    }
+}
+
+/// The kinds of entity found wandering a settlement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Townsfolk {
+    Animal,
+    Bird,
+    Guard,
+    Merchant,
+    Villager,
+}
+
+impl Townsfolk {
+    /// Choose what kind of entity to spawn, if any. At night, animals are
+    /// penned, most civilians stay indoors and more guards patrol the
+    /// streets. Without a time of day, daytime behaviour is used.
+    fn pick(period: Option<DayPeriod>, dynamic_rng: &mut impl Rng) -> Option<Self> {
+        let is_night = period.map_or(false, |period| period.is_dark());
+        match (dynamic_rng.gen_range(0..=4), is_night) {
+            (0 | 1, true) => None,
+            (0, false) => Some(Townsfolk::Animal),
+            (1, false) => Some(Townsfolk::Bird),
+            (_, false) => Some(match dynamic_rng.gen_range(0..8) {
+                0 | 1 => Townsfolk::Guard,
+                2 => Townsfolk::Merchant,
+                _ => Townsfolk::Villager,
+            }),
+            (_, true) => match dynamic_rng.gen_range(0..8) {
+                0..=3 => Some(Townsfolk::Guard),
+                4 => Some(Townsfolk::Villager),
+                _ => None,
+            },
+        }
+    }
+
+    /// The chance of [`Townsfolk::pick`] picking this kind by day.
+    fn daytime_chance(self) -> f32 {
+        match self {
+            Townsfolk::Animal | Townsfolk::Bird => 1.0 / 5.0,
+            Townsfolk::Guard => 3.0 / 5.0 * 2.0 / 8.0,
+            Townsfolk::Merchant => 3.0 / 5.0 * 1.0 / 8.0,
+            Townsfolk::Villager => 3.0 / 5.0 * 5.0 / 8.0,
+        }
+    }
+}
+
+fn humanoid(
+    kind: Townsfolk,
+    pos: Vec3<f32>,
+    economy: &SiteInformation,
+    dynamic_rng: &mut impl Rng,
+    time: Option<&(TimeOfDay, Calendar)>,
+) -> EntityInfo {
+    let entity = EntityInfo::at(pos);
+    match kind {
+        Townsfolk::Guard => entity
+            .with_agent_mark(agent::Mark::Guard)
+            .with_asset_expect("common.entity.village.guard", dynamic_rng, time),
+        Townsfolk::Merchant => entity
+            .with_agent_mark(agent::Mark::Merchant)
+            .with_economy(economy)
+            .with_lazy_loadout(merchant_loadout)
+            .with_asset_expect("common.entity.village.merchant", dynamic_rng, time),
+        _ => entity.with_asset_expect("common.entity.village.villager", dynamic_rng, time),
+    }
+}
+
+pub fn merchant_loadout(
+    loadout_builder: LoadoutBuilder,
+    economy: Option<&SiteInformation>,
     time: Option<&(TimeOfDay, Calendar)>,
 ) -> LoadoutBuilder {
     trader_loadout(
@@ -1032,447 +5541,5579 @@ pub fn merchant_loadout(
     )
 }
 
-pub fn trader_loadout(
-    loadout_builder: LoadoutBuilder,
-    economy: Option<&SiteInformation>,
-    mut permitted: impl FnMut(Good) -> bool,
-) -> LoadoutBuilder {
-    let rng = &mut thread_rng();
+pub fn trader_loadout(
+    loadout_builder: LoadoutBuilder,
+    economy: Option<&SiteInformation>,
+    mut permitted: impl FnMut(Good) -> bool,
+) -> LoadoutBuilder {
+    let rng = &mut thread_rng();
+
+    let mut backpack = Item::new_from_asset_expect("common.items.armor.misc.back.backpack");
+    let mut bag1 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
+    let mut bag2 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
+    let mut bag3 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
+    let mut bag4 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
+    let slots = backpack.slots().len() + 4 * bag1.slots().len();
+    let mut stockmap: HashMap<Good, f32> = economy
+        .map(|e| {
+            e.unconsumed_stock
+                .clone()
+                .into_iter()
+                .filter(|(good, _)| permitted(*good))
+                .collect()
+        })
+        .unwrap_or_default();
+    // modify stock for better gameplay
+
+    // TODO: currently econsim spends all its food on population, resulting in none
+    // for the players to buy; the `.max` is temporary to ensure that there's some
+    // food for sale at every site, to be used until we have some solution like NPC
+    // houses as a limit on econsim population growth
+    if permitted(Good::Food) {
+        stockmap
+            .entry(Good::Food)
+            .and_modify(|e| *e = e.max(10_000.0))
+            .or_insert(10_000.0);
+    }
+    // Reduce amount of potions so merchants do not oversupply potions.
+    // TODO: Maybe remove when merchants and their inventories are rtsim?
+    // Note: Likely without effect now that potions are counted as food
+    if permitted(Good::Potions) {
+        stockmap
+            .entry(Good::Potions)
+            .and_modify(|e| *e = e.powf(0.25));
+    }
+    // It's safe to truncate here, because coins clamped to 3000 max
+    // also we don't really want negative values here
+    if permitted(Good::Coin) {
+        stockmap
+            .entry(Good::Coin)
+            .and_modify(|e| *e = e.min(rng.gen_range(1000.0..3000.0)));
+    }
+    // assume roughly 10 merchants sharing a town's stock (other logic for coins)
+    stockmap
+        .iter_mut()
+        .filter(|(good, _amount)| **good != Good::Coin)
+        .for_each(|(_good, amount)| *amount *= 0.1);
+    // Fill bags with stuff according to unclaimed stock
+    let ability_map = &comp::tool::AbilityMap::load().read();
+    let msm = &comp::item::MaterialStatManifest::load().read();
+    let mut wares: Vec<Item> =
+        TradePricing::random_items(&mut stockmap, slots as u32, true, true, 16)
+            .iter()
+            .filter_map(|(n, a)| {
+                let i = Item::new_from_item_definition_id(n.as_ref(), ability_map, msm).ok();
+                i.map(|mut i| {
+                    i.set_amount(*a)
+                        .map_err(|_| tracing::error!("merchant loadout amount failure"))
+                        .ok();
+                    i
+                })
+            })
+            .collect();
+    sort_wares(&mut wares);
+    transfer(&mut wares, &mut backpack);
+    transfer(&mut wares, &mut bag1);
+    transfer(&mut wares, &mut bag2);
+    transfer(&mut wares, &mut bag3);
+    transfer(&mut wares, &mut bag4);
+
+    loadout_builder
+        .back(Some(backpack))
+        .bag(ArmorSlot::Bag1, Some(bag1))
+        .bag(ArmorSlot::Bag2, Some(bag2))
+        .bag(ArmorSlot::Bag3, Some(bag3))
+        .bag(ArmorSlot::Bag4, Some(bag4))
+}
+
+fn sort_wares(bag: &mut [Item]) {
+    use common::comp::item::TagExampleInfo;
+
+    bag.sort_by(|a, b| {
+        a.quality()
+            .cmp(&b.quality())
+        // sort by kind
+        .then(
+            Ord::cmp(
+                a.tags().first().map_or("", |tag| tag.name()),
+                b.tags().first().map_or("", |tag| tag.name()),
+            )
+        )
+        // sort by name
+        .then(#[allow(deprecated)] Ord::cmp(&a.name(), &b.name()))
+    });
+}
+
+fn transfer(wares: &mut Vec<Item>, bag: &mut Item) {
+    let capacity = bag.slots().len();
+    for (s, w) in bag
+        .slots_mut()
+        .iter_mut()
+        .zip(wares.drain(0..wares.len().min(capacity)))
+    {
+        *s = Some(w);
+    }
+}
+
+/// A kind of tree planted in orchards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TreeSpecies {
+    Apple,
+    Pear,
+    DatePalm,
+    CoconutPalm,
+}
+
+impl TreeSpecies {
+    /// The trees that grow well in the given biome.
+    pub fn for_biome(biome: BiomeKind) -> Vec<TreeSpecies> {
+        match biome {
+            BiomeKind::Desert | BiomeKind::Savannah => vec![TreeSpecies::DatePalm],
+            BiomeKind::Jungle | BiomeKind::Ocean => vec![TreeSpecies::CoconutPalm],
+            // Only the hardiest fruit trees survive the cold
+            BiomeKind::Snowland | BiomeKind::Taiga => vec![TreeSpecies::Apple],
+            _ => vec![TreeSpecies::Apple, TreeSpecies::Pear],
+        }
+    }
+
+    fn is_palm(&self) -> bool { matches!(self, TreeSpecies::DatePalm | TreeSpecies::CoconutPalm) }
+
+    /// The height of the tree, in blocks.
+    fn height(&self) -> i32 { if self.is_palm() { 10 } else { 8 } }
+
+    /// The block of the tree `offs` blocks from its trunk and `z` blocks above
+    /// the ground, if any.
+    fn block(&self, colors: &Colors, offs: Vec2<i32>, z: i32) -> Option<Block> {
+        let dist_sq = offs.magnitude_squared();
+        let fruit = match self {
+            TreeSpecies::Apple => Some(SpriteKind::Apple),
+            TreeSpecies::CoconutPalm => Some(SpriteKind::Coconut),
+            TreeSpecies::Pear | TreeSpecies::DatePalm => None,
+        };
+
+        if self.is_palm() {
+            const TRUNK_HEIGHT: i32 = 8;
+            // Fronds spread out along the diagonals and axes from the crown
+            let frond = (offs.x.abs() == offs.y.abs() || offs.x == 0 || offs.y == 0)
+                && dist_sq <= 3i32.pow(2) * 2;
+            if dist_sq == 0 && z < TRUNK_HEIGHT {
+                Some(Block::new(BlockKind::Wood, colors.palm_trunk.into()))
+            } else if (z == TRUNK_HEIGHT && frond)
+                || (z == TRUNK_HEIGHT - 1 && dist_sq > 4 && frond)
+            {
+                Some(Block::new(BlockKind::Leaves, colors.palm_leaves.into()))
+            } else if z == TRUNK_HEIGHT - 2 && dist_sq == 1 {
+                fruit.map(Block::air)
+            } else {
+                None
+            }
+        } else {
+            const TRUNK_HEIGHT: i32 = 4;
+            const CANOPY_Z: i32 = 5;
+            if dist_sq == 0 && z < TRUNK_HEIGHT {
+                Some(Block::new(BlockKind::Wood, colors.orchard_trunk.into()))
+            } else if dist_sq + (z - CANOPY_Z).pow(2) <= 6 {
+                Some(Block::new(BlockKind::Leaves, colors.orchard_leaves.into()))
+            } else if z == 0 && dist_sq == 5 {
+                // Windfall beneath the canopy
+                fruit.map(Block::air)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// The trunk of the orchard tree nearest to `rpos`, relative to the
+/// settlement's origin. Trees stand in a regular grid.
+fn orchard_tree_pos(rpos: Vec2<i32>) -> Vec2<i32> {
+    const TREE_SPACING: i32 = 8;
+    rpos.map(|e| e.div_euclid(TREE_SPACING) * TREE_SPACING + TREE_SPACING / 2)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Crop {
+    Corn,
+    Wheat,
+    Cabbage,
+    Pumpkin,
+    Flax,
+    Carrot,
+    Tomato,
+    Radish,
+    Turnip,
+    Sunflower,
+}
+
+impl Crop {
+    /// The width of each crop row, in blocks.
+    pub fn row_width(&self) -> i32 {
+        match self {
+            Crop::Wheat | Crop::Flax | Crop::Radish => 1,
+            Crop::Pumpkin => 3,
+            _ => 2,
+        }
+    }
+
+    /// The distance between the starts of neighbouring crop rows, in blocks.
+    pub fn row_spacing(&self) -> i32 {
+        match self {
+            Crop::Wheat | Crop::Flax => 3,
+            Crop::Corn | Crop::Carrot | Crop::Radish | Crop::Turnip => 4,
+            Crop::Cabbage | Crop::Tomato => 5,
+            Crop::Sunflower => 6,
+            Crop::Pumpkin => 7,
+        }
+    }
+
+    /// The share of the blocks along the crop's rows that a plant grows on.
+    /// Crops sown close together, like wheat, fill their rows, while crops
+    /// that need room to grow, like pumpkins, are spaced out along them.
+    pub fn planting_density(&self) -> f32 {
+        match self {
+            Crop::Wheat => 0.6,
+            Crop::Flax => 0.5,
+            Crop::Radish => 0.4,
+            Crop::Corn => 0.35,
+            Crop::Carrot => 0.3,
+            Crop::Turnip | Crop::Sunflower => 0.25,
+            Crop::Tomato => 0.2,
+            Crop::Cabbage => 0.15,
+            Crop::Pumpkin => 0.08,
+        }
+    }
+
+    /// How much produce a single field tile of this crop yields per harvest.
+    pub fn base_yield(&self) -> u32 {
+        match self {
+            Crop::Wheat => 16,
+            Crop::Radish => 14,
+            Crop::Corn | Crop::Carrot => 12,
+            Crop::Flax | Crop::Tomato | Crop::Turnip => 10,
+            Crop::Cabbage => 8,
+            Crop::Sunflower => 6,
+            Crop::Pumpkin => 4,
+        }
+    }
+
+    /// The item asset of the produce harvested from this crop. Crops without
+    /// an item of their own yet map to the closest existing produce: there is
+    /// no grain item, so wheat yields plant fiber.
+    pub fn item_asset(&self) -> &'static str {
+        match self {
+            Crop::Corn => "common.items.food.honeycorn",
+            Crop::Wheat => "common.items.flowers.plant_fiber",
+            Crop::Cabbage | Crop::Pumpkin => "common.items.food.lettuce",
+            Crop::Flax => "common.items.flowers.wild_flax",
+            Crop::Carrot | Crop::Radish => "common.items.food.carrot",
+            Crop::Tomato => "common.items.food.tomato",
+            Crop::Turnip => "common.items.food.onion",
+            Crop::Sunflower => "common.items.flowers.sunflower",
+        }
+    }
+}
+
+// NOTE: No support for struct variants in make_case_elim yet, unfortunately, so
+// we can't use it.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Plot {
+    Hazard,
+    Dirt,
+    Grass,
+    /// Open grazing land for herds of animals.
+    Pasture,
+    /// Rows of trees of a single species.
+    Orchard {
+        species: TreeSpecies,
+    },
+    Water,
+    /// Kept clear during generation for content placed later on.
+    Reserved,
+    Town {
+        district: Option<Id<District>>,
+    },
+    Field {
+        farm: Id<Farm>,
+        seed: u32,
+        crop: Crop,
+        /// The tile that the field grew from.
+        center: Vec2<i32>,
+    },
+}
+
+/// The volume containing a world column, along with the world position of the
+/// volume's minimum corner.
+fn vol_at<'v, V: RectSizedVol>(
+    vols: &'v mut [(Vec2<i32>, &mut V)],
+    wpos2d: Vec2<i32>,
+) -> Option<(Vec2<i32>, &'v mut V)> {
+    vols.iter_mut().find_map(|(min, vol)| {
+        let offs = wpos2d - *min;
+        (offs.x >= 0
+            && offs.y >= 0
+            && offs.x < vol.size_xy().x as i32
+            && offs.y < vol.size_xy().y as i32)
+            .then_some((*min, &mut **vol))
+    })
+}
+
+/// Height above the ground of the walkway along the top of settlement walls.
+const WALL_WALK_Z: i32 = 8;
+
+/// How far below its base a wall may reach down to meet the ground.
+const WALL_FOUNDATION_DEPTH: i32 = 16;
+
+/// Height of the opening of a gate through the wall, beneath its walkway.
+const GATE_HEIGHT: i32 = WALL_WALK_Z - 3;
+
+/// Height of the open space above the deck of a bridge.
+const BRIDGE_CLEARANCE: i32 = 4;
+
+/// How far the opening of a gate reaches from the centre of its tile.
+const GATE_HALF_WIDTH: i32 = 3;
+
+/// Where the guaranteed scarecrow of a field grown from the tile `center`
+/// stands, relative to the settlement's origin.
+fn scarecrow_pos(center: Vec2<i32>) -> Vec2<i32> {
+    center * AREA_SIZE as i32 + AREA_SIZE as i32 / 2
+}
+
+/// Height of each step of a terraced field.
+const TERRACE_HEIGHT: i32 = 3;
+
+/// Distance, in blocks, between the stilts that structures stand on.
+const STILT_SPACING: i32 = 4;
+
+/// Distance, in tiles, over which the leveled ground of a town is averaged.
+const LEVEL_RADIUS: i32 = 2;
+
+/// Steepest drop between neighbouring columns that a field is farmed on
+/// without being terraced.
+const TERRACE_MIN_SLOPE: f32 = 0.4;
+
+/// Whether a wall is solid at height `z`, `dist` blocks from its centre line.
+/// The top of the wall is a flat walkway with a low parapet along each edge,
+/// raised into a crenellation where `merlon` is set.
+fn wall_is_solid(dist: f32, z: i32, merlon: bool) -> bool {
+    let edge = dist / WayKind::Wall.width();
+    if edge >= 1.0 {
+        false
+    } else if z < WALL_WALK_Z {
+        true
+    } else if edge > 0.65 {
+        z == WALL_WALK_Z || (merlon && z == WALL_WALK_Z + 1)
+    } else {
+        false
+    }
+}
+
+/// Whether a position lies within one of the crop rows of a field.
+fn in_furrow(pos: Vec2<i32>, seed: u32, crop: Crop) -> bool {
+    let furrow_dirs = [
+        Vec2::new(1, 0),
+        Vec2::new(0, 1),
+        Vec2::new(1, 1),
+        Vec2::new(-1, 1),
+    ];
+    let furrow_dir = furrow_dirs[seed as usize % furrow_dirs.len()];
+    (pos * furrow_dir).sum().rem_euclid(crop.row_spacing()) < crop.row_width()
+}
+
+/// Like [`in_furrow`], but for crop rows running along the contours of ground
+/// that slopes down towards `downhill`, a unit vector.
+fn in_contour_furrow(pos: Vec2<i32>, downhill: Vec2<f32>, crop: Crop) -> bool {
+    pos.map(|e| e as f32)
+        .dot(downhill)
+        .rem_euclid(crop.row_spacing() as f32)
+        < crop.row_width() as f32
+}
+
+/// Share of the buildings of a town tile `dist` tiles from the town center
+/// that are placed, falling off with `steepness`. The center always gets all
+/// of its buildings, as does every tile if `steepness` is zero.
+fn building_density(dist: f32, steepness: f32) -> f32 {
+    1.0 / (1.0 + steepness * (dist / Town::RADIUS as f32).powi(2))
+}
+
+/// Depth of a water plot's basin, `edge_dist` blocks away from the plot's edge.
+/// The basin is shallow near its banks so that it blends into the surrounding
+/// terrain.
+fn water_depth(edge_dist: f32) -> i32 { (edge_dist / 3.0).clamp(1.0, 6.0) as i32 }
+
+/// The depth of the water over a ford, see [`Settlement::place_crossings`].
+const FORD_DEPTH: i32 = 1;
+
+/// The block of a water plot's column at `z`, relative to the surrounding
+/// surface. The water sits one block below the surface, on an earthen bed.
+fn water_block(z: i32, depth: i32, bed_color: Rgb<u8>) -> Block {
+    if z >= -1 {
+        Block::air(SpriteKind::Empty)
+    } else if z >= -1 - depth {
+        Block::water(SpriteKind::Empty)
+    } else {
+        Block::new(BlockKind::Earth, bed_color)
+    }
+}
+
+const CARDINALS: [Vec2<i32>; 4] = [
+    Vec2::new(0, 1),
+    Vec2::new(1, 0),
+    Vec2::new(0, -1),
+    Vec2::new(-1, 0),
+];
+
+/// The direction of each entry of [`Tile::ways`]: west, north, east, south.
+const WAY_DIRS: [Vec2<i32>; 4] = [
+    Vec2::new(-1, 0),
+    Vec2::new(0, 1),
+    Vec2::new(1, 0),
+    Vec2::new(0, -1),
+];
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum WayKind {
+    Path,
+    #[allow(dead_code)]
+    Wall,
+    /// A path carried over a wooden deck.
+    Bridge,
+    /// A path wading through shallow water.
+    Ford,
+}
+
+impl WayKind {
+    pub fn width(&self) -> f32 {
+        match self {
+            WayKind::Path | WayKind::Bridge | WayKind::Ford => 4.0,
+            WayKind::Wall => 3.0,
+        }
+    }
+
+    /// Which kind wins where two ways of different kinds meet along the same
+    /// edge, see [`Land::normalize_ways`]. Walls stand in the way of anything,
+    /// bridges carry paths over water and fords lead paths through it.
+    fn priority(&self) -> u8 {
+        match self {
+            WayKind::Path => 0,
+            WayKind::Ford => 1,
+            WayKind::Bridge => 2,
+            WayKind::Wall => 3,
+        }
+    }
+}
+
+/// A settlement's roads as a graph, see [`Settlement::road_graph`]. Tiles are
+/// in tile coordinates, relative to the settlement's origin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoadGraph {
+    /// The tiles where roads end, turn or meet, in order of their position.
+    pub nodes: Vec<Vec2<i32>>,
+    /// The straight runs of road between nodes.
+    pub edges: Vec<RoadEdge>,
+}
+
+/// A straight run of road between two nodes of a [`RoadGraph`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoadEdge {
+    /// The index of the node the run starts at, in [`RoadGraph::nodes`].
+    pub from: usize,
+    /// The index of the node the run ends at, in [`RoadGraph::nodes`].
+    pub to: usize,
+    /// The number of tiles the run crosses from one node to the other.
+    pub length: u32,
+    /// The cost of travelling the run, in blocks. Wading through a ford costs
+    /// twice as much as walking.
+    pub cost: f32,
+}
+
+impl RoadGraph {
+    /// The edges leaving the node with the given index, each along with the
+    /// index of the node at its other end.
+    pub fn edges_from(&self, node: usize) -> impl Iterator<Item = (&RoadEdge, usize)> {
+        self.edges.iter().filter_map(move |edge| {
+            if edge.from == node {
+                Some((edge, edge.to))
+            } else if edge.to == node {
+                Some((edge, edge.from))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Tower {
+    #[allow(dead_code)]
+    Wall,
+}
+
+impl Tower {
+    pub fn radius(&self) -> f32 {
+        match self {
+            Tower::Wall => 6.0,
+        }
+    }
+}
+
+pub struct Tile {
+    plot: Id<Plot>,
+    ways: [Option<WayKind>; 4],
+    tower: Option<Tower>,
+}
+
+impl Tile {
+    pub fn contains(&self, kind: WayKind) -> bool { self.ways.iter().any(|way| way == &Some(kind)) }
+
+    /// The ways leaving this tile to the west, north, east and south, in that
+    /// order.
+    pub fn ways(&self) -> &[Option<WayKind>; 4] { &self.ways }
+
+    /// The width of a way leaving this tile, `t` being how far along the way
+    /// towards the neighbouring tile's centre the sample is. Dead ends taper
+    /// to a point at the centre of the tile and junctions flare out slightly,
+    /// both reaching the nominal width by the edge of the tile.
+    pub fn way_width(&self, kind: WayKind, t: f32) -> f32 {
+        // Bridge decks keep their width right up to their ends
+        if kind == WayKind::Bridge {
+            return kind.width();
+        }
+        let blend = (t * 2.0).min(1.0);
+        let scale = match self.ways.iter().filter(|way| **way == Some(kind)).count() {
+            0 | 1 => blend,
+            2 => 1.0,
+            _ => 1.25 - 0.25 * blend,
+        };
+        kind.width() * scale
+    }
+}
+
+/// A tile on the frontier of [`Land::find_paths_to`]'s search, ordered such
+/// that the cheapest tile is popped first.
+struct SearchEntry {
+    cost: f32,
+    pos: Vec2<i32>,
+}
+
+impl PartialEq for SearchEntry {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for SearchEntry {}
+
+impl Ord for SearchEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, since `BinaryHeap` is a max-heap. Ties are broken by
+        // position to keep the search deterministic.
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| (other.pos.x, other.pos.y).cmp(&(self.pos.x, self.pos.y)))
+    }
+}
+
+impl PartialOrd for SearchEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+#[derive(Default)]
+pub struct Sample<'a> {
+    plot: Option<&'a Plot>,
+    way: Option<(&'a WayKind, f32, Vec2<f32>)>,
+    /// How far along its way the sample lies, from the center of its tile.
+    way_along: f32,
+    tower: Option<(&'a Tower, Vec2<i32>)>,
+    /// How far the sample lies within its tile, see
+    /// [`Settlement::edge_distance`].
+    edge_dist: f32,
+    /// The tile whose plot the sample lies on.
+    closest: Vec2<i32>,
+    second_closest: Vec2<i32>,
+}
+
+pub struct Land {
+    /// We use this hasher (FxHasher64) because
+    /// (1) we need determinism across computers (ruling out AAHash);
+    /// (2) we don't care about DDOS attacks (ruling out SipHash);
+    /// (3) we have 8-byte keys (for which FxHash is fastest).
+    tiles: HashMap<Vec2<i32>, Tile, BuildHasherDefault<FxHasher64>>,
+    plots: Store<Plot>,
+    sampler_warp: StructureGen2d,
+    hazard: Id<Plot>,
+    /// Plots shared by every tile that asks for them, see
+    /// [`Land::intern_plot`].
+    interned: Vec<(Plot, Id<Plot>)>,
+    /// Maximum number of A* iterations spent on a single path.
+    astar_budget: usize,
+    /// Number of nodes expanded by pathfinding so far, if they're being
+    /// counted, see [`Land::count_astar_nodes`].
+    astar_nodes: Option<AtomicUsize>,
+}
+
+impl Land {
+    /// Pathfinding budget of a settlement with the default radius.
+    pub const DEFAULT_ASTAR_BUDGET: usize = 250;
+    /// How many tiles around a point are considered when searching for a
+    /// suitable tile, matching the area designated from the world.
+    const SEARCH_TILES: usize = (2 * Self::TILE_RADIUS as usize - 1).pow(2);
+    /// Tiles are designated from the world out to this many tiles from the
+    /// settlement's origin, exclusive, along either axis.
+    const TILE_RADIUS: i32 = Settlement::RADIUS as i32 / AREA_SIZE as i32;
+
+    pub fn new(rng: &mut impl Rng) -> Self { Self::with_warp_seed(rng.gen()) }
+
+    /// Create land whose blocks are warped onto its tiles by a field seeded
+    /// with `seed`.
+    pub fn with_warp_seed(seed: u32) -> Self {
+        let mut plots = Store::default();
+        let hazard = plots.insert(Plot::Hazard);
+        Self {
+            tiles: HashMap::default(),
+            plots,
+            sampler_warp: Self::warp(seed),
+            hazard,
+            interned: vec![(Plot::Hazard, hazard)],
+            astar_budget: Self::DEFAULT_ASTAR_BUDGET,
+            astar_nodes: None,
+        }
+    }
+
+    /// Reseed the field warping blocks onto tiles, see
+    /// [`Land::with_warp_seed`].
+    pub fn set_warp_seed(&mut self, seed: u32) { self.sampler_warp = Self::warp(seed); }
+
+    fn warp(seed: u32) -> StructureGen2d { StructureGen2d::new(seed, AREA_SIZE, AREA_SIZE * 2 / 5) }
+
+    /// Pathfinding budget for a settlement of the given radius. It grows with
+    /// the settlement's area such that the default radius gets
+    /// [`Land::DEFAULT_ASTAR_BUDGET`].
+    pub fn astar_budget_for_radius(radius: f32) -> usize {
+        (Self::DEFAULT_ASTAR_BUDGET as f32 * (radius / Settlement::RADIUS).powi(2)).ceil() as usize
+    }
+
+    pub fn astar_budget(&self) -> usize { self.astar_budget }
+
+    pub fn set_astar_budget(&mut self, budget: usize) { self.astar_budget = budget; }
+
+    /// Start counting the nodes expanded by pathfinding on this land, which
+    /// is only worth the cost when collecting statistics.
+    pub fn count_astar_nodes(&mut self) { self.astar_nodes = Some(AtomicUsize::new(0)); }
+
+    /// Number of nodes expanded by pathfinding on this land since
+    /// [`Land::count_astar_nodes`], if it was called.
+    pub fn astar_nodes(&self) -> Option<usize> {
+        self.astar_nodes
+            .as_ref()
+            .map(|nodes| nodes.load(AtomicOrdering::Relaxed))
+    }
+
+    pub fn get_at_block(&self, pos: Vec2<i32>) -> Sample {
+        self.get_at_block_with(pos, &self.sampler_warp.get(pos))
+    }
+
+    /// The cell of the warp field containing `pos`. Positions within the same
+    /// cell share their warp neighbors.
+    fn warp_cell(&self, pos: Vec2<i32>) -> Vec2<i32> { self.sampler_warp.sample_to_index(pos) }
+
+    /// Like [`Land::get_at_block`], but with the warp neighbors of `pos`
+    /// already sampled.
+    fn get_at_block_with(&self, pos: Vec2<i32>, neighbors: &[StructureField; 9]) -> Sample {
+        let mut sample = Sample::default();
+
+        let closest = neighbors
+            .iter()
+            .min_by_key(|(center, _)| center.distance_squared(pos))
+            .unwrap()
+            .0;
+        let second_closest = neighbors
+            .iter()
+            .filter(|(center, _)| *center != closest)
+            .min_by_key(|(center, _)| center.distance_squared(pos))
+            .unwrap()
+            .0;
+        sample.second_closest = second_closest.map(to_tile);
+        sample.edge_dist = (second_closest - pos).map(|e| e as f32).magnitude()
+            - (closest - pos).map(|e| e as f32).magnitude();
+
+        let center_tile = self.tile_at(neighbors[4].0.map(to_tile));
+
+        if let Some(tower) = center_tile.and_then(|tile| tile.tower.as_ref()) {
+            if (neighbors[4].0.distance_squared(pos) as f32) < tower.radius().powi(2) {
+                sample.tower = Some((tower, neighbors[4].0));
+            }
+        }
+
+        for (i, _) in CARDINALS.iter().enumerate() {
+            let map = [1, 5, 7, 3];
+            let line = LineSegment2 {
+                start: neighbors[4].0.map(|e| e as f32),
+                end: neighbors[map[i]].0.map(|e| e as f32),
+            };
+            if let Some((tile, way)) =
+                center_tile.and_then(|tile| Some((tile, tile.ways[i].as_ref()?)))
+            {
+                let proj_point = line.projected_point(pos.map(|e| e as f32));
+                let dist = proj_point.distance(pos.map(|e| e as f32));
+                let t = proj_point.distance(line.start) / line.length().max(1.0);
+                if dist < tile.way_width(*way, t) && sample.way.map_or(true, |(_, d, _)| dist <= d)
+                {
+                    sample.way = Some((way, dist, proj_point));
+                    sample.way_along = proj_point.distance(line.start);
+                }
+            }
+        }
+
+        sample.closest = closest.map(to_tile);
+        sample.plot = self.plot_at(sample.closest);
+
+        sample
+    }
+
+    pub fn tile_at(&self, pos: Vec2<i32>) -> Option<&Tile> { self.tiles.get(&pos) }
+
+    #[allow(dead_code)]
+    pub fn tile_at_mut(&mut self, pos: Vec2<i32>) -> Option<&mut Tile> { self.tiles.get_mut(&pos) }
+
+    /// The four cardinal neighbours of a tile and their tiles, if any. They
+    /// are yielded in the same order as [`Tile::ways`]: west, north, east,
+    /// south.
+    pub fn neighbors(&self, pos: Vec2<i32>) -> impl Iterator<Item = (Vec2<i32>, Option<&Tile>)> {
+        WAY_DIRS.iter().map(move |dir| {
+            let neighbor = pos + *dir;
+            (neighbor, self.tile_at(neighbor))
+        })
+    }
+
+    pub fn plot(&self, id: Id<Plot>) -> &Plot { self.plots.get(id) }
+
+    pub fn plot_at(&self, pos: Vec2<i32>) -> Option<&Plot> {
+        self.tiles.get(&pos).map(|tile| self.plots.get(tile.plot))
+    }
+
+    #[allow(dead_code)]
+    pub fn plot_at_mut(&mut self, pos: Vec2<i32>) -> Option<&mut Plot> {
+        self.tiles
+            .get(&pos)
+            .map(|tile| tile.plot)
+            .map(move |plot| self.plots.get_mut(plot))
+    }
+
+    pub fn set(&mut self, pos: Vec2<i32>, plot: Id<Plot>) {
+        self.tiles.insert(pos, Tile {
+            plot,
+            ways: [None; 4],
+            tower: None,
+        });
+    }
+
+    /// The share of the tiles within `radius` of `center` taken by fields.
+    fn field_share(&self, center: Vec2<i32>, radius: i32) -> f32 {
+        let (fields, total) = (-radius..radius + 1)
+            .flat_map(|x| (-radius..radius + 1).map(move |y| Vec2::new(x, y)))
+            .filter(|offs| offs.magnitude_squared() < radius.pow(2))
+            .fold((0, 0), |(fields, total), offs| {
+                let is_field = matches!(self.plot_at(center + offs), Some(Plot::Field { .. }));
+                (fields + is_field as usize, total + 1)
+            });
+        fields as f32 / total.max(1) as f32
+    }
+
+    /// Whether `tile` lies within the area designated from the world. Tiles
+    /// outside of it were never checked for hazards, so searches skip them.
+    fn is_designated(tile: Vec2<i32>) -> bool {
+        tile.map(|e| e.abs()).reduce_max() < Self::TILE_RADIUS
+    }
+
+    fn find_tile_near(
+        &self,
+        origin: Vec2<i32>,
+        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
+    ) -> Option<Vec2<i32>> {
+        Spiral2d::new()
+            .take(Self::SEARCH_TILES)
+            .map(|pos| origin + pos)
+            .filter(|pos| Self::is_designated(*pos))
+            .find(|pos| match_fn(self.plot_at(*pos)))
+    }
+
+    #[allow(dead_code)]
+    fn find_tile_dir(
+        &self,
+        origin: Vec2<i32>,
+        dir: Vec2<i32>,
+        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
+    ) -> Option<Vec2<i32>> {
+        (0..)
+            .map(|i| origin + dir * i)
+            .find(|pos| match_fn(self.plot_at(*pos)))
+    }
+
+    pub(crate) fn find_path(
+        &self,
+        origin: Vec2<i32>,
+        dest: Vec2<i32>,
+        path_cost_fn: impl Fn(Option<&Tile>, Option<&Tile>) -> f32,
+    ) -> Option<Path<Vec2<i32>>> {
+        let heuristic = |pos: &Vec2<i32>, _: &Vec2<i32>| (pos - dest).map(|e| e as f32).magnitude();
+        let transition =
+            |from: Vec2<i32>, to: Vec2<i32>| path_cost_fn(self.tile_at(from), self.tile_at(to));
+        let mut expanded = 0;
+        let neighbors = |pos: &Vec2<i32>| {
+            expanded += 1;
+            let pos = *pos;
+            let transition = &transition;
+            CARDINALS.iter().map(move |dir| {
+                let to = pos + *dir;
+                (to, transition(pos, to))
+            })
+        };
+        let satisfied = |pos: &Vec2<i32>| *pos == dest;
+
+        // We use this hasher (FxHasher64) because
+        // (1) we don't care about DDOS attacks (ruling out SipHash);
+        // (2) we don't care about determinism across computers (we could use AAHash);
+        // (3) we have 8-byte keys (for which FxHash is fastest).
+        let path = Astar::new(
+            self.astar_budget,
+            origin,
+            BuildHasherDefault::<FxHasher64>::default(),
+        )
+        .poll(self.astar_budget, heuristic, neighbors, satisfied)
+        .into_path()
+        .map(|(p, _c)| p);
+        if let Some(nodes) = &self.astar_nodes {
+            nodes.fetch_add(expanded, AtomicOrdering::Relaxed);
+        }
+        path
+    }
+
+    /// Find the cheapest paths from each of `origins` to `dest`. Rather than
+    /// pathfinding from every origin separately, this runs a single search
+    /// outwards from `dest` and follows it back from each origin. Paths are
+    /// the cheapest under `path_cost_fn`, so they cost the same as those of
+    /// [`Land::find_path`] wherever every step costs at least one, which its
+    /// heuristic assumes, and may cost less where some steps are cheaper, such
+    /// as along existing paths. Origins that aren't reached within the
+    /// pathfinding budget get no path.
+    ///
+    /// Only [`Settlement::place_paths`] uses this, and that pass is disabled,
+    /// so this doesn't run in generation yet.
+    fn find_paths_to(
+        &self,
+        origins: &[Vec2<i32>],
+        dest: Vec2<i32>,
+        path_cost_fn: impl Fn(Option<&Tile>, Option<&Tile>) -> f32,
+    ) -> Vec<Option<Path<Vec2<i32>>>> {
+        // The cheapest known cost to `dest` of each visited tile, and the next
+        // tile along the way there
+        let mut visited = HashMap::<_, (f32, Vec2<i32>), BuildHasherDefault<FxHasher64>>::default();
+        let mut remaining = origins
+            .iter()
+            .copied()
+            .collect::<HashSet<_, BuildHasherDefault<FxHasher64>>>();
+        let mut frontier = BinaryHeap::new();
+        let initial_budget = self.astar_budget * origins.len();
+        let mut budget = initial_budget;
+
+        visited.insert(dest, (0.0, dest));
+        frontier.push(SearchEntry {
+            cost: 0.0,
+            pos: dest,
+        });
+        while let Some(SearchEntry { cost, pos }) = frontier.pop() {
+            if remaining.is_empty() || budget == 0 {
+                break;
+            } else if visited[&pos].0 < cost {
+                // Superseded by a cheaper entry
+                continue;
+            }
+            budget -= 1;
+            remaining.remove(&pos);
+
+            for dir in CARDINALS {
+                let from = pos + dir;
+                let cost = cost + path_cost_fn(self.tile_at(from), self.tile_at(pos));
+                if visited.get(&from).map_or(true, |(c, _)| cost < *c) {
+                    visited.insert(from, (cost, pos));
+                    frontier.push(SearchEntry { cost, pos: from });
+                }
+            }
+        }
+        if let Some(nodes) = &self.astar_nodes {
+            nodes.fetch_add(initial_budget - budget, AtomicOrdering::Relaxed);
+        }
+
+        origins
+            .iter()
+            .map(|origin| {
+                if remaining.contains(origin) {
+                    return None;
+                }
+                let mut path = vec![*origin];
+                while let Some(&pos) = path.last().filter(|pos| **pos != dest) {
+                    path.push(visited[&pos].1);
+                }
+                Some(path.into_iter().collect())
+            })
+            .collect()
+    }
+
+    /// We use this hasher (FxHasher64) because
+    /// (1) we don't care about DDOS attacks (ruling out SipHash);
+    /// (2) we care about determinism across computers (ruling out AAHash);
+    /// (3) we have 8-byte keys (for which FxHash is fastest).
+    pub(crate) fn grow_from(
+        &self,
+        start: Vec2<i32>,
+        max_size: usize,
+        _rng: &mut impl Rng,
+        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
+    ) -> HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>> {
+        let mut open = VecDeque::new();
+        open.push_back(start);
+        // We use this hasher (FxHasher64) because
+        // (1) we don't care about DDOS attacks (ruling out SipHash);
+        // (2) we care about determinism across computers (ruling out AAHash);
+        // (3) we have 8-byte keys (for which FxHash is fastest).
+        let mut closed = HashSet::with_hasher(BuildHasherDefault::<FxHasher64>::default());
+
+        while open.len() + closed.len() < max_size {
+            let next_pos = if let Some(next_pos) = open.pop_front() {
+                closed.insert(next_pos);
+                next_pos
+            } else {
+                break;
+            };
+
+            let dirs = [
+                Vec2::new(1, 0),
+                Vec2::new(-1, 0),
+                Vec2::new(0, 1),
+                Vec2::new(0, -1),
+            ];
+
+            for dir in dirs.iter() {
+                let neighbor = next_pos + dir;
+                if !closed.contains(&neighbor) && match_fn(self.plot_at(neighbor)) {
+                    open.push_back(neighbor);
+                }
+            }
+        }
+
+        closed.into_iter().chain(open).collect()
+    }
+
+    pub(crate) fn write_path(
+        &mut self,
+        tiles: &[Vec2<i32>],
+        kind: WayKind,
+        mut permit_fn: impl FnMut(&Plot) -> bool,
+        overwrite: bool,
+    ) {
+        for tiles in tiles.windows(2) {
+            let dir = tiles[1] - tiles[0];
+            let idx = if dir.y > 0 {
+                1
+            } else if dir.x > 0 {
+                2
+            } else if dir.y < 0 {
+                3
+            } else if dir.x < 0 {
+                0
+            } else {
+                continue;
+            };
+            if self.tile_at(tiles[0]).is_none() {
+                self.set(tiles[0], self.hazard);
+            }
+            let plots = &self.plots;
+
+            self.tiles
+                .get_mut(&tiles[1])
+                .filter(|tile| permit_fn(plots.get(tile.plot)))
+                .map(|tile| {
+                    if overwrite || tile.ways[(idx + 2) % 4].is_none() {
+                        tile.ways[(idx + 2) % 4] = Some(kind);
+                    }
+                });
+            self.tiles
+                .get_mut(&tiles[0])
+                .filter(|tile| permit_fn(plots.get(tile.plot)))
+                .map(|tile| {
+                    if overwrite || tile.ways[idx].is_none() {
+                        tile.ways[idx] = Some(kind);
+                    }
+                });
+        }
+    }
+
+    /// Resolve ways whose halves disagree across the edge between two tiles,
+    /// as left by writing a path over only one of them. Both halves get the
+    /// kind of the highest [`WayKind::priority`]. Ways of different kinds
+    /// leaving a tile in different directions are left alone: where a wall
+    /// crosses a path like that, the tile gets a gate.
+    pub fn normalize_ways(&mut self) {
+        let mut resolved = Vec::new();
+        for (pos, tile) in self.tiles.iter() {
+            // Visit each edge once, from the tile to its north or east
+            for idx in [1, 2] {
+                let other = pos + WAY_DIRS[idx];
+                let other_way = self.tiles.get(&other).and_then(|t| t.ways[(idx + 2) % 4]);
+                if let (Some(way), Some(other_way)) = (tile.ways[idx], other_way) {
+                    if way != other_way {
+                        let kind = if way.priority() > other_way.priority() {
+                            way
+                        } else {
+                            other_way
+                        };
+                        resolved.push((*pos, idx, kind));
+                        resolved.push((other, (idx + 2) % 4, kind));
+                    }
+                }
+            }
+        }
+        for (pos, idx, kind) in resolved {
+            if let Some(tile) = self.tiles.get_mut(&pos) {
+                tile.ways[idx] = Some(kind);
+            }
+        }
+    }
+
+    /// Remove the halves of ways leading onto a neighbouring tile that has no
+    /// way back, as left where a path was cut short by the plots it may be
+    /// written over.
+    pub fn prune_ways(&mut self) {
+        let orphaned = self
+            .tiles
+            .iter()
+            .flat_map(|(pos, tile)| {
+                (0..4)
+                    .filter(move |idx| {
+                        tile.ways[*idx].is_some()
+                            && self
+                                .tiles
+                                .get(&(pos + WAY_DIRS[*idx]))
+                                .map_or(false, |other| other.ways[(idx + 2) % 4].is_none())
+                    })
+                    .map(move |idx| (*pos, idx))
+            })
+            .collect::<Vec<_>>();
+        for (pos, idx) in orphaned {
+            if let Some(tile) = self.tiles.get_mut(&pos) {
+                tile.ways[idx] = None;
+            }
+        }
+    }
+
+    pub fn new_plot(&mut self, plot: Plot) -> Id<Plot> { self.plots.insert(plot) }
+
+    /// Like [`Land::new_plot`], but reuse the id of an equal plot for plots
+    /// that carry no data of their own: hazards, dirt and grass. Other plots
+    /// always get a new id, since their id tells them apart (each body of
+    /// water has its own level, for example).
+    pub fn intern_plot(&mut self, plot: Plot) -> Id<Plot> {
+        if !matches!(plot, Plot::Hazard | Plot::Dirt | Plot::Grass) {
+            return self.new_plot(plot);
+        }
+        if let Some((_, id)) = self.interned.iter().find(|(p, _)| *p == plot) {
+            return *id;
+        }
+        let id = self.new_plot(plot);
+        self.interned.push((plot, id));
+        id
+    }
+}
+
+#[cfg(test)]
+impl Land {
+    /// A land made up of the given tiles and their plots, for tests. Equal
+    /// plots share a single plot id.
+    pub(crate) fn with_plots_for_test(tiles: impl IntoIterator<Item = (Vec2<i32>, Plot)>) -> Self {
+        let mut land = Self::new(&mut rand_chacha::ChaChaRng::seed_from_u64(0));
+        let mut plots = Vec::<(Plot, Id<Plot>)>::new();
+        for (pos, plot) in tiles {
+            let id = match plots.iter().find(|(p, _)| *p == plot) {
+                Some((_, id)) => *id,
+                None => {
+                    let id = land.new_plot(plot);
+                    plots.push((plot, id));
+                    id
+                },
+            };
+            land.set(pos, id);
+        }
+        land
+    }
+
+    /// Replace the plot of a tile, keeping its ways.
+    pub(crate) fn set_plot_for_test(&mut self, pos: Vec2<i32>, plot: Plot) {
+        let id = self.new_plot(plot);
+        match self.tiles.get_mut(&pos) {
+            Some(tile) => tile.plot = id,
+            None => self.set(pos, id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        all::ForestKind,
+        sim::{FileOpts, GenOpts, RiverData, SimChunk, WorldOpts},
+    };
+    use building::archetype::crypt::CHAMBER_DEPTH;
+    use common::terrain::{TerrainChunk, TerrainChunkMeta};
+    use rand_chacha::ChaChaRng;
+
+    fn settlement(seed: u64) -> Settlement {
+        let settlement =
+            Settlement::generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(seed));
+        assert_eq!(settlement.validate(), Ok(()));
+        settlement
+    }
+
+    fn with_index(f: impl FnOnce(IndexRef)) {
+        let index = crate::index::Index::new(0);
+        f(IndexRef {
+            colors: &index.colors(),
+            features: &index.features(),
+            index: &index,
+        });
+    }
+
+    fn flat_chunk() -> SimChunk {
+        SimChunk {
+            chaos: 0.0,
+            alt: 0.0,
+            basement: -8.0,
+            water_alt: 0.0,
+            downhill: None,
+            flux: 0.0,
+            temp: 0.0,
+            humidity: 0.0,
+            rockiness: 0.0,
+            tree_density: 0.0,
+            forest_kind: ForestKind::Oak,
+            spawn_rate: 0.0,
+            river: RiverData::default(),
+            surface_veg: 0.0,
+            sites: Vec::new(),
+            place: None,
+            poi: None,
+            path: Default::default(),
+            cave: Default::default(),
+            cliff_height: 0.0,
+            spot: None,
+            contains_waypoint: false,
+        }
+    }
+
+    /// A column of flat, dry land at an altitude of zero.
+    fn flat_column(chunk: &SimChunk) -> ColumnSample {
+        ColumnSample {
+            alt: 0.0,
+            riverless_alt: 0.0,
+            basement: -8.0,
+            chaos: 0.0,
+            water_level: 0.0,
+            warp_factor: 0.0,
+            surface_color: Rgb::zero(),
+            sub_surface_color: Rgb::zero(),
+            tree_density: 0.0,
+            forest_kind: ForestKind::Oak,
+            marble: 0.0,
+            marble_mid: 0.0,
+            marble_small: 0.0,
+            rock_density: 0.0,
+            temp: 0.0,
+            humidity: 0.0,
+            spawn_rate: 0.0,
+            stone_col: Rgb::zero(),
+            water_dist: None,
+            gradient: Some(0.0),
+            path: None,
+            cave: None,
+            snow_cover: false,
+            cliff_offset: 0.0,
+            cliff_height: 0.0,
+            water_vel: Vec3::zero(),
+            ice_depth: 0.0,
+            chunk,
+        }
+    }
+
+    fn terrain_chunk() -> TerrainChunk {
+        TerrainChunk::new(
+            0,
+            Block::new(BlockKind::Earth, Rgb::zero()),
+            Block::air(SpriteKind::Empty),
+            TerrainChunkMeta::void(),
+        )
+    }
+
+    fn tile_center(settlement: &Settlement, tile: Vec2<i32>) -> Vec2<i32> {
+        settlement.origin + tile * AREA_SIZE as i32 + AREA_SIZE as i32 / 2
+    }
+
+    #[test]
+    fn safe_zone_encloses_town() {
+        let settlement = settlement(0);
+        let zone = settlement.safe_zone();
+        let inside = |p: Vec2<i32>| {
+            (0..zone.len()).all(|i| cross(zone[i], zone[(i + 1) % zone.len()], p) >= 0)
+        };
+
+        assert!(zone.len() >= 3);
+        for (&tile, _) in settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+        {
+            assert!(inside(tile_center(&settlement, tile)));
+        }
+        assert!(!inside(tile_center(&settlement, Vec2::new(1000, 0))));
+        assert!(!inside(tile_center(&settlement, Vec2::new(-1000, -1000))));
+    }
+
+    #[test]
+    fn cold_settlement_is_snowy() {
+        let mut settlement = settlement(1);
+        let positions = (0..64)
+            .flat_map(|x| (0..64).map(move |y| Vec3::new(x, y, CONFIG.sea_level as i32)))
+            .collect::<Vec<_>>();
+
+        assert!(!positions.iter().any(|wpos| settlement.snow_cover(*wpos)));
+
+        settlement.cold = true;
+        let snowy = positions
+            .iter()
+            .filter(|wpos| settlement.snow_cover(**wpos))
+            .count();
+        assert!(snowy > positions.len() / 2);
+    }
+
+    #[test]
+    fn cold_settlement_is_snowed_over() { with_index(cold_settlement_is_snowed_over_with) }
+
+    fn cold_settlement_is_snowed_over_with(index: IndexRef) {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let snow = |cold| {
+            let mut settlement = settlement(0);
+            settlement.cold = cold;
+            let house = settlement
+                .structures
+                .iter()
+                .find(|s| matches!(s.kind, StructureKind::House(_)))
+                .unwrap()
+                .bounds();
+            let wpos2d =
+                (settlement.origin + Vec2::from(house.center())).map(|e| e.div_euclid(32) * 32);
+            let mut vol = terrain_chunk();
+            settlement.apply_to(index, wpos2d, |_| Some(&col), &mut vol);
+
+            let is_snow = |x, y, z| {
+                vol.get(Vec3::new(x, y, z))
+                    .map_or(false, |block| block.kind() == BlockKind::ArtSnow)
+            };
+            let (mut roofs, mut ground) = (0, 0);
+            for x in 0..32 {
+                for y in 0..32 {
+                    let rpos = wpos2d + Vec2::new(x, y) - settlement.origin;
+                    if settlement
+                        .structures
+                        .iter()
+                        .any(|s| s.bounds_2d().contains_point(rpos))
+                    {
+                        if (2..house.max.z + 2).any(|z| is_snow(x, y, z)) {
+                            roofs += 1;
+                        }
+                    } else if is_snow(x, y, -1) {
+                        ground += 1;
+                    }
+                }
+            }
+            (roofs, ground)
+        };
+
+        assert_eq!(snow(false), (0, 0));
+        let (roofs, ground) = snow(true);
+        assert!(roofs > 0);
+        assert!(ground > 0);
+    }
+
+    #[test]
+    fn astar_budget_limits_path_length() {
+        let mut land = Land::new(&mut ChaChaRng::seed_from_u64(0));
+        let dest = Vec2::new(300, 0);
+
+        assert_eq!(land.astar_budget(), Land::DEFAULT_ASTAR_BUDGET);
+        assert!(land.find_path(Vec2::zero(), dest, |_, _| 1.0).is_none());
+
+        land.set_astar_budget(1000);
+        assert!(land.find_path(Vec2::zero(), dest, |_, _| 1.0).is_some());
+    }
+
+    #[test]
+    fn water_plot_is_carved() {
+        let bed = Rgb::new(60, 50, 40);
+        for edge_dist in [0.0, 5.0, 50.0] {
+            let depth = water_depth(edge_dist);
+            assert!(depth >= 1);
+            assert_eq!(water_block(-1, depth, bed).kind(), BlockKind::Air);
+            assert_eq!(water_block(-2, depth, bed).kind(), BlockKind::Water);
+            assert_eq!(water_block(-1 - depth, depth, bed).kind(), BlockKind::Water);
+            assert_eq!(water_block(-2 - depth, depth, bed).kind(), BlockKind::Earth);
+        }
+        assert!(water_depth(50.0) > water_depth(0.0));
+    }
+
+    #[test]
+    fn fields_avoid_structures() {
+        for seed in 0..4 {
+            let settlement = settlement(seed);
+            assert!(!settlement.structures.is_empty());
+            for (&tile, _) in
+                settlement.land.tiles.iter().filter(|(_, tile)| {
+                    matches!(settlement.land.plot(tile.plot), Plot::Field { .. })
+                })
+            {
+                for structure in settlement.structures.iter() {
+                    assert!(!tile_aabr(tile).collides_with_aabr(structure.bounds_2d()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn population_seed_leaves_layout_alone() {
+        let generate = |builder: SettlementBuilder| {
+            builder.generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(3))
+        };
+        let base = generate(SettlementBuilder::new());
+        let reseeded =
+            generate(SettlementBuilder::new().stream_seed(SeedStream::Population, 12345));
+
+        assert_eq!(reseeded.stream_seed(SeedStream::Population), 12345);
+        assert_ne!(base.stream_seed(SeedStream::Population), 12345);
+        assert_eq!(
+            base.stream_seed(SeedStream::Layout),
+            reseeded.stream_seed(SeedStream::Layout)
+        );
+        assert_eq!(base.seed(), reseeded.seed());
+
+        assert!(!base.structures.is_empty());
+        assert_eq!(base.structures.len(), reseeded.structures.len());
+        for (a, b) in base.structures.iter().zip(reseeded.structures.iter()) {
+            assert_eq!(a.bounds(), b.bounds());
+            assert_eq!(a.seed(), b.seed());
+        }
+
+        // Each stream gets a seed of its own
+        assert_ne!(
+            base.stream_seed(SeedStream::Layout),
+            base.stream_seed(SeedStream::Decoration)
+        );
+        assert_ne!(
+            base.stream_seed(SeedStream::Decoration),
+            base.stream_seed(SeedStream::Population)
+        );
+    }
+
+    #[test]
+    fn decoration_seed_leaves_spawns_alone() {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let spawns = |builder: SettlementBuilder| {
+            let settlement = builder.generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(3));
+            let mut spawns = Vec::new();
+            for tile in settlement.land.tiles.keys() {
+                for entity in settlement.sample_entities(
+                    settlement.origin + *tile * AREA_SIZE as i32,
+                    |_| Some(&col),
+                    &economy,
+                    None,
+                    1.0,
+                ) {
+                    spawns.push((entity.pos.map(|e| e.to_bits()).into_tuple(), entity.body));
+                }
+            }
+            spawns.sort_by_key(|(pos, _)| *pos);
+            let bounds = settlement
+                .structures
+                .iter()
+                .map(|structure| structure.bounds())
+                .collect::<Vec<_>>();
+            (settlement.seed(), spawns, bounds)
+        };
+
+        let (seed, base, bounds) = spawns(SettlementBuilder::new());
+        let (reseed, redecorated, redecorated_bounds) =
+            spawns(SettlementBuilder::new().stream_seed(SeedStream::Decoration, 12345));
+        assert_ne!(seed, reseed);
+        assert!(!base.is_empty());
+        assert_eq!(base, redecorated);
+        assert!(!bounds.is_empty());
+        assert_eq!(bounds, redecorated_bounds);
+    }
+
+    #[test]
+    fn layout_seed_decides_layout() {
+        // Settlements generated from different rngs but the same layout seed
+        let generate = |rng_seed| {
+            SettlementBuilder::new()
+                .stream_seed(SeedStream::Layout, 12345)
+                .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(rng_seed))
+        };
+        let a = generate(3);
+        let b = generate(4);
+        assert_ne!(a.seed(), b.seed());
+
+        assert!(!a.structures.is_empty());
+        assert_eq!(a.structures.len(), b.structures.len());
+        for (a, b) in a.structures.iter().zip(b.structures.iter()) {
+            assert_eq!(a.bounds(), b.bounds());
+        }
+        for x in -64..64 {
+            for y in -64..64 {
+                let pos = Vec2::new(x, y) * 7;
+                assert_eq!(a.land.warp_cell(pos), b.land.warp_cell(pos));
+            }
+        }
+    }
+
+    #[test]
+    fn seed_uses_are_independent() {
+        let uses = [
+            SeedUse::Warp,
+            SeedUse::Tavern,
+            SeedUse::RoofStyle,
+            SeedUse::Orchard,
+            SeedUse::SpawnChance,
+            SeedUse::Spawn,
+            SeedUse::Dummy,
+            SeedUse::StartingInventory,
+            SeedUse::BunkChance,
+            SeedUse::BunkGuard,
+        ];
+        let settlement = settlement(0);
+        let seeds = uses
+            .iter()
+            .map(|seed_use| settlement.use_seed(*seed_use))
+            .collect::<HashSet<_>>();
+        assert_eq!(seeds.len(), uses.len());
+        assert!(!seeds.contains(&settlement.stream_seed(SeedStream::Layout)));
+        assert!(!seeds.contains(&settlement.stream_seed(SeedStream::Population)));
+    }
+
+    #[test]
+    fn noise_matches_seed() {
+        let settlement = settlement(2);
+        let field = RandomField::new(settlement.seed());
+        for wpos in [Vec3::zero(), Vec3::new(17, -40, 250), Vec3::new(-1, 3, -9)] {
+            assert_eq!(settlement.noise_at(wpos), settlement.noise_at(wpos));
+            assert_eq!(settlement.noise_at(wpos), field.get(wpos));
+        }
+    }
+
+    #[test]
+    fn porch_extends_beyond_wall() {
+        let index = crate::index::Index::new(0);
+        let index = IndexRef {
+            colors: &index.colors(),
+            features: &index.features(),
+            index: &index,
+        };
+        let house = || Building::<House>::generate(3, Vec3::zero(), None);
+        let plain = house();
+        let porched = house().with_porch(Vec2::unit_x());
+        let solid = |building: &Building<House>, pos| {
+            building
+                .sample(index, pos)
+                .map_or(false, |block| block.is_filled())
+        };
+
+        let bounds = porched.bounds_2d();
+        let porch_blocks = (bounds.min.x..=bounds.max.x)
+            .flat_map(|x| (bounds.min.y..=bounds.max.y).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..=building::Porch::HEIGHT).map(move |z| Vec3::new(x, y, z)))
+            .filter(|pos| solid(&porched, *pos) && !solid(&plain, *pos))
+            .collect::<Vec<_>>();
+
+        assert!(!porch_blocks.is_empty());
+        for pos in porch_blocks {
+            let wall_x = (bounds.min.x..=bounds.max.x)
+                .filter(|x| solid(&plain, Vec3::new(*x, pos.y, 1)))
+                .max()
+                .unwrap_or(i32::MIN);
+            assert!(pos.x > wall_x);
+        }
+    }
+
+    #[test]
+    fn neighbors_follow_way_order() {
+        let mut land = Land::new(&mut ChaChaRng::seed_from_u64(0));
+        let grass = land.new_plot(Plot::Grass);
+        land.set(Vec2::new(0, 1), grass);
+        land.set(Vec2::new(1, 0), grass);
+        land.write_path(
+            &[Vec2::zero(), Vec2::new(1, 0)],
+            WayKind::Path,
+            |_| true,
+            false,
+        );
+
+        let neighbors = land.neighbors(Vec2::zero()).collect::<Vec<_>>();
+        assert_eq!(
+            neighbors.iter().map(|(pos, _)| *pos).collect::<Vec<_>>(),
+            vec![
+                Vec2::new(-1, 0),
+                Vec2::new(0, 1),
+                Vec2::new(1, 0),
+                Vec2::new(0, -1)
+            ],
+        );
+        let origin = land.tile_at(Vec2::zero()).unwrap();
+        for (i, (_, tile)) in neighbors.iter().enumerate() {
+            assert_eq!(origin.ways()[i].is_some(), i == 2);
+            assert_eq!(tile.is_some(), i == 1 || i == 2);
+        }
+    }
+
+    #[test]
+    fn plaza_has_one_monument() {
+        let mut plazas = 0;
+        for seed in 0..8 {
+            let settlement = settlement(seed);
+            let plaza = match settlement.town.as_ref().and_then(|town| town.plaza()) {
+                Some(plaza) => plaza.center(),
+                None => continue,
+            };
+            plazas += 1;
+
+            let monuments = settlement
+                .structures
+                .iter()
+                .filter(|s| matches!(s.kind, StructureKind::Monument(_)))
+                .collect::<Vec<_>>();
+            assert_eq!(monuments.len(), 1);
+            assert_eq!(monuments[0].bounds_2d().center().map(to_tile), plaza);
+            assert_eq!(
+                settlement
+                    .landmarks()
+                    .filter(|landmark| landmark.kind == LandmarkKind::Monument)
+                    .count(),
+                1
+            );
+        }
+        assert!(plazas > 0);
+    }
+
+    #[test]
+    fn furrow_period_depends_on_crop() {
+        let period = |crop| {
+            (1..32)
+                .find(|p| {
+                    (0..64).all(|x| {
+                        in_furrow(Vec2::new(x, 0), 0, crop)
+                            == in_furrow(Vec2::new(x + p, 0), 0, crop)
+                    })
+                })
+                .unwrap()
+        };
+
+        assert_eq!(period(Crop::Wheat), Crop::Wheat.row_spacing());
+        assert_eq!(period(Crop::Pumpkin), Crop::Pumpkin.row_spacing());
+        assert_ne!(period(Crop::Wheat), period(Crop::Pumpkin));
+    }
+
+    #[test]
+    fn reserved_plot_excludes_buildings() {
+        let mut settlement = settlement(0);
+        let town_center = settlement.town.as_ref().unwrap().base_tile;
+        let plot = settlement.reserve_plot(town_center, |_| None).unwrap();
+        let tile = settlement
+            .land
+            .tiles
+            .iter()
+            .find(|(_, tile)| tile.plot == plot)
+            .map(|(pos, _)| *pos)
+            .unwrap();
+
+        settlement.structures.clear();
+        settlement.reserved.clear();
+        settlement.place_buildings(&mut GenCtx {
+            sim: None,
+            rng: &mut ChaChaRng::seed_from_u64(0),
+        });
+
+        assert!(!settlement.structures.is_empty());
+        assert!(matches!(
+            settlement.land.plot_at(tile),
+            Some(Plot::Reserved)
+        ));
+        for structure in settlement.structures.iter() {
+            assert!(!tile_aabr(tile).collides_with_aabr(structure.bounds_2d()));
+        }
+    }
+
+    #[test]
+    fn reserved_plot_is_flat() {
+        let mut settlement = settlement(0);
+        let town_center = settlement.town.as_ref().unwrap().base_tile;
+        let plot = settlement
+            .reserve_plot(town_center, |_| Some(10.0))
+            .unwrap();
+
+        let mut checked = 0;
+        for x in -AREA_SIZE as i32 * 2..AREA_SIZE as i32 * 3 {
+            let rpos = town_center * AREA_SIZE as i32 + Vec2::new(x, AREA_SIZE as i32 / 2);
+            let sample = settlement.land.get_at_block(rpos);
+            if settlement.land.tile_at(sample.closest).map(|t| t.plot) != Some(plot) {
+                continue;
+            }
+            // The natural ground slopes steeply beneath the plot
+            let land_surface_z = x;
+            let (surface_z, _) = settlement.surface_z(
+                settlement.origin + rpos,
+                land_surface_z,
+                &sample,
+                &mut |_| None,
+            );
+            assert_eq!(surface_z, 10);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn fewer_civilians_at_night() {
+        let civilians = |period| {
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            (0..1000)
+                .filter_map(|_| Townsfolk::pick(period, &mut rng))
+                .filter(|kind| matches!(kind, Townsfolk::Villager | Townsfolk::Merchant))
+                .count()
+        };
+
+        assert_eq!(civilians(None), civilians(Some(DayPeriod::Noon)));
+        assert!(civilians(Some(DayPeriod::Night)) < civilians(Some(DayPeriod::Noon)));
+    }
+
+    #[test]
+    fn inhospitable_land_is_empty() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        settlement.designate(|_| false, ctx.rng);
+        settlement.populate(&mut ctx);
+
+        assert!(settlement.is_empty());
+        assert!(!self::settlement(0).is_empty());
+    }
+
+    #[test]
+    fn searches_skip_undesignated_land() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut land = Land::new(&mut rng);
+        let hazard = land.hazard;
+        for tile in Spiral2d::new().take(Land::SEARCH_TILES) {
+            land.set(tile, hazard);
+        }
+
+        for origin in [Vec2::zero(), Vec2::new(4, -4)] {
+            assert_eq!(land.find_tile_near(origin, |plot| plot.is_none()), None);
+        }
+    }
+
+    #[test]
+    fn region_matches_chunks() {
+        let settlement = settlement(0);
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        // Cover the keep, which straddles chunk borders
+        let keep = settlement
+            .structures
+            .iter()
+            .find(|s| matches!(s.kind, StructureKind::Keep(_)))
+            .unwrap();
+        let base =
+            (settlement.origin + keep.bounds_2d().min).map2(size, |e, sz| e.div_euclid(sz) * sz);
+        let chunk_wposes = [base, base + Vec2::new(size.x, 0), base + size];
+
+        with_index(|index| {
+            let mut per_chunk = chunk_wposes.map(|_| terrain_chunk());
+            for (wpos, vol) in chunk_wposes.iter().zip(per_chunk.iter_mut()) {
+                settlement.apply_to(index, *wpos, |_| Some(&col), vol);
+            }
+
+            let mut regional = chunk_wposes.map(|_| terrain_chunk());
+            let [a, b, c] = &mut regional;
+            settlement.apply_to_region(
+                index,
+                Aabr {
+                    min: base,
+                    max: base + size * 2,
+                },
+                |_| Some(&col),
+                &mut [
+                    (chunk_wposes[0], a),
+                    (chunk_wposes[1], b),
+                    (chunk_wposes[2], c),
+                ],
+            );
+
+            for (a, b) in per_chunk.iter().zip(regional.iter()) {
+                for x in 0..size.x {
+                    for y in 0..size.y {
+                        for z in -16..64 {
+                            let pos = Vec3::new(x, y, z);
+                            assert!(a.get(pos).ok() == b.get(pos).ok());
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn district_tiles_within_aabr() {
+        for seed in 0..4 {
+            let settlement = settlement(seed);
+            let town = settlement.town.as_ref().unwrap();
+            assert!(town.districts().values().count() > 1);
+            for district in town.districts().values() {
+                let tiles = district.tiles.as_ref().unwrap();
+                assert!(!tiles.is_empty());
+                for tile in tiles.iter() {
+                    assert!(tile.x >= district.aabr.min.x && tile.x < district.aabr.max.x);
+                    assert!(tile.y >= district.aabr.min.y && tile.y < district.aabr.max.y);
+                }
+                assert!(tiles.contains(&district.center()));
+            }
+        }
+    }
+
+    #[test]
+    fn wall_top_is_walkable() {
+        let width = WayKind::Wall.width();
+        for i in 0..8 {
+            let dist = i as f32 / 8.0 * width;
+            for merlon in [false, true] {
+                assert!(wall_is_solid(dist, WALL_WALK_Z - 1, merlon));
+                if dist / width < 0.65 {
+                    // Walkway
+                    assert!(!wall_is_solid(dist, WALL_WALK_Z, merlon));
+                    assert!(!wall_is_solid(dist, WALL_WALK_Z + 1, merlon));
+                } else {
+                    // Parapet
+                    assert!(wall_is_solid(dist, WALL_WALK_Z, merlon));
+                    assert_eq!(wall_is_solid(dist, WALL_WALK_Z + 1, merlon), merlon);
+                }
+            }
+        }
+        assert!(!wall_is_solid(width, 0, true));
+    }
+
+    #[test]
+    fn keep_chest_is_lootable() {
+        with_index(|index| {
+            let settlement = settlement(0);
+            let keep = settlement
+                .structures
+                .iter()
+                .find_map(|s| match &s.kind {
+                    StructureKind::Keep(keep) => Some(keep),
+                    _ => None,
+                })
+                .unwrap();
+            let treasury = keep.treasury();
+            assert!(keep.bounds().contains_point(treasury));
+            let col = flat_column(&flat_chunk());
+            let tile = treasury.xy().map(|e| e.div_euclid(AREA_SIZE as i32));
+            let economy = SiteInformation {
+                id: 0,
+                unconsumed_stock: Default::default(),
+            };
+            assert!(
+                settlement
+                    .sample_entities(
+                        settlement.origin + tile * AREA_SIZE as i32,
+                        |_| Some(&col),
+                        &economy,
+                        None,
+                        1.0,
+                    )
+                    .iter()
+                    .any(|entity| {
+                        entity.body == comp::Body::Object(comp::object::Body::Chest)
+                            && entity.pos.map(|e| e.floor() as i32)
+                                == Vec3::from(settlement.origin) + treasury
+                    })
+            );
+
+            let interactables = settlement.interactables(index);
+            assert_eq!(interactables, settlement.interactables(index));
+
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            let mut ctx = GenCtx {
+                sim: None,
+                rng: &mut rng,
+            };
+            let mut empty = Settlement::new(Vec2::zero(), &mut ctx);
+            empty.designate(|_| false, ctx.rng);
+            empty.populate(&mut ctx);
+            assert!(empty.interactables(index).is_empty());
+        });
+    }
+
+    #[test]
+    fn higher_tiers_have_richer_keep_chests() {
+        let col = flat_column(&flat_chunk());
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let keep_loot = |tier| {
+            let settlement = SettlementBuilder::new().tier(tier).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(0),
+            );
+            assert_eq!(settlement.tier(), tier.min(Settlement::MAX_TIER));
+            let treasury = settlement
+                .structures
+                .iter()
+                .find_map(|s| match &s.kind {
+                    StructureKind::Keep(keep) => Some(keep.treasury()),
+                    _ => None,
+                })
+                .unwrap();
+            let tile = treasury.xy().map(|e| e.div_euclid(AREA_SIZE as i32));
+            settlement
+                .sample_entities(
+                    settlement.origin + tile * AREA_SIZE as i32,
+                    |_| Some(&col),
+                    &economy,
+                    None,
+                    1.0,
+                )
+                .into_iter()
+                .find(|entity| entity.body == comp::Body::Object(comp::object::Body::Chest))
+                .unwrap()
+                .loot
+        };
+
+        let loots = (0..=Settlement::MAX_TIER)
+            .map(keep_loot)
+            .collect::<Vec<_>>();
+        for (tier, loot) in loots.iter().enumerate() {
+            assert_eq!(*loot, LootSpec::LootTable(CHEST_LOOT[tier].into()));
+        }
+        assert_ne!(loots[0], loots[Settlement::MAX_TIER as usize]);
+        assert_eq!(keep_loot(u8::MAX), loots[Settlement::MAX_TIER as usize]);
+
+        assert!(
+            Settlement::tier_for_biome(BiomeKind::Desert)
+                > Settlement::tier_for_biome(BiomeKind::Forest)
+        );
+    }
+
+    #[test]
+    fn dead_ends_taper() {
+        let tile = |ways| Tile {
+            plot: Land::new(&mut ChaChaRng::seed_from_u64(0)).hazard,
+            ways,
+            tower: None,
+        };
+        let path = Some(WayKind::Path);
+        let dead_end = tile([path, None, None, None]);
+        let straight = tile([path, None, path, None]);
+        let junction = tile([path, path, path, None]);
+
+        let max_width = |tile: &Tile| {
+            (0..8)
+                .map(|i| tile.way_width(WayKind::Path, i as f32 / 20.0))
+                .fold(0.0, f32::max)
+        };
+        assert!(max_width(&dead_end) < max_width(&straight));
+        assert!(max_width(&junction) > max_width(&straight));
+        assert_eq!(dead_end.way_width(WayKind::Path, 0.0), 0.0);
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert_eq!(straight.way_width(WayKind::Path, t), WayKind::Path.width());
+        }
+        assert_eq!(
+            dead_end.way_width(WayKind::Path, 0.5),
+            junction.way_width(WayKind::Path, 0.5)
+        );
+    }
+
+    #[test]
+    fn wheat_fields_yield_plant_fiber() {
+        let (settlement, wheat_tiles) = (0..16)
+            .map(settlement)
+            .map(|settlement| {
+                let wheat_tiles = settlement
+                    .land
+                    .tiles
+                    .values()
+                    .filter(|tile| {
+                        matches!(settlement.land.plot(tile.plot), Plot::Field {
+                            crop: Crop::Wheat,
+                            ..
+                        })
+                    })
+                    .count() as u32;
+                (settlement, wheat_tiles)
+            })
+            .find(|(_, wheat_tiles)| *wheat_tiles > 0)
+            .unwrap();
+
+        let yields = settlement.total_yield();
+        assert!(yields[&Crop::Wheat] > 0);
+        assert_eq!(yields[&Crop::Wheat], wheat_tiles * Crop::Wheat.base_yield());
+        assert_eq!(Crop::Wheat.item_asset(), "common.items.flowers.plant_fiber");
+        for crop in [
+            Crop::Corn,
+            Crop::Wheat,
+            Crop::Cabbage,
+            Crop::Pumpkin,
+            Crop::Flax,
+            Crop::Carrot,
+            Crop::Tomato,
+            Crop::Radish,
+            Crop::Turnip,
+            Crop::Sunflower,
+        ] {
+            assert!(Item::new_from_asset(crop.item_asset()).is_ok());
+        }
+    }
+
+    #[test]
+    fn shared_paths_match_single_paths() {
+        let mut land = Land::new(&mut ChaChaRng::seed_from_u64(0));
+        let dirt = land.new_plot(Plot::Dirt);
+        let water = land.new_plot(Plot::Water);
+        for x in -6..=6 {
+            for y in -6..=6 {
+                // A pond with a gap, which paths should go around
+                let plot = if x == 0 && y != 4 { water } else { dirt };
+                land.set(Vec2::new(x, y), plot);
+            }
+        }
+        let cost_fn = |_: Option<&Tile>, to: Option<&Tile>| match to {
+            Some(tile) if land.plot(tile.plot) == &Plot::Water => 8.0,
+            Some(_) => 1.0,
+            None => 1000.0,
+        };
+        let path_cost = |path: &Path<Vec2<i32>>| {
+            path.nodes()
+                .windows(2)
+                .map(|w| cost_fn(land.tile_at(w[0]), land.tile_at(w[1])))
+                .sum::<f32>()
+        };
+
+        let dest = Vec2::new(3, -2);
+        let origins = [
+            Vec2::new(-5, -5),
+            Vec2::new(-4, 3),
+            Vec2::new(5, 6),
+            Vec2::new(-1, 0),
+            dest,
+        ];
+        let paths = land.find_paths_to(&origins, dest, cost_fn);
+        assert_eq!(paths.len(), origins.len());
+        for (origin, path) in origins.iter().zip(paths) {
+            let path = path.unwrap();
+            let single = land.find_path(*origin, dest, cost_fn).unwrap();
+            assert_eq!(path.start(), Some(origin));
+            assert_eq!(path.end(), Some(&dest));
+            assert!(
+                path.nodes()
+                    .windows(2)
+                    .all(|w| (w[1] - w[0]).map(|e| e.abs()).sum() == 1)
+            );
+            assert_eq!(path_cost(&path), path_cost(&single));
+        }
+    }
+
+    #[test]
+    fn shared_paths_are_cheapest_over_free_steps() {
+        let mut land = Land::new(&mut ChaChaRng::seed_from_u64(0));
+        let grass = land.new_plot(Plot::Grass);
+        let dirt = land.new_plot(Plot::Dirt);
+        for x in -6..=6 {
+            for y in -6..=6 {
+                // A ring of road that costs nothing to follow
+                let road = x.abs() == 5 || y.abs() == 5;
+                land.set(Vec2::new(x, y), if road { dirt } else { grass });
+            }
+        }
+        let cost_fn = |_: Option<&Tile>, to: Option<&Tile>| match to {
+            Some(tile) if land.plot(tile.plot) == &Plot::Dirt => 0.0,
+            Some(_) => 1.0,
+            None => 1000.0,
+        };
+        let path_cost = |path: &Path<Vec2<i32>>| {
+            path.nodes()
+                .windows(2)
+                .map(|w| cost_fn(land.tile_at(w[0]), land.tile_at(w[1])))
+                .sum::<f32>()
+        };
+
+        let dest = Vec2::new(5, 4);
+        let origins = [Vec2::new(-5, -4), Vec2::new(-4, 5), Vec2::new(0, 0)];
+        let paths = land.find_paths_to(&origins, dest, cost_fn);
+        for (origin, path) in origins.iter().zip(paths.iter()) {
+            let path = path.as_ref().unwrap();
+            let single = land.find_path(*origin, dest, cost_fn).unwrap();
+            assert_eq!(path.start(), Some(origin));
+            assert_eq!(path.end(), Some(&dest));
+            assert!(path_cost(path) <= path_cost(&single));
+        }
+        // Around the ring, from one road tile to another, is free
+        assert_eq!(path_cost(paths[0].as_ref().unwrap()), 0.0);
+    }
+
+    #[test]
+    fn roof_styles_share_walls() { with_index(|index| roof_styles_share_walls_with(index)); }
+
+    fn roof_styles_share_walls_with(index: IndexRef) {
+        let house = |roof_style| {
+            Building::<House>::generate(5, Vec3::zero(), None).with_roof_style(roof_style)
+        };
+        let gable = house(RoofStyle::Gable);
+        let flat = house(RoofStyle::Flat);
+        assert!(flat.bounds().max.z < gable.bounds().max.z);
+
+        let bounds = gable.bounds();
+        let columns = (bounds.min.x..=bounds.max.x)
+            .flat_map(|x| (bounds.min.y..=bounds.max.y).map(move |y| Vec2::new(x, y)))
+            .collect::<Vec<_>>();
+        let differs = |pos: Vec3<i32>| {
+            let solid = |house: &Building<House>| {
+                house
+                    .sample(index, pos)
+                    .map_or(false, |block| block.is_filled())
+            };
+            solid(&gable) != solid(&flat)
+        };
+
+        // The ground floor is below any roof
+        for z in 0..6 {
+            assert!(columns.iter().all(|pos| !differs(pos.with_z(z))));
+        }
+        assert!((6..=bounds.max.z).any(|z| columns.iter().any(|pos| differs(pos.with_z(z)))));
+    }
+
+    #[test]
+    fn excluded_crops_are_never_planted() {
+        let crops = |settlement: &Settlement| {
+            settlement
+                .land
+                .tiles
+                .values()
+                .filter_map(|tile| match settlement.land.plot(tile.plot) {
+                    Plot::Field { crop, .. } => Some(*crop),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let build = |seed, excluded: &[Crop]| {
+            SettlementBuilder::new().exclude_crops(excluded).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            )
+        };
+
+        for seed in 0..4 {
+            let settlement = build(seed, &[Crop::Sunflower]);
+            assert!(!crops(&settlement).is_empty());
+            assert!(
+                crops(&settlement)
+                    .iter()
+                    .all(|crop| *crop != Crop::Sunflower)
+            );
+
+            // The remaining crops share all of the fields
+            let settlement = build(seed, &[
+                Crop::Corn,
+                Crop::Wheat,
+                Crop::Cabbage,
+                Crop::Pumpkin,
+                Crop::Flax,
+                Crop::Carrot,
+                Crop::Tomato,
+            ]);
+            assert!(!crops(&settlement).is_empty());
+            assert!(crops(&settlement).iter().all(|crop| *crop == Crop::Radish));
+
+            let settlement = build(seed, &[Crop::Radish, Crop::Sunflower, Crop::Corn]);
+            let fields = settlement
+                .land
+                .tiles
+                .iter()
+                .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Field { .. }))
+                .map(|(pos, _)| *pos)
+                .take(4)
+                .collect::<Vec<_>>();
+            let chunk = flat_chunk();
+            let col = flat_column(&chunk);
+            with_index(|index| {
+                for tile in fields {
+                    let mut vol = terrain_chunk();
+                    let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+                    let wpos =
+                        tile_center(&settlement, tile).map2(size, |e, sz| e.div_euclid(sz) * sz);
+                    settlement.apply_to(index, wpos, |_| Some(&col), &mut vol);
+                    assert!(vol.iter_changed().all(|(_, block)| !matches!(
+                        block.get_sprite(),
+                        Some(SpriteKind::Sunflower | SpriteKind::Radish | SpriteKind::Corn)
+                    )));
+                }
+            });
+        }
+
+        // With nothing left to plant, there are no fields at all
+        assert!(
+            crops(&build(0, &[
+                Crop::Corn,
+                Crop::Wheat,
+                Crop::Cabbage,
+                Crop::Pumpkin,
+                Crop::Flax,
+                Crop::Carrot,
+                Crop::Tomato,
+                Crop::Radish,
+            ]))
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn walls_step_without_gaps() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let hazard = settlement.land.hazard;
+        let tiles = [Vec2::new(0, 0), Vec2::new(1, 0)];
+        for tile in tiles {
+            settlement.land.set(tile, hazard);
+        }
+        settlement
+            .land
+            .write_path(&tiles, WayKind::Wall, |_| true, true);
+
+        // The ground rises steeply at the border between the two tiles
+        let step_x = AREA_SIZE as i32;
+        let chunk = flat_chunk();
+        let low = flat_column(&chunk);
+        let mut high = flat_column(&chunk);
+        high.alt = 12.0;
+        high.riverless_alt = 12.0;
+
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let chunk_wposes =
+            [(0, -1), (1, -1), (0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| Vec2::new(x, y) * size);
+        let mut vols = chunk_wposes.map(|_| terrain_chunk());
+        with_index(|index| {
+            let [a, b, c, d, e, f] = &mut vols;
+            settlement.apply_to_region(
+                index,
+                Aabr {
+                    min: chunk_wposes[0],
+                    max: chunk_wposes[5] + size,
+                },
+                |wpos| Some(if wpos.x < step_x { &low } else { &high }),
+                &mut [
+                    (chunk_wposes[0], a),
+                    (chunk_wposes[1], b),
+                    (chunk_wposes[2], c),
+                    (chunk_wposes[3], d),
+                    (chunk_wposes[4], e),
+                    (chunk_wposes[5], f),
+                ],
+            );
+        });
+
+        let wall_zs = |wpos: Vec2<i32>| {
+            let (vol_wpos, vol) = chunk_wposes
+                .iter()
+                .zip(vols.iter())
+                .find(|(min, _)| {
+                    let offs = wpos - **min;
+                    offs.x >= 0 && offs.y >= 0 && offs.x < size.x && offs.y < size.y
+                })
+                .unwrap();
+            (-8..32)
+                .filter(|z| {
+                    vol.get((wpos - *vol_wpos).with_z(*z))
+                        .map_or(false, |block| block.kind() == BlockKind::Wood)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut steps = 0;
+        for y in -size.y..size.y * 2 {
+            let lower = wall_zs(Vec2::new(step_x - 1, y));
+            let upper = wall_zs(Vec2::new(step_x, y));
+            if let (Some(lower_top), Some(upper_bottom)) = (lower.last(), upper.first()) {
+                steps += 1;
+                assert!(upper_bottom <= &(lower_top + 1));
+                // The upper column is solid all the way down
+                assert!(
+                    upper
+                        .windows(2)
+                        .take_while(|w| w[0] < 12)
+                        .all(|w| w[1] == w[0] + 1)
+                );
+            }
+        }
+        assert!(steps > 0);
+    }
+
+    #[test]
+    fn synthetic_land() {
+        // A 5x5 land with a pond in the middle of its eastern edge
+        let mut land = Land::with_plots_for_test(
+            (0..5)
+                .flat_map(|x| (0..5).map(move |y| Vec2::new(x, y)))
+                .map(|pos| (pos, Plot::Grass)),
+        );
+        land.set_plot_for_test(Vec2::new(4, 2), Plot::Water);
+        assert!(matches!(land.plot_at(Vec2::new(0, 0)), Some(Plot::Grass)));
+        assert!(matches!(land.plot_at(Vec2::new(4, 2)), Some(Plot::Water)));
+        assert!(land.plot_at(Vec2::new(5, 0)).is_none());
+
+        let grown = land.grow_from(
+            Vec2::new(2, 2),
+            100,
+            &mut ChaChaRng::seed_from_u64(0),
+            |plot| matches!(plot, Some(Plot::Grass)),
+        );
+        assert_eq!(grown.len(), 24);
+        assert!(!grown.contains(&Vec2::new(4, 2)));
+
+        let path = land
+            .find_path(Vec2::new(4, 0), Vec2::new(4, 4), |_, to| match to {
+                Some(tile) if land.plot(tile.plot) == &Plot::Grass => 1.0,
+                _ => 100.0,
+            })
+            .unwrap();
+        assert_eq!(path.start(), Some(&Vec2::new(4, 0)));
+        assert_eq!(path.end(), Some(&Vec2::new(4, 4)));
+        assert!(!path.iter().any(|pos| *pos == Vec2::new(4, 2)));
+
+        let path = path.iter().copied().collect::<Vec<_>>();
+        land.write_path(&path, WayKind::Path, |_| true, false);
+        for pos in &path {
+            assert!(land.tile_at(*pos).unwrap().contains(WayKind::Path));
+        }
+        assert!(
+            !land
+                .tile_at(Vec2::new(0, 0))
+                .unwrap()
+                .contains(WayKind::Path)
+        );
+    }
+
+    #[test]
+    fn sparser_lamps_along_path() {
+        let lamps = |spacing| {
+            let settlement = SettlementBuilder::new()
+                .lamps(LampParams {
+                    spacing,
+                    random_chance: 0.0,
+                    ..LampParams::default()
+                })
+                .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+            // A straight path running diagonally away from the origin
+            let dir = Vec2::new(0.8, 0.6);
+            (0..200)
+                .flat_map(|x| (0..200).map(move |y| Vec2::new(x, y)))
+                .filter(|wpos| {
+                    let nearest = dir * wpos.map(|e| e as f32).dot(dir);
+                    let dist = nearest.distance(wpos.map(|e| e as f32));
+                    nearest.magnitude() < 150.0
+                        && settlement.has_street_lamp(*wpos, Some((dist, nearest)))
+                })
+                .count()
+        };
+
+        assert_eq!(LampParams::default().spacing, 15.0);
+        let dense = lamps(15.0);
+        assert!(dense > 0);
+        assert!(lamps(30.0) < dense);
+    }
+
+    #[test]
+    fn random_lamps_stand_away_from_paths() {
+        let settlement = SettlementBuilder::new()
+            .lamps(LampParams {
+                random_chance: 1.0,
+                ..LampParams::default()
+            })
+            .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+        assert!(settlement.has_street_lamp(Vec2::new(3, 5), None));
+        assert!(!settlement.has_street_lamp(Vec2::new(3, 5), Some((10.0, Vec2::new(3.0, 15.0)))));
+
+        let none = SettlementBuilder::new()
+            .lamps(LampParams {
+                random_chance: 0.0,
+                ..LampParams::default()
+            })
+            .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+        assert!(!none.has_street_lamp(Vec2::new(3, 5), None));
+    }
+
+    #[test]
+    fn pastures_attract_grazers() {
+        let (settlement, pasture) = (0..16)
+            .map(settlement)
+            .find_map(|settlement| {
+                let pasture = settlement
+                    .land
+                    .tiles
+                    .iter()
+                    .filter(|(_, tile)| settlement.land.plot(tile.plot) == &Plot::Pasture)
+                    .map(|(pos, _)| *pos)
+                    .collect::<Vec<_>>();
+                Some((settlement, pasture)).filter(|(_, pasture)| !pasture.is_empty())
+            })
+            .expect("no settlement with a pasture");
+        let town = settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+            .map(|(pos, _)| *pos)
+            .take(pasture.len())
+            .collect::<Vec<_>>();
+
+        let chunk = flat_chunk();
+        let column = flat_column(&chunk);
+        let animals = |tiles: &[Vec2<i32>]| {
+            let mut supplement = ChunkSupplement::default();
+            for tile in tiles {
+                settlement.apply_supplement(
+                    &mut ChaChaRng::seed_from_u64(0),
+                    settlement.origin + tile * AREA_SIZE as i32,
+                    |_| Some(&column),
+                    &mut supplement,
+                    SiteInformation {
+                        id: 0,
+                        unconsumed_stock: Default::default(),
+                    },
+                    None,
+                );
+            }
+            supplement
+                .entities
+                .iter()
+                .filter(|entity| {
+                    matches!(
+                        entity.body,
+                        comp::Body::QuadrupedSmall(_) | comp::Body::QuadrupedMedium(_)
+                    )
+                })
+                .count()
+        };
+
+        assert!(animals(&pasture) > animals(&town));
+    }
+
+    #[test]
+    fn same_seed_same_building() { with_index(|index| same_seed_same_building_with(index)); }
+
+    fn same_seed_same_building_with(index: IndexRef) {
+        let a = Building::<House>::generate(7, Vec3::zero(), None);
+        let b = Building::<House>::generate(7, Vec3::new(100, -50, 20), None);
+        assert_eq!(a.seed(), b.seed());
+
+        let bounds = a.bounds();
+        let offset = Vec3::new(100, -50, 20);
+        assert_eq!(b.bounds().min, bounds.min + offset);
+        assert_eq!(b.bounds().max, bounds.max + offset);
+        for x in bounds.min.x..bounds.max.x {
+            for y in bounds.min.y..bounds.max.y {
+                for z in bounds.min.z..bounds.max.z {
+                    let pos = Vec3::new(x, y, z);
+                    assert_eq!(a.sample(index, pos), b.sample(index, pos + offset));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn color_map_matches_get_color() {
+        with_index(|index| color_map_matches_get_color_with(index));
+    }
+
+    fn color_map_matches_get_color_with(index: IndexRef) {
+        let settlement = settlement(0);
+        let rect = Aabr {
+            min: Vec2::new(-150, -120),
+            max: Vec2::new(150, 130),
+        };
+        let map = settlement.color_map(index, rect);
+
+        assert_eq!(map.len(), 300 * 250);
+        let positions = (rect.min.y..rect.max.y)
+            .flat_map(|y| (rect.min.x..rect.max.x).map(move |x| Vec2::new(x, y)));
+        for (pos, color) in positions.zip(map) {
+            assert_eq!(settlement.get_color(index, pos), color, "at {:?}", pos);
+        }
+    }
+
+    #[test]
+    fn props_stay_off_paths() { with_index(|index| props_stay_off_paths_with(index)); }
+
+    fn props_stay_off_paths_with(index: IndexRef) {
+        let settlement = SettlementBuilder::new().prop_density(0.25).generate(
+            Vec2::zero(),
+            None,
+            &mut ChaChaRng::seed_from_u64(0),
+        );
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let footprints = settlement
+            .structures
+            .iter()
+            .map(Structure::bounds_2d)
+            .collect::<Vec<_>>();
+
+        let mut props = 0;
+        for bounds in footprints.iter().take(4) {
+            let chunk_min = (settlement.origin + bounds.min).map2(size, |e, sz| e.div_euclid(sz));
+            let chunk_max = (settlement.origin + bounds.max).map2(size, |e, sz| e.div_euclid(sz));
+            for cx in chunk_min.x..=chunk_max.x {
+                for cy in chunk_min.y..=chunk_max.y {
+                    let chunk_wpos = Vec2::new(cx, cy) * size;
+                    let mut vol = terrain_chunk();
+                    settlement.apply_to(index, chunk_wpos, |_| Some(&col), &mut vol);
+
+                    for x in 0..size.x {
+                        for y in 0..size.y {
+                            let rpos = chunk_wpos + Vec2::new(x, y) - settlement.origin;
+                            if footprints.iter().any(|b| b.contains_point(rpos)) {
+                                continue;
+                            }
+                            let is_prop = matches!(
+                                vol.get(Vec3::new(x, y, 0))
+                                    .ok()
+                                    .and_then(|b| b.get_sprite()),
+                                Some(
+                                    SpriteKind::Barrel
+                                        | SpriteKind::CrateBlock
+                                        | SpriteKind::WheatYellow
+                                )
+                            );
+                            let sample = settlement.land.get_at_block(rpos);
+                            if is_prop && matches!(sample.plot, Some(Plot::Town { .. })) {
+                                assert!(sample.way.is_none());
+                                props += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        assert!(props > 0);
+    }
+
+    #[test]
+    fn walls_fill_dips() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let hazard = settlement.land.hazard;
+        let tiles = [Vec2::new(0, 0), Vec2::new(1, 0)];
+        for tile in tiles {
+            settlement.land.set(tile, hazard);
+        }
+        settlement
+            .land
+            .write_path(&tiles, WayKind::Wall, |_| true, true);
+
+        // A dip four blocks deep beneath part of the wall
+        let mut vol = terrain_chunk();
+        let dip = 8..24;
+        for x in dip.clone() {
+            for y in 0..AREA_SIZE as i32 {
+                for z in -4..0 {
+                    vol.set(Vec3::new(x, y, z), Block::air(SpriteKind::Empty))
+                        .unwrap();
+                }
+            }
+        }
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        with_index(|index| settlement.apply_to(index, Vec2::zero(), |_| Some(&col), &mut vol));
+
+        let mut wall_columns = 0;
+        for x in dip {
+            for y in 0..AREA_SIZE as i32 {
+                let is_wall = vol
+                    .get(Vec3::new(x, y, 0))
+                    .map_or(false, |block| block.kind() == BlockKind::Wood);
+                if is_wall {
+                    wall_columns += 1;
+                    for z in -5..0 {
+                        assert!(vol.get(Vec3::new(x, y, z)).unwrap().is_filled());
+                    }
+                }
+            }
+        }
+        assert!(wall_columns > 0);
+    }
+
+    #[test]
+    fn data_reflects_generation() {
+        let settlement = settlement(0);
+        let data = settlement.data();
+
+        assert_eq!(data.name(), settlement.name());
+        assert_eq!(data.plots().count(), settlement.land.tiles.len());
+        assert!(
+            data.plots()
+                .any(|(_, plot)| matches!(plot, Plot::Town { .. }))
+        );
+        assert!(data.ways().any(|(_, _, way)| way == WayKind::Path));
+        assert_eq!(data.structures().len(), settlement.structures.len());
+        assert!(!data.structures().is_empty());
+        assert!(data.landmarks().any(|l| l.kind == LandmarkKind::Keep));
+        with_index(|index| assert!(!data.interactables(index).is_empty()));
+    }
+
+    #[test]
+    fn town_spares_fields() {
+        for seed in 0..8 {
+            let mut rng = ChaChaRng::seed_from_u64(seed);
+            let mut ctx = GenCtx {
+                sim: None,
+                rng: &mut rng,
+            };
+            let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+            settlement.place_farms(&mut ctx);
+            let fields = settlement
+                .land
+                .tiles
+                .iter()
+                .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Field { .. }))
+                .map(|(pos, tile)| (*pos, tile.plot))
+                .collect::<Vec<_>>();
+            assert!(!fields.is_empty());
+
+            settlement.place_town(&mut ctx);
+            assert!(settlement.town.is_some());
+            for (pos, plot) in fields {
+                assert_eq!(
+                    settlement.land.tile_at(pos).map(|tile| tile.plot),
+                    Some(plot)
+                );
+            }
+        }
+    }
+
+    struct Pillar;
+
+    impl StructureSource for Pillar {
+        fn bounds_2d(&self) -> Aabr<i32> {
+            Aabr {
+                min: Vec2::new(500, 500),
+                max: Vec2::new(502, 502),
+            }
+        }
+
+        fn bounds(&self) -> Aabb<i32> {
+            Aabb {
+                min: Vec3::new(500, 500, 0),
+                max: Vec3::new(502, 502, 10),
+            }
+        }
+
+        fn sample(&self, _index: IndexRef, rpos: Vec3<i32>) -> Option<Block> {
+            Aabb {
+                min: Vec3::new(500, 500, 0),
+                max: Vec3::new(502, 502, 10),
+            }
+            .contains_point(rpos)
+            .then(|| Block::new(BlockKind::Rock, Rgb::zero()))
+        }
+
+        fn seed(&self) -> u32 { 42 }
+    }
+
+    #[test]
+    fn custom_structures_are_placed() {
+        let mut settlement = settlement(0);
+        settlement.add_custom_structure(Box::new(Pillar));
+        let structure = settlement.structures.last().unwrap();
+        assert_eq!(structure.bounds_2d(), Pillar.bounds_2d());
+        assert_eq!(structure.bounds(), Pillar.bounds());
+        assert_eq!(structure.seed(), 42);
+        assert!(settlement.reserved.contains(&Vec2::new(15, 15)));
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let mut vol = terrain_chunk();
+        let chunk_wpos = settlement.origin + Vec2::new(480, 480);
+        with_index(|index| {
+            assert!(structure.sample(index, Vec3::new(501, 501, 5)).is_some());
+            settlement.apply_to(index, chunk_wpos, |_| Some(&col), &mut vol);
+        });
+        assert_eq!(
+            vol.get(Vec3::new(21, 21, 5)).unwrap().kind(),
+            BlockKind::Rock
+        );
+    }
+
+    #[test]
+    fn entities_are_reproducible() {
+        let settlement = settlement(0);
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let sample = |tile: Vec2<i32>| {
+            settlement
+                .sample_entities(
+                    settlement.origin + tile * AREA_SIZE as i32,
+                    |_| Some(&col),
+                    &economy,
+                    None,
+                    1.0,
+                )
+                .into_iter()
+                .map(|entity| (entity.pos, entity.body))
+                .collect::<Vec<_>>()
+        };
+
+        let mut sampled = 0;
+        for (tile, _) in settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+        {
+            let entities = sample(*tile);
+            sampled += entities.len();
+            assert_eq!(entities, sample(*tile));
+        }
+        assert!(sampled > 0);
+    }
+
+    #[test]
+    fn houses_have_exterior_windows() {
+        with_index(|index| houses_have_exterior_windows_with(index));
+    }
+
+    fn houses_have_exterior_windows_with(index: IndexRef) {
+        let house = Building::<House>::generate(3, Vec3::zero(), None);
+        let bounds = house.bounds();
+        let is_window = |pos: Vec3<i32>| {
+            matches!(
+                house
+                    .sample(index, pos)
+                    .and_then(|block| block.get_sprite()),
+                Some(
+                    SpriteKind::Window1
+                        | SpriteKind::Window2
+                        | SpriteKind::Window3
+                        | SpriteKind::Window4
+                )
+            )
+        };
+
+        let windows = (bounds.min.x..=bounds.max.x)
+            .flat_map(|x| (bounds.min.y..=bounds.max.y).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (bounds.min.z..=bounds.max.z).map(move |z| Vec3::new(x, y, z)))
+            .filter(|pos| is_window(*pos))
+            .collect::<Vec<_>>();
+
+        assert!(windows.len() > 2);
+        for pos in windows {
+            // Every window looks out onto open air
+            assert!(
+                CARDINALS
+                    .iter()
+                    .any(|dir| house.sample(index, pos + Vec3::from(*dir)).is_none()),
+                "window at {:?} is not on an exterior wall",
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn map_icons() {
+        let mut fort = settlement(0);
+        assert!(
+            fort.structures
+                .iter()
+                .any(|s| matches!(s.kind, StructureKind::Keep(_)))
+        );
+        let base = fort.town.as_ref().unwrap().base_tile;
+        let wall = [base + Vec2::new(6, 0), base + Vec2::new(6, 1)];
+        fort.land.write_path(&wall, WayKind::Wall, |_| true, true);
+        assert_eq!(fort.map_icon(), MapIconKind::Fort);
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut hamlet = Settlement::new(Vec2::zero(), &mut ctx);
+        assert_eq!(hamlet.map_icon(), MapIconKind::Ruins);
+        hamlet.place_farms(&mut ctx);
+        assert_eq!(hamlet.map_icon(), MapIconKind::Hamlet);
+        assert_ne!(hamlet.map_color(), fort.map_color());
+    }
+
+    #[test]
+    fn cramped_sites_get_fewer_farms() {
+        let farms = |site_chunks: i32| {
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            let mut ctx = GenCtx {
+                sim: None,
+                rng: &mut rng,
+            };
+            let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+            settlement.designate(
+                |cpos| cpos.map(|e| e.abs()).reduce_max() < site_chunks,
+                ctx.rng,
+            );
+            settlement.place_farms(&mut ctx);
+            settlement.farms.values().len()
+        };
+
+        assert_eq!(FarmParams::for_land(1000), FarmParams::default());
+        let tiny = farms(4);
+        let large = farms(64);
+        assert!(tiny > 0);
+        assert!(tiny < large);
+    }
+
+    #[test]
+    fn terrain_and_structures_apply_separately() {
+        let settlement = settlement(0);
+        let keep = settlement
+            .structures
+            .iter()
+            .find(|s| matches!(s.kind, StructureKind::Keep(_)))
+            .unwrap();
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let chunk_wpos = (settlement.origin + keep.bounds_2d().center())
+            .map2(size, |e, sz| e.div_euclid(sz) * sz);
+        let in_structure = |x: i32, y: i32| {
+            let rpos = chunk_wpos + Vec2::new(x, y) - settlement.origin;
+            settlement
+                .structures
+                .iter()
+                .any(|s| s.bounds_2d().contains_point(rpos))
+        };
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let fresh = terrain_chunk();
+        let (mut full, mut terrain, mut structures) =
+            (terrain_chunk(), terrain_chunk(), terrain_chunk());
+        with_index(|index| {
+            settlement.apply_to(index, chunk_wpos, |_| Some(&col), &mut full);
+            settlement.apply_terrain(index, chunk_wpos, |_| Some(&col), &mut terrain);
+            settlement.apply_structures(index, chunk_wpos, |_| Some(&col), &mut structures);
+        });
+
+        let mut building_blocks = 0;
+        for x in 0..size.x {
+            for y in 0..size.y {
+                for z in -8..32 {
+                    let pos = Vec3::new(x, y, z);
+                    let get = |vol: &TerrainChunk| *vol.get(pos).unwrap();
+                    if in_structure(x, y) {
+                        if z > WALL_WALK_Z + 2 {
+                            // Nothing but structures stands this high
+                            assert_eq!(get(&terrain), get(&fresh));
+                        }
+                        if get(&structures) != get(&fresh) {
+                            building_blocks += 1;
+                        }
+                    } else {
+                        assert_eq!(get(&structures), get(&fresh));
+                        assert_eq!(get(&terrain), get(&full));
+                    }
+                }
+            }
+        }
+        assert!(building_blocks > 0);
+    }
+
+    #[test]
+    fn sprite_spots_cover_drawn_sprites() { with_index(sprite_spots_cover_drawn_sprites_with) }
+
+    fn sprite_spots_cover_drawn_sprites_with(index: IndexRef) {
+        let notable = |block: &Block| {
+            block.get_glow().is_some() || block.get_sprite().map_or(false, |s| is_interactable(&s))
+        };
+        for seed in 0..4 {
+            let settlement = settlement(seed);
+            for structure in settlement.structures.iter() {
+                let bounds = structure.bounds();
+                let mut drawn = (bounds.min.x..bounds.max.x + 1)
+                    .flat_map(|x| (bounds.min.y..bounds.max.y + 1).map(move |y| (x, y)))
+                    .flat_map(|(x, y)| {
+                        (bounds.min.z..bounds.max.z + 1).map(move |z| Vec3::new(x, y, z))
+                    })
+                    .filter(|rpos| {
+                        structure
+                            .sample(index, *rpos)
+                            .map_or(false, |b| notable(&b))
+                    })
+                    .map(|rpos| rpos.into_tuple())
+                    .collect::<Vec<_>>();
+                let mut listed = structure
+                    .sprites(index)
+                    .into_iter()
+                    .filter(|(_, block)| notable(block))
+                    .map(|(rpos, _)| rpos.into_tuple())
+                    .collect::<Vec<_>>();
+                drawn.sort_unstable();
+                listed.sort_unstable();
+                assert_eq!(drawn, listed, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn towns_have_one_notice_board() { with_index(towns_have_one_notice_board_with) }
+
+    fn towns_have_one_notice_board_with(index: IndexRef) {
+        let to_tile = |e: i32| e.div_euclid(AREA_SIZE as i32);
+        let mut towns = 0;
+        for seed in 0..8 {
+            let settlement = settlement(seed);
+            let boards = settlement
+                .interactables(index)
+                .into_iter()
+                .filter(|(_, sprite)| *sprite == SpriteKind::Sign)
+                .map(|(wpos, _)| (Vec2::from(wpos) - settlement.origin).map(to_tile))
+                .collect::<Vec<_>>();
+
+            match settlement.map_icon() {
+                MapIconKind::Town | MapIconKind::Fort => {
+                    towns += 1;
+                    assert_eq!(boards.len(), 1);
+                    let town = settlement.town.as_ref().unwrap();
+                    let tile = boards[0];
+                    assert!(
+                        town.plaza().map_or(false, |plaza| plaza.contains(tile))
+                            || tile.distance_squared(town.base_tile) <= 2i32.pow(2)
+                            || town
+                                .plaza()
+                                .map_or(false, |plaza| tile.distance_squared(plaza.center())
+                                    <= 2i32.pow(2))
+                    );
+                },
+                _ => assert!(boards.is_empty()),
+            }
+        }
+        assert!(towns > 0);
+    }
+
+    #[test]
+    fn surface_alt_matches_applied_ground() {
+        let chunk = flat_chunk();
+        // Ground rising by half a block per block along x
+        let cols = (-1..AREA_SIZE as i32 + 1)
+            .map(|x| ColumnSample {
+                alt: x as f32 * 0.5,
+                riverless_alt: x as f32 * 0.5,
+                ..flat_column(&chunk)
+            })
+            .collect::<Vec<_>>();
+        let get_column = |wpos: Vec2<i32>| cols.get((wpos.x + 1) as usize);
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        settlement.terraced_fields = true;
+        settlement.leveling = 1.0;
+        let farm = settlement.farms.insert(Farm {
+            base_tile: Vec2::zero(),
+        });
+        let field = settlement.land.new_plot(Plot::Field {
+            farm,
+            seed: 0,
+            crop: Crop::Wheat,
+            center: Vec2::zero(),
+        });
+        let town = settlement.land.new_plot(Plot::Town { district: None });
+
+        for plot in [field, town] {
+            for x in -1..2 {
+                for y in -1..2 {
+                    settlement.land.set(Vec2::new(x, y), plot);
+                }
+            }
+            settlement.level_town(|_| Some(6.0));
+
+            let mut vol = terrain_chunk();
+            with_index(|index| settlement.apply_terrain(index, Vec2::zero(), get_column, &mut vol));
+
+            for x in 4..AREA_SIZE as i32 - 4 {
+                let top = (-16..32)
+                    .rev()
+                    .find(|z| vol.get(Vec3::new(x, 16, *z)).unwrap().is_filled())
+                    .unwrap();
+                assert_eq!(
+                    settlement.surface_alt(Vec2::new(x, 16), get_column),
+                    Some((top + 1) as f32),
+                    "x {}",
+                    x,
+                );
+            }
+        }
+
+        // The town's ground is leveled towards the altitude it was given
+        let center = Vec2::broadcast(AREA_SIZE as i32 / 2);
+        assert_eq!(settlement.surface_alt(center, get_column), Some(6.0));
+        // Beyond the settlement, the terrain is left as it is
+        let far = Vec2::new(AREA_SIZE as i32, 100 * AREA_SIZE as i32);
+        assert_eq!(settlement.surface_alt(far, get_column), Some(16.0));
+        assert_eq!(settlement.surface_alt(Vec2::new(-5, 0), get_column), None);
+    }
+
+    #[test]
+    fn terraced_fields_are_stepped() {
+        let surfaces = |terraced| {
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+                sim: None,
+                rng: &mut rng,
+            });
+            settlement.terraced_fields = terraced;
+            let farm = settlement.farms.insert(Farm {
+                base_tile: Vec2::zero(),
+            });
+            let field = settlement.land.new_plot(Plot::Field {
+                farm,
+                seed: 0,
+                crop: Crop::Wheat,
+                center: Vec2::zero(),
+            });
+            for x in -1..2 {
+                for y in -1..2 {
+                    settlement.land.set(Vec2::new(x, y), field);
+                }
+            }
+
+            // Ground rising by half a block per block along x
+            let chunk = flat_chunk();
+            let cols = (-1..AREA_SIZE as i32 + 1)
+                .map(|x| ColumnSample {
+                    alt: x as f32 * 0.5,
+                    riverless_alt: x as f32 * 0.5,
+                    ..flat_column(&chunk)
+                })
+                .collect::<Vec<_>>();
+            let mut vol = terrain_chunk();
+            with_index(|index| {
+                settlement.apply_terrain(
+                    index,
+                    Vec2::zero(),
+                    |wpos| cols.get((wpos.x + 1) as usize),
+                    &mut vol,
+                )
+            });
+
+            (4..AREA_SIZE as i32 - 4)
+                .map(|x| {
+                    let top = (-16..32)
+                        .rev()
+                        .find(|z| vol.get(Vec3::new(x, 16, *z)).unwrap().is_filled())
+                        .unwrap();
+                    (x, top + 1, vol.get(Vec3::new(x, 16, top)).unwrap().kind())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Without terraces, the field follows the slope
+        for (x, surface, kind) in surfaces(false) {
+            assert_eq!(surface, (x as f32 * 0.5).floor() as i32);
+            assert_ne!(kind, BlockKind::Rock);
+        }
+
+        // With terraces, the crops sit on flat steps held up by retaining edges
+        let terraced = surfaces(true);
+        for (_, surface, _) in &terraced {
+            assert_eq!(surface.rem_euclid(TERRACE_HEIGHT), 0);
+        }
+        assert!(terraced.iter().any(|(_, _, kind)| *kind == BlockKind::Rock));
+        assert!(
+            terraced
+                .iter()
+                .any(|(_, _, kind)| *kind == BlockKind::Earth)
+        );
+    }
+
+    #[test]
+    fn structures_are_listed_in_every_chunk_they_span() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::new(64, -32), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        // Centered on the boundary between two chunks
+        settlement.structures.push(Structure {
+            kind: StructureKind::Keep(Building::<Keep>::generate(0, Vec3::new(32, 16, 0), None)),
+        });
+
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let west = Vec2::new(64, -32);
+        let east = west + Vec2::unit_x() * size.x;
+        for chunk_wpos in [west, east] {
+            let structures = settlement.structures_in_chunk(chunk_wpos);
+            assert_eq!(structures.len(), 1, "chunk at {}", chunk_wpos);
+            assert!(std::ptr::eq(structures[0], &settlement.structures[0]));
+        }
+        assert!(settlement.structures_in_chunk(east + size * 4).is_empty());
+
+        // Which is what gets applied to each chunk
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        with_index(|index| {
+            for chunk_wpos in [west, east, east + size * 4] {
+                let mut vol = terrain_chunk();
+                settlement.apply_structures(index, chunk_wpos, |_| Some(&col), &mut vol);
+                // Anything standing above the ground was put there
+                let built = (0..size.x)
+                    .flat_map(|x| (0..size.y).map(move |y| (x, y)))
+                    .flat_map(|(x, y)| (1..12).map(move |z| Vec3::new(x, y, z)))
+                    .any(|pos| vol.get(pos).map_or(false, |block| block.is_filled()));
+                assert_eq!(
+                    built,
+                    !settlement.structures_in_chunk(chunk_wpos).is_empty(),
+                    "chunk at {}",
+                    chunk_wpos,
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn center_of_mass_follows_structures() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let origin = Vec2::new(1000, -200);
+        let mut settlement = Settlement::new(origin, &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        assert_eq!(settlement.center_of_mass(), origin);
+
+        settlement.add_custom_structure(Box::new(Pillar));
+        settlement.structures.push(Structure {
+            kind: StructureKind::Keep(Building::<Keep>::generate(0, Vec3::new(200, -40, 0), None)),
+        });
+
+        let center = settlement.center_of_mass();
+        assert!(center.x > origin.x + 100);
+        assert!(center.x < origin.x + 500);
+    }
+
+    #[test]
+    fn fields_have_central_scarecrow() { with_index(fields_have_central_scarecrow_with) }
+
+    fn fields_have_central_scarecrow_with(index: IndexRef) {
+        let settlement = settlement(0);
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+
+        let mut fields = 0;
+        for (tile_pos, tile) in settlement.land.tiles.iter() {
+            let center = match settlement.land.plot(tile.plot) {
+                Plot::Field { center, .. } if center == tile_pos => *center,
+                _ => continue,
+            };
+            fields += 1;
+
+            let wpos = settlement.origin + scarecrow_pos(center);
+            let chunk_wpos = wpos.map2(size, |e, sz| e.div_euclid(sz) * sz);
+            let mut vol = terrain_chunk();
+            settlement.apply_terrain(index, chunk_wpos, |_| Some(&col), &mut vol);
+
+            let offs = wpos - chunk_wpos;
+            let scarecrows = Spiral2d::new()
+                .take(5usize.pow(2))
+                .map(|d| offs + d)
+                .filter(|pos| {
+                    vol.get(Vec3::new(pos.x, pos.y, 0)).map_or(false, |block| {
+                        block.get_sprite() == Some(SpriteKind::Scarecrow)
+                    })
+                })
+                .count();
+            assert!(scarecrows > 0);
+        }
+        assert!(fields > 0);
+    }
+
+    #[test]
+    fn closed_gates_block_the_path() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        let hazard = settlement.land.hazard;
+        let wall = [Vec2::new(0, 0), Vec2::new(1, 0), Vec2::new(2, 0)];
+        let path = [Vec2::new(1, -1), Vec2::new(1, 0), Vec2::new(1, 1)];
+        for tile in wall.iter().chain(path.iter()) {
+            settlement.land.set(*tile, hazard);
+        }
+        settlement
+            .land
+            .write_path(&wall, WayKind::Wall, |_| true, true);
+        settlement
+            .land
+            .write_path(&path, WayKind::Path, |_| true, false);
+        settlement.place_gates(&mut ctx);
+
+        let gates = settlement.gates();
+        assert_eq!(gates.len(), 1);
+        assert_eq!(gates[0].0, Vec3::new(48, 16, 0));
+        assert_eq!(gates[0].1, GateKind::Doors);
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let mut vol = terrain_chunk();
+        let chunk_wpos = Vec2::new(32, 0);
+        let filled_columns = |vol: &TerrainChunk| {
+            let mut filled = 0;
+            for x in -GATE_HALF_WIDTH..GATE_HALF_WIDTH + 1 {
+                for y in -GATE_HALF_WIDTH..GATE_HALF_WIDTH + 1 {
+                    if (0..GATE_HEIGHT)
+                        .any(|z| vol.get(Vec3::new(16 + x, 16 + y, z)).unwrap().is_filled())
+                    {
+                        filled += 1;
+                    }
+                }
+            }
+            filled
+        };
+
+        with_index(|index| {
+            settlement.apply_terrain(index, chunk_wpos, |_| Some(&col), &mut vol);
+            assert_eq!(filled_columns(&vol), 0);
+
+            assert!(settlement.set_gate_open(Vec2::new(48, 16), false));
+            settlement.apply_terrain(index, chunk_wpos, |_| Some(&col), &mut vol);
+            assert_eq!(
+                filled_columns(&vol),
+                (GATE_HALF_WIDTH * 2 + 1).pow(2) as usize
+            );
+
+            assert!(settlement.set_gate_open(Vec2::new(48, 16), true));
+            settlement.apply_terrain(index, chunk_wpos, |_| Some(&col), &mut vol);
+            assert_eq!(filled_columns(&vol), 0);
+        });
+        assert!(!settlement.set_gate_open(Vec2::zero(), false));
+    }
+
+    #[test]
+    fn farms_are_spaced_apart() {
+        let spacings = |settlement: &Settlement| {
+            let tiles = settlement
+                .farms
+                .values()
+                .map(|farm| farm.base_tile)
+                .collect::<Vec<_>>();
+            assert!(!tiles.is_empty());
+            tiles
+                .iter()
+                .enumerate()
+                .flat_map(|(i, a)| tiles[i + 1..].iter().map(move |b| a.distance_squared(*b)))
+                .collect::<Vec<_>>()
+        };
+
+        for seed in 0..4 {
+            let min_spacing = FarmParams::default().min_spacing;
+            assert!(
+                spacings(&settlement(seed))
+                    .into_iter()
+                    .all(|dist| dist >= min_spacing.pow(2))
+            );
+
+            let settlement = SettlementBuilder::new()
+                .farms(FarmParams {
+                    min_spacing: 6,
+                    ..FarmParams::default()
+                })
+                .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(seed));
+            assert!(
+                spacings(&settlement)
+                    .into_iter()
+                    .all(|dist| dist >= 6i32.pow(2))
+            );
+        }
+    }
+
+    #[test]
+    fn dangling_farm_is_invalid() {
+        let mut settlement = settlement(0);
+        let (tile_pos, farm) = settlement
+            .land
+            .tiles
+            .iter()
+            .find_map(|(pos, tile)| match settlement.land.plot(tile.plot) {
+                Plot::Field { farm, .. } => Some((*pos, *farm)),
+                _ => None,
+            })
+            .unwrap();
+
+        // An id from a larger store doesn't resolve in the settlement's farms
+        let mut farms = Store::default();
+        let dangling = (0..=settlement.farms.values().len())
+            .map(|_| {
+                farms.insert(Farm {
+                    base_tile: Vec2::zero(),
+                })
+            })
+            .last()
+            .unwrap();
+        let field = settlement.land.new_plot(Plot::Field {
+            farm: dangling,
+            seed: 0,
+            crop: Crop::Wheat,
+            center: tile_pos,
+        });
+        settlement.land.set(tile_pos, field);
+
+        let issues = settlement.validate().unwrap_err();
+        assert_eq!(issues, vec![SettlementIssue::DanglingFarm {
+            tile: tile_pos
+        }]);
+        assert_ne!(farm, dangling);
+    }
+
+    #[test]
+    fn orchards_suit_the_biome() {
+        let orchard = |biome| {
+            (0..16)
+                .find_map(|seed| {
+                    let mut rng = ChaChaRng::seed_from_u64(seed);
+                    let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+                        sim: None,
+                        rng: &mut rng,
+                    });
+                    settlement.biome = biome;
+                    let orchard = settlement.place_orchard()?;
+                    match settlement.land.plot(orchard) {
+                        Plot::Orchard { species } => Some(*species),
+                        _ => None,
+                    }
+                })
+                .unwrap()
+        };
+
+        let arid = orchard(BiomeKind::Desert);
+        let temperate = orchard(BiomeKind::Grassland);
+        assert!(TreeSpecies::for_biome(BiomeKind::Desert).contains(&arid));
+        assert!(TreeSpecies::for_biome(BiomeKind::Grassland).contains(&temperate));
+        assert_ne!(arid, temperate);
+        assert_eq!(orchard(BiomeKind::Desert), arid);
+    }
+
+    struct Scarecrows;
+
+    impl SpawnPolicy for Scarecrows {
+        fn entity_for(&self, ctx: &SpawnContext) -> Option<EntityInfo> {
+            matches!(ctx.plot, Plot::Town { .. }).then(|| {
+                EntityInfo::at(ctx.wpos)
+                    .with_body(comp::Body::Object(comp::object::Body::Scarecrow))
+            })
+        }
+    }
+
+    #[test]
+    fn spawn_policy_replaces_population() {
+        let mut settlement = settlement(0);
+        settlement.set_spawn_policy(Some(Box::new(Scarecrows)));
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+
+        let mut spawned = 0;
+        for (tile, _) in settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+        {
+            for entity in settlement.sample_entities(
+                settlement.origin + *tile * AREA_SIZE as i32,
+                |_| Some(&col),
+                &economy,
+                None,
+                1.0,
+            ) {
+                spawned += 1;
+                assert_eq!(
+                    entity.body,
+                    comp::Body::Object(comp::object::Body::Scarecrow)
+                );
+            }
+        }
+        assert!(spawned > 0);
+    }
+
+    #[test]
+    fn grid_snap_aligns_buildings() {
+        let origins = |grid_snap| {
+            let settlement = SettlementBuilder::new().grid_snap(grid_snap).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(0),
+            );
+            settlement
+                .structures
+                .iter()
+                .filter_map(|structure| match &structure.kind {
+                    StructureKind::House(house) => Some(Vec2::from(house.origin())),
+                    StructureKind::Keep(keep) => Some(Vec2::from(keep.origin())),
+                    _ => None,
+                })
+                .collect::<Vec<Vec2<i32>>>()
+        };
+        let on_grid =
+            |pos: &Vec2<i32>| pos.map(|e| e.rem_euclid(Settlement::TOWN_GRID)) == Vec2::zero();
+
+        let planned = origins(true);
+        assert!(!planned.is_empty());
+        assert!(planned.iter().all(on_grid));
+
+        let organic = origins(false);
+        assert!(organic.iter().filter(|pos| on_grid(pos)).count() < organic.len() / 2);
+    }
+
+    #[test]
+    fn water_fills_to_its_level() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let water = settlement.land.new_plot(Plot::Water);
+        for x in -1..2 {
+            for y in -1..2 {
+                settlement.land.set(Vec2::new(x, y), water);
+            }
+        }
+        assert!(settlement.set_water_level(Vec2::zero(), 3));
+        assert_eq!(settlement.water_level(Vec2::new(1, 1)), Some(3));
+        assert!(!settlement.set_water_level(Vec2::new(10, 10), 3));
+
+        // Ground rising by half a block per block along x
+        let chunk = flat_chunk();
+        let cols = (-1..AREA_SIZE as i32 + 1)
+            .map(|x| ColumnSample {
+                alt: x as f32 * 0.5,
+                riverless_alt: x as f32 * 0.5,
+                ..flat_column(&chunk)
+            })
+            .collect::<Vec<_>>();
+        let mut vol = terrain_chunk();
+        with_index(|index| {
+            settlement.apply_terrain(
+                index,
+                Vec2::zero(),
+                |wpos| cols.get((wpos.x + 1) as usize),
+                &mut vol,
+            )
+        });
+
+        for x in 0..AREA_SIZE as i32 {
+            for y in 0..AREA_SIZE as i32 {
+                let top = (-16..32)
+                    .rev()
+                    .find(|z| vol.get(Vec3::new(x, y, *z)).unwrap().kind() == BlockKind::Water);
+                assert_eq!(top, Some(3));
+            }
+        }
+    }
+
+    #[test]
+    fn plot_at_tile_matches_sampled_plot() {
+        let settlement = Settlement::generate(
+            Vec2::new(1000, -213),
+            None,
+            &mut ChaChaRng::seed_from_u64(11),
+        );
+        assert_eq!(settlement.validate(), Ok(()));
+
+        let mut count = 0;
+        for (tile, plot) in settlement.tiles() {
+            let center = settlement.world_tile_center(tile);
+            assert_eq!(settlement.world_tile_at(center), tile);
+            assert!(settlement.plot_at_tile(tile) == Some(plot));
+
+            let sample = settlement.land.get_at_block(center - settlement.origin);
+            assert!(sample.plot == Some(plot), "tile {:?}", tile);
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn water_gate_uses_grate_material() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        let hazard = settlement.land.hazard;
+        let wall = [Vec2::new(0, 0), Vec2::new(1, 0)];
+        for tile in wall.iter() {
+            settlement.land.set(*tile, hazard);
+        }
+        settlement
+            .land
+            .write_path(&wall, WayKind::Wall, |_| true, true);
+
+        // A river crosses the western half of the chunk
+        let chunk = flat_chunk();
+        let dry = flat_column(&chunk);
+        let wet = ColumnSample {
+            water_dist: Some(3.0),
+            ..flat_column(&chunk)
+        };
+        let mut vol = terrain_chunk();
+
+        with_index(|index| {
+            let colors = &index.colors.site.settlement;
+            settlement.apply_terrain(
+                index,
+                Vec2::zero(),
+                |wpos| Some(if wpos.x < 24 { &wet } else { &dry }),
+                &mut vol,
+            );
+
+            let grate = Block::new(BlockKind::Rock, colors.water_gate.into());
+            let (mut gate_blocks, mut wall_blocks) = (0, 0);
+            for x in 0..32 {
+                for y in 0..32 {
+                    for z in 4..WALL_WALK_Z {
+                        let block = *vol.get(Vec3::new(x, y, z)).unwrap();
+                        if !block.is_filled() {
+                            continue;
+                        }
+                        if x < 24 {
+                            assert_eq!(block, grate);
+                            gate_blocks += 1;
+                        } else {
+                            assert_eq!(block.kind(), BlockKind::Wood);
+                            wall_blocks += 1;
+                        }
+                    }
+                }
+            }
+            assert!(gate_blocks > 0);
+            assert!(wall_blocks > 0);
+        });
+    }
+
+    #[test]
+    fn structures_outside_z_range_are_skipped() {
+        let mut settlement = settlement(0);
+        settlement.add_custom_structure(Box::new(Pillar));
+        assert_eq!(settlement.structures.last().unwrap().z_range(), 0..11);
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let chunk_wpos = settlement.origin + Vec2::new(480, 480);
+        let region = Aabr {
+            min: chunk_wpos,
+            max: chunk_wpos + TerrainChunkSize::RECT_SIZE.map(|e| e as i32),
+        };
+        let pillar = settlement.structures.last().unwrap();
+        let pillar_at = |z_range: Range<i32>| {
+            let mut vol = terrain_chunk();
+            with_index(|index| {
+                pillar.apply_to(
+                    index,
+                    chunk_wpos - settlement.origin,
+                    z_range,
+                    |_| Some(&col),
+                    &mut vol,
+                );
+            });
+            vol.get(Vec3::new(21, 21, 5)).unwrap().kind() == BlockKind::Rock
+        };
+
+        assert!(pillar_at(0..64));
+        assert!(pillar_at(10..64));
+        assert!(!pillar_at(11..64));
+        assert!(!pillar_at(-64..0));
+
+        // The settlement culls the same way
+        let mut vol = terrain_chunk();
+        with_index(|index| {
+            settlement.apply_structures_within(index, region, 11..64, |_| Some(&col), &mut [(
+                chunk_wpos, &mut vol,
+            )]);
+        });
+        assert_ne!(
+            vol.get(Vec3::new(21, 21, 5)).unwrap().kind(),
+            BlockKind::Rock
+        );
+    }
+
+    #[test]
+    fn districts_have_streets() {
+        const MIN_TILES: usize = 6;
+
+        let mut checked = 0;
+        for seed in 0..8 {
+            let settlement = settlement(seed);
+            let town = settlement.town.as_ref().unwrap();
+            for (id, district) in town.districts().iter() {
+                if town.is_plaza(id) {
+                    continue;
+                }
+                let tiles = district
+                    .tiles()
+                    .filter(|tile| {
+                        matches!(
+                            settlement.land.plot_at(*tile),
+                            Some(Plot::Town { district: Some(d) }) if *d == id
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if tiles.len() <= MIN_TILES {
+                    continue;
+                }
+                assert!(
+                    tiles.iter().any(|tile| settlement
+                        .land
+                        .tile_at(*tile)
+                        .map_or(false, |t| t.contains(WayKind::Path))),
+                    "seed {}: district at {:?} has no street",
+                    seed,
+                    district.center(),
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn plain_plots_are_interned() {
+        let mut land = Land::new(&mut ChaChaRng::seed_from_u64(0));
+        let grass = land.intern_plot(Plot::Grass);
+        assert!(land.intern_plot(Plot::Grass) == grass);
+        assert!(land.intern_plot(Plot::Dirt) != grass);
+        assert!(land.intern_plot(Plot::Hazard) == land.hazard);
+        assert!(land.intern_plot(Plot::Water) != land.intern_plot(Plot::Water));
+    }
+
+    #[test]
+    fn houses_have_a_bed_and_a_table() {
+        let mut furnished = 0;
+        for seed in 0..16 {
+            let house = Building::<House>::generate(seed, Vec3::new(100, -40, 12), None);
+            let furniture = house.furniture(1.0);
+            assert!(furniture == house.furniture(1.0));
+            for (pos, _) in &furniture {
+                assert!(house.bounds_2d().contains_point(pos.xy()));
+                assert_eq!(pos.z, 13);
+            }
+            assert!(house.furniture(0.0).is_empty());
+
+            // Only houses with rooms wide enough to furnish
+            if furniture.len() < 2 {
+                continue;
+            }
+            furnished += 1;
+            for density in [1.0, 0.3] {
+                let furniture = house.furniture(density);
+                let has = |body| furniture.iter().any(|(_, b)| *b == body);
+                assert!(has(comp::object::Body::BedBlue));
+                assert!(has(comp::object::Body::Table));
+            }
+        }
+        assert!(furnished > 0);
+    }
+
+    #[test]
+    fn taverns_hang_a_sign_by_their_door() { with_index(taverns_hang_a_sign_by_their_door_with) }
+
+    fn taverns_hang_a_sign_by_their_door_with(index: IndexRef) {
+        let sprite_at = |house: &Building<House>, pos| {
+            house
+                .sample(index, pos)
+                .and_then(|block| block.get_sprite())
+        };
+
+        for seed in 0..8 {
+            let origin = Vec3::new(100, -40, 12);
+            let tavern = Building::<House>::generate_tavern(seed, origin, None);
+            let sign = tavern.sign().unwrap();
+            assert!(tavern.bounds().contains_point(sign));
+            assert_eq!(sprite_at(&tavern, sign), Some(SpriteKind::HangingSign));
+            // It hangs on the outside of a wall
+            assert!(
+                [
+                    Vec2::unit_x(),
+                    -Vec2::unit_x(),
+                    Vec2::unit_y(),
+                    -Vec2::unit_y()
+                ]
+                .iter()
+                .any(|dir| tavern
+                    .sample(index, sign + Vec3::from(*dir))
+                    .map_or(false, |block| block.is_solid()))
+            );
+
+            let house = Building::<House>::generate(seed, origin, None);
+            assert_eq!(house.sign(), None);
+            let bounds = house.bounds();
+            for x in bounds.min.x..bounds.max.x + 1 {
+                for y in bounds.min.y..bounds.max.y + 1 {
+                    for z in origin.z + 1..origin.z + 7 {
+                        assert_ne!(
+                            sprite_at(&house, Vec3::new(x, y, z)),
+                            Some(SpriteKind::HangingSign)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grand_keeps_are_larger() {
+        let area = |bounds: Aabr<i32>| bounds.size().product();
+        let tower_area = |keep: &Building<Keep>| keep.towers().into_iter().map(area).sum::<i32>();
+
+        for seed in 0..4 {
+            let fort = Building::<Keep>::generate_tier(seed, Vec3::zero(), KeepTier::Fort);
+            let castle = Building::<Keep>::generate_tier(seed, Vec3::zero(), KeepTier::Castle);
+            assert!(area(castle.bounds_2d()) > area(fort.bounds_2d()));
+            assert!(castle.bounds().max.z > fort.bounds().max.z);
+            assert!(castle.towers().len() > fort.towers().len(), "seed {}", seed);
+            assert!(tower_area(&castle) > tower_area(&fort), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn keeps_have_exterior_torches() { with_index(keeps_have_exterior_torches_with); }
+
+    fn keeps_have_exterior_torches_with(index: IndexRef) {
+        for seed in 0..4 {
+            let mut rng = ChaChaRng::seed_from_u64(seed);
+            let mut settlement = Settlement::new(Vec2::new(300, -100), &mut GenCtx {
+                sim: None,
+                rng: &mut rng,
+            });
+            let keep = Building::<Keep>::generate(seed as u32, Vec3::new(10, 20, 0), None);
+            let bounds = keep.bounds();
+            let filled = |pos| keep.sample(index, pos).map_or(false, |b| b.is_filled());
+
+            let torches = (bounds.min.x..bounds.max.x + 1)
+                .flat_map(|x| (bounds.min.y..bounds.max.y + 1).map(move |y| (x, y)))
+                .flat_map(|(x, y)| {
+                    (bounds.min.z..bounds.max.z + 1).map(move |z| Vec3::new(x, y, z))
+                })
+                .filter(|pos| {
+                    keep.sample(index, *pos).and_then(|b| b.get_sprite())
+                        == Some(SpriteKind::WallSconce)
+                })
+                .collect::<Vec<_>>();
+            assert!(!torches.is_empty(), "seed {}", seed);
+            for pos in &torches {
+                // Mounted against a wall, with open air on the other side all
+                // the way out of the keep
+                let dirs = [
+                    Vec2::unit_x(),
+                    -Vec2::unit_x(),
+                    Vec2::unit_y(),
+                    -Vec2::unit_y(),
+                ];
+                assert!(
+                    dirs.iter().any(|dir| filled(*pos - Vec3::from(*dir))),
+                    "seed {}, torch at {}",
+                    seed,
+                    pos,
+                );
+                assert!(
+                    dirs.iter().any(|dir| {
+                        (1..bounds.size().reduce_max())
+                            .all(|i| !filled(*pos + Vec3::from(*dir * i)))
+                    }),
+                    "seed {}, torch at {} is indoors",
+                    seed,
+                    pos,
+                );
+            }
+
+            settlement.structures.push(Structure {
+                kind: StructureKind::Keep(keep),
+            });
+            let lights = settlement.light_sources(index);
+            for pos in torches {
+                assert!(lights.contains(&(Vec3::from(settlement.origin) + pos)));
+            }
+        }
+    }
+
+    #[test]
+    fn house_roofs_overhang_their_walls() { with_index(house_roofs_overhang_their_walls_with); }
+
+    fn house_roofs_overhang_their_walls_with(index: IndexRef) {
+        for seed in 0..8 {
+            let house = Building::<House>::generate(seed, Vec3::zero(), None);
+            let bounds = house.bounds();
+            let filled = |pos| house.sample(index, pos).map_or(false, |b| b.is_filled());
+
+            // Columns beyond the walls and their foundations, but beneath the
+            // roof
+            let eaves = (bounds.min.x..bounds.max.x + 1)
+                .flat_map(|x| (bounds.min.y..bounds.max.y + 1).map(move |y| Vec2::new(x, y)))
+                .filter(|pos| {
+                    !filled(Vec3::new(pos.x, pos.y, 0))
+                        && (1..bounds.max.z + 1).any(|z| filled(Vec3::new(pos.x, pos.y, z)))
+                })
+                .count();
+            assert!(eaves > 0, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn baked_settlement_matches_apply_to() {
+        let settlement = settlement(0);
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let keep = settlement
+            .structures
+            .iter()
+            .find(|structure| matches!(structure.kind, StructureKind::Keep(_)))
+            .unwrap();
+        let chunk_wpos = (settlement.origin + keep.bounds_2d().center())
+            .map2(size, |e, sz| e.div_euclid(sz) * sz);
+
+        with_index(|index| {
+            let baked = settlement.bake(
+                index,
+                |_| Some(&col),
+                |wpos| (wpos == chunk_wpos).then(terrain_chunk),
+            );
+            assert_eq!(baked.chunks().collect::<Vec<_>>(), vec![chunk_wpos]);
+
+            let mut applied = terrain_chunk();
+            settlement.apply_to(index, chunk_wpos, |_| Some(&col), &mut applied);
+            let mut replayed = terrain_chunk();
+            assert!(baked.apply_baked(chunk_wpos, &mut replayed));
+            assert!(!baked.apply_baked(chunk_wpos + size, &mut terrain_chunk()));
+
+            for z in -32..96 {
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        let pos = Vec3::new(x, y, z);
+                        assert_eq!(applied.get(pos).unwrap(), replayed.get(pos).unwrap());
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn forts_are_ringed_by_a_moat() {
+        let mut forts = 0;
+        for seed in 0..8 {
+            let settlement = SettlementBuilder::new()
+                .town_walls(true)
+                .moat(true)
+                .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(seed));
+            assert_eq!(settlement.validate(), Ok(()));
+            if settlement.map_icon() != MapIconKind::Fort {
+                continue;
+            }
+            forts += 1;
+
+            let is_water = |tile: Vec2<i32>| settlement.land.plot_at(tile) == Some(&Plot::Water);
+            let walls = settlement
+                .land
+                .tiles
+                .iter()
+                .filter(|(_, tile)| tile.contains(WayKind::Wall))
+                .map(|(pos, _)| *pos)
+                .collect::<Vec<_>>();
+            assert!(!walls.is_empty());
+
+            // Nothing gets to the wall from far outside without crossing water
+            let mut outside = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+            let mut open = vec![Vec2::broadcast(-64)];
+            while let Some(tile) = open.pop() {
+                if tile.map(|e| e.abs()).reduce_max() > 64
+                    || is_water(tile)
+                    || !outside.insert(tile)
+                {
+                    continue;
+                }
+                open.extend(WAY_DIRS.iter().map(|dir| tile + *dir));
+            }
+            assert!(
+                walls.iter().all(|wall| !outside.contains(wall)),
+                "seed {}",
+                seed
+            );
+
+            // The moat is bridged, and kept clear of structures
+            let moat = |tile: Vec2<i32>| {
+                is_water(tile) && MOORE_DIRS.iter().any(|dir| walls.contains(&(tile + *dir)))
+            };
+            assert!(
+                settlement
+                    .land
+                    .tiles
+                    .iter()
+                    .any(|(pos, tile)| moat(*pos) && tile.contains(WayKind::Bridge)),
+                "seed {}",
+                seed,
+            );
+            for structure in settlement.structures.iter() {
+                assert!(!footprint_tiles(structure.bounds_2d()).any(moat));
+            }
+            assert!(!settlement.gates().is_empty());
+        }
+        assert!(forts > 0);
+
+        // Without a keep and wall, there's no moat
+        let settlement = SettlementBuilder::new().moat(true).generate(
+            Vec2::zero(),
+            None,
+            &mut ChaChaRng::seed_from_u64(0),
+        );
+        assert!(
+            settlement
+                .land
+                .tiles
+                .values()
+                .all(|tile| settlement.land.plot(tile.plot) != &Plot::Water)
+        );
+    }
+
+    #[test]
+    fn town_walls_leave_fields_outside() {
+        let mut walled = 0;
+        for seed in 0..8 {
+            let settlement = SettlementBuilder::new().town_walls(true).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            assert_eq!(settlement.validate(), Ok(()));
+
+            // The wall's segments, between the centers of neighbouring tiles
+            let mut segments = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+            for (pos, tile) in settlement.land.tiles.iter() {
+                for (dir, way) in WAY_DIRS.iter().zip(tile.ways().iter()) {
+                    if *way == Some(WayKind::Wall) {
+                        assert!(matches!(settlement.land.plot(tile.plot), Plot::Town { .. }));
+                        let other = *pos + *dir;
+                        segments.insert(if (pos.x, pos.y) < (other.x, other.y) {
+                            (*pos, other)
+                        } else {
+                            (other, *pos)
+                        });
+                    }
+                }
+            }
+            if segments.is_empty() {
+                continue;
+            }
+            walled += 1;
+
+            // Flood the squares between tile centers from far outside, without
+            // crossing the wall. Each square is named by its lowest corner.
+            let mut outside = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+            let mut open = vec![Vec2::broadcast(-64)];
+            while let Some(cell) = open.pop() {
+                if cell.map(|e| e.abs()).reduce_max() > 64 || !outside.insert(cell) {
+                    continue;
+                }
+                let x = Vec2::unit_x();
+                let y = Vec2::unit_y();
+                for (neighbor, edge) in [
+                    (cell + x, (cell + x, cell + x + y)),
+                    (cell - x, (cell, cell + y)),
+                    (cell + y, (cell + y, cell + x + y)),
+                    (cell - y, (cell, cell + x)),
+                ] {
+                    if !segments.contains(&edge) {
+                        open.push(neighbor);
+                    }
+                }
+            }
+            let enclosed = |tile: Vec2<i32>| {
+                [Vec2::zero(), Vec2::unit_x(), Vec2::unit_y(), Vec2::one()]
+                    .iter()
+                    .all(|offs| !outside.contains(&(tile - *offs)))
+            };
+
+            // Fields hemmed in on every side by the town can't be walled out,
+            // so only look at those that the open country can reach
+            let mut open = vec![Vec2::broadcast(-64)];
+            let mut country = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+            while let Some(tile) = open.pop() {
+                if tile.map(|e| e.abs()).reduce_max() > 64 || !country.insert(tile) {
+                    continue;
+                }
+                if !matches!(settlement.land.plot_at(tile), Some(Plot::Town { .. })) {
+                    open.extend(WAY_DIRS.iter().map(|dir| tile + *dir));
+                }
+            }
+            for (pos, tile) in settlement.land.tiles.iter() {
+                if matches!(settlement.land.plot(tile.plot), Plot::Field { .. })
+                    && country.contains(pos)
+                {
+                    assert!(
+                        !enclosed(*pos),
+                        "seed {}: field at {:?} is walled in",
+                        seed,
+                        pos
+                    );
+                }
+            }
+        }
+        assert!(walled > 0);
+    }
+
+    #[test]
+    fn structure_mix_favours_taverns() {
+        let (mut houses, mut taverns) = (0, 0);
+        for seed in 0..4 {
+            let settlement = SettlementBuilder::new()
+                .structure_mix(StructureMix {
+                    house: 1.0,
+                    tavern: 9.0,
+                })
+                .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(seed));
+            assert_eq!(settlement.validate(), Ok(()));
+            for structure in &settlement.structures {
+                match structure.kind {
+                    StructureKind::House(_) => houses += 1,
+                    StructureKind::Tavern(_) => taverns += 1,
+                    _ => {},
+                }
+            }
+        }
+        assert!(taverns > houses, "{} taverns, {} houses", taverns, houses);
+
+        // Towns are built from houses alone by default
+        let settlement = settlement(0);
+        assert!(
+            !settlement
+                .structures
+                .iter()
+                .any(|s| matches!(s.kind, StructureKind::Tavern(_)))
+        );
+    }
+
+    #[test]
+    fn nearest_buildable_is_close_and_open() {
+        let settlement = settlement(0);
+        let town_center = settlement.town.as_ref().unwrap().base_tile;
+        let wpos = tile_center(&settlement, town_center);
+
+        let found = settlement.nearest_buildable(wpos, 12).unwrap();
+        let tile = (found - settlement.origin).map(to_tile);
+        assert!(settlement.is_buildable(tile));
+        assert!((tile - town_center).map(|e| e.abs()).reduce_max() <= 12);
+        assert_eq!(tile_center(&settlement, tile), found);
+        // The town center is built up, so the search has to look further out
+        assert!(!settlement.is_buildable(town_center));
+
+        // A buildable tile is its own nearest
+        let wpos = found + Vec2::new(3, -2);
+        assert_eq!(settlement.nearest_buildable(wpos, 0), Some(found));
+    }
+
+    #[test]
+    fn leveling_smooths_town_ground() {
+        let hills = |wpos: Vec2<i32>| {
+            Some(((wpos.x as f32 / 23.0).sin() + (wpos.y as f32 / 17.0).cos()) * 20.0)
+        };
+        let variance = |alts: &[f32]| {
+            let mean = alts.iter().sum::<f32>() / alts.len() as f32;
+            alts.iter().map(|alt| (alt - mean).powi(2)).sum::<f32>() / alts.len() as f32
+        };
+
+        let mut settlement = settlement(0);
+        // Towns aren't leveled by default
+        assert!(settlement.town_alts.is_empty());
+        settlement.leveling = 0.8;
+        settlement.level_town(hills);
+
+        let centers = settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+            .map(|(pos, _)| *pos * AREA_SIZE as i32 + AREA_SIZE as i32 / 2)
+            .collect::<Vec<_>>();
+        assert!(centers.len() > 4);
+        let raw = centers
+            .iter()
+            .map(|rpos| hills(settlement.origin + *rpos).unwrap())
+            .collect::<Vec<_>>();
+        let leveled = centers
+            .iter()
+            .zip(raw.iter())
+            .map(|(rpos, alt)| settlement.leveled_alt(*rpos, *alt))
+            .collect::<Vec<_>>();
+        assert!(
+            variance(&leveled) < variance(&raw),
+            "leveled variance {} isn't below raw variance {}",
+            variance(&leveled),
+            variance(&raw)
+        );
+
+        // Far from the town, the terrain is left alone
+        let far = Vec2::broadcast(100 * AREA_SIZE as i32);
+        assert_eq!(settlement.leveled_alt(far, 7.0), 7.0);
+    }
+
+    #[test]
+    fn on_structure_fires_once_per_structure() {
+        let mut placed = Vec::new();
+        let settlement = SettlementBuilder::new().generate_with(
+            Vec2::new(96, -32),
+            None,
+            &mut ChaChaRng::seed_from_u64(0),
+            |structure, wpos| placed.push((structure.bounds_2d(), wpos)),
+        );
+
+        let structures = settlement.data().structures();
+        assert!(!structures.is_empty());
+        assert_eq!(placed.len(), structures.len());
+        for ((bounds, wpos), structure) in placed.iter().zip(structures) {
+            assert_eq!(*bounds, structure.bounds_2d());
+            assert_eq!(*wpos, settlement.origin + bounds.center());
+        }
+    }
+
+    #[test]
+    fn paths_are_cleared_of_snow() { with_index(paths_are_cleared_of_snow_with); }
+
+    fn paths_are_cleared_of_snow_with(index: IndexRef) {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        settlement.cold = true;
+        let grass = settlement.land.intern_plot(Plot::Grass);
+        settlement.land.set(Vec2::new(0, 0), grass);
+        let street = [Vec2::new(1, 0), Vec2::new(2, 0)];
+        for tile in street.iter() {
+            let town = settlement.land.new_plot(Plot::Town { district: None });
+            settlement.land.set(*tile, town);
+        }
+        settlement
+            .land
+            .write_path(&street, WayKind::Path, |_| true, true);
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let surface = |wpos2d: Vec2<i32>, x: i32| {
+            let mut vol = terrain_chunk();
+            settlement.apply_terrain(index, wpos2d, |_| Some(&col), &mut vol);
+            *vol.get(Vec3::new(x, 16, -1)).unwrap()
+        };
+
+        // Along the street, between the centers of its tiles
+        let path_color = Rgb::from(index.colors.site.settlement.plot_town_path);
+        for x in 20..32 {
+            let block = surface(Vec2::new(32, 0), x);
+            assert_eq!(block.kind(), BlockKind::Earth);
+            let color = block.get_color().unwrap();
+            assert!((color.r as i32 - path_color.r as i32).abs() <= 12);
+        }
+        // Beside it, on the grass
+        let snowy = (0..32)
+            .filter(|x| surface(Vec2::zero(), *x).kind() == BlockKind::ArtSnow)
+            .count();
+        assert!(snowy > 16);
+    }
+
+    #[test]
+    fn foundations_fill_the_gap_beneath_structures() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        let house = Building::<House>::generate(0, Vec3::new(16, 16, 20), None);
+        let base = house.bounds().min.z;
+        settlement.structures.push(Structure {
+            kind: StructureKind::House(house),
+        });
+
+        // The ground slopes up to the east
+        let chunk = flat_chunk();
+        let low = flat_column(&chunk);
+        let high = ColumnSample {
+            alt: 4.0,
+            ..flat_column(&chunk)
+        };
+        let ground = |x: i32| if x < 16 { 0 } else { 4 };
+
+        let mut foundations = |foundation| {
+            settlement.foundation = foundation;
+            let mut vol = terrain_chunk();
+            with_index(|index| {
+                settlement.apply_structures(
+                    index,
+                    Vec2::zero(),
+                    |wpos| Some(if wpos.x < 16 { &low } else { &high }),
+                    &mut vol,
+                )
+            });
+            (0..32)
+                .flat_map(|x| (0..32).map(move |y| Vec2::new(x, y)))
+                .filter(|pos| vol.get(Vec3::new(pos.x, pos.y, base)).unwrap().is_filled())
+                .map(|pos| {
+                    (ground(pos.x)..base)
+                        .map(|z| *vol.get(Vec3::new(pos.x, pos.y, z)).unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let plinth = foundations(FoundationStyle::Stone);
+        assert!(!plinth.is_empty());
+        assert!(
+            plinth
+                .iter()
+                .flatten()
+                .all(|block| block.kind() == BlockKind::Rock)
+        );
+
+        let stilts = foundations(FoundationStyle::Stilts);
+        let posts = stilts
+            .iter()
+            .filter(|column| column.iter().all(|block| block.kind() == BlockKind::Wood))
+            .count();
+        let open = stilts
+            .iter()
+            .filter(|column| column.iter().all(|block| !block.is_filled()))
+            .count();
+        assert!(posts > 0);
+        assert!(open > posts);
+        assert_eq!(posts + open, stilts.len());
+    }
+
+    #[test]
+    fn hostile_settlements_spawn_enemies() {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let skillsets =
+            |tier| {
+                let settlement = SettlementBuilder::new()
+                    .faction(Faction::Hostile)
+                    .tier(tier)
+                    .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+                assert_eq!(settlement.faction(), Faction::Hostile);
+
+                let mut skillsets = Vec::new();
+                for (tile, _) in settlement.land.tiles.iter().filter(|(_, tile)| {
+                    matches!(settlement.land.plot(tile.plot), Plot::Town { .. })
+                }) {
+                    for entity in settlement.sample_entities(
+                        settlement.origin + *tile * AREA_SIZE as i32,
+                        |_| Some(&col),
+                        &economy,
+                        None,
+                        1.0,
+                    ) {
+                        if matches!(entity.body, comp::Body::Humanoid(_)) {
+                            assert_eq!(entity.alignment, comp::Alignment::Enemy);
+                            assert!(entity.agent_mark.is_none());
+                            skillsets.push(entity.skillset_asset);
+                        }
+                    }
+                }
+                assert!(!skillsets.is_empty());
+                skillsets.dedup();
+                skillsets
+            };
+
+        // Raiders of richer settlements are more skilled
+        let weak = skillsets(0);
+        let strong = skillsets(Settlement::MAX_TIER);
+        assert_eq!(weak.len(), 1);
+        assert_eq!(strong.len(), 1);
+        assert_ne!(weak, strong);
+    }
+
+    #[test]
+    fn richer_settlements_build_stone_walls() {
+        let wall_kinds = |tier| {
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+                sim: None,
+                rng: &mut rng,
+            });
+            settlement.tier = tier;
+            let hazard = settlement.land.hazard;
+            let tiles = [Vec2::new(0, 0), Vec2::new(1, 0)];
+            for tile in tiles {
+                settlement.land.set(tile, hazard);
+            }
+            settlement
+                .land
+                .write_path(&tiles, WayKind::Wall, |_| true, true);
+
+            let chunk = flat_chunk();
+            let col = flat_column(&chunk);
+            let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+            let mut vol = terrain_chunk();
+            with_index(|index| {
+                settlement.apply_to_region(
+                    index,
+                    Aabr {
+                        min: Vec2::zero(),
+                        max: size,
+                    },
+                    |_| Some(&col),
+                    &mut [(Vec2::zero(), &mut vol)],
+                );
+            });
+
+            (0..size.x)
+                .flat_map(|x| (0..size.y).map(move |y| Vec2::new(x, y)))
+                .filter(|pos| {
+                    matches!(
+                        settlement.land.get_at_block(*pos).way,
+                        Some((WayKind::Wall, _, _))
+                    )
+                })
+                .filter_map(|pos| vol.get(pos.with_z(WALL_WALK_Z - 1)).ok().copied())
+                .map(|block| block.kind())
+                .collect::<Vec<_>>()
+        };
+
+        // Wall towers are stone whatever the tier
+        let wood = wall_kinds(STONE_WALL_TIER - 1);
+        assert!(wood.contains(&BlockKind::Wood));
+        let stone = wall_kinds(STONE_WALL_TIER);
+        assert!(stone.contains(&BlockKind::Rock));
+        assert!(!stone.contains(&BlockKind::Wood));
+    }
+
+    #[test]
+    fn villagers_carry_local_produce() {
+        let settlement = SettlementBuilder::new()
+            .forced_crops(vec![Crop::Wheat])
+            .starting_inventories(true)
+            .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+        assert!(settlement.total_yield().contains_key(&Crop::Wheat));
+        let wheat = Crop::Wheat.item_asset();
+
+        let inventory = settlement.starting_inventory(Townsfolk::Villager, 7);
+        assert_eq!(
+            inventory,
+            settlement.starting_inventory(Townsfolk::Villager, 7)
+        );
+        assert!(inventory.iter().any(|(_, asset)| *asset == wheat));
+        assert!(
+            settlement
+                .starting_inventory(Townsfolk::Guard, 7)
+                .is_empty()
+        );
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let mut villagers = 0;
+        for (tile, _) in settlement
+            .land
+            .tiles
+            .iter()
+            .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+        {
+            for entity in settlement.sample_entities(
+                settlement.origin + *tile * AREA_SIZE as i32,
+                |_| Some(&col),
+                &economy,
+                None,
+                1.0,
+            ) {
+                if matches!(entity.body, comp::Body::Humanoid(_))
+                    && entity.has_agency
+                    && entity.agent_mark.is_none()
+                {
+                    assert!(
+                        entity
+                            .inventory
+                            .iter()
+                            .any(|(_, item)| item.persistence_item_id() == wheat)
+                    );
+                    villagers += 1;
+                }
+            }
+        }
+        assert!(villagers > 0);
+    }
+
+    #[test]
+    fn spawn_density_scale_thins_populations() {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+
+        let spawned = |scale: f32| {
+            let mut spawned = 0;
+            for seed in 0..8 {
+                let settlement = SettlementBuilder::new().generate(
+                    Vec2::zero(),
+                    None,
+                    &mut ChaChaRng::seed_from_u64(seed),
+                );
+                for tile in settlement.land.tiles.keys() {
+                    spawned += settlement
+                        .sample_entities(
+                            settlement.origin + *tile * AREA_SIZE as i32,
+                            |_| Some(&col),
+                            &economy,
+                            None,
+                            scale,
+                        )
+                        .len();
+                }
+            }
+            spawned
+        };
+
+        let full = spawned(1.0);
+        let half = spawned(0.5);
+        assert!(full > 100);
+        assert!(
+            (half as f32 / full as f32 - 0.5).abs() < 0.1,
+            "{half} of {full}"
+        );
+        assert_eq!(half, spawned(0.5));
+        assert_eq!(spawned(0.0), 0);
+    }
 
-    let mut backpack = Item::new_from_asset_expect("common.items.armor.misc.back.backpack");
-    let mut bag1 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
-    let mut bag2 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
-    let mut bag3 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
-    let mut bag4 = Item::new_from_asset_expect("common.items.armor.misc.bag.sturdy_red_backpack");
-    let slots = backpack.slots().len() + 4 * bag1.slots().len();
-    let mut stockmap: HashMap<Good, f32> = economy
-        .map(|e| {
-            e.unconsumed_stock
-                .clone()
-                .into_iter()
-                .filter(|(good, _)| permitted(*good))
-                .collect()
-        })
-        .unwrap_or_default();
-    // modify stock for better gameplay
+    #[test]
+    fn spawn_density_scale_leaves_furniture_alone() {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+        let settlement = SettlementBuilder::new().furniture_density(1.0).generate(
+            Vec2::zero(),
+            None,
+            &mut ChaChaRng::seed_from_u64(0),
+        );
+        let beds = |scale: f32| {
+            let mut beds = 0;
+            for tile in settlement.land.tiles.keys() {
+                beds += settlement
+                    .sample_entities(
+                        settlement.origin + *tile * AREA_SIZE as i32,
+                        |_| Some(&col),
+                        &economy,
+                        None,
+                        scale,
+                    )
+                    .iter()
+                    .filter(|entity| entity.body == comp::Body::Object(comp::object::Body::BedBlue))
+                    .count();
+            }
+            beds
+        };
+        assert!(beds(1.0) > 0);
+        assert_eq!(beds(0.0), beds(1.0));
+    }
 
-    // TODO: currently econsim spends all its food on population, resulting in none
-    // for the players to buy; the `.max` is temporary to ensure that there's some
-    // food for sale at every site, to be used until we have some solution like NPC
-    // houses as a limit on econsim population growth
-    if permitted(Good::Food) {
-        stockmap
-            .entry(Good::Food)
-            .and_modify(|e| *e = e.max(10_000.0))
-            .or_insert(10_000.0);
+    #[test]
+    fn spawn_density_scale_is_clamped() {
+        let threadpool = rayon::ThreadPoolBuilder::new().build().unwrap();
+        let scale = |spawn_density_scale| {
+            let opts = WorldOpts {
+                world_file: FileOpts::Generate(GenOpts {
+                    x_lg: 1,
+                    y_lg: 1,
+                    ..GenOpts::default()
+                }),
+                spawn_density_scale,
+                ..WorldOpts::default()
+            };
+            WorldSim::generate(0, opts, &threadpool, &|_| {}).spawn_density_scale
+        };
+        assert_eq!(scale(0.5), 0.5);
+        assert_eq!(scale(-1.0), 0.0);
+        assert_eq!(scale(4.0), 1.0);
+        assert_eq!(scale(f32::NAN), 1.0);
     }
-    // Reduce amount of potions so merchants do not oversupply potions.
-    // TODO: Maybe remove when merchants and their inventories are rtsim?
-    // Note: Likely without effect now that potions are counted as food
-    if permitted(Good::Potions) {
-        stockmap
-            .entry(Good::Potions)
-            .and_modify(|e| *e = e.powf(0.25));
+
+    #[test]
+    fn fort_guards_live_in_barracks_near_the_keep() {
+        with_index(fort_guards_live_in_barracks_near_the_keep_with)
     }
-    // It's safe to truncate here, because coins clamped to 3000 max
-    // also we don't really want negative values here
-    if permitted(Good::Coin) {
-        stockmap
-            .entry(Good::Coin)
-            .and_modify(|e| *e = e.min(rng.gen_range(1000.0..3000.0)));
+
+    fn fort_guards_live_in_barracks_near_the_keep_with(index: IndexRef) {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+
+        let mut forts = 0;
+        for seed in 0..8 {
+            let settlement = SettlementBuilder::new().town_walls(true).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            assert_eq!(settlement.validate(), Ok(()));
+            if settlement.map_icon() != MapIconKind::Fort {
+                continue;
+            }
+            forts += 1;
+
+            let keep = settlement
+                .structures
+                .iter()
+                .find(|s| matches!(s.kind, StructureKind::Keep(_)))
+                .unwrap()
+                .bounds_2d()
+                .center();
+            let barracks = settlement
+                .structures
+                .iter()
+                .filter(|s| matches!(s.kind, StructureKind::Barracks(_)))
+                .collect::<Vec<_>>();
+            assert!(!barracks.is_empty());
+            for bounds in barracks.iter().map(|s| s.bounds_2d()) {
+                assert!(
+                    bounds.center().distance_squared(keep) < (4 * AREA_SIZE as i32).pow(2),
+                    "barracks at {:?}, keep at {:?}",
+                    bounds,
+                    keep
+                );
+            }
+
+            let mut guards = 0;
+            for tile in settlement.land.tiles.keys() {
+                for entity in settlement.sample_entities(
+                    settlement.origin + *tile * AREA_SIZE as i32,
+                    |_| Some(&col),
+                    &economy,
+                    None,
+                    1.0,
+                ) {
+                    if entity.agent_mark == Some(agent::Mark::Guard) {
+                        let pos =
+                            entity.pos.map(|e| e.floor() as i32) - Vec3::from(settlement.origin);
+                        let building = barracks
+                            .iter()
+                            .find(|s| s.bounds_2d().contains_point(pos.xy()));
+                        assert!(
+                            building.is_some(),
+                            "guard at {:?} outside of the barracks",
+                            pos
+                        );
+                        // Not stuck in the planks of the bunk
+                        assert!(
+                            building
+                                .and_then(|s| s.sample(index, pos))
+                                .map_or(true, |block| !block.is_solid()),
+                            "guard at {:?} spawned inside a solid block",
+                            pos
+                        );
+                        guards += 1;
+                    }
+                }
+            }
+            assert!(guards > 0);
+        }
+        assert!(forts > 0);
     }
-    // assume roughly 10 merchants sharing a town's stock (other logic for coins)
-    stockmap
-        .iter_mut()
-        .filter(|(good, _amount)| **good != Good::Coin)
-        .for_each(|(_good, amount)| *amount *= 0.1);
-    // Fill bags with stuff according to unclaimed stock
-    let ability_map = &comp::tool::AbilityMap::load().read();
-    let msm = &comp::item::MaterialStatManifest::load().read();
-    let mut wares: Vec<Item> =
-        TradePricing::random_items(&mut stockmap, slots as u32, true, true, 16)
-            .iter()
-            .filter_map(|(n, a)| {
-                let i = Item::new_from_item_definition_id(n.as_ref(), ability_map, msm).ok();
-                i.map(|mut i| {
-                    i.set_amount(*a)
-                        .map_err(|_| tracing::error!("merchant loadout amount failure"))
-                        .ok();
-                    i
+
+    #[test]
+    fn large_towns_have_a_crypt_below_their_graveyard() {
+        with_index(large_towns_have_a_crypt_below_their_graveyard_with)
+    }
+
+    fn large_towns_have_a_crypt_below_their_graveyard_with(index: IndexRef) {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
+
+        let mut large_towns = 0;
+        for seed in 0..8 {
+            let settlement = settlement(seed);
+            let town_tiles = settlement
+                .land
+                .tiles
+                .values()
+                .filter(|tile| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+                .count();
+            let crypts = settlement
+                .structures
+                .iter()
+                .filter_map(|s| match &s.kind {
+                    StructureKind::Crypt(crypt) => Some(crypt),
+                    _ => None,
                 })
-            })
-            .collect();
-    sort_wares(&mut wares);
-    transfer(&mut wares, &mut backpack);
-    transfer(&mut wares, &mut bag1);
-    transfer(&mut wares, &mut bag2);
-    transfer(&mut wares, &mut bag3);
-    transfer(&mut wares, &mut bag4);
+                .collect::<Vec<_>>();
+            if town_tiles < CRYPT_MIN_TOWN_TILES {
+                assert!(crypts.is_empty());
+                continue;
+            }
+            large_towns += 1;
+
+            assert_eq!(crypts.len(), 1);
+            let crypt = crypts[0];
+            // The vault floor lies deeper than other buildings reach, so the
+            // crypt's bounds must reach deeper than theirs to cover it
+            let floor = crypt.origin().z - CHAMBER_DEPTH;
+            assert!(crypt.bounds().min.z <= floor);
+            assert!(
+                Building::<House>::generate(0, crypt.origin(), None)
+                    .bounds()
+                    .min
+                    .z
+                    > floor
+            );
+            let loot = crypt.loot();
+            assert!(!loot.is_empty());
+            for pos in &loot {
+                // Grave goods lie on the floor of the vault, below the ground
+                assert!(pos.z < crypt.origin().z);
+                assert!(crypt.sample(index, *pos).map_or(false, |b| b.is_air()));
+                assert!(
+                    crypt
+                        .sample(index, *pos - Vec3::unit_z())
+                        .map_or(false, |b| b.is_solid())
+                );
+            }
 
-    loadout_builder
-        .back(Some(backpack))
-        .bag(ArmorSlot::Bag1, Some(bag1))
-        .bag(ArmorSlot::Bag2, Some(bag2))
-        .bag(ArmorSlot::Bag3, Some(bag3))
-        .bag(ArmorSlot::Bag4, Some(bag4))
-}
+            let tile = loot[0].xy().map(|e| e.div_euclid(AREA_SIZE as i32));
+            let chests = settlement
+                .sample_entities(
+                    settlement.origin + tile * AREA_SIZE as i32,
+                    |_| Some(&col),
+                    &economy,
+                    None,
+                    1.0,
+                )
+                .into_iter()
+                .filter(|entity| {
+                    entity.body == comp::Body::Object(comp::object::Body::Chest)
+                        && entity.loot == LootSpec::LootTable(settlement.chest_loot().into())
+                })
+                .count();
+            assert_eq!(chests, loot.len());
+        }
+        assert!(large_towns > 0);
+    }
 
-fn sort_wares(bag: &mut [Item]) {
-    use common::comp::item::TagExampleInfo;
+    #[test]
+    fn road_graph_condenses_straight_runs() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let grass = settlement.land.new_plot(Plot::Grass);
+        let path = [
+            Vec2::new(0, 0),
+            Vec2::new(1, 0),
+            Vec2::new(2, 0),
+            Vec2::new(2, 1),
+            Vec2::new(2, 2),
+        ];
+        for tile in path.iter() {
+            settlement.land.set(*tile, grass);
+        }
+        settlement
+            .land
+            .write_path(&path, WayKind::Path, |_| true, true);
+
+        let graph = settlement.road_graph();
+        assert_eq!(graph.edges.len(), 2);
+        assert!(graph.edges.iter().all(|edge| edge.length == 2));
+        // Only the corner joins two runs
+        let junctions = (0..graph.nodes.len())
+            .filter(|node| graph.edges_from(*node).count() > 1)
+            .map(|node| graph.nodes[node])
+            .collect::<Vec<_>>();
+        assert_eq!(junctions, vec![Vec2::new(2, 0)]);
+    }
 
-    bag.sort_by(|a, b| {
-        a.quality()
-            .cmp(&b.quality())
-        // sort by kind
-        .then(
-            Ord::cmp(
-                a.tags().first().map_or("", |tag| tag.name()),
-                b.tags().first().map_or("", |tag| tag.name()),
-            )
-        )
-        // sort by name
-        .then(#[allow(deprecated)] Ord::cmp(&a.name(), &b.name()))
-    });
-}
+    #[test]
+    fn shallow_narrow_crossings_are_forded() {
+        with_index(shallow_narrow_crossings_are_forded_with)
+    }
 
-fn transfer(wares: &mut Vec<Item>, bag: &mut Item) {
-    let capacity = bag.slots().len();
-    for (s, w) in bag
-        .slots_mut()
-        .iter_mut()
-        .zip(wares.drain(0..wares.len().min(capacity)))
-    {
-        *s = Some(w);
+    fn shallow_narrow_crossings_are_forded_with(index: IndexRef) {
+        // A path across `width` tiles of water whose level lies `depth` blocks
+        // below the ground
+        let crossing = |width: i32, depth: i32| {
+            let mut rng = ChaChaRng::seed_from_u64(0);
+            let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+                sim: None,
+                rng: &mut rng,
+            });
+            let (grass, water) = (
+                settlement.land.new_plot(Plot::Grass),
+                settlement.land.new_plot(Plot::Water),
+            );
+            let path = (-1..width + 1).map(|x| Vec2::new(x, 0)).collect::<Vec<_>>();
+            for tile in path.iter() {
+                let plot = if (0..width).contains(&tile.x) {
+                    water
+                } else {
+                    grass
+                };
+                settlement.land.set(*tile, plot);
+            }
+            settlement
+                .land
+                .write_path(&path, WayKind::Path, |_| true, true);
+            settlement.set_water_level(Vec2::zero(), -2);
+            settlement.place_crossings(|_| Some((depth - 2) as f32));
+            settlement
+        };
+
+        let ford = crossing(1, 2);
+        let ways = |settlement: &Settlement, tile| *settlement.land.tile_at(tile).unwrap().ways();
+        assert_eq!(ways(&ford, Vec2::zero())[2], Some(WayKind::Ford));
+        // The path on the bank leads into the ford
+        assert_eq!(ways(&ford, Vec2::new(-1, 0))[2], Some(WayKind::Ford));
+        assert!(ways(&crossing(1, 12), Vec2::zero()).contains(&Some(WayKind::Bridge)));
+        assert!(ways(&crossing(3, 2), Vec2::new(1, 0)).contains(&Some(WayKind::Bridge)));
+
+        // The path wades through a single block of water, on a bed that is
+        // lower than the bank but still walkable
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let mut vol = terrain_chunk();
+        ford.apply_terrain(index, Vec2::zero(), |_| Some(&col), &mut vol);
+        let center = Vec2::broadcast(AREA_SIZE as i32 / 2);
+        let block = |z| *vol.get(Vec3::new(center.x, center.y, z)).unwrap();
+        assert!(!block(-1).is_filled() && !block(-1).is_fluid());
+        assert!(block(-2).is_fluid());
+        assert!(block(-3).is_filled());
     }
-}
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum Crop {
-    Corn,
-    Wheat,
-    Cabbage,
-    Pumpkin,
-    Flax,
-    Carrot,
-    Tomato,
-    Radish,
-    Turnip,
-    Sunflower,
-}
+    #[test]
+    fn bridges_have_railings() { with_index(bridges_have_railings_with); }
 
-// NOTE: No support for struct variants in make_case_elim yet, unfortunately, so
-// we can't use it.
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum Plot {
-    Hazard,
-    Dirt,
-    Grass,
-    Water,
-    Town {
-        district: Option<Id<District>>,
-    },
-    Field {
-        farm: Id<Farm>,
-        seed: u32,
-        crop: Crop,
-    },
-}
+    fn bridges_have_railings_with(index: IndexRef) {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        let water = settlement.land.new_plot(Plot::Water);
+        let span = [Vec2::new(0, 0), Vec2::new(1, 0), Vec2::new(2, 0)];
+        for tile in span.iter() {
+            settlement.land.set(*tile, water);
+        }
+        settlement
+            .land
+            .write_path(&span, WayKind::Bridge, |_| true, true);
 
-const CARDINALS: [Vec2<i32>; 4] = [
-    Vec2::new(0, 1),
-    Vec2::new(1, 0),
-    Vec2::new(0, -1),
-    Vec2::new(-1, 0),
-];
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let mut vol = terrain_chunk();
+        settlement.apply_terrain(index, Vec2::new(32, 0), |_| Some(&col), &mut vol);
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum WayKind {
-    Path,
-    #[allow(dead_code)]
-    Wall,
-}
+        let colors = &index.colors.site.settlement;
+        let deck = Block::new(BlockKind::Wood, colors.bridge_deck.into());
+        let railing = Block::new(BlockKind::Wood, colors.bridge_railing.into());
+        // Around the middle of the span, away from its ends
+        for x in 10..22 {
+            let wpos2d = Vec2::new(32 + x, 0);
+            let edge = (0..32)
+                .filter(|y| {
+                    let sample = settlement.land.get_at_block(wpos2d + Vec2::unit_y() * *y);
+                    matches!(sample.way, Some((WayKind::Bridge, dist, _)) if dist >= 3.0)
+                })
+                .collect::<Vec<_>>();
+            assert!(edge.len() >= 2);
+            for y in edge {
+                assert_eq!(*vol.get(Vec3::new(x, y, 1)).unwrap(), railing);
+            }
 
-impl WayKind {
-    pub fn width(&self) -> f32 {
-        match self {
-            WayKind::Path => 4.0,
-            WayKind::Wall => 3.0,
+            let center = (0..32)
+                .min_by_key(|y| {
+                    let sample = settlement.land.get_at_block(wpos2d + Vec2::unit_y() * *y);
+                    sample
+                        .way
+                        .map_or(i32::MAX, |(_, dist, _)| (dist * 16.0) as i32)
+                })
+                .unwrap();
+            assert_eq!(*vol.get(Vec3::new(x, center, -1)).unwrap(), deck);
+            for z in 0..BRIDGE_CLEARANCE {
+                assert!(!vol.get(Vec3::new(x, center, z)).unwrap().is_filled());
+            }
         }
     }
-}
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum Tower {
-    #[allow(dead_code)]
-    Wall,
-}
+    #[test]
+    fn population_summary_grows_with_the_town() {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let economy = SiteInformation {
+            id: 0,
+            unconsumed_stock: Default::default(),
+        };
 
-impl Tower {
-    pub fn radius(&self) -> f32 {
-        match self {
-            Tower::Wall => 6.0,
+        let mut summaries = Vec::new();
+        let (mut expected, mut spawned) = (0.0, 0);
+        for seed in 0..4 {
+            let settlement = settlement(seed);
+            let summary = settlement.population_summary();
+            let town_tiles = settlement
+                .land
+                .tiles
+                .values()
+                .filter(|tile| matches!(settlement.land.plot(tile.plot), Plot::Town { .. }))
+                .count();
+            summaries.push((town_tiles, summary));
+
+            // The summary matches the entities actually sampled, on average
+            expected += summary.total();
+            for tile in settlement.land.tiles.keys() {
+                spawned += settlement
+                    .sample_entities(
+                        settlement.origin + *tile * AREA_SIZE as i32,
+                        |_| Some(&col),
+                        &economy,
+                        None,
+                        1.0,
+                    )
+                    .len();
+            }
         }
+        assert!(
+            (spawned as f32) > expected * 0.5 && (spawned as f32) < expected * 1.5,
+            "expected {} entities, spawned {}",
+            expected,
+            spawned
+        );
+
+        summaries.sort_by_key(|(town_tiles, _)| *town_tiles);
+        let (hamlet_tiles, hamlet) = summaries.first().unwrap();
+        let (town_tiles, town) = summaries.last().unwrap();
+        assert!(town_tiles > hamlet_tiles);
+        assert!(town.villagers > hamlet.villagers);
     }
-}
 
-pub struct Tile {
-    plot: Id<Plot>,
-    ways: [Option<WayKind>; 4],
-    tower: Option<Tower>,
-}
+    #[test]
+    fn contour_furrows_run_across_the_slope() {
+        let mut settlement = settlement(0);
+        assert!(settlement.field_slopes.is_empty());
+        // Ground rising along (1, 3), whose contours run along (-3, 1)
+        settlement.place_field_slopes(|wpos| Some(wpos.x as f32 + wpos.y as f32 * 3.0));
+        assert!(!settlement.field_slopes.is_empty());
+
+        let uphill = Vec2::new(1.0, 3.0).normalized();
+        for (center, downhill) in settlement.field_slopes.iter() {
+            assert!(downhill.dot(-uphill) > 0.99);
+            let (seed, crop) = settlement
+                .land
+                .tiles
+                .values()
+                .find_map(|tile| match settlement.land.plot(tile.plot) {
+                    Plot::Field {
+                        seed,
+                        crop,
+                        center: c,
+                        ..
+                    } if c == center => Some((*seed, *crop)),
+                    _ => None,
+                })
+                .unwrap();
+            let furrow = |pos| settlement.in_field_furrow(pos, seed, crop, *center);
+            let changes = |step: Vec2<i32>| {
+                (0..64)
+                    .map(|i| Vec2::unit_x() + step * i)
+                    .filter(|pos| furrow(*pos) != furrow(*pos + step))
+                    .count()
+            };
+            // Following the contour stays within the same row, while heading
+            // uphill crosses one row after another
+            assert_eq!(changes(Vec2::new(-3, 1)), 0);
+            assert!(changes(Vec2::unit_y()) > 8);
+        }
+    }
 
-impl Tile {
-    pub fn contains(&self, kind: WayKind) -> bool { self.ways.iter().any(|way| way == &Some(kind)) }
-}
+    #[test]
+    fn generate_with_stats_counts_building_attempts() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let (settlement, stats) =
+            SettlementBuilder::new().generate_with_stats(Vec2::zero(), None, &mut rng);
+
+        assert!(stats.building_attempts > 0);
+        assert!(stats.failed_building_attempts < stats.building_attempts);
+        assert!(stats.astar_nodes > 0);
+        assert!(stats.pass("place_buildings").is_some());
+        assert!(settlement.stats.is_none());
+
+        // Plain generation collects nothing, but generates the same settlement
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let plain = SettlementBuilder::new().generate(Vec2::zero(), None, &mut rng);
+        assert!(plain.stats.is_none());
+        assert!(plain.land.astar_nodes().is_none());
+        assert_eq!(plain.structures.len(), settlement.structures.len());
+    }
 
-#[derive(Default)]
-pub struct Sample<'a> {
-    plot: Option<&'a Plot>,
-    way: Option<(&'a WayKind, f32, Vec2<f32>)>,
-    tower: Option<(&'a Tower, Vec2<i32>)>,
-    edge_dist: f32,
-    second_closest: Vec2<i32>,
-}
+    #[test]
+    fn structure_applies_alone() {
+        let settlement = settlement(0);
+        let keep = settlement
+            .structures
+            .iter()
+            .find(|s| matches!(s.kind, StructureKind::Keep(_)))
+            .unwrap();
+        let size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        let chunk_wpos = (settlement.origin + keep.bounds_2d().center())
+            .map2(size, |e, sz| e.div_euclid(sz) * sz);
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let fresh = terrain_chunk();
+        let (mut structures, mut alone) = (terrain_chunk(), terrain_chunk());
+        with_index(|index| {
+            settlement.apply_structures(index, chunk_wpos, |_| Some(&col), &mut structures);
+            keep.apply_to(
+                index,
+                chunk_wpos - settlement.origin,
+                i32::MIN..i32::MAX,
+                |_| Some(&col),
+                &mut alone,
+            );
+        });
 
-pub struct Land {
-    /// We use this hasher (FxHasher64) because
-    /// (1) we need determinism across computers (ruling out AAHash);
-    /// (2) we don't care about DDOS attacks (ruling out SipHash);
-    /// (3) we have 8-byte keys (for which FxHash is fastest).
-    tiles: HashMap<Vec2<i32>, Tile, BuildHasherDefault<FxHasher64>>,
-    plots: Store<Plot>,
-    sampler_warp: StructureGen2d,
-    hazard: Id<Plot>,
-}
+        let mut keep_blocks = 0;
+        for x in 0..size.x {
+            for y in 0..size.y {
+                let in_keep = keep
+                    .bounds_2d()
+                    .contains_point(chunk_wpos + Vec2::new(x, y) - settlement.origin);
+                for z in -8..64 {
+                    let pos = Vec3::new(x, y, z);
+                    let get = |vol: &TerrainChunk| *vol.get(pos).unwrap();
+                    if in_keep {
+                        assert_eq!(get(&alone), get(&structures));
+                        if get(&alone) != get(&fresh) {
+                            keep_blocks += 1;
+                        }
+                    } else {
+                        assert_eq!(get(&alone), get(&fresh));
+                    }
+                }
+            }
+        }
+        assert!(keep_blocks > 0);
+    }
 
-impl Land {
-    pub fn new(rng: &mut impl Rng) -> Self {
-        let mut plots = Store::default();
-        let hazard = plots.insert(Plot::Hazard);
-        Self {
-            tiles: HashMap::default(),
-            plots,
-            sampler_warp: StructureGen2d::new(rng.gen(), AREA_SIZE, AREA_SIZE * 2 / 5),
-            hazard,
+    #[test]
+    fn decoration_density_decorates_grass() { with_index(decoration_density_decorates_grass_with) }
+
+    fn decoration_density_decorates_grass_with(index: IndexRef) {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut ctx = GenCtx {
+            sim: None,
+            rng: &mut rng,
+        };
+        let mut settlement = Settlement::new(Vec2::zero(), &mut ctx);
+        let grass = settlement.land.intern_plot(Plot::Grass);
+        for x in -1..2 {
+            for y in -1..2 {
+                settlement.land.set(Vec2::new(x, y), grass);
+            }
         }
+        let path = [Vec2::new(-1, 0), Vec2::new(0, 0), Vec2::new(1, 0)];
+        settlement
+            .land
+            .write_path(&path, WayKind::Path, |_| true, true);
+
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let decorations = |settlement: &Settlement| {
+            let mut vol = terrain_chunk();
+            settlement.apply_terrain(index, Vec2::zero(), |_| Some(&col), &mut vol);
+            let mut count = 0;
+            for x in 0..AREA_SIZE as i32 {
+                for y in 0..AREA_SIZE as i32 {
+                    let decorated = matches!(
+                        vol.get(Vec3::new(x, y, 0)).unwrap().get_sprite(),
+                        Some(
+                            SpriteKind::ShortGrass
+                                | SpriteKind::MediumGrass
+                                | SpriteKind::RedFlower
+                                | SpriteKind::YellowFlower
+                                | SpriteKind::WhiteFlower
+                                | SpriteKind::Stones2
+                        )
+                    );
+                    if decorated {
+                        // Paths are kept clear
+                        assert!(settlement.land.get_at_block(Vec2::new(x, y)).way.is_none());
+                        count += 1;
+                    }
+                }
+            }
+            count
+        };
+
+        settlement.decoration_density = 0.02;
+        let sparse = decorations(&settlement);
+        settlement.decoration_density = 0.5;
+        let dense = decorations(&settlement);
+        assert!(dense > sparse);
+        assert!(dense > 100);
     }
 
-    pub fn get_at_block(&self, pos: Vec2<i32>) -> Sample {
-        let mut sample = Sample::default();
+    #[test]
+    fn normalize_ways_resolves_disagreeing_halves() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let span = [Vec2::new(0, 0), Vec2::new(1, 0)];
+        let mut normalized = |first: WayKind, second: WayKind| {
+            let mut land = Land::new(&mut rng);
+            let (a, b) = (land.new_plot(Plot::Dirt), land.new_plot(Plot::Grass));
+            land.set(span[0], a);
+            land.set(span[1], b);
+            land.write_path(&span, first, |_| true, true);
+            // Only the second tile's half is overwritten
+            land.write_path(&span, second, |plot| *plot == Plot::Grass, true);
+            assert!(
+                land.tile_at(span[0]).unwrap().ways()[2]
+                    != land.tile_at(span[1]).unwrap().ways()[0]
+            );
+            land.normalize_ways();
+            let (east, west) = (
+                land.tile_at(span[0]).unwrap().ways()[2],
+                land.tile_at(span[1]).unwrap().ways()[0],
+            );
+            assert!(east == west);
+            east
+        };
 
-        let neighbors = self.sampler_warp.get(pos);
-        let closest = neighbors
-            .iter()
-            .min_by_key(|(center, _)| center.distance_squared(pos))
-            .unwrap()
-            .0;
-        let second_closest = neighbors
-            .iter()
-            .filter(|(center, _)| *center != closest)
-            .min_by_key(|(center, _)| center.distance_squared(pos))
-            .unwrap()
-            .0;
-        sample.second_closest = second_closest.map(to_tile);
-        sample.edge_dist = (second_closest - pos).map(|e| e as f32).magnitude()
-            - (closest - pos).map(|e| e as f32).magnitude();
+        // The outcome doesn't depend on which way was written first
+        assert!(normalized(WayKind::Path, WayKind::Wall) == Some(WayKind::Wall));
+        assert!(normalized(WayKind::Wall, WayKind::Path) == Some(WayKind::Wall));
+        assert!(normalized(WayKind::Bridge, WayKind::Path) == Some(WayKind::Bridge));
+    }
 
-        let center_tile = self.tile_at(neighbors[4].0.map(to_tile));
+    #[test]
+    fn settlements_form_around_features() {
+        let feature_wpos = Vec2::new(200, 40);
+        let feature = feature_wpos.map(to_tile);
+        for seed in 0..4 {
+            let settlement = Settlement::generate_around(
+                Vec2::zero(),
+                feature_wpos,
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            assert_eq!(settlement.validate(), Ok(()));
+            assert!(matches!(
+                settlement.land.plot_at(feature),
+                Some(Plot::Reserved)
+            ));
+            for structure in settlement.structures.iter() {
+                assert!(footprint_tiles(structure.bounds_2d()).all(|tile| tile != feature));
+            }
 
-        if let Some(tower) = center_tile.and_then(|tile| tile.tower.as_ref()) {
-            if (neighbors[4].0.distance_squared(pos) as f32) < tower.radius().powi(2) {
-                sample.tower = Some((tower, neighbors[4].0));
+            // Follow the ways out of the feature's tile to the town center
+            let base_tile = settlement.town.as_ref().unwrap().base_tile;
+            let mut visited = HashSet::<_, BuildHasherDefault<FxHasher64>>::default();
+            let mut open = vec![feature];
+            let mut reached = false;
+            while let Some(tile) = open.pop() {
+                if !visited.insert(tile) {
+                    continue;
+                }
+                if (tile - base_tile).map(|e| e.abs()).sum() <= 1 {
+                    reached = true;
+                    break;
+                }
+                let ways = settlement
+                    .land
+                    .tile_at(tile)
+                    .map_or([None; 4], |t| *t.ways());
+                for (dir, way) in WAY_DIRS.iter().zip(ways.iter()) {
+                    if *way == Some(WayKind::Path) {
+                        open.push(tile + *dir);
+                    }
+                }
             }
+            assert!(reached);
         }
+    }
 
-        for (i, _) in CARDINALS.iter().enumerate() {
-            let map = [1, 5, 7, 3];
-            let line = LineSegment2 {
-                start: neighbors[4].0.map(|e| e as f32),
-                end: neighbors[map[i]].0.map(|e| e as f32),
-            };
-            if let Some(way) = center_tile.and_then(|tile| tile.ways[i].as_ref()) {
-                let proj_point = line.projected_point(pos.map(|e| e as f32));
-                let dist = proj_point.distance(pos.map(|e| e as f32));
-                if dist < way.width() {
-                    sample.way = sample
-                        .way
-                        .filter(|(_, d, _)| *d < dist)
-                        .or(Some((way, dist, proj_point)));
+    #[test]
+    fn edge_distance_grows_towards_tile_centers() {
+        let settlement = settlement(0);
+        let fields = settlement.land.sampler_warp.get(Vec2::zero());
+        let (center, neighbor) = (fields[4].0, fields[5].0);
+        let tile = settlement.land.get_at_block(center).closest;
+
+        // Walk towards the neighbouring center until leaving the tile
+        let steps = (neighbor - center).map(|e| e.abs()).reduce_max();
+        let at = |i: i32| center + (neighbor - center) * i / steps;
+        let boundary = (0..steps)
+            .find(|i| settlement.land.get_at_block(at(*i + 1)).closest != tile)
+            .unwrap();
+
+        let edge = |i: i32| settlement.edge_distance(settlement.origin + at(i));
+        assert!(edge(boundary) < 3.0);
+        assert!(edge(boundary) < edge(boundary / 2));
+        assert!(edge(boundary / 2) < edge(0));
+        assert!(edge(0) > 6.0);
+    }
+
+    #[test]
+    fn density_falloff_packs_the_town_center() {
+        let (mut central, mut fringe) = ((0, 0), (0, 0));
+        for seed in 0..4 {
+            let settlement = SettlementBuilder::new().density_falloff(4.0).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            let town_center = settlement.town.as_ref().unwrap().base_tile;
+            let is_central = |tile: Vec2<i32>| tile.distance_squared(town_center) <= 4;
+            for (pos, tile) in settlement.land.tiles.iter() {
+                if matches!(settlement.land.plot(tile.plot), Plot::Town { .. }) {
+                    let (_, tiles) = if is_central(*pos) {
+                        &mut central
+                    } else {
+                        &mut fringe
+                    };
+                    *tiles += 1;
+                }
+            }
+            for structure in settlement.structures.iter() {
+                if matches!(
+                    structure.kind,
+                    StructureKind::House(_) | StructureKind::Tavern(_)
+                ) {
+                    let tile = structure.bounds_2d().center().map(to_tile);
+                    let (buildings, _) = if is_central(tile) {
+                        &mut central
+                    } else {
+                        &mut fringe
+                    };
+                    *buildings += 1;
                 }
             }
         }
 
-        sample.plot = self.plot_at(closest.map(to_tile));
-
-        sample
+        let per_tile = |(buildings, tiles): (i32, i32)| buildings as f32 / tiles.max(1) as f32;
+        assert!(per_tile(central) > per_tile(fringe));
     }
 
-    pub fn tile_at(&self, pos: Vec2<i32>) -> Option<&Tile> { self.tiles.get(&pos) }
+    #[test]
+    fn relax_nudges_crowded_structures_apart() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let town = settlement.land.new_plot(Plot::Town { district: None });
+        for x in -3..4 {
+            for y in -3..4 {
+                settlement.land.set(Vec2::new(x, y), town);
+            }
+        }
 
-    #[allow(dead_code)]
-    pub fn tile_at_mut(&mut self, pos: Vec2<i32>) -> Option<&mut Tile> { self.tiles.get_mut(&pos) }
+        // Notice boards packed edge to edge around the first
+        let origin = Vec3::new(16, 16, 0);
+        let first = Building::<NoticeBoard>::generate(0, origin, None);
+        let bounds = first.bounds_2d();
+        settlement.structures.push(Structure {
+            kind: StructureKind::NoticeBoard(first),
+        });
+        for (seed, dir) in [
+            (1, Vec2::unit_x()),
+            (2, Vec2::unit_y()),
+            (3, -Vec2::unit_x()),
+        ] {
+            let board = Building::<NoticeBoard>::generate(seed, origin, None);
+            let other = board.bounds_2d();
+            let offs = dir
+                * if dir.sum() > 0 {
+                    bounds.max - other.min + 1
+                } else {
+                    other.max - bounds.min + 1
+                };
+            settlement.structures.push(Structure {
+                kind: StructureKind::NoticeBoard(board.moved_by(Vec3::from(offs))),
+            });
+        }
 
-    pub fn plot(&self, id: Id<Plot>) -> &Plot { self.plots.get(id) }
+        let crowded_pairs = |settlement: &Settlement| {
+            let structures = &settlement.structures;
+            (0..structures.len())
+                .flat_map(|i| (i + 1..structures.len()).map(move |j| (i, j)))
+                .filter(|(i, j)| settlement.structures_crowd(&structures[*i], &structures[*j]))
+                .count()
+        };
+        let descriptors = |settlement: &Settlement| {
+            settlement
+                .structures
+                .iter()
+                .map(|structure| structure.descriptor())
+                .collect::<Vec<_>>()
+        };
 
-    pub fn plot_at(&self, pos: Vec2<i32>) -> Option<&Plot> {
-        self.tiles.get(&pos).map(|tile| self.plots.get(tile.plot))
-    }
+        let before = crowded_pairs(&settlement);
+        assert!(before > 0);
+        settlement.relax();
+        assert!(crowded_pairs(&settlement) < before);
+        assert_eq!(settlement.validate(), Ok(()));
 
-    #[allow(dead_code)]
-    pub fn plot_at_mut(&mut self, pos: Vec2<i32>) -> Option<&mut Plot> {
-        self.tiles
-            .get(&pos)
-            .map(|tile| tile.plot)
-            .map(move |plot| self.plots.get_mut(plot))
+        // Relaxing again changes nothing
+        let relaxed = descriptors(&settlement);
+        settlement.relax();
+        assert_eq!(descriptors(&settlement), relaxed);
     }
 
-    pub fn set(&mut self, pos: Vec2<i32>, plot: Id<Plot>) {
-        self.tiles.insert(pos, Tile {
-            plot,
-            ways: [None; 4],
-            tower: None,
+    #[test]
+    fn removed_structures_release_their_tiles() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
         });
+        for (seed, origin) in [(0, Vec3::new(16, 16, 0)), (1, Vec3::new(80, 16, 0))] {
+            let structure = Structure {
+                kind: StructureKind::NoticeBoard(Building::<NoticeBoard>::generate(
+                    seed, origin, None,
+                )),
+            };
+            settlement.reserve_footprint(structure.bounds_2d());
+            settlement.structures.push(structure);
+        }
+        let kept = settlement.structures[1].bounds_2d();
+
+        settlement.retain_structures(|structure| structure.bounds_2d() == kept);
+        assert_eq!(
+            settlement.reserved,
+            footprint_tiles(kept).collect::<HashSet<_, _>>()
+        );
+        assert!(!settlement.reserved.contains(&Vec2::new(0, 0)));
     }
 
-    fn find_tile_near(
-        &self,
-        origin: Vec2<i32>,
-        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
-    ) -> Option<Vec2<i32>> {
-        Spiral2d::new()
-            .map(|pos| origin + pos)
-            .find(|pos| match_fn(self.plot_at(*pos)))
+    #[test]
+    fn relax_settles_buildings_and_prunes_ways() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut settlement = Settlement::new(Vec2::zero(), &mut GenCtx {
+            sim: None,
+            rng: &mut rng,
+        });
+        let town = settlement.land.new_plot(Plot::Town { district: None });
+        for x in -3..4 {
+            for y in -3..4 {
+                settlement.land.set(Vec2::new(x, y), town);
+            }
+        }
+
+        // One board floating above the ground, and one sunk into it
+        for (seed, origin) in [(0, Vec3::new(16, 16, 20)), (1, Vec3::new(80, 16, 0))] {
+            settlement.structures.push(Structure {
+                kind: StructureKind::NoticeBoard(Building::<NoticeBoard>::generate(
+                    seed, origin, None,
+                )),
+            });
+        }
+
+        // A path with no way back, one with a way back, and a gate where a
+        // wall crosses a path beside a gate where nothing does
+        let mut set_way = |pos: Vec2<i32>, idx: usize, kind| {
+            settlement.land.tile_at_mut(pos).unwrap().ways[idx] = Some(kind);
+        };
+        set_way(Vec2::new(0, 0), 2, WayKind::Path);
+        set_way(Vec2::new(0, 1), 2, WayKind::Path);
+        set_way(Vec2::new(1, 1), 0, WayKind::Path);
+        set_way(Vec2::new(-2, -2), 0, WayKind::Wall);
+        set_way(Vec2::new(-3, -2), 2, WayKind::Wall);
+        set_way(Vec2::new(-2, -2), 1, WayKind::Path);
+        set_way(Vec2::new(-2, -1), 3, WayKind::Path);
+        for tile in [Vec2::new(-2, -2), Vec2::new(2, 2)] {
+            settlement.gates.push(Gate {
+                tile,
+                wpos: Vec3::from(tile * AREA_SIZE as i32),
+                kind: GateKind::Doors,
+                open: true,
+            });
+        }
+
+        let ground = 5.0;
+        settlement.relax_with(|_| Some(ground));
+
+        let origins = settlement
+            .structures
+            .iter()
+            .map(|structure| structure.descriptor().unwrap().origin)
+            .collect::<Vec<_>>();
+        let settled = settlement.leveled_alt(Vec2::new(16, 16), ground).ceil() as i32;
+        assert_eq!(origins, vec![
+            Vec3::new(16, 16, settled),
+            Vec3::new(80, 16, 0)
+        ]);
+
+        let way = |pos: Vec2<i32>, idx: usize| settlement.land.tile_at(pos).unwrap().ways[idx];
+        assert!(way(Vec2::new(0, 0), 2).is_none());
+        assert!(way(Vec2::new(0, 1), 2) == Some(WayKind::Path));
+        assert!(way(Vec2::new(1, 1), 0) == Some(WayKind::Path));
+        assert_eq!(
+            settlement
+                .gates
+                .iter()
+                .map(|gate| gate.tile)
+                .collect::<Vec<_>>(),
+            vec![Vec2::new(-2, -2)]
+        );
     }
 
-    #[allow(dead_code)]
-    fn find_tile_dir(
-        &self,
-        origin: Vec2<i32>,
-        dir: Vec2<i32>,
-        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
-    ) -> Option<Vec2<i32>> {
-        (0..)
-            .map(|i| origin + dir * i)
-            .find(|pos| match_fn(self.plot_at(*pos)))
+    #[test]
+    fn towns_have_one_temple_near_their_center() {
+        let mut towns = 0;
+        for seed in 0..8 {
+            let settlement = SettlementBuilder::new().temple(true).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            assert_eq!(settlement.validate(), Ok(()));
+            let temples = settlement
+                .structures
+                .iter()
+                .filter(|structure| matches!(structure.kind, StructureKind::Temple(_)))
+                .collect::<Vec<_>>();
+
+            match settlement.map_icon() {
+                MapIconKind::Town | MapIconKind::Fort => {
+                    towns += 1;
+                    assert_eq!(temples.len(), 1);
+                    let town = settlement.town.as_ref().unwrap();
+                    let center = town.plaza().map_or(town.base_tile, |plaza| plaza.center());
+                    let tile = temples[0].bounds_2d().center().map(to_tile);
+                    assert!(tile.distance_squared(center) <= 3i32.pow(2));
+
+                    // Its spire rises well above the houses
+                    let landmark = settlement
+                        .landmarks()
+                        .find(|landmark| landmark.kind == LandmarkKind::Temple)
+                        .unwrap();
+                    assert!(landmark.height > 30);
+                },
+                _ => assert!(temples.is_empty()),
+            }
+        }
+        assert!(towns > 0);
     }
 
-    fn find_path(
-        &self,
-        origin: Vec2<i32>,
-        dest: Vec2<i32>,
-        path_cost_fn: impl Fn(Option<&Tile>, Option<&Tile>) -> f32,
-    ) -> Option<Path<Vec2<i32>>> {
-        let heuristic = |pos: &Vec2<i32>, _: &Vec2<i32>| (pos - dest).map(|e| e as f32).magnitude();
-        let transition =
-            |from: Vec2<i32>, to: Vec2<i32>| path_cost_fn(self.tile_at(from), self.tile_at(to));
-        let neighbors = |pos: &Vec2<i32>| {
-            let pos = *pos;
-            let transition = &transition;
-            CARDINALS.iter().map(move |dir| {
-                let to = pos + *dir;
-                (to, transition(pos, to))
-            })
+    #[test]
+    fn dense_towns_share_walls() {
+        let shared_walls = |settlement: &Settlement| {
+            let cores = settlement
+                .structures
+                .iter()
+                .filter_map(|structure| structure.house_core())
+                .collect::<Vec<_>>();
+            cores
+                .iter()
+                .enumerate()
+                .flat_map(|(i, a)| cores[i + 1..].iter().map(move |b| (*a, *b)))
+                .filter(|(a, b)| shares_wall(*a, *b))
+                .count()
         };
-        let satisfied = |pos: &Vec2<i32>| *pos == dest;
 
-        // We use this hasher (FxHasher64) because
-        // (1) we don't care about DDOS attacks (ruling out SipHash);
-        // (2) we don't care about determinism across computers (we could use AAHash);
-        // (3) we have 8-byte keys (for which FxHash is fastest).
-        Astar::new(250, origin, BuildHasherDefault::<FxHasher64>::default())
-            .poll(250, heuristic, neighbors, satisfied)
-            .into_path()
-            .map(|(p, _c)| p)
+        let mut shared = 0;
+        for seed in 0..4 {
+            let dense = SettlementBuilder::new().clustering(1.0).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(seed),
+            );
+            assert!(dense.validate().is_ok());
+            shared += shared_walls(&dense);
+
+            // Without clustering, buildings keep their distance
+            let sparse = settlement(seed);
+            for (i, a) in sparse.structures.iter().enumerate() {
+                for b in &sparse.structures[i + 1..] {
+                    assert!(!a.bounds_2d().collides_with_aabr(b.bounds_2d()));
+                }
+            }
+        }
+        assert!(shared > 0);
     }
 
-    /// We use this hasher (FxHasher64) because
-    /// (1) we don't care about DDOS attacks (ruling out SipHash);
-    /// (2) we care about determinism across computers (ruling out AAHash);
-    /// (3) we have 8-byte keys (for which FxHash is fastest).
-    fn grow_from(
-        &self,
-        start: Vec2<i32>,
-        max_size: usize,
-        _rng: &mut impl Rng,
-        mut match_fn: impl FnMut(Option<&Plot>) -> bool,
-    ) -> HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>> {
-        let mut open = VecDeque::new();
-        open.push_back(start);
-        // We use this hasher (FxHasher64) because
-        // (1) we don't care about DDOS attacks (ruling out SipHash);
-        // (2) we care about determinism across computers (ruling out AAHash);
-        // (3) we have 8-byte keys (for which FxHash is fastest).
-        let mut closed = HashSet::with_hasher(BuildHasherDefault::<FxHasher64>::default());
+    #[test]
+    fn structures_regenerate_from_their_descriptors() {
+        with_index(structures_regenerate_from_their_descriptors_with)
+    }
 
-        while open.len() + closed.len() < max_size {
-            let next_pos = if let Some(next_pos) = open.pop_front() {
-                closed.insert(next_pos);
-                next_pos
-            } else {
-                break;
-            };
+    fn structures_regenerate_from_their_descriptors_with(index: IndexRef) {
+        let settlement = SettlementBuilder::new()
+            .grid_snap(true)
+            .structure_mix(StructureMix {
+                house: 1.0,
+                tavern: 1.0,
+            })
+            .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+        assert!(!settlement.structures.is_empty());
+        for structure in settlement.structures.iter() {
+            let desc = structure.descriptor().unwrap();
+            let copy = Structure::from_descriptor(desc);
+            assert_eq!(copy.descriptor(), Some(desc));
+            assert_eq!(copy.bounds(), structure.bounds());
 
-            let dirs = [
-                Vec2::new(1, 0),
-                Vec2::new(-1, 0),
-                Vec2::new(0, 1),
-                Vec2::new(0, -1),
-            ];
+            let bounds = structure.bounds();
+            for x in (bounds.min.x..bounds.max.x + 1).step_by(2) {
+                for y in (bounds.min.y..bounds.max.y + 1).step_by(2) {
+                    for z in (bounds.min.z..bounds.max.z + 1).step_by(2) {
+                        let pos = Vec3::new(x, y, z);
+                        assert_eq!(copy.sample(index, pos), structure.sample(index, pos));
+                    }
+                }
+            }
+        }
+    }
 
-            for dir in dirs.iter() {
-                let neighbor = next_pos + dir;
-                if !closed.contains(&neighbor) && match_fn(self.plot_at(neighbor)) {
-                    open.push_back(neighbor);
+    #[test]
+    fn clamped_positions_can_be_interpolated() {
+        let size = Vec2::new(16u32, 12);
+        let chunk = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+        // Mirrors the chunks sampled by `WorldSim::get_interpolated`
+        let interpolable = |wpos: Vec2<i32>| {
+            let cpos = wpos.map2(chunk, |e, sz| e.div_euclid(sz));
+            cpos.x >= 1 && cpos.y >= 1 && cpos.x + 2 < size.x as i32 && cpos.y + 2 < size.y as i32
+        };
+
+        // A settlement at the corner of the world reaches beyond its edges
+        let settlement = settlement(0);
+        let reach = Settlement::RADIUS as i32;
+        for x in (-reach..reach).step_by(16) {
+            for y in (-reach..reach).step_by(16) {
+                let wpos = settlement.origin + Vec2::new(x, y);
+                let clamped = clamp_to_interpolable(wpos, size).unwrap();
+                assert!(interpolable(clamped));
+                if interpolable(wpos) {
+                    assert_eq!(clamped, wpos);
                 }
             }
         }
 
-        closed.into_iter().chain(open).collect()
+        // Narrower worlds have nowhere to clamp to
+        assert_eq!(clamp_to_interpolable(Vec2::zero(), Vec2::new(3, 16)), None);
+        assert!(clamp_to_interpolable(Vec2::zero(), Vec2::new(4, 16)).is_some());
     }
 
-    fn write_path(
-        &mut self,
-        tiles: &[Vec2<i32>],
-        kind: WayKind,
-        mut permit_fn: impl FnMut(&Plot) -> bool,
-        overwrite: bool,
-    ) {
-        for tiles in tiles.windows(2) {
-            let dir = tiles[1] - tiles[0];
-            let idx = if dir.y > 0 {
-                1
-            } else if dir.x > 0 {
-                2
-            } else if dir.y < 0 {
-                3
-            } else if dir.x < 0 {
-                0
-            } else {
-                continue;
-            };
-            if self.tile_at(tiles[0]).is_none() {
-                self.set(tiles[0], self.hazard);
+    #[test]
+    fn settlements_generate_in_tiny_worlds() {
+        let threadpool = rayon::ThreadPoolBuilder::new().build().unwrap();
+        let opts = WorldOpts {
+            world_file: FileOpts::Generate(GenOpts {
+                x_lg: 1,
+                y_lg: 1,
+                ..GenOpts::default()
+            }),
+            ..WorldOpts::default()
+        };
+        let sim = WorldSim::generate(0, opts, &threadpool, &|_| {});
+        assert_eq!(sim.get_size(), Vec2::new(2, 2));
+
+        // There's no terrain to stand on, but the settlement still generates
+        let settlement = SettlementBuilder::new().generate(
+            Vec2::new(32, 32),
+            Some(&sim),
+            &mut ChaChaRng::seed_from_u64(0),
+        );
+        assert!(sim.get_alt_clamped(settlement.origin).is_none());
+    }
+
+    #[test]
+    fn dense_crops_are_planted_closer() { with_index(dense_crops_are_planted_closer_with) }
+
+    fn dense_crops_are_planted_closer_with(index: IndexRef) {
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        // Crop sprites per block of field
+        let density = |crop: Crop| {
+            let settlement = SettlementBuilder::new().forced_crops(vec![crop]).generate(
+                Vec2::zero(),
+                None,
+                &mut ChaChaRng::seed_from_u64(0),
+            );
+            let mut field_tiles = settlement
+                .land
+                .tiles
+                .iter()
+                .filter(|(_, tile)| matches!(settlement.land.plot(tile.plot), Plot::Field { .. }))
+                .map(|(pos, _)| *pos)
+                .collect::<Vec<_>>();
+            field_tiles.sort_by_key(|pos| (pos.x, pos.y));
+            let (mut area, mut sprites) = (0, 0);
+            for tile in field_tiles.iter().take(8) {
+                let mut vol = terrain_chunk();
+                let wpos2d = settlement.origin + *tile * AREA_SIZE as i32;
+                settlement.apply_terrain(index, wpos2d, |_| Some(&col), &mut vol);
+                for x in 0..AREA_SIZE as i32 {
+                    for y in 0..AREA_SIZE as i32 {
+                        let rpos = wpos2d + Vec2::new(x, y) - settlement.origin;
+                        if !matches!(
+                            settlement.land.get_at_block(rpos).plot,
+                            Some(Plot::Field { .. })
+                        ) {
+                            continue;
+                        }
+                        area += 1;
+                        if matches!(
+                            vol.get(Vec3::new(x, y, 0)).unwrap().get_sprite(),
+                            Some(
+                                SpriteKind::WheatYellow
+                                    | SpriteKind::WheatGreen
+                                    | SpriteKind::Pumpkin
+                            )
+                        ) {
+                            sprites += 1;
+                        }
+                    }
+                }
             }
-            let plots = &self.plots;
+            assert!(area > 0);
+            sprites as f32 / area as f32
+        };
 
-            self.tiles
-                .get_mut(&tiles[1])
-                .filter(|tile| permit_fn(plots.get(tile.plot)))
-                .map(|tile| {
-                    if overwrite || tile.ways[(idx + 2) % 4].is_none() {
-                        tile.ways[(idx + 2) % 4] = Some(kind);
+        let (wheat, pumpkins) = (density(Crop::Wheat), density(Crop::Pumpkin));
+        assert!(pumpkins > 0.0);
+        assert!(
+            wheat > pumpkins * 2.0,
+            "{} wheat, {} pumpkins",
+            wheat,
+            pumpkins
+        );
+    }
+
+    #[test]
+    fn forced_crops_are_grown_everywhere() { with_index(forced_crops_are_grown_everywhere_with) }
+
+    fn forced_crops_are_grown_everywhere_with(index: IndexRef) {
+        let settlement = SettlementBuilder::new()
+            .forced_crops(vec![Crop::Sunflower])
+            .generate(Vec2::zero(), None, &mut ChaChaRng::seed_from_u64(0));
+        assert_eq!(settlement.validate(), Ok(()));
+        let mut field_tiles = settlement
+            .land
+            .tiles
+            .iter()
+            .filter_map(|(pos, tile)| match settlement.land.plot(tile.plot) {
+                Plot::Field { crop, .. } => Some((*pos, *crop)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!field_tiles.is_empty());
+        assert!(field_tiles.iter().all(|(_, crop)| *crop == Crop::Sunflower));
+
+        // Only sunflowers grow in the rows of the fields
+        field_tiles.sort_by_key(|(pos, _)| (pos.x, pos.y));
+        let chunk = flat_chunk();
+        let col = flat_column(&chunk);
+        let mut sunflowers = 0;
+        for (tile, _) in field_tiles.iter().take(8) {
+            let mut vol = terrain_chunk();
+            let wpos2d = settlement.origin + *tile * AREA_SIZE as i32;
+            settlement.apply_terrain(index, wpos2d, |_| Some(&col), &mut vol);
+            for x in 0..AREA_SIZE as i32 {
+                for y in 0..AREA_SIZE as i32 {
+                    let rpos = wpos2d + Vec2::new(x, y) - settlement.origin;
+                    if !matches!(
+                        settlement.land.get_at_block(rpos).plot,
+                        Some(Plot::Field { .. })
+                    ) {
+                        continue;
                     }
-                });
-            self.tiles
-                .get_mut(&tiles[0])
-                .filter(|tile| permit_fn(plots.get(tile.plot)))
-                .map(|tile| {
-                    if overwrite || tile.ways[idx].is_none() {
-                        tile.ways[idx] = Some(kind);
+                    match vol.get(Vec3::new(x, y, 0)).unwrap().get_sprite() {
+                        Some(SpriteKind::Sunflower) => sunflowers += 1,
+                        Some(
+                            SpriteKind::Corn
+                            | SpriteKind::WheatYellow
+                            | SpriteKind::WheatGreen
+                            | SpriteKind::Cabbage
+                            | SpriteKind::Pumpkin
+                            | SpriteKind::Flax
+                            | SpriteKind::Carrot
+                            | SpriteKind::Tomato
+                            | SpriteKind::Radish
+                            | SpriteKind::Turnip,
+                        ) => panic!("Another crop grows in a field of sunflowers"),
+                        _ => {},
                     }
-                });
+                }
+            }
         }
+        assert!(sunflowers > 0);
     }
-
-    pub fn new_plot(&mut self, plot: Plot) -> Id<Plot> { self.plots.insert(plot) }
 }
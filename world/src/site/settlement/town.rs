@@ -1,82 +1,150 @@
 use super::{GenCtx, AREA_SIZE};
-use common::store::Store;
+use common::store::{Id, Store};
+use fxhash::FxHasher64;
+use hashbrown::{HashMap, HashSet};
 use rand::prelude::*;
+use std::{collections::VecDeque, hash::BuildHasherDefault};
 use vek::*;
 
 pub struct Town {
     pub base_tile: Vec2<i32>,
     radius: i32,
     districts: Store<District>,
+    /// An open district around a central monument.
+    plaza: Option<Id<District>>,
 }
 
 impl Town {
+    /// Radius of a town, in tiles, around its base tile.
+    pub const RADIUS: i32 = 4;
+
     pub fn districts(&self) -> &Store<District> { &self.districts }
 
+    pub fn plaza(&self) -> Option<&District> { self.plaza.map(|id| self.districts.get(id)) }
+
+    pub fn is_plaza(&self, id: Id<District>) -> bool { self.plaza == Some(id) }
+
     pub fn generate(origin: Vec2<i32>, base_tile: Vec2<i32>, ctx: &mut GenCtx<impl Rng>) -> Self {
         let mut this = Self {
             base_tile,
-            radius: 4,
+            radius: Self::RADIUS,
             districts: Store::default(),
+            plaza: None,
         };
 
         this.generate_districts(origin, ctx);
+        this.plaza = this
+            .districts
+            .iter()
+            .filter(|(_, district)| !district.contains(base_tile))
+            .map(|(id, _)| id)
+            .choose(ctx.rng);
 
         this
     }
 
     fn generate_districts(&mut self, origin: Vec2<i32>, ctx: &mut GenCtx<impl Rng>) {
-        let base_aabr = Aabr {
-            min: self.base_tile - self.radius,
-            max: self.base_tile + self.radius,
-        };
+        const TILES_PER_DISTRICT: usize = 6;
 
-        gen_plot(base_aabr, ctx).for_each(base_aabr, &mut |aabr| {
-            if aabr.center().distance_squared(self.base_tile) < self.radius.pow(2) {
-                self.districts.insert(District {
-                    seed: ctx.rng.gen(),
-                    aabr,
-                    alt: ctx
-                        .sim
-                        .and_then(|sim| {
-                            sim.get_alt_approx(
-                                origin + aabr.center() * AREA_SIZE as i32 + AREA_SIZE as i32 / 2,
-                            )
-                        })
-                        .unwrap_or(0.0) as i32,
-                });
+        let base_tile = self.base_tile;
+        let radius = self.radius;
+        let in_town = |tile: Vec2<i32>| tile.distance_squared(base_tile) < radius.pow(2);
+
+        let mut town_tiles = (-radius..radius + 1)
+            .flat_map(|x| (-radius..radius + 1).map(move |y| base_tile + Vec2::new(x, y)))
+            .filter(|tile| in_town(*tile))
+            .collect::<Vec<_>>();
+        town_tiles.shuffle(ctx.rng);
+        let seeds = &town_tiles[..(town_tiles.len() / TILES_PER_DISTRICT).max(1)];
+
+        // Grow every district outwards from its seed in turn, so that districts
+        // take on organic shapes that meet at irregular borders
+        let mut claimed = HashMap::with_hasher(BuildHasherDefault::<FxHasher64>::default());
+        let mut open = VecDeque::new();
+        for (i, seed) in seeds.iter().enumerate() {
+            claimed.insert(*seed, i);
+            open.push_back((*seed, i));
+        }
+        while let Some((tile, i)) = open.pop_front() {
+            for dir in [
+                Vec2::new(1, 0),
+                Vec2::new(-1, 0),
+                Vec2::new(0, 1),
+                Vec2::new(0, -1),
+            ] {
+                let neighbor = tile + dir;
+                if in_town(neighbor) && !claimed.contains_key(&neighbor) {
+                    claimed.insert(neighbor, i);
+                    open.push_back((neighbor, i));
+                }
             }
-        });
+        }
+
+        for (i, seed) in seeds.iter().enumerate() {
+            let tiles = claimed
+                .iter()
+                .filter(|(_, district)| **district == i)
+                .map(|(tile, _)| *tile)
+                .collect::<HashSet<_, _>>();
+            let aabr = tiles.iter().fold(Aabr::new_empty(*seed), |aabr, tile| {
+                aabr.expanded_to_contain_point(*tile)
+            });
+            // Districts exclude the maximum bound of their `aabr`
+            let aabr = Aabr {
+                min: aabr.min,
+                max: aabr.max + 1,
+            };
+
+            self.districts.insert(District {
+                seed: ctx.rng.gen(),
+                aabr,
+                tiles: Some(tiles),
+                alt: ctx
+                    .sim
+                    .and_then(|sim| {
+                        sim.get_alt_approx(
+                            origin + aabr.center() * AREA_SIZE as i32 + AREA_SIZE as i32 / 2,
+                        )
+                    })
+                    .unwrap_or(0.0) as i32,
+            });
+        }
     }
 }
 
 pub struct District {
     pub seed: u32,
+    /// The bounds of the district, excluding `aabr.max`.
     pub aabr: Aabr<i32>,
+    /// The tiles of a non-rectangular district. If absent, the district
+    /// covers the whole of its `aabr`.
+    pub tiles: Option<HashSet<Vec2<i32>, BuildHasherDefault<FxHasher64>>>,
     pub alt: i32,
 }
 
-enum Plot {
-    District,
-    Parent(Vec<(Aabr<i32>, Plot)>),
-}
+impl District {
+    pub fn contains(&self, tile: Vec2<i32>) -> bool {
+        (self.aabr.min.x..self.aabr.max.x).contains(&tile.x)
+            && (self.aabr.min.y..self.aabr.max.y).contains(&tile.y)
+            && self
+                .tiles
+                .as_ref()
+                .map_or(true, |tiles| tiles.contains(&tile))
+    }
 
-impl Plot {
-    fn for_each(&self, aabr: Aabr<i32>, f: &mut impl FnMut(Aabr<i32>)) {
-        match self {
-            Plot::District => f(aabr),
-            Plot::Parent(children) => children.iter().for_each(|(aabr, p)| p.for_each(*aabr, f)),
-        }
+    /// All tiles covered by the district.
+    pub fn tiles(&self) -> impl Iterator<Item = Vec2<i32>> + '_ {
+        let aabr = self.aabr;
+        (aabr.min.x..aabr.max.x)
+            .flat_map(move |x| (aabr.min.y..aabr.max.y).map(move |y| Vec2::new(x, y)))
+            .filter(|tile| self.contains(*tile))
     }
-}
 
-fn gen_plot(aabr: Aabr<i32>, ctx: &mut GenCtx<impl Rng>) -> Plot {
-    if aabr.size().product() <= 9 {
-        Plot::District
-    } else if aabr.size().w < aabr.size().h {
-        let [a, b] = aabr.split_at_y(aabr.min.y + ctx.rng.gen_range(1..aabr.size().h));
-        Plot::Parent(vec![(a, gen_plot(a, ctx)), (b, gen_plot(b, ctx))])
-    } else {
-        let [a, b] = aabr.split_at_x(aabr.min.x + ctx.rng.gen_range(1..aabr.size().w));
-        Plot::Parent(vec![(a, gen_plot(a, ctx)), (b, gen_plot(b, ctx))])
+    /// The tile at the heart of the district.
+    pub fn center(&self) -> Vec2<i32> {
+        let center = self.aabr.center();
+        self.tiles()
+            .min_by_key(|tile| (tile.distance_squared(center), tile.x, tile.y))
+            .unwrap_or(center)
     }
 }